@@ -0,0 +1,4 @@
+-- migrations/20240201000000_node_agent.sql
+
+-- Token presented by the coordinator when calling a node's replica endpoints
+ALTER TABLE nodes ADD COLUMN access_token TEXT;
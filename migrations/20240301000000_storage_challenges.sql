@@ -0,0 +1,4 @@
+-- migrations/20240301000000_storage_challenges.sql
+
+-- Consecutive proof-of-storage challenges a replica has failed
+ALTER TABLE replicas ADD COLUMN failed_challenges INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+-- migrations/20240401000000_node_reputation.sql
+
+-- Banned nodes are refused on heartbeat/registration and never picked as targets
+ALTER TABLE nodes ADD COLUMN is_banned INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+-- migrations/20240501000000_repo_placement.sql
+
+-- Repositories that must keep at least one replica on an anchor node
+ALTER TABLE repositories ADD COLUMN require_anchor INTEGER NOT NULL DEFAULT 0;
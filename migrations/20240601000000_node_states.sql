@@ -0,0 +1,18 @@
+-- migrations/20240601000000_node_states.sql
+
+-- Liveness state: online -> suspect -> offline -> evicted
+ALTER TABLE nodes ADD COLUMN state TEXT NOT NULL DEFAULT 'online';
+ALTER TABLE nodes ADD COLUMN state_changed_at TEXT;
+UPDATE nodes SET state_changed_at = last_seen;
+
+-- History of liveness changes shown on the admin pages
+CREATE TABLE IF NOT EXISTS node_state_transitions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    node_id TEXT NOT NULL,
+    from_state TEXT NOT NULL,
+    to_state TEXT NOT NULL,
+    silent_minutes INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_node_transitions_node ON node_state_transitions(node_id);
@@ -0,0 +1,25 @@
+-- migrations/20240701000000_replication_jobs.sql
+
+-- Durable queue of "add one replica of this repo" jobs
+-- state: pending, running, succeeded, failed, cancelled
+CREATE TABLE IF NOT EXISTS replication_jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    repo_hash TEXT NOT NULL,
+    state TEXT NOT NULL DEFAULT 'pending',
+    reason TEXT NOT NULL DEFAULT 'manual',
+    priority INTEGER NOT NULL DEFAULT 0,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 5,
+    next_attempt_at TEXT NOT NULL DEFAULT (datetime('now')),
+    last_error TEXT,
+    target_node_id TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (repo_hash) REFERENCES repositories(repo_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_replication_jobs_due ON replication_jobs(state, next_attempt_at);
+
+-- At most one queued or running job per repository
+CREATE UNIQUE INDEX IF NOT EXISTS idx_replication_jobs_active
+    ON replication_jobs(repo_hash) WHERE state IN ('pending', 'running');
@@ -0,0 +1,6 @@
+-- migrations/20240801000000_replica_ref_state.sql
+
+-- Refs a replica was last confirmed to hold, one "<oid> <refname>" per line.
+-- NULL for replicas transferred before ref state was tracked.
+ALTER TABLE replicas ADD COLUMN ref_state TEXT;
+ALTER TABLE replicas ADD COLUMN synced_at DATETIME;
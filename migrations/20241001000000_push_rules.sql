@@ -0,0 +1,26 @@
+-- migrations/20241001000000_push_rules.sql
+
+-- Per-repository checks run on every push before any ref is updated
+CREATE TABLE IF NOT EXISTS push_rules (
+    repo_hash TEXT PRIMARY KEY,
+    -- Largest blob a push may add, in bytes; NULL for no limit
+    max_blob_size INTEGER,
+    -- Glob patterns, one per line, no pushed file may match
+    forbidden_paths TEXT NOT NULL DEFAULT '',
+    -- Every new commit's author email must belong to a verified account
+    require_verified_email INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (repo_hash) REFERENCES repositories(repo_hash) ON DELETE CASCADE
+);
+
+-- Branches that may not be force-pushed; patterns may use * and ?
+CREATE TABLE IF NOT EXISTS protected_branches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    repo_hash TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (repo_hash, pattern),
+    FOREIGN KEY (repo_hash) REFERENCES repositories(repo_hash) ON DELETE CASCADE
+);
+
+ALTER TABLE users ADD COLUMN email_verified INTEGER NOT NULL DEFAULT 0;
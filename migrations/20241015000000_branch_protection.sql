@@ -0,0 +1,17 @@
+-- migrations/20241015000000_branch_protection.sql
+
+-- Per-branch rules; a protected branch refuses deletion and force-pushes
+-- unless allowed here
+ALTER TABLE protected_branches ADD COLUMN allow_deletion INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE protected_branches ADD COLUMN allow_force_push INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE protected_branches ADD COLUMN require_linear_history INTEGER NOT NULL DEFAULT 0;
+
+-- Users allowed to push to a protected branch; none listed means anyone
+-- with push access
+CREATE TABLE IF NOT EXISTS protected_branch_pushers (
+    branch_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    PRIMARY KEY (branch_id, user_id),
+    FOREIGN KEY (branch_id) REFERENCES protected_branches(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
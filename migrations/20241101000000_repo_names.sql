@@ -0,0 +1,21 @@
+-- migrations/20241101000000_repo_names.sql
+
+-- Repositories are addressed as owner/name, so names must be unique per
+-- owner. Existing duplicates keep the oldest repository's name; later ones
+-- get their hash prefix appended.
+UPDATE repositories
+SET name = name || '-' || substr(repo_hash, 1, 8)
+WHERE rowid NOT IN (SELECT MIN(rowid) FROM repositories GROUP BY owner_id, name);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_repositories_owner_name ON repositories(owner_id, name);
+
+-- Names a repository had before it was renamed, so old URLs keep working
+CREATE TABLE IF NOT EXISTS repo_redirects (
+    owner_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    repo_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (owner_id, name),
+    FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (repo_hash) REFERENCES repositories(repo_hash) ON DELETE CASCADE
+);
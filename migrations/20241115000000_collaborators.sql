@@ -0,0 +1,31 @@
+-- migrations/20241115000000_collaborators.sql
+
+-- Users other than the owner who may access a repository. The owner is
+-- never listed here; their access comes from repositories.owner_id.
+CREATE TABLE IF NOT EXISTS repo_collaborators (
+    repo_hash TEXT NOT NULL,
+    user_id INTEGER NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('read', 'write', 'admin')),
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (repo_hash, user_id),
+    FOREIGN KEY (repo_hash) REFERENCES repositories(repo_hash) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_repo_collaborators_user ON repo_collaborators(user_id);
+
+-- Pending invitations; accepting one turns it into a collaborator row
+CREATE TABLE IF NOT EXISTS repo_invitations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    repo_hash TEXT NOT NULL,
+    invitee_id INTEGER NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('read', 'write', 'admin')),
+    inviter_id INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (repo_hash, invitee_id),
+    FOREIGN KEY (repo_hash) REFERENCES repositories(repo_hash) ON DELETE CASCADE,
+    FOREIGN KEY (invitee_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (inviter_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_repo_invitations_invitee ON repo_invitations(invitee_id);
@@ -0,0 +1,86 @@
+-- migrations/20241201000000_organizations.sql
+
+-- Organizations own repositories instead of a personal account. Their
+-- names share the `/:owner` namespace with usernames.
+CREATE TABLE IF NOT EXISTS organizations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    storage_quota INTEGER NOT NULL DEFAULT 1073741824,
+    storage_used INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Owners manage the organization, its teams and its repositories
+CREATE TABLE IF NOT EXISTS org_members (
+    org_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'member')),
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (org_id, user_id),
+    FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_org_members_user ON org_members(user_id);
+
+CREATE TABLE IF NOT EXISTS teams (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    org_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (org_id, name),
+    FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS team_members (
+    team_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    PRIMARY KEY (team_id, user_id),
+    FOREIGN KEY (team_id) REFERENCES teams(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- What each team may do with the organization's repositories
+CREATE TABLE IF NOT EXISTS team_repos (
+    team_id INTEGER NOT NULL,
+    repo_hash TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('read', 'write', 'admin')),
+    PRIMARY KEY (team_id, repo_hash),
+    FOREIGN KEY (team_id) REFERENCES teams(id) ON DELETE CASCADE,
+    FOREIGN KEY (repo_hash) REFERENCES repositories(repo_hash) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_team_repos_repo ON team_repos(repo_hash);
+
+-- Organization repositories keep the creating user in owner_id but are
+-- named within the organization
+ALTER TABLE repositories ADD COLUMN org_id INTEGER REFERENCES organizations(id);
+
+DROP INDEX IF EXISTS idx_repositories_owner_name;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_repositories_owner_name ON repositories(owner_id, name) WHERE org_id IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_repositories_org_name ON repositories(org_id, name) WHERE org_id IS NOT NULL;
+
+-- Redirects from old names follow the same split between personal and
+-- organization namespaces
+CREATE TABLE repo_redirects_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner_id INTEGER,
+    org_id INTEGER,
+    name TEXT NOT NULL,
+    repo_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE,
+    FOREIGN KEY (repo_hash) REFERENCES repositories(repo_hash) ON DELETE CASCADE
+);
+
+INSERT INTO repo_redirects_new (owner_id, name, repo_hash, created_at)
+SELECT owner_id, name, repo_hash, created_at FROM repo_redirects;
+
+DROP TABLE repo_redirects;
+ALTER TABLE repo_redirects_new RENAME TO repo_redirects;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_repo_redirects_owner_name ON repo_redirects(owner_id, name) WHERE org_id IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_repo_redirects_org_name ON repo_redirects(org_id, name) WHERE org_id IS NOT NULL;
@@ -0,0 +1,6 @@
+-- migrations/20241210000000_personal_access_tokens.sql
+
+-- api_keys become personal access tokens. Only a hash of the token is
+-- stored; the prefix lets users tell their tokens apart.
+ALTER TABLE api_keys ADD COLUMN scopes TEXT NOT NULL DEFAULT '';
+ALTER TABLE api_keys ADD COLUMN token_prefix TEXT NOT NULL DEFAULT '';
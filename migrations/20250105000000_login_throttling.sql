@@ -0,0 +1,15 @@
+-- migrations/20250105000000_login_throttling.sql
+
+-- Recent failed logins per account (scope 'account', keyed by lowercased
+-- username) and per client address (scope 'ip'). Rows are cleared on a
+-- successful login or by an admin, and forgotten once stale.
+CREATE TABLE IF NOT EXISTS login_throttles (
+    scope TEXT NOT NULL CHECK (scope IN ('account', 'ip')),
+    subject TEXT NOT NULL,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TEXT NOT NULL,
+    locked_until TEXT,
+    PRIMARY KEY (scope, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_throttles_locked ON login_throttles(locked_until);
@@ -0,0 +1,9 @@
+-- migrations/20250115000000_account_email.sql
+
+-- Set for accounts whose password was generated for them, which must pick
+-- their own before doing anything else
+ALTER TABLE users ADD COLUMN must_change_password INTEGER NOT NULL DEFAULT 0;
+
+-- The bootstrap admin's password was printed to the console and could not
+-- be changed until now
+UPDATE users SET must_change_password = 1 WHERE username = 'admin';
@@ -0,0 +1,6 @@
+-- migrations/20250120000000_login_attempt_counting.sql
+
+-- Login attempts are now counted before the password is checked, in one
+-- statement, so concurrent guesses can't all read the same count. The time
+-- of the attempt before the latest decides whether the latest came too soon.
+ALTER TABLE login_throttles ADD COLUMN previous_failure_at TEXT;
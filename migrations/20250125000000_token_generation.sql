@@ -0,0 +1,5 @@
+-- migrations/20250125000000_token_generation.sql
+
+-- Bumped whenever the password changes. API tokens carry the generation
+-- they were issued in and stop working once it moves on.
+ALTER TABLE users ADD COLUMN token_generation INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+-- migrations/20250130000000_restricted_branch_pushers.sql
+
+-- Set when only the listed users may push to a protected branch. Pushers
+-- leave the list when their accounts are deleted, and an emptied list must
+-- keep meaning nobody rather than anyone with push access.
+ALTER TABLE protected_branches ADD COLUMN restrict_pushes INTEGER NOT NULL DEFAULT 0;
+
+UPDATE protected_branches SET restrict_pushes = 1
+WHERE id IN (SELECT branch_id FROM protected_branch_pushers);
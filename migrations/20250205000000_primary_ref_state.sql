@@ -0,0 +1,6 @@
+-- migrations/20250205000000_primary_ref_state.sql
+
+-- Refs of the primary copy as of the last push or replication, encoded like
+-- replicas.ref_state. A primary restored from a replica is checked against
+-- these, since the primary itself is gone by then.
+ALTER TABLE repositories ADD COLUMN ref_state TEXT;
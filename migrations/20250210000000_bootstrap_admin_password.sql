@@ -0,0 +1,14 @@
+-- migrations/20250210000000_bootstrap_admin_password.sql
+
+-- 20250115000000 flagged any account called 'admin', but only the bootstrap
+-- admin had its password generated. That account is the administrator
+-- seeded at startup with the placeholder address and key; anyone else who
+-- happened to be called 'admin' keeps the password they chose.
+UPDATE users SET must_change_password = 0
+WHERE username = 'admin'
+  AND must_change_password = 1
+  AND NOT (
+      is_admin = 1
+      AND email = 'admin@hyrule.local'
+      AND public_key = 'd289b2da9b7051f36b4e396e0af3e069e78cf119a7fdcb6437b685c4875e9f9e'
+  );
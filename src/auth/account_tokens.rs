@@ -0,0 +1,158 @@
+// src/auth/account_tokens.rs
+//! Signed, expiring tokens for the links in account emails. They are JWTs
+//! signed with the JWT secret like login tokens, but name their purpose and
+//! carry a fingerprint of what they vouch for: a reset link stops working
+//! once the password changes, and a verification link once the email does.
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl Purpose {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Purpose::VerifyEmail => "verify_email",
+            Purpose::ResetPassword => "reset_password",
+        }
+    }
+
+    pub fn lifetime(&self) -> Duration {
+        match self {
+            Purpose::VerifyEmail => Duration::hours(48),
+            Purpose::ResetPassword => Duration::hours(1),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountClaims {
+    sub: i64,
+    purpose: String,
+    /// Fingerprint of the email or password hash the token is bound to
+    fp: String,
+    exp: i64,
+    iat: i64,
+}
+
+/// Fingerprints are keyed by a hash of the secret, so they can't be
+/// matched against known emails without it
+fn fingerprint_key(secret: &str) -> [u8; 32] {
+    *blake3::hash(secret.as_bytes()).as_bytes()
+}
+
+fn fingerprint(key: &[u8; 32], bound_to: &str) -> String {
+    blake3::keyed_hash(key, bound_to.as_bytes()).to_hex()[..32].to_string()
+}
+
+/// A token for `user_id` that is only honoured while `bound_to` is unchanged
+pub fn issue(
+    secret: &str,
+    purpose: Purpose,
+    user_id: i64,
+    bound_to: &str,
+    now: DateTime<Utc>,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = AccountClaims {
+        sub: user_id,
+        purpose: purpose.as_str().to_string(),
+        fp: fingerprint(&fingerprint_key(secret), bound_to),
+        exp: (now + purpose.lifetime()).timestamp(),
+        iat: now.timestamp(),
+    };
+
+    encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+}
+
+/// A token whose signature, purpose and expiry have been checked
+pub struct AccountToken {
+    pub user_id: i64,
+    fingerprint: String,
+    fingerprint_key: [u8; 32],
+}
+
+impl AccountToken {
+    /// Whether the token was issued for this value of what it is bound to
+    pub fn is_bound_to(&self, value: &str) -> bool {
+        use subtle::ConstantTimeEq;
+        self.fingerprint
+            .as_bytes()
+            .ct_eq(fingerprint(&self.fingerprint_key, value).as_bytes())
+            .into()
+    }
+}
+
+/// Check a token from a link. `now` is passed in so tests choose the clock.
+pub fn verify(secret: &str, token: &str, purpose: Purpose, now: DateTime<Utc>) -> Option<AccountToken> {
+    if token.is_empty() || token.len() > 2048 {
+        return None;
+    }
+
+    // Expiry is checked below against `now`
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.validate_exp = false;
+    validation.required_spec_claims.clear();
+
+    let claims = decode::<AccountClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &validation,
+    )
+    .ok()?
+    .claims;
+
+    if claims.purpose != purpose.as_str() || claims.exp <= now.timestamp() || claims.sub <= 0 {
+        return None;
+    }
+
+    Some(AccountToken {
+        user_id: claims.sub,
+        fingerprint: claims.fp,
+        fingerprint_key: fingerprint_key(secret),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test_secret_key_minimum_32_characters_long_12345";
+
+    #[test]
+    fn tokens_are_bound_to_purpose_and_value() {
+        let now = Utc::now();
+        let token = issue(SECRET, Purpose::ResetPassword, 7, "hash-one", now).unwrap();
+
+        let verified = verify(SECRET, &token, Purpose::ResetPassword, now).unwrap();
+        assert_eq!(verified.user_id, 7);
+        assert!(verified.is_bound_to("hash-one"));
+        assert!(!verified.is_bound_to("hash-two"));
+
+        assert!(verify(SECRET, &token, Purpose::VerifyEmail, now).is_none());
+        assert!(verify(SECRET, &format!("{}x", token), Purpose::ResetPassword, now).is_none());
+    }
+
+    #[test]
+    fn tokens_expire() {
+        let now = Utc::now();
+        let token = issue(SECRET, Purpose::ResetPassword, 7, "hash", now).unwrap();
+
+        let later = now + Purpose::ResetPassword.lifetime();
+        assert!(verify(SECRET, &token, Purpose::ResetPassword, later - Duration::seconds(1)).is_some());
+        assert!(verify(SECRET, &token, Purpose::ResetPassword, later).is_none());
+    }
+
+    #[test]
+    fn login_tokens_are_not_account_tokens() {
+        let login = crate::auth::jwt::generate_token(SECRET, 7, "alice", 0).unwrap();
+        assert!(verify(SECRET, &login, Purpose::VerifyEmail, Utc::now()).is_none());
+    }
+}
@@ -1,5 +1,5 @@
 use axum::{
-    extract::{State, Form},
+    extract::{Query, State, Form},
     http::StatusCode,
     response::{Html, Redirect},
 };
@@ -9,7 +9,11 @@ use std::sync::Arc;
 use time::Duration as TimeDuration;
 
 use crate::AppState;
-use crate::auth::session::SessionStore;
+use crate::auth::session::{ClientInfo, SessionStore};
+use crate::services::{accounts, login_throttle, two_factor};
+
+/// Holds the login challenge between the password and the code
+const LOGIN_CHALLENGE_COOKIE: &str = "login_challenge";
 
 #[derive(Debug, Deserialize)]
 pub struct LoginForm {
@@ -20,25 +24,53 @@ pub struct LoginForm {
 pub async fn login_form_handler(
     State(state): State<Arc<AppState>>,
     jar: CookieJar,
+    client: ClientInfo,
     Form(form): Form<LoginForm>,
 ) -> Result<(CookieJar, Redirect), (StatusCode, Html<String>)> {
-    // Find user
-    let user = state.db
-        .get_user_by_username(&form.username)
+    let ip = client.ip_address.as_deref();
+    let now = chrono::Utc::now();
+    login_throttle::begin_attempt(&state.db, &form.username, ip, now)
         .await
-        .map_err(|_| render_login_error("Invalid username or password"))?;
-    
-    // Verify password
-    let is_valid = crate::auth::password::verify_password(&form.password, &user.password_hash)
-        .map_err(|_| render_login_error("Invalid username or password"))?;
-    
-    if !is_valid {
-        return Err(render_login_error("Invalid username or password"));
+        .map_err(|throttled| render_throttled(&throttled))?;
+
+    // Find user and verify password
+    let user = match state.db.get_user_by_username(&form.username).await {
+        Ok(user) if crate::auth::password::verify_password(&form.password, &user.password_hash).unwrap_or(false) => user,
+        _ => {
+            login_throttle::record_failure(&state.db, &form.username, ip, now).await;
+            return Err(render_login_error("Invalid username or password"));
+        }
+    };
+
+    // The password is right but a code is still owed
+    if user.totp_enabled != 0 {
+        let challenge = two_factor::start_login_challenge(&state.db, user.id)
+            .await
+            .map_err(|(_, message)| render_login_error(&message))?;
+        let cookie = Cookie::build((LOGIN_CHALLENGE_COOKIE, challenge))
+            .path("/login")
+            .max_age(TimeDuration::minutes(5))
+            .http_only(true)
+            .build();
+
+        return Ok((jar.add(cookie), Redirect::to("/login/2fa")));
     }
-    
+
+    Ok(start_session(&state, jar, &user, &client).await)
+}
+
+/// Create a session for a fully authenticated user
+async fn start_session(
+    state: &Arc<AppState>,
+    jar: CookieJar,
+    user: &crate::models::User,
+    client: &ClientInfo,
+) -> (CookieJar, Redirect) {
+    login_throttle::record_success(&state.db, user, client.ip_address.as_deref()).await;
+
     // Create session
     let session_id = state.session_store
-        .create_session(user.id, user.username.clone())
+        .create_session(user.id, user.username.clone(), client)
         .await;
     
     // Set cookie (HttpOnly, Secure in production)
@@ -47,8 +79,60 @@ pub async fn login_form_handler(
         .max_age(TimeDuration::days(7))
         .http_only(true)
         .build();
-    
-    Ok((jar.add(cookie), Redirect::to("/dashboard")))
+
+    (jar.add(cookie), Redirect::to(next_page(state, user).await))
+}
+
+/// Where a newly signed in user goes: a generated password is replaced
+/// first, then admins who must use two-factor set it up
+async fn next_page(state: &Arc<AppState>, user: &crate::models::User) -> &'static str {
+    if user.must_change_password != 0 {
+        "/change-password"
+    } else if two_factor::must_enroll(&state.db, user).await {
+        "/profile/2fa"
+    } else {
+        "/dashboard"
+    }
+}
+
+/// Second login step, asking for the authenticator or a recovery code
+pub async fn two_factor_page(jar: CookieJar) -> Result<Html<String>, Redirect> {
+    if jar.get(LOGIN_CHALLENGE_COOKIE).is_none() {
+        return Err(Redirect::to("/login"));
+    }
+
+    Ok(Html(crate::templates::login::render_two_factor(None)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorForm {
+    pub code: String,
+}
+
+pub async fn two_factor_handler(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Form(form): Form<TwoFactorForm>,
+) -> Result<(CookieJar, Redirect), (StatusCode, Html<String>)> {
+    let challenge = jar
+        .get(LOGIN_CHALLENGE_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .ok_or_else(|| render_login_error("Your login expired, please sign in again"))?;
+
+    let user = two_factor::complete_login_challenge(
+        &state.db,
+        &challenge,
+        &form.code,
+        chrono::Utc::now().timestamp(),
+    )
+    .await
+    .map_err(|(status, message)| {
+        (status, Html(crate::templates::login::render_two_factor(Some(&message))))
+    })?;
+
+    let jar = jar.remove(Cookie::build(LOGIN_CHALLENGE_COOKIE).path("/login"));
+    Ok(start_session(&state, jar, &user, &client).await)
 }
 
 pub async fn logout_handler(
@@ -90,17 +174,24 @@ fn render_login_error(message: &str) -> (StatusCode, Html<String>) {
 #[derive(Debug, Deserialize)]
 pub struct SignupForm {
     pub username: String,
+    pub email: Option<String>,
     pub password: String,
 }
 
 pub async fn signup_form_handler(
     State(state): State<Arc<AppState>>,
     jar: CookieJar,
+    client: ClientInfo,
     Form(form): Form<SignupForm>,
 ) -> Result<(CookieJar, Redirect), (StatusCode, Html<String>)> {
     // Validate
-    if form.username.len() < 3 || form.username.len() > 32 {
-        return Err(render_signup_error("Username must be 3-32 characters"));
+    // Usernames become the first segment of repository URLs
+    if let Err(e) = crate::utils::validation::validate_username(&form.username) {
+        return Err(render_signup_error(e));
+    }
+    // Organizations share that namespace
+    if state.db.organization_name_taken(&form.username).await.unwrap_or(false) {
+        return Err(render_signup_error("Username already taken"));
     }
     
     if form.password.len() < 8 {
@@ -111,9 +202,12 @@ pub async fn signup_form_handler(
     let password_hash = crate::auth::password::hash_password(&form.password)
         .map_err(|_| render_signup_error("Password hashing failed"))?;
     
+    let email = accounts::signup_email(&state.db, &form.username, form.email.as_deref())
+        .await
+        .map_err(|(_, message)| render_signup_error(&message))?;
+
     // Generate public key
     let public_key = hex::encode(blake3::hash(form.username.as_bytes()).as_bytes());
-    let email = format!("{}@hyrule.local", form.username);
     
     let user_req = crate::models::CreateUserRequest {
         username: form.username.clone(),
@@ -135,9 +229,11 @@ pub async fn signup_form_handler(
             }
         })?;
     
+    accounts::spawn_verification(state.mailer.clone(), state.config.site_origin.clone(), state.config.jwt_secret.clone(), user.clone());
+
     // Create session
     let session_id = state.session_store
-        .create_session(user.id, user.username.clone())
+        .create_session(user.id, user.username.clone(), &client)
         .await;
     
     // Set cookie
@@ -150,6 +246,161 @@ pub async fn signup_form_handler(
     Ok((jar.add(cookie), Redirect::to("/dashboard")))
 }
 
+pub async fn forgot_password_page() -> Html<String> {
+    Html(crate::templates::password::render_forgot(false, None))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordForm {
+    pub email: String,
+}
+
+pub async fn forgot_password_handler(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<ForgotPasswordForm>,
+) -> Html<String> {
+    // Sent in the background so the response time doesn't give away
+    // whether the address has an account
+    let state = state.clone();
+    tokio::spawn(async move {
+        accounts::request_password_reset(
+            &state.db,
+            state.mailer.as_ref(),
+            &state.config.site_origin,
+            &state.config.jwt_secret,
+            &form.email,
+            chrono::Utc::now(),
+        )
+        .await;
+    });
+
+    Html(crate::templates::password::render_forgot(true, None))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenQuery {
+    pub token: String,
+}
+
+pub async fn reset_password_page(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TokenQuery>,
+) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    accounts::check_reset_token(&state.db, &state.config.jwt_secret, &query.token, chrono::Utc::now())
+        .await
+        .map_err(|(status, message)| {
+            (status, Html(crate::templates::password::render_forgot(false, Some(&message))))
+        })?;
+
+    Ok(Html(crate::templates::password::render_reset(&query.token, None)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordForm {
+    pub token: String,
+    pub new_password: String,
+    pub confirm_password: String,
+}
+
+pub async fn reset_password_handler(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<ResetPasswordForm>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let reset_error = |status: StatusCode, message: &str| {
+        (status, Html(crate::templates::password::render_reset(&form.token, Some(message))))
+    };
+    if form.new_password != form.confirm_password {
+        return Err(reset_error(StatusCode::BAD_REQUEST, "Passwords don't match"));
+    }
+
+    let user = accounts::reset_password(&state.db, &state.config.jwt_secret, &form.token, &form.new_password, chrono::Utc::now())
+        .await
+        .map_err(|(status, message)| reset_error(status, &message))?;
+    // Whoever knew the old password is signed out everywhere
+    state.session_store.revoke_other_sessions(user.id, None).await;
+
+    Ok(Redirect::to("/login?success=Password changed. Log in with your new password."))
+}
+
+pub async fn verify_email_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TokenQuery>,
+) -> (StatusCode, Html<String>) {
+    match accounts::verify_email(&state.db, &state.config.jwt_secret, &query.token, chrono::Utc::now()).await {
+        Ok(user) => (
+            StatusCode::OK,
+            Html(crate::templates::password::render_verified(Ok(&user.email))),
+        ),
+        Err((status, message)) => (
+            status,
+            Html(crate::templates::password::render_verified(Err(&message))),
+        ),
+    }
+}
+
+/// The signed in user and their session key
+async fn session_user(
+    state: &Arc<AppState>,
+    jar: &CookieJar,
+) -> Option<(crate::models::User, String)> {
+    let session_id = jar.get("session_id")?.value().to_string();
+    let session = state.session_store.get_session(&session_id).await?;
+    let user = state.db.get_user_by_id(session.user_id).await.ok()?;
+    Some((user, SessionStore::session_key(&session_id)))
+}
+
+/// Replace a generated password, which signed in users must do first
+pub async fn change_password_page(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Result<Html<String>, Redirect> {
+    let (user, _) = session_user(&state, &jar).await.ok_or(Redirect::to("/login"))?;
+    if user.must_change_password == 0 {
+        return Err(Redirect::to("/profile/settings"));
+    }
+
+    Ok(Html(crate::templates::password::render_change(&user.username, None)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordForm {
+    pub current_password: String,
+    pub new_password: String,
+    pub confirm_password: String,
+}
+
+pub async fn change_password_handler(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<ChangePasswordForm>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (user, session_key) = session_user(&state, &jar)
+        .await
+        .ok_or_else(|| render_login_error("Please log in first"))?;
+    let change_error = |status: StatusCode, message: &str| {
+        (status, Html(crate::templates::password::render_change(&user.username, Some(message))))
+    };
+    if form.new_password != form.confirm_password {
+        return Err(change_error(StatusCode::BAD_REQUEST, "Passwords don't match"));
+    }
+
+    accounts::change_password(&state.db, &user, &form.current_password, &form.new_password)
+        .await
+        .map_err(|(status, message)| change_error(status, &message))?;
+    state
+        .session_store
+        .revoke_other_sessions(user.id, Some(&session_key))
+        .await;
+
+    let user = state.db.get_user_by_id(user.id).await.unwrap_or(user);
+    Ok(Redirect::to(next_page(&state, &user).await))
+}
+
+fn render_throttled(throttled: &login_throttle::Throttled) -> (StatusCode, Html<String>) {
+    let (_, page) = render_login_error(&throttled.message());
+    (StatusCode::TOO_MANY_REQUESTS, page)
+}
+
 fn render_signup_error(message: &str) -> (StatusCode, Html<String>) {
     let content = format!(
         r#"
@@ -1,17 +1,15 @@
 // src/auth/jwt.rs - Enhanced Security Version
 use crate::auth::Claims;
+use crate::db::Database;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation, Algorithm};
-use std::env;
 
 const TOKEN_EXPIRY_HOURS: i64 = 24;
 const MAX_TOKEN_AGE_HOURS: i64 = 48; // Maximum acceptable token age
 
-pub fn generate_token(user_id: i64, username: &str) -> Result<String, jsonwebtoken::errors::Error> {
-    // CRITICAL: JWT_SECRET must be set - no fallback
-    let secret = env::var("JWT_SECRET")
-        .expect("CRITICAL: JWT_SECRET environment variable must be set. Generate with: openssl rand -base64 32");
-
+/// `secret` is `Config::jwt_secret`. `token_generation` is the user's
+/// current one; changing the password moves it on and so invalidates the token
+pub fn generate_token(secret: &str, user_id: i64, username: &str, token_generation: i64) -> Result<String, jsonwebtoken::errors::Error> {
     // Validate secret strength
     if secret.len() < 32 {
         panic!("JWT_SECRET must be at least 32 characters long for security");
@@ -26,6 +24,7 @@ pub fn generate_token(user_id: i64, username: &str) -> Result<String, jsonwebtok
         username: username.to_string(),
         exp,
         iat,
+        gen: token_generation,
     };
 
     encode(
@@ -35,15 +34,12 @@ pub fn generate_token(user_id: i64, username: &str) -> Result<String, jsonwebtok
     )
 }
 
-pub fn validate_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+pub fn validate_token(secret: &str, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
     // Validate token format before processing
     if token.is_empty() || token.len() > 2048 {
         return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into());
     }
 
-    let secret = env::var("JWT_SECRET")
-        .expect("JWT_SECRET environment variable must be set");
-
     // Use strict validation
     let mut validation = Validation::new(Algorithm::HS256);
     validation.leeway = 0; // No leeway for exp/nbf/iat
@@ -80,16 +76,28 @@ pub fn validate_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error
     Ok(claims)
 }
 
+/// Validate a token and check it was issued since the user's password last
+/// changed
+pub async fn authenticate(db: &Database, secret: &str, token: &str) -> Option<Claims> {
+    let claims = validate_token(secret, token).ok()?;
+    let user = db.get_user_by_id(claims.sub).await.ok()?;
+    if user.token_generation != claims.gen {
+        return None;
+    }
+
+    Some(claims)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SECRET: &str = "test_secret_key_minimum_32_characters_long_12345";
+
     #[test]
     fn test_token_validation() {
-        std::env::set_var("JWT_SECRET", "test_secret_key_minimum_32_characters_long_12345");
-        
-        let token = generate_token(1, "testuser").unwrap();
-        let claims = validate_token(&token).unwrap();
+        let token = generate_token(SECRET, 1, "testuser", 0).unwrap();
+        let claims = validate_token(SECRET, &token).unwrap();
         
         assert_eq!(claims.sub, 1);
         assert_eq!(claims.username, "testuser");
@@ -97,10 +105,8 @@ mod tests {
 
     #[test]
     fn test_invalid_username() {
-        std::env::set_var("JWT_SECRET", "test_secret_key_minimum_32_characters_long_12345");
-        
         // Token with invalid characters should fail
-        let token = generate_token(1, "test<script>").unwrap();
-        assert!(validate_token(&token).is_err());
+        let token = generate_token(SECRET, 1, "test<script>", 0).unwrap();
+        assert!(validate_token(SECRET, &token).is_err());
     }
 }
@@ -19,9 +19,13 @@ pub async fn require_auth(
     if let Some(auth) = auth_header {
         if auth.starts_with("Bearer ") {
             let token = &auth[7..];
+            let state = request
+                .extensions()
+                .get::<std::sync::Arc<crate::AppState>>()
+                .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
             
             // Validate token
-            match crate::auth::jwt::validate_token(token) {
+            match crate::auth::jwt::validate_token(&state.config.jwt_secret, token) {
                 Ok(_) => return Ok(next.run(request).await),
                 Err(_) => return Err(StatusCode::UNAUTHORIZED),
             }
@@ -44,7 +48,11 @@ pub async fn optional_auth(
     if let Some(auth) = auth_header {
         if auth.starts_with("Bearer ") {
             let token = &auth[7..];
-            if let Ok(claims) = crate::auth::jwt::validate_token(token) {
+            let claims = request
+                .extensions()
+                .get::<std::sync::Arc<crate::AppState>>()
+                .and_then(|state| crate::auth::jwt::validate_token(&state.config.jwt_secret, token).ok());
+            if let Some(claims) = claims {
                 // You could add user info to request extensions here
                 request.extensions_mut().insert(claims);
             }
@@ -1,9 +1,13 @@
 // src/auth/mod.rs
+pub mod account_tokens;
 pub mod jwt;
 pub mod password;
 pub mod middleware;
 pub mod auth_session;
 pub mod session;
+pub mod repo_access;
+pub mod tokens;
+pub mod totp;
 
 use axum::{
     async_trait,
@@ -38,6 +42,11 @@ where
             .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
         
         if user.is_admin != 0 {
+            if user.must_change_password != 0
+                || crate::services::two_factor::must_enroll(&state.db, &user).await
+            {
+                return Err(axum::http::StatusCode::FORBIDDEN);
+            }
             Ok(AdminUser {
                 id: auth_user.id,
                 username: auth_user.username,
@@ -53,6 +62,9 @@ pub struct Claims {
     pub username: String,
     pub exp: usize,
     pub iat: usize,
+    /// The user's token generation when this was issued
+    #[serde(default)]
+    pub gen: i64,
 }
 
 #[derive(Debug)]
@@ -81,8 +93,17 @@ where
         }
 
         let token = &auth_header[7..];
-        let claims = jwt::validate_token(token)
-            .map_err(|_| axum::http::StatusCode::UNAUTHORIZED)?;
+        if token.starts_with(tokens::TOKEN_PREFIX) {
+            return token_user(parts, token)
+                .await
+                .ok_or(axum::http::StatusCode::UNAUTHORIZED);
+        }
+
+        let state = parts.extensions.get::<Arc<crate::AppState>>()
+            .ok_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
+        let claims = jwt::authenticate(&state.db, &state.config.jwt_secret, token)
+            .await
+            .ok_or(axum::http::StatusCode::UNAUTHORIZED)?;
 
         Ok(AuthUser {
             id: claims.sub,
@@ -91,6 +112,21 @@ where
     }
 }
 
+/// The owner of a personal access token, if it may use the API
+async fn token_user(parts: &Parts, token: &str) -> Option<AuthUser> {
+    let state = parts.extensions.get::<Arc<crate::AppState>>()?;
+    let key = tokens::authenticate(&state.db, token).await?;
+    if !tokens::has_scope(&key, tokens::TokenScope::Admin) {
+        return None;
+    }
+    let user = state.db.get_user_by_id(key.user_id).await.ok()?;
+
+    Some(AuthUser {
+        id: user.id,
+        username: user.username,
+    })
+}
+
 // Optional authentication - doesn't fail if not authenticated
 #[derive(Debug)]
 pub struct OptionalAuthUser(pub Option<AuthUser>);
@@ -111,7 +147,13 @@ where
         if let Some(auth) = auth_header {
             if auth.starts_with("Bearer ") {
                 let token = &auth[7..];
-                if let Ok(claims) = jwt::validate_token(token) {
+                if token.starts_with(tokens::TOKEN_PREFIX) {
+                    return Ok(OptionalAuthUser(token_user(parts, token).await));
+                }
+                let Some(state) = parts.extensions.get::<Arc<crate::AppState>>() else {
+                    return Ok(OptionalAuthUser(None));
+                };
+                if let Some(claims) = jwt::authenticate(&state.db, &state.config.jwt_secret, token).await {
                     return Ok(OptionalAuthUser(Some(AuthUser {
                         id: claims.sub,
                         username: claims.username,
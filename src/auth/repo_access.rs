@@ -0,0 +1,159 @@
+// src/auth/repo_access.rs
+//! Who may do what with a repository. Every git, browser and API handler
+//! goes through `authorize` so owners, collaborators, organization teams
+//! and anonymous users are treated the same everywhere.
+use axum::http::StatusCode;
+use serde::Serialize;
+
+use crate::db::Database;
+use crate::models::{RepoInvitation, Repository};
+
+/// Permission levels on a repository, weakest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoRole {
+    /// Browse and clone
+    Read,
+    /// Push
+    Write,
+    /// Change settings and manage collaborators
+    Admin,
+    /// Delete the repository; only ever the owner, or an owner of the
+    /// organization it belongs to
+    Owner,
+}
+
+impl RepoRole {
+    /// Roles that can be granted to a collaborator
+    pub const GRANTABLE: [RepoRole; 3] = [RepoRole::Read, RepoRole::Write, RepoRole::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepoRole::Read => "read",
+            RepoRole::Write => "write",
+            RepoRole::Admin => "admin",
+            RepoRole::Owner => "owner",
+        }
+    }
+
+    /// Parse a collaborator role; `owner` is not one
+    pub fn parse_grantable(role: &str) -> Option<Self> {
+        Self::GRANTABLE.into_iter().find(|r| r.as_str() == role)
+    }
+}
+
+/// The strongest role `user_id` has on the repository, or None if they
+/// cannot see it. Anyone, signed in or not, can read a public repository.
+/// In an organization every member can read, owners own, and teams and
+/// collaborators grant whatever they were given.
+pub async fn repo_role(
+    db: &Database,
+    repo: &Repository,
+    user_id: Option<i64>,
+) -> Result<Option<RepoRole>, sqlx::Error> {
+    let public = (repo.is_private == 0).then_some(RepoRole::Read);
+    let Some(id) = user_id else {
+        return Ok(public);
+    };
+
+    let membership = match repo.org_id {
+        Some(org_id) => match db.get_org_role(org_id, id).await?.as_deref() {
+            Some("owner") => return Ok(Some(RepoRole::Owner)),
+            Some(_) => Some(RepoRole::Read),
+            None => None,
+        },
+        None if id == repo.owner_id => return Ok(Some(RepoRole::Owner)),
+        None => None,
+    };
+
+    let collaborator = db
+        .get_collaborator_role(&repo.repo_hash, id)
+        .await?
+        .and_then(|role| RepoRole::parse_grantable(&role));
+    let team = match repo.org_id {
+        Some(_) => db
+            .get_team_repo_roles(&repo.repo_hash, id)
+            .await?
+            .iter()
+            .filter_map(|role| RepoRole::parse_grantable(role))
+            .max(),
+        None => None,
+    };
+
+    Ok([public, membership, collaborator, team].into_iter().flatten().max())
+}
+
+/// Check that the user has at least `needed` on the repository and return
+/// their actual role. Anonymous users get UNAUTHORIZED so clients know to
+/// retry with credentials; signed-in users without access get FORBIDDEN.
+pub async fn authorize(
+    db: &Database,
+    repo: &Repository,
+    user_id: Option<i64>,
+    needed: RepoRole,
+) -> Result<RepoRole, StatusCode> {
+    let role = repo_role(db, repo, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    match role {
+        Some(role) if role >= needed => Ok(role),
+        _ if user_id.is_none() => Err(StatusCode::UNAUTHORIZED),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// Invite `username` to collaborate on the repository. The invitee gets
+/// access once they accept.
+pub async fn invite_collaborator(
+    db: &Database,
+    repo: &Repository,
+    inviter_id: i64,
+    username: &str,
+    role: &str,
+) -> Result<RepoInvitation, (StatusCode, String)> {
+    let role = RepoRole::parse_grantable(role).ok_or_else(|| {
+        (StatusCode::BAD_REQUEST, "Role must be read, write or admin".to_string())
+    })?;
+    let invitee = db
+        .get_user_by_username(username.trim())
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, format!("Unknown user {}", username.trim())))?;
+
+    if repo.org_id.is_none() && invitee.id == repo.owner_id {
+        return Err((StatusCode::BAD_REQUEST, "The owner already has full access".to_string()));
+    }
+    let existing = db
+        .get_collaborator_role(&repo.repo_hash, invitee.id)
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to invite collaborator".to_string()))?;
+    if existing.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{} is already a collaborator", invitee.username),
+        ));
+    }
+
+    db.create_invitation(&repo.repo_hash, invitee.id, role.as_str(), inviter_id)
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to invite collaborator".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_are_ordered_by_strength() {
+        assert!(RepoRole::Read < RepoRole::Write);
+        assert!(RepoRole::Write < RepoRole::Admin);
+        assert!(RepoRole::Admin < RepoRole::Owner);
+    }
+
+    #[test]
+    fn owner_cannot_be_granted() {
+        assert_eq!(RepoRole::parse_grantable("write"), Some(RepoRole::Write));
+        assert_eq!(RepoRole::parse_grantable("owner"), None);
+        assert_eq!(RepoRole::parse_grantable("Admin"), None);
+    }
+}
@@ -2,74 +2,289 @@
 use axum::{
     async_trait,
     extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    http::{header, request::Parts, StatusCode},
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use chrono::{DateTime, Utc, Duration};
 use rand::rngs::OsRng;
 use rand::RngCore;
 
+use crate::db::Database;
+
+/// How long a session lasts after login
+const SESSION_LIFETIME_HOURS: i64 = 24;
+/// `last_seen` is only written when it is older than this, so browsing
+/// does not turn every request into a database write
+const LAST_SEEN_RESOLUTION_SECS: i64 = 60;
+const MAX_USER_AGENT_LEN: usize = 256;
+
 fn generate_session_id() -> String {
     let mut random_bytes = [0u8; 32];
     OsRng.fill_bytes(&mut random_bytes);
     hex::encode(random_bytes)
 }
-#[derive(Debug, Clone, Serialize, Deserialize)]
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Session {
     pub user_id: i64,
     pub username: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// A session as listed to its owner. `key` identifies it without being
+/// usable as a cookie.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ActiveSession {
+    pub key: String,
+    #[sqlx(flatten)]
+    pub session: Session,
+}
+
+/// Where a request came from, recorded on the sessions it creates
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip_address = crate::middleware::rate_limit::client_ip(&parts.headers, &parts.extensions);
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|agent| agent.chars().take(MAX_USER_AGENT_LEN).collect());
+
+        Ok(ClientInfo { ip_address, user_agent })
+    }
+}
+
+/// Where sessions are kept. Backends are addressed by session key, the
+/// hash of the cookie value, so a leaked store cannot be replayed.
+#[async_trait]
+pub trait SessionBackend: Send + Sync {
+    async fn insert(&self, key: &str, session: &Session);
+    async fn get(&self, key: &str) -> Option<Session>;
+    async fn touch(&self, key: &str, last_seen: DateTime<Utc>);
+    async fn remove(&self, key: &str);
+    /// Unexpired sessions of the user, most recently used first
+    async fn list_for_user(&self, user_id: i64) -> Vec<ActiveSession>;
+    async fn remove_expired(&self, now: DateTime<Utc>);
+}
+
+/// Sessions held in process memory. They are lost on restart and not
+/// shared between instances.
+#[derive(Default)]
+pub struct MemorySessionBackend {
+    sessions: RwLock<HashMap<String, Session>>,
+}
+
+#[async_trait]
+impl SessionBackend for MemorySessionBackend {
+    async fn insert(&self, key: &str, session: &Session) {
+        self.sessions.write().await.insert(key.to_string(), session.clone());
+    }
+
+    async fn get(&self, key: &str) -> Option<Session> {
+        self.sessions.read().await.get(key).cloned()
+    }
+
+    async fn touch(&self, key: &str, last_seen: DateTime<Utc>) {
+        if let Some(session) = self.sessions.write().await.get_mut(key) {
+            session.last_seen = last_seen;
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        self.sessions.write().await.remove(key);
+    }
+
+    async fn list_for_user(&self, user_id: i64) -> Vec<ActiveSession> {
+        let now = Utc::now();
+        let mut sessions: Vec<ActiveSession> = self
+            .sessions
+            .read()
+            .await
+            .iter()
+            .filter(|(_, session)| session.user_id == user_id && session.expires_at > now)
+            .map(|(key, session)| ActiveSession {
+                key: key.clone(),
+                session: session.clone(),
+            })
+            .collect();
+        sessions.sort_by_key(|active| std::cmp::Reverse(active.session.last_seen));
+        sessions
+    }
+
+    async fn remove_expired(&self, now: DateTime<Utc>) {
+        self.sessions.write().await.retain(|_, session| {
+            session.expires_at > now
+        });
+    }
+}
+
+/// Sessions in the `sessions` table, surviving restarts and shared by
+/// every instance using the same database
+pub struct SqliteSessionBackend {
+    db: Database,
+}
+
+impl SqliteSessionBackend {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl SessionBackend for SqliteSessionBackend {
+    async fn insert(&self, key: &str, session: &Session) {
+        if let Err(e) = self.db.insert_session(key, session).await {
+            tracing::error!("Failed to store session: {}", e);
+        }
+    }
+
+    async fn get(&self, key: &str) -> Option<Session> {
+        match self.db.get_session(key).await {
+            Ok(session) => session,
+            Err(e) => {
+                tracing::error!("Failed to load session: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn touch(&self, key: &str, last_seen: DateTime<Utc>) {
+        if let Err(e) = self.db.touch_session(key, last_seen).await {
+            tracing::warn!("Failed to update session: {}", e);
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        if let Err(e) = self.db.delete_session(key).await {
+            tracing::error!("Failed to delete session: {}", e);
+        }
+    }
+
+    async fn list_for_user(&self, user_id: i64) -> Vec<ActiveSession> {
+        self.db
+            .list_user_sessions(user_id, Utc::now())
+            .await
+            .unwrap_or_default()
+    }
+
+    async fn remove_expired(&self, now: DateTime<Utc>) {
+        if let Err(e) = self.db.delete_expired_sessions(now).await {
+            tracing::warn!("Failed to clean up sessions: {}", e);
+        }
+    }
 }
 
-// In-memory session store (in production, use Redis or database)
+/// Session handling on top of a backend: creating ids, expiry and
+/// recording activity
 pub struct SessionStore {
-    sessions: Arc<RwLock<HashMap<String, Session>>>,
+    backend: Arc<dyn SessionBackend>,
 }
 
 impl SessionStore {
+    /// A store keeping sessions in memory
     pub fn new() -> Self {
-        Self {
-            sessions: Arc::new(RwLock::new(HashMap::new())),
-        }
+        Self::with_backend(Arc::new(MemorySessionBackend::default()))
     }
-    
-    pub async fn create_session(&self, user_id: i64, username: String) -> String {
+
+    pub fn with_backend(backend: Arc<dyn SessionBackend>) -> Self {
+        Self { backend }
+    }
+
+    /// The key a session is stored under
+    pub fn session_key(session_id: &str) -> String {
+        blake3::hash(session_id.as_bytes()).to_hex().to_string()
+    }
+
+    pub async fn create_session(&self, user_id: i64, username: String, client: &ClientInfo) -> String {
         let session_id = generate_session_id();
+        let now = Utc::now();
         let session = Session {
             user_id,
             username,
-            expires_at: Utc::now() + Duration::hours(24),
+            created_at: now,
+            last_seen: now,
+            expires_at: now + Duration::hours(SESSION_LIFETIME_HOURS),
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
         };
-        
-        self.sessions.write().await.insert(session_id.clone(), session);
+
+        self.backend.insert(&Self::session_key(&session_id), &session).await;
         session_id
     }
-    
+
     pub async fn get_session(&self, session_id: &str) -> Option<Session> {
-        let sessions = self.sessions.read().await;
-        let session = sessions.get(session_id)?;
-        
+        let key = Self::session_key(session_id);
+        let mut session = self.backend.get(&key).await?;
+
         // Check if expired
-        if session.expires_at < Utc::now() {
+        let now = Utc::now();
+        if session.expires_at < now {
             return None;
         }
-        
-        Some(session.clone())
+
+        if now - session.last_seen >= Duration::seconds(LAST_SEEN_RESOLUTION_SECS) {
+            self.backend.touch(&key, now).await;
+            session.last_seen = now;
+        }
+
+        Some(session)
     }
-    
+
     pub async fn delete_session(&self, session_id: &str) {
-        self.sessions.write().await.remove(session_id);
+        self.backend.remove(&Self::session_key(session_id)).await;
+    }
+
+    pub async fn list_user_sessions(&self, user_id: i64) -> Vec<ActiveSession> {
+        self.backend.list_for_user(user_id).await
+    }
+
+    /// End one of the user's sessions by key. Returns false if the user
+    /// has no such session.
+    pub async fn revoke_session(&self, user_id: i64, key: &str) -> bool {
+        match self.backend.get(key).await {
+            Some(session) if session.user_id == user_id => {
+                self.backend.remove(key).await;
+                true
+            }
+            _ => false,
+        }
     }
-    
+
+    /// End every session of the user except the one with `keep_key`
+    pub async fn revoke_other_sessions(&self, user_id: i64, keep_key: Option<&str>) -> usize {
+        let mut revoked = 0;
+        for active in self.backend.list_for_user(user_id).await {
+            if Some(active.key.as_str()) != keep_key {
+                self.backend.remove(&active.key).await;
+                revoked += 1;
+            }
+        }
+        revoked
+    }
+
     pub async fn cleanup_expired(&self) {
-        let now = Utc::now();
-        self.sessions.write().await.retain(|_, session| {
-            session.expires_at > now
-        });
+        self.backend.remove_expired(Utc::now()).await;
     }
 }
 
@@ -143,3 +358,79 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDb;
+
+    async fn sqlite_db() -> (TestDb, i64) {
+        let db = TestDb::new().await;
+        let user = db.add_named_user("alice").await;
+        (db, user.id)
+    }
+
+    fn client(agent: &str) -> ClientInfo {
+        ClientInfo {
+            ip_address: Some("192.0.2.7".to_string()),
+            user_agent: Some(agent.to_string()),
+        }
+    }
+
+    async fn exercise(store: &SessionStore, user_id: i64) {
+        let laptop = store.create_session(user_id, "alice".to_string(), &client("laptop")).await;
+        let phone = store.create_session(user_id, "alice".to_string(), &client("phone")).await;
+
+        let session = store.get_session(&laptop).await.unwrap();
+        assert_eq!(session.user_id, user_id);
+        assert_eq!(session.username, "alice");
+        assert_eq!(session.ip_address.as_deref(), Some("192.0.2.7"));
+        assert_eq!(session.user_agent.as_deref(), Some("laptop"));
+        assert!(store.get_session("not-a-session").await.is_none());
+
+        let sessions = store.list_user_sessions(user_id).await;
+        assert_eq!(sessions.len(), 2);
+        // Listings never expose the cookie value
+        assert!(sessions.iter().all(|s| s.key != laptop && s.key != phone));
+
+        let phone_key = SessionStore::session_key(&phone);
+        assert!(!store.revoke_session(user_id + 1, &phone_key).await);
+        assert!(store.revoke_session(user_id, &phone_key).await);
+        assert!(store.get_session(&phone).await.is_none());
+        assert!(store.get_session(&laptop).await.is_some());
+
+        store.create_session(user_id, "alice".to_string(), &client("tablet")).await;
+        let laptop_key = SessionStore::session_key(&laptop);
+        assert_eq!(store.revoke_other_sessions(user_id, Some(&laptop_key)).await, 1);
+        assert_eq!(store.list_user_sessions(user_id).await.len(), 1);
+
+        store.delete_session(&laptop).await;
+        assert!(store.get_session(&laptop).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn memory_backend() {
+        exercise(&SessionStore::new(), 1).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_backend() {
+        let (db, user_id) = sqlite_db().await;
+        exercise(&SessionStore::with_backend(Arc::new(SqliteSessionBackend::new(db.clone()))), user_id).await;
+    }
+
+    #[tokio::test]
+    async fn sqlite_sessions_survive_restart_and_expire() {
+        let (db, user_id) = sqlite_db().await;
+        let session_id = SessionStore::with_backend(Arc::new(SqliteSessionBackend::new(db.clone())))
+            .create_session(user_id, "alice".to_string(), &ClientInfo::default())
+            .await;
+
+        let backend = Arc::new(SqliteSessionBackend::new(db.clone()));
+        let store = SessionStore::with_backend(backend.clone());
+        assert_eq!(store.get_session(&session_id).await.unwrap().user_id, user_id);
+
+        backend.remove_expired(Utc::now() + Duration::hours(SESSION_LIFETIME_HOURS + 1)).await;
+        assert!(store.get_session(&session_id).await.is_none());
+    }
+}
@@ -0,0 +1,196 @@
+// src/auth/tokens.rs
+//! Personal access tokens for git clients and scripts that cannot hold a
+//! session. Tokens look like `hyr_<40 hex>`; only their BLAKE3 hash is
+//! stored, and their scopes cap what they can do whatever the owner's role.
+use axum::http::StatusCode;
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+
+use crate::auth::repo_access::RepoRole;
+use crate::db::Database;
+use crate::models::ApiKey;
+
+/// Every token starts with this, so they are easy to spot in leaked logs
+pub const TOKEN_PREFIX: &str = "hyr_";
+/// Characters of the token kept for display
+const DISPLAY_PREFIX_LEN: usize = 12;
+const MAX_NAME_LEN: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenScope {
+    /// Clone and fetch repositories the owner can read
+    RepoRead,
+    /// Also push where the owner can write
+    RepoWrite,
+    /// Everything the owner can do, including the REST API
+    Admin,
+}
+
+impl TokenScope {
+    pub const ALL: [TokenScope; 3] = [TokenScope::RepoRead, TokenScope::RepoWrite, TokenScope::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::RepoRead => "repo:read",
+            TokenScope::RepoWrite => "repo:write",
+            TokenScope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str() == scope)
+    }
+
+    /// The strongest repository role the scope allows
+    fn repo_role(&self) -> RepoRole {
+        match self {
+            TokenScope::RepoRead => RepoRole::Read,
+            TokenScope::RepoWrite => RepoRole::Write,
+            TokenScope::Admin => RepoRole::Owner,
+        }
+    }
+}
+
+fn scopes(key: &ApiKey) -> impl Iterator<Item = TokenScope> + '_ {
+    key.scopes.split_whitespace().filter_map(TokenScope::parse)
+}
+
+/// The strongest repository role a token may use, or None if it has no
+/// repository scope at all
+pub fn max_repo_role(key: &ApiKey) -> Option<RepoRole> {
+    scopes(key).map(|s| s.repo_role()).max()
+}
+
+pub fn has_scope(key: &ApiKey, scope: TokenScope) -> bool {
+    scopes(key).any(|s| s == scope)
+}
+
+pub fn hash_token(token: &str) -> String {
+    blake3::hash(token.as_bytes()).to_hex().to_string()
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", TOKEN_PREFIX, hex::encode(bytes))
+}
+
+/// Create a token for the user. Returns the stored key and the token,
+/// which cannot be recovered later.
+pub async fn create_token(
+    db: &Database,
+    user_id: i64,
+    name: &str,
+    requested: &[String],
+    expires_in_days: Option<i64>,
+) -> Result<(ApiKey, String), (StatusCode, String)> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err((StatusCode::BAD_REQUEST, "Token name must be 1-100 characters".to_string()));
+    }
+
+    let mut scopes = Vec::new();
+    for scope in requested {
+        let scope = TokenScope::parse(scope.trim())
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unknown scope {}", scope.trim())))?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    if scopes.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Choose at least one scope".to_string()));
+    }
+    if expires_in_days.is_some_and(|days| !(1..=3650).contains(&days)) {
+        return Err((StatusCode::BAD_REQUEST, "Expiry must be between 1 and 3650 days".to_string()));
+    }
+
+    let token = generate_token();
+    let scopes = scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ");
+    let key = db
+        .create_api_key(
+            user_id,
+            name,
+            &hash_token(&token),
+            &token[..DISPLAY_PREFIX_LEN],
+            &scopes,
+            expires_in_days,
+        )
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create token".to_string()))?;
+
+    Ok((key, token))
+}
+
+/// Look up an active token and record that it was used
+pub async fn authenticate(db: &Database, token: &str) -> Option<ApiKey> {
+    if !token.starts_with(TOKEN_PREFIX) {
+        return None;
+    }
+
+    let key = db.get_active_api_key(&hash_token(token)).await.ok()??;
+    if let Err(e) = db.touch_api_key(key.id).await {
+        tracing::warn!("Failed to record use of token {}: {}", key.id, e);
+    }
+
+    Some(key)
+}
+
+/// The password from an HTTP Basic `Authorization` header. Git sends
+/// whatever username the user configured, so only the password matters.
+pub fn basic_auth_password(header: &str) -> Option<String> {
+    let encoded = header.strip_prefix("Basic ")?;
+    let decoded = general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+    let credentials = String::from_utf8(decoded).ok()?;
+    let (_, password) = credentials.split_once(':')?;
+
+    Some(password.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(scopes: &str) -> ApiKey {
+        ApiKey {
+            id: 1,
+            user_id: 1,
+            name: "ci".to_string(),
+            scopes: scopes.to_string(),
+            token_prefix: String::new(),
+            created_at: String::new(),
+            last_used: None,
+            expires_at: None,
+            is_active: 1,
+        }
+    }
+
+    #[test]
+    fn scopes_cap_repository_role() {
+        assert_eq!(max_repo_role(&key("repo:read")), Some(RepoRole::Read));
+        assert_eq!(max_repo_role(&key("repo:read repo:write")), Some(RepoRole::Write));
+        assert_eq!(max_repo_role(&key("admin")), Some(RepoRole::Owner));
+        assert_eq!(max_repo_role(&key("")), None);
+        assert!(has_scope(&key("repo:read admin"), TokenScope::Admin));
+        assert!(!has_scope(&key("repo:write"), TokenScope::Admin));
+    }
+
+    #[test]
+    fn basic_auth_ignores_username() {
+        let header = format!(
+            "Basic {}",
+            general_purpose::STANDARD.encode("x-token:hyr_secret")
+        );
+        assert_eq!(basic_auth_password(&header).as_deref(), Some("hyr_secret"));
+        assert_eq!(basic_auth_password("Bearer hyr_secret"), None);
+        assert_eq!(basic_auth_password("Basic not-base64!"), None);
+    }
+
+    #[test]
+    fn generated_tokens_are_distinct() {
+        let token = generate_token();
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_eq!(token.len(), TOKEN_PREFIX.len() + 40);
+        assert_ne!(token, generate_token());
+        assert_eq!(hash_token(&token), hash_token(&token));
+    }
+}
@@ -0,0 +1,52 @@
+// src/bin/hyrule-node/config.rs
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct NodeConfig {
+    pub coordinator_url: String,
+    pub host: String,
+    pub port: u16,
+    pub advertise_address: String,
+    pub data_dir: PathBuf,
+    pub storage_capacity: i64,
+    pub is_anchor: bool,
+    pub heartbeat_interval_secs: u64,
+}
+
+impl NodeConfig {
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        let port: u16 = std::env::var("NODE_PORT")
+            .unwrap_or_else(|_| "4000".to_string())
+            .parse()?;
+
+        Ok(Self {
+            coordinator_url: std::env::var("COORDINATOR_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:3000".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            host: std::env::var("NODE_HOST")
+                .unwrap_or_else(|_| "0.0.0.0".to_string()),
+            port,
+            advertise_address: std::env::var("NODE_ADVERTISE_ADDRESS")
+                .unwrap_or_else(|_| "127.0.0.1".to_string()),
+            // Default to a per-port directory so several nodes can share a host
+            data_dir: std::env::var("NODE_DATA_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from(format!("storage/node-{}", port))),
+            storage_capacity: std::env::var("NODE_STORAGE_CAPACITY")
+                .unwrap_or_else(|_| "10737418240".to_string()) // 10GB
+                .parse()?,
+            is_anchor: std::env::var("NODE_ANCHOR").unwrap_or_default() == "true",
+            heartbeat_interval_secs: std::env::var("NODE_HEARTBEAT_INTERVAL")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()?,
+        })
+    }
+
+    pub fn listen_addr(&self) -> SocketAddr {
+        format!("{}:{}", self.host, self.port)
+            .parse()
+            .expect("Invalid node listen address")
+    }
+}
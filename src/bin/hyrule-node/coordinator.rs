@@ -0,0 +1,149 @@
+// src/bin/hyrule-node/coordinator.rs
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::NodeConfig;
+use crate::node_protocol::{self, NodeHeartbeat, PackRequest, RegisterNodeRequest};
+use crate::server::NodeState;
+
+/// Talks to the Hyrule coordinator on behalf of this node
+pub struct CoordinatorClient {
+    http: reqwest::Client,
+    config: NodeConfig,
+    state: Arc<NodeState>,
+}
+
+impl CoordinatorClient {
+    pub fn new(config: NodeConfig, state: Arc<NodeState>) -> Result<Self, reqwest::Error> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self { http, config, state })
+    }
+
+    pub async fn register(&self) -> Result<(), String> {
+        let mut request = RegisterNodeRequest {
+            node_id: self.state.node_id.clone(),
+            address: self.config.advertise_address.clone(),
+            port: self.config.port as i32,
+            storage_capacity: self.config.storage_capacity,
+            is_anchor: self.config.is_anchor,
+            access_token: Some(self.state.access_token.clone()),
+            timestamp: node_protocol::unix_now(),
+            signature: None,
+        };
+        node_protocol::sign(&mut request, &self.state.signing_key);
+
+        let response = self
+            .http
+            .post(format!("{}/api/nodes", self.config.coordinator_url))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| format!("Registration failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("Registration rejected: {}", response.status()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn heartbeat(&self) -> Result<(), String> {
+        let store = &self.state.store;
+        let mut heartbeat = NodeHeartbeat {
+            node_id: self.state.node_id.clone(),
+            storage_used: store.storage_used().map_err(|e| e.to_string())?,
+            hosted_repos: store.list_repos().map_err(|e| e.to_string())?,
+            timestamp: node_protocol::unix_now(),
+            signature: None,
+        };
+        node_protocol::sign(&mut heartbeat, &self.state.signing_key);
+
+        let response = self
+            .http
+            .post(format!("{}/api/nodes/heartbeat", self.config.coordinator_url))
+            .json(&heartbeat)
+            .send()
+            .await
+            .map_err(|e| format!("Heartbeat failed: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            // Coordinator forgot about us (e.g. fresh database) - join again
+            tracing::warn!("Coordinator does not know this node, re-registering");
+            return self.register().await;
+        }
+
+        if !response.status().is_success() {
+            return Err(format!("Heartbeat rejected: {}", response.status()));
+        }
+
+        tracing::debug!(
+            "Heartbeat sent ({} repos, {} bytes)",
+            heartbeat.hosted_repos.len(),
+            heartbeat.storage_used
+        );
+        Ok(())
+    }
+
+    /// Register, then report in every `heartbeat_interval_secs` forever
+    pub async fn run(self) {
+        let mut retry = Duration::from_secs(1);
+        while let Err(e) = self.register().await {
+            tracing::warn!("{} - retrying in {:?}", e, retry);
+            tokio::time::sleep(retry).await;
+            retry = (retry * 2).min(Duration::from_secs(60));
+        }
+        tracing::info!("Registered with coordinator {}", self.config.coordinator_url);
+
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.heartbeat_interval_secs));
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.heartbeat().await {
+                tracing::warn!("{}", e);
+            }
+        }
+    }
+}
+
+/// Download the objects between `haves` and `wants` as a thin pack
+pub async fn fetch_pack(
+    state: &NodeState,
+    repo_hash: &str,
+    wants: Vec<String>,
+    haves: Vec<String>,
+) -> Result<Vec<u8>, String> {
+    let mut request = PackRequest {
+        node_id: state.node_id.clone(),
+        repo_hash: repo_hash.to_string(),
+        wants,
+        haves,
+        timestamp: node_protocol::unix_now(),
+        signature: None,
+    };
+    node_protocol::sign(&mut request, &state.signing_key);
+
+    let http = reqwest::Client::builder()
+        .timeout(Duration::from_secs(300))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let response = http
+        .post(format!("{}{}", state.coordinator_url, node_protocol::pack_path(repo_hash)))
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| format!("Pack request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Pack request rejected: {}", response.status()));
+    }
+
+    response
+        .bytes()
+        .await
+        .map(|b| b.to_vec())
+        .map_err(|e| format!("Pack download failed: {}", e))
+}
@@ -0,0 +1,104 @@
+// Hyrule/src/bin/hyrule-node/main.rs - Storage node agent
+//
+// Registers with a Hyrule coordinator, accepts replicated repositories into
+// its own bare-repo store, serves them read-only over git smart HTTP and
+// reports storage usage through periodic heartbeats. After a push the
+// coordinator tells it to catch up, and it pulls just the new objects.
+mod config;
+mod coordinator;
+mod server;
+mod store;
+
+// Shared with the coordinator; not every item is used on this side
+#[allow(dead_code)]
+#[path = "../../services/node_protocol.rs"]
+mod node_protocol;
+
+use crate::config::NodeConfig;
+use crate::coordinator::CoordinatorClient;
+use crate::server::{create_router, NodeState};
+use crate::store::ReplicaStore;
+use ed25519_dalek::SigningKey;
+use rand::RngCore;
+use std::path::Path;
+use std::sync::Arc;
+use tower_http::trace::TraceLayer;
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Load the node's ed25519 key from the data directory, creating one on first start
+fn load_signing_key(data_dir: &Path) -> std::io::Result<SigningKey> {
+    let path = data_dir.join("node_key");
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        let secret: Option<[u8; 32]> = hex::decode(existing.trim())
+            .ok()
+            .and_then(|b| b.try_into().ok());
+        match secret {
+            Some(secret) => return Ok(SigningKey::from_bytes(&secret)),
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{} is not a valid node key", path.display()),
+                ))
+            }
+        }
+    }
+
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    std::fs::write(&path, hex::encode(secret))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_target(true)
+        .with_level(true)
+        .init();
+
+    dotenvy::dotenv().ok();
+    let config = NodeConfig::from_env()?;
+
+    std::fs::create_dir_all(&config.data_dir)?;
+    let store = ReplicaStore::new(&config.data_dir)?;
+    let signing_key = load_signing_key(&config.data_dir)?;
+    let node_id = node_protocol::node_id_from_key(&signing_key.verifying_key());
+
+    tracing::info!("🚀 Starting Hyrule node {}", node_id);
+    tracing::info!("📁 Data directory: {}", config.data_dir.display());
+
+    let state = Arc::new(NodeState {
+        node_id,
+        signing_key,
+        // Fresh token per run; the coordinator learns it on registration
+        access_token: random_hex(32),
+        coordinator_url: config.coordinator_url.clone(),
+        store,
+    });
+
+    let coordinator = CoordinatorClient::new(config.clone(), state.clone())?;
+    tokio::spawn(coordinator.run());
+
+    let app = create_router(state).layer(TraceLayer::new_for_http());
+
+    let addr = config.listen_addr();
+    tracing::info!("✅ Node listening on {}", addr);
+    tracing::info!("🔗 Coordinator: {}", config.coordinator_url);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
@@ -0,0 +1,475 @@
+// src/bin/hyrule-node/server.rs
+use async_compression::tokio::bufread::GzipDecoder;
+use axum::{
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    routing::{get, post, put},
+    Json, Router,
+};
+use ed25519_dalek::SigningKey;
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use tokio::process::{Child, Command};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::coordinator;
+use crate::node_protocol::{self, ChallengeResponse, ReplicaAck, StorageChallenge, SyncRequest};
+use crate::store::ReplicaStore;
+
+/// Largest upload-pack request accepted from anonymous clients, counted
+/// both as sent and after gunzipping
+const MAX_NEGOTIATION_SIZE: u64 = 32 * 1024 * 1024;
+
+const RPC_CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct NodeState {
+    pub node_id: String,
+    pub signing_key: SigningKey,
+    pub access_token: String,
+    /// Where thin packs are fetched from when syncing
+    pub coordinator_url: String,
+    pub store: ReplicaStore,
+}
+
+pub fn create_router(state: Arc<NodeState>) -> Router {
+    Router::new()
+        // Coordinator-only replica management
+        .route("/replicas/:hash", put(receive_replica).get(send_replica))
+        .route("/replicas/:hash/sync", post(sync_replica))
+        .route("/replicas/:hash/challenge", post(answer_challenge))
+        // Replica bundles are as large as the repositories they carry; the
+        // layer only covers the routes above
+        .layer(DefaultBodyLimit::disable())
+        // Read-only git smart HTTP
+        .route("/git/:hash/info/refs", get(git_info_refs))
+        .route("/git/:hash/git-upload-pack", post(git_upload_pack))
+        .route("/api/health", get(health))
+        .with_state(state)
+}
+
+fn check_coordinator(state: &NodeState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    use subtle::ConstantTimeEq;
+    if provided.as_bytes().ct_eq(state.access_token.as_bytes()).into() {
+        Ok(())
+    } else {
+        tracing::warn!("Rejected replica request with invalid coordinator token");
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Store a replica pushed by the coordinator and acknowledge its refs
+async fn receive_replica(
+    State(state): State<Arc<NodeState>>,
+    Path(repo_hash): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ReplicaAck>, (StatusCode, String)> {
+    check_coordinator(&state, &headers).map_err(|s| (s, "Unauthorized".to_string()))?;
+
+    if !ReplicaStore::is_valid_hash(&repo_hash) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid repository hash".to_string()));
+    }
+
+    let is_private = headers
+        .get(node_protocol::PRIVATE_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(|v| v == "1")
+        .unwrap_or(true);
+
+    state
+        .store
+        .import_bundle(&repo_hash, &body, is_private)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to import replica {}: {}", repo_hash, e);
+            (StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+        })?;
+
+    let refs = state
+        .store
+        .list_refs(&repo_hash)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("Stored replica {} ({} refs, {} bytes)", repo_hash, refs.len(), body.len());
+
+    let mut ack = ReplicaAck {
+        node_id: state.node_id.clone(),
+        repo_hash,
+        refs,
+        timestamp: node_protocol::unix_now(),
+        signature: None,
+    };
+    node_protocol::sign(&mut ack, &state.signing_key);
+
+    Ok(Json(ack))
+}
+
+/// Hand a full replica back to the coordinator, e.g. after it lost its copy
+async fn send_replica(
+    State(state): State<Arc<NodeState>>,
+    Path(repo_hash): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    check_coordinator(&state, &headers).map_err(|s| (s, "Unauthorized".to_string()))?;
+
+    if !ReplicaStore::is_valid_hash(&repo_hash) || !state.store.has_repo(&repo_hash) {
+        return Err((StatusCode::NOT_FOUND, "Replica not found".to_string()));
+    }
+
+    let bundle = state.store.create_bundle(&repo_hash).await.map_err(|e| {
+        tracing::error!("Failed to bundle replica {}: {}", repo_hash, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+
+    tracing::info!("Sent replica {} to coordinator ({} bytes)", repo_hash, bundle.len());
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, node_protocol::BUNDLE_CONTENT_TYPE)
+        .body(Body::from(bundle))
+        .unwrap())
+}
+
+/// Bring a replica up to date with the refs the coordinator now holds
+///
+/// Only objects missing locally are fetched, as a thin pack against the
+/// refs this node already has. Replicas the node does not hold yet must
+/// be transferred in full with `receive_replica`.
+async fn sync_replica(
+    State(state): State<Arc<NodeState>>,
+    Path(repo_hash): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<SyncRequest>,
+) -> Result<Json<ReplicaAck>, (StatusCode, String)> {
+    check_coordinator(&state, &headers).map_err(|s| (s, "Unauthorized".to_string()))?;
+
+    let well_formed = request.refs.iter().all(|(name, oid)| {
+        name.starts_with("refs/")
+            && !name.chars().any(|c| c.is_whitespace() || c.is_control())
+            && ReplicaStore::is_valid_hash(oid)
+    });
+    if request.repo_hash != repo_hash || !ReplicaStore::is_valid_hash(&repo_hash) || !well_formed {
+        return Err((StatusCode::BAD_REQUEST, "Malformed sync request".to_string()));
+    }
+
+    if !state.store.has_repo(&repo_hash) {
+        return Err((StatusCode::NOT_FOUND, "Replica not found".to_string()));
+    }
+
+    let internal = |e: anyhow::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    let current = state.store.list_refs(&repo_hash).await.map_err(internal)?;
+
+    let mut tips: Vec<String> = request.refs.values().cloned().collect();
+    tips.sort();
+    tips.dedup();
+    let wants = state.store.missing_objects(&repo_hash, &tips).await.map_err(internal)?;
+
+    if !wants.is_empty() {
+        let mut haves: Vec<String> = current.values().cloned().collect();
+        haves.sort();
+        haves.dedup();
+
+        let pack = coordinator::fetch_pack(&state, &repo_hash, wants.clone(), haves)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+
+        state.store.index_thin_pack(&repo_hash, &pack).await.map_err(|e| {
+            tracing::error!("Failed to index pack for {}: {}", repo_hash, e);
+            (StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+        })?;
+
+        tracing::info!("Fetched {} new tips for {} ({} bytes)", wants.len(), repo_hash, pack.len());
+    }
+
+    state
+        .store
+        .set_refs(&repo_hash, &request.refs)
+        .await
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+
+    let refs = state.store.list_refs(&repo_hash).await.map_err(internal)?;
+
+    let mut ack = ReplicaAck {
+        node_id: state.node_id.clone(),
+        repo_hash,
+        refs,
+        timestamp: node_protocol::unix_now(),
+        signature: None,
+    };
+    node_protocol::sign(&mut ack, &state.signing_key);
+
+    Ok(Json(ack))
+}
+
+/// Prove possession of a replica by hashing the requested objects
+async fn answer_challenge(
+    State(state): State<Arc<NodeState>>,
+    Path(repo_hash): Path<String>,
+    headers: HeaderMap,
+    Json(challenge): Json<StorageChallenge>,
+) -> Result<Json<ChallengeResponse>, (StatusCode, String)> {
+    check_coordinator(&state, &headers).map_err(|s| (s, "Unauthorized".to_string()))?;
+
+    if challenge.repo_hash != repo_hash
+        || !ReplicaStore::is_valid_hash(&repo_hash)
+        || !challenge.object_ids.iter().all(|oid| ReplicaStore::is_valid_hash(oid))
+    {
+        return Err((StatusCode::BAD_REQUEST, "Malformed challenge".to_string()));
+    }
+
+    if !state.store.has_repo(&repo_hash) {
+        return Err((StatusCode::NOT_FOUND, "Replica not found".to_string()));
+    }
+
+    let objects = state
+        .store
+        .read_objects(&repo_hash, &challenge.object_ids)
+        .await
+        .map_err(|e| {
+            tracing::warn!("Cannot answer challenge for {}: {}", repo_hash, e);
+            (StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+        })?;
+
+    let mut response = ChallengeResponse {
+        node_id: state.node_id.clone(),
+        digest: node_protocol::challenge_digest(&challenge.nonce, &objects),
+        repo_hash,
+        nonce: challenge.nonce,
+        timestamp: node_protocol::unix_now(),
+        signature: None,
+    };
+    node_protocol::sign(&mut response, &state.signing_key);
+
+    Ok(Json(response))
+}
+
+/// Resolve a replica that may be served to anonymous git clients
+fn public_repo_path(state: &NodeState, repo_hash: &str) -> Result<std::path::PathBuf, StatusCode> {
+    if !ReplicaStore::is_valid_hash(repo_hash)
+        || !state.store.has_repo(repo_hash)
+        || state.store.is_private(repo_hash)
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(state.store.repo_path(repo_hash))
+}
+
+#[derive(Debug, Deserialize)]
+struct GitService {
+    service: String,
+}
+
+async fn git_info_refs(
+    State(state): State<Arc<NodeState>>,
+    Path(repo_hash): Path<String>,
+    Query(params): Query<GitService>,
+) -> Result<Response, StatusCode> {
+    // Replicas are read-only
+    if params.service != "git-upload-pack" {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let repo_path = public_repo_path(&state, &repo_hash)?;
+
+    let output = Command::new("git")
+        .arg("upload-pack")
+        .arg("--stateless-rpc")
+        .arg("--advertise-refs")
+        .arg(&repo_path)
+        .output()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !output.status.success() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let service_line = format!("# service={}\n", params.service);
+    let mut body = format!("{:04x}{}", service_line.len() + 4, service_line).into_bytes();
+    body.extend_from_slice(b"0000");
+    body.extend_from_slice(&output.stdout);
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-git-upload-pack-advertisement")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from(body))
+        .unwrap())
+}
+
+async fn git_upload_pack(
+    State(state): State<Arc<NodeState>>,
+    Path(repo_hash): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, StatusCode> {
+    let repo_path = public_repo_path(&state, &repo_hash)?;
+
+    let mut child = Command::new("git")
+        .arg("upload-pack")
+        .arg("--stateless-rpc")
+        .arg(&repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Fed from a background task so the request never sits in memory; an
+    // oversized one is cut off and git fails on the truncated input
+    let mut input = request_reader(&headers, body);
+    let mut stdin = child.stdin.take().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    tokio::spawn(async move {
+        if let Err(e) = tokio::io::copy(&mut input, &mut stdin).await {
+            tracing::warn!("git-upload-pack request aborted: {}", e);
+        }
+    });
+
+    let stdout = child.stdout.take().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    tokio::spawn(finish_upload_pack(repo_hash, child));
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-git-upload-pack-result")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from_stream(ReaderStream::with_capacity(stdout, RPC_CHUNK_SIZE)))
+        .unwrap())
+}
+
+/// The request body as a byte stream, gunzipped if the client compressed
+/// it, failing once it grows past `MAX_NEGOTIATION_SIZE`
+fn request_reader(headers: &HeaderMap, body: Body) -> Pin<Box<dyn AsyncRead + Send>> {
+    let reader = Capped::new(StreamReader::new(
+        body.into_data_stream().map_err(io::Error::other),
+    ));
+
+    // git clients gzip large negotiation requests
+    let gzipped = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|h| h.to_str().ok())
+        .map(|v| v.eq_ignore_ascii_case("gzip"))
+        .unwrap_or(false);
+
+    if gzipped {
+        Box::pin(Capped::new(GzipDecoder::new(tokio::io::BufReader::new(reader))))
+    } else {
+        Box::pin(reader)
+    }
+}
+
+/// Wait for upload-pack to exit, logging its stderr if it failed
+async fn finish_upload_pack(repo_hash: String, mut child: Child) {
+    let mut stderr = Vec::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_end(&mut stderr).await;
+    }
+
+    match child.wait().await {
+        Ok(status) if status.success() => {}
+        status => tracing::warn!(
+            "git-upload-pack failed for {} ({:?}): {}",
+            repo_hash,
+            status.map(|s| s.code()),
+            String::from_utf8_lossy(&stderr).trim()
+        ),
+    }
+}
+
+/// A reader that fails instead of yielding more than `MAX_NEGOTIATION_SIZE`
+/// bytes
+struct Capped<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R> Capped<R> {
+    fn new(inner: R) -> Self {
+        Capped { inner, remaining: MAX_NEGOTIATION_SIZE }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Capped<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+
+        let read = (buf.filled().len() - before) as u64;
+        if read > self.remaining {
+            buf.set_filled(before);
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, "request too large")));
+        }
+        self.remaining -= read;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: String,
+    node_id: String,
+    hosted_repos: usize,
+}
+
+async fn health(State(state): State<Arc<NodeState>>) -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "healthy".to_string(),
+        node_id: state.node_id.clone(),
+        hosted_repos: state.store.list_repos().map(|r| r.len()).unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn gzip_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_ENCODING, "gzip".parse().unwrap());
+        headers
+    }
+
+    async fn read_request(headers: &HeaderMap, body: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        request_reader(headers, Body::from(body)).read_to_end(&mut decoded).await?;
+        Ok(decoded)
+    }
+
+    #[tokio::test]
+    async fn requests_are_capped_after_gunzipping() {
+        let request = b"0032want 0123456789012345678901234567890123456789\n00000009done\n";
+        assert_eq!(read_request(&HeaderMap::new(), request.to_vec()).await.unwrap(), request);
+        assert_eq!(read_request(&gzip_headers(), gzip(request)).await.unwrap(), request);
+
+        // A few kilobytes on the wire must not expand without bound
+        let bomb = gzip(&vec![0; MAX_NEGOTIATION_SIZE as usize + 1]);
+        assert!(bomb.len() < 1024 * 1024);
+        let err = read_request(&gzip_headers(), bomb).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = read_request(&HeaderMap::new(), vec![0; MAX_NEGOTIATION_SIZE as usize + 1]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
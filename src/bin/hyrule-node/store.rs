@@ -0,0 +1,414 @@
+// src/bin/hyrule-node/store.rs
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Marker file written into replicas that must not be served publicly
+const PRIVATE_MARKER: &str = "hyrule-private";
+
+/// Bare repositories hosted by this node, one directory per repo hash
+pub struct ReplicaStore {
+    repos_path: PathBuf,
+    tmp_path: PathBuf,
+}
+
+impl ReplicaStore {
+    pub fn new(data_dir: &Path) -> Result<Self> {
+        let repos_path = data_dir.join("repos");
+        let tmp_path = data_dir.join("tmp");
+        std::fs::create_dir_all(&repos_path)?;
+        std::fs::create_dir_all(&tmp_path)?;
+        Ok(Self { repos_path, tmp_path })
+    }
+
+    /// Repo hashes (see `utils::hash`) and SHA-1 object ids are 40 lowercase hex characters
+    pub fn is_valid_hash(repo_hash: &str) -> bool {
+        repo_hash.len() == 40
+            && repo_hash.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    }
+
+    pub fn repo_path(&self, repo_hash: &str) -> PathBuf {
+        self.repos_path.join(repo_hash)
+    }
+
+    pub fn has_repo(&self, repo_hash: &str) -> bool {
+        self.repo_path(repo_hash).join("HEAD").exists()
+    }
+
+    pub fn is_private(&self, repo_hash: &str) -> bool {
+        self.repo_path(repo_hash).join(PRIVATE_MARKER).exists()
+    }
+
+    /// Hashes of every replica currently stored
+    pub fn list_repos(&self) -> Result<Vec<String>> {
+        let mut repos = Vec::new();
+        for entry in std::fs::read_dir(&self.repos_path)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if Self::is_valid_hash(&name) && self.has_repo(&name) {
+                repos.push(name);
+            }
+        }
+        repos.sort();
+        Ok(repos)
+    }
+
+    /// Bytes used by all stored replicas
+    pub fn storage_used(&self) -> Result<i64> {
+        let mut total = 0u64;
+        for entry in walkdir::WalkDir::new(&self.repos_path) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                total += entry.metadata()?.len();
+            }
+        }
+        Ok(total as i64)
+    }
+
+    /// Replace a replica's contents with the refs and objects of a bundle
+    pub async fn import_bundle(&self, repo_hash: &str, bundle: &[u8], is_private: bool) -> Result<()> {
+        let bundle_path = self
+            .tmp_path
+            .join(format!("{}-{:016x}.bundle", repo_hash, rand::random::<u64>()));
+        tokio::fs::write(&bundle_path, bundle).await?;
+
+        let result = self.fetch_bundle(repo_hash, &bundle_path).await;
+        let _ = tokio::fs::remove_file(&bundle_path).await;
+        result?;
+
+        let marker = self.repo_path(repo_hash).join(PRIVATE_MARKER);
+        if is_private {
+            tokio::fs::write(marker, b"").await?;
+        } else if marker.exists() {
+            tokio::fs::remove_file(marker).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Package every ref of a replica into a bundle
+    pub async fn create_bundle(&self, repo_hash: &str) -> Result<Vec<u8>> {
+        run_git(
+            Command::new("git")
+                .arg("--git-dir")
+                .arg(self.repo_path(repo_hash))
+                .arg("bundle")
+                .arg("create")
+                .arg("-")
+                .arg("--all"),
+        )
+        .await
+    }
+
+    async fn fetch_bundle(&self, repo_hash: &str, bundle_path: &Path) -> Result<()> {
+        let repo_path = self.repo_path(repo_hash);
+        let created = !self.has_repo(repo_hash);
+
+        if created {
+            run_git(Command::new("git").arg("init").arg("--bare").arg(&repo_path)).await?;
+        }
+
+        let result = Self::mirror_bundle(&repo_path, bundle_path).await;
+
+        // Never leave an empty repository behind for a failed first transfer
+        if result.is_err() && created {
+            let _ = tokio::fs::remove_dir_all(&repo_path).await;
+        }
+
+        result
+    }
+
+    async fn mirror_bundle(repo_path: &Path, bundle_path: &Path) -> Result<()> {
+        run_git(
+            Command::new("git")
+                .arg("--git-dir")
+                .arg(repo_path)
+                .arg("bundle")
+                .arg("verify")
+                .arg(bundle_path),
+        )
+        .await?;
+
+        // Mirror every ref in the bundle and drop refs it no longer contains
+        run_git(
+            Command::new("git")
+                .arg("--git-dir")
+                .arg(repo_path)
+                .arg("fetch")
+                .arg("--prune")
+                .arg(bundle_path)
+                .arg("+refs/*:refs/*"),
+        )
+        .await?;
+
+        Self::point_head(repo_path, bundle_path).await
+    }
+
+    /// Point HEAD at the branch the bundle's HEAD resolves to, if any
+    async fn point_head(repo_path: &Path, bundle_path: &Path) -> Result<()> {
+        let heads = run_git(
+            Command::new("git")
+                .arg("--git-dir")
+                .arg(repo_path)
+                .arg("bundle")
+                .arg("list-heads")
+                .arg(bundle_path),
+        )
+        .await?;
+        let heads = String::from_utf8_lossy(&heads);
+
+        let head_oid = heads
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .find(|(_, name)| *name == "HEAD")
+            .map(|(oid, _)| oid.to_string());
+
+        let branch = head_oid.and_then(|oid| {
+            heads
+                .lines()
+                .filter_map(|line| line.split_once(' '))
+                .find(|(o, name)| *o == oid && name.starts_with("refs/heads/"))
+                .map(|(_, name)| name.to_string())
+        });
+
+        if let Some(branch) = branch {
+            run_git(
+                Command::new("git")
+                    .arg("--git-dir")
+                    .arg(repo_path)
+                    .arg("symbolic-ref")
+                    .arg("HEAD")
+                    .arg(&branch),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// List refs of a stored replica as refname -> object id
+    pub async fn list_refs(&self, repo_hash: &str) -> Result<BTreeMap<String, String>> {
+        let stdout = run_git(
+            Command::new("git")
+                .arg("--git-dir")
+                .arg(self.repo_path(repo_hash))
+                .arg("for-each-ref")
+                .arg("--format=%(objectname) %(refname)"),
+        )
+        .await?;
+
+        Ok(String::from_utf8_lossy(&stdout)
+            .lines()
+            .filter_map(|line| {
+                let (oid, name) = line.split_once(' ')?;
+                Some((name.to_string(), oid.to_string()))
+            })
+            .collect())
+    }
+
+    /// Which of the given objects this replica does not have yet
+    pub async fn missing_objects(&self, repo_hash: &str, object_ids: &[String]) -> Result<Vec<String>> {
+        let stdout = pipe_git(
+            Command::new("git")
+                .arg("--git-dir")
+                .arg(self.repo_path(repo_hash))
+                .arg("cat-file")
+                .arg("--batch-check"),
+            format!("{}\n", object_ids.join("\n")).as_bytes(),
+        )
+        .await?;
+
+        Ok(String::from_utf8_lossy(&stdout)
+            .lines()
+            .filter_map(|line| line.strip_suffix(" missing"))
+            .map(|oid| oid.to_string())
+            .collect())
+    }
+
+    /// Store the objects of a thin pack, completing deltas from local objects
+    pub async fn index_thin_pack(&self, repo_hash: &str, pack: &[u8]) -> Result<()> {
+        pipe_git(
+            Command::new("git")
+                .arg("--git-dir")
+                .arg(self.repo_path(repo_hash))
+                .arg("index-pack")
+                .arg("--stdin")
+                .arg("--fix-thin"),
+            pack,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Atomically make the replica's refs exactly `refs`
+    pub async fn set_refs(&self, repo_hash: &str, refs: &BTreeMap<String, String>) -> Result<()> {
+        let mut transaction = String::new();
+        for name in self.list_refs(repo_hash).await?.keys() {
+            if !refs.contains_key(name) {
+                transaction.push_str(&format!("delete {}\n", name));
+            }
+        }
+        for (name, oid) in refs {
+            transaction.push_str(&format!("update {} {}\n", name, oid));
+        }
+
+        pipe_git(
+            Command::new("git")
+                .arg("--git-dir")
+                .arg(self.repo_path(repo_hash))
+                .arg("update-ref")
+                .arg("--stdin"),
+            transaction.as_bytes(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Read the raw contents of the given objects with `git cat-file --batch`
+    pub async fn read_objects(&self, repo_hash: &str, object_ids: &[String]) -> Result<Vec<(String, Vec<u8>)>> {
+        use std::process::Stdio;
+        use tokio::io::AsyncWriteExt;
+
+        let mut child = Command::new("git")
+            .arg("--git-dir")
+            .arg(self.repo_path(repo_hash))
+            .arg("cat-file")
+            .arg("--batch")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(format!("{}\n", object_ids.join("\n")).as_bytes()).await?;
+        }
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            anyhow::bail!("git failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+
+        crate::node_protocol::parse_cat_file_batch(&output.stdout).map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+/// Run a git command to completion and return its stdout
+pub async fn run_git(command: &mut Command) -> Result<Vec<u8>> {
+    let output = command.output().await?;
+
+    if !output.status.success() {
+        anyhow::bail!("git failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(output.stdout)
+}
+
+/// Run a git command with `input` on stdin and return its stdout
+pub async fn pipe_git(command: &mut Command, input: &[u8]) -> Result<Vec<u8>> {
+    use std::process::Stdio;
+    use tokio::io::AsyncWriteExt;
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input).await?;
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        anyhow::bail!("git failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory removed when dropped
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    async fn git(dir: &Path, args: &[&str]) -> String {
+        let stdout = run_git(
+            Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+                .args(args),
+        )
+        .await
+        .unwrap();
+        String::from_utf8_lossy(&stdout).to_string()
+    }
+
+    async fn refs_of(dir: &Path) -> BTreeMap<String, String> {
+        git(dir, &["for-each-ref", "--format=%(objectname) %(refname)"])
+            .await
+            .lines()
+            .filter_map(|line| {
+                let (oid, name) = line.split_once(' ')?;
+                Some((name.to_string(), oid.to_string()))
+            })
+            .collect()
+    }
+
+    async fn git_bundle(dir: &Path) -> Vec<u8> {
+        run_git(Command::new("git").arg("-C").arg(dir).args(["bundle", "create", "-", "--all"]))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn bundles_round_trip() {
+        let dir = TempDir(std::env::temp_dir().join(format!("hyrule-node-{:016x}", rand::random::<u64>())));
+        let source = dir.0.join("source");
+        std::fs::create_dir_all(&source).unwrap();
+        git(&source, &["init", "--quiet", "--initial-branch=main"]).await;
+        git(&source, &["commit", "--quiet", "--allow-empty", "-m", "first"]).await;
+        git(&source, &["branch", "feature"]).await;
+        git(&source, &["commit", "--quiet", "--allow-empty", "-m", "second"]).await;
+        git(&source, &["tag", "v1"]).await;
+        let bundle = git_bundle(&source).await;
+
+        let store = ReplicaStore::new(&dir.0.join("node")).unwrap();
+        let (first, second) = ("a".repeat(40), "b".repeat(40));
+        store.import_bundle(&first, &bundle, true).await.unwrap();
+        assert!(store.has_repo(&first));
+        assert!(store.is_private(&first));
+        assert_eq!(store.list_refs(&first).await.unwrap(), refs_of(&source).await);
+        assert_eq!(git(&store.repo_path(&first), &["symbolic-ref", "HEAD"]).await.trim(), "refs/heads/main");
+
+        // What the node hands back is a complete copy
+        let returned = store.create_bundle(&first).await.unwrap();
+        store.import_bundle(&second, &returned, false).await.unwrap();
+        assert!(!store.is_private(&second));
+        assert_eq!(store.list_refs(&second).await.unwrap(), refs_of(&source).await);
+        assert_eq!(store.list_repos().unwrap(), vec![first.clone(), second]);
+
+        // A later bundle replaces the refs, dropping deleted ones
+        git(&source, &["branch", "--quiet", "-D", "feature"]).await;
+        git(&source, &["commit", "--quiet", "--allow-empty", "-m", "third"]).await;
+        store.import_bundle(&first, &git_bundle(&source).await, false).await.unwrap();
+        assert!(!store.is_private(&first));
+        let refs = store.list_refs(&first).await.unwrap();
+        assert_eq!(refs, refs_of(&source).await);
+        assert!(!refs.contains_key("refs/heads/feature"));
+
+        // A broken transfer leaves nothing behind
+        let broken = "c".repeat(40);
+        assert!(store.import_bundle(&broken, b"not a bundle", false).await.is_err());
+        assert!(!store.has_repo(&broken));
+        assert!(!store.repo_path(&broken).exists());
+    }
+}
@@ -6,9 +6,35 @@ pub struct Config {
     pub database_url: String,
     pub host: String,
     pub port: u16,
+    /// Signs login and account-link tokens; checked at startup
+    pub jwt_secret: String,
     pub default_storage_quota: i64,
     pub min_replica_count: i32,
     pub node_heartbeat_timeout_minutes: i32,
+    /// Minutes of silence after which a node's replicas are evicted
+    pub node_eviction_grace_minutes: i32,
+    /// Concurrent replication job workers
+    pub replication_workers: usize,
+    /// Port of the git SSH server; 0 disables it
+    pub ssh_port: u16,
+    /// Host key for the SSH server, generated on first start
+    pub ssh_host_key_path: String,
+    /// Where login sessions are kept: "sqlite" or "memory"
+    pub session_backend: String,
+    /// Public base URL, used for links in emails
+    pub site_origin: String,
+    /// How email is sent: "log", "file" or "smtp"
+    pub mailer: String,
+    /// Directory the file mailer writes messages to
+    pub mail_dir: String,
+    /// Sender address of outgoing email
+    pub mail_from: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    /// "starttls", "tls" for implicit TLS, or "none" for a local relay
+    pub smtp_tls: String,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
 }
 
 impl Config {
@@ -21,6 +47,7 @@ impl Config {
             port: std::env::var("PORT")
                 .unwrap_or_else(|_| "3000".to_string())
                 .parse()?,
+            jwt_secret: std::env::var("JWT_SECRET").unwrap_or_default(),
             default_storage_quota: std::env::var("DEFAULT_STORAGE_QUOTA")
                 .unwrap_or_else(|_| "1073741824".to_string()) // 1GB
                 .parse()?,
@@ -30,9 +57,47 @@ impl Config {
             node_heartbeat_timeout_minutes: std::env::var("NODE_HEARTBEAT_TIMEOUT")
                 .unwrap_or_else(|_| "10".to_string())
                 .parse()?,
+            node_eviction_grace_minutes: std::env::var("NODE_EVICTION_GRACE")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()?,
+            replication_workers: std::env::var("REPLICATION_WORKERS")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()?,
+            ssh_port: std::env::var("SSH_PORT")
+                .unwrap_or_else(|_| "2222".to_string())
+                .parse()?,
+            ssh_host_key_path: std::env::var("SSH_HOST_KEY")
+                .unwrap_or_else(|_| "storage/ssh_host_ed25519_key".to_string()),
+            session_backend: std::env::var("SESSION_BACKEND")
+                .unwrap_or_else(|_| "sqlite".to_string()),
+            site_origin: std::env::var("SITE_ORIGIN")
+                .unwrap_or_else(|_| "http://localhost:3000".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            mailer: std::env::var("MAILER")
+                .unwrap_or_else(|_| "log".to_string()),
+            mail_dir: std::env::var("MAIL_DIR")
+                .unwrap_or_else(|_| "storage/mail".to_string()),
+            mail_from: std::env::var("MAIL_FROM")
+                .unwrap_or_else(|_| "Hyrule <noreply@hyrule.local>".to_string()),
+            smtp_host: std::env::var("SMTP_HOST")
+                .unwrap_or_else(|_| "localhost".to_string()),
+            smtp_port: std::env::var("SMTP_PORT")
+                .unwrap_or_else(|_| "587".to_string())
+                .parse()?,
+            smtp_tls: std::env::var("SMTP_TLS")
+                .unwrap_or_else(|_| "starttls".to_string()),
+            smtp_username: std::env::var("SMTP_USERNAME").ok(),
+            smtp_password: std::env::var("SMTP_PASSWORD").ok(),
         })
     }
     
+    /// Whether emailed links reach users; the log mailer only writes them
+    /// to the server log
+    pub fn sends_email(&self) -> bool {
+        self.mailer != "log"
+    }
+
     pub fn server_addr(&self) -> SocketAddr {
         format!("{}:{}", self.host, self.port)
             .parse()
@@ -1,5 +1,7 @@
 // src/db.rs
+use crate::auth::session::{ActiveSession, Session};
 use crate::models::*;
+use chrono::{DateTime, Utc};
 use sqlx::{Row, SqlitePool};
 
 #[derive(Clone)]
@@ -337,190 +339,2271 @@ impl Database {
             .await
     }
 
+    pub async fn set_user_admin(&self, user_id: i64, is_admin: bool) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET is_admin = ? WHERE id = ?")
+            .bind(is_admin as i32)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE lower(email) = lower(?)")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Mark the email verified, if it is still the user's address
+    pub async fn mark_email_verified(&self, user_id: i64, email: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET email_verified = 1 WHERE id = ? AND lower(email) = lower(?)",
+        )
+        .bind(user_id)
+        .bind(email)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Store a new password hash, which also satisfies a forced change and
+    /// invalidates login tokens issued before it
+    pub async fn update_password(&self, user_id: i64, password_hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE users SET password_hash = ?, must_change_password = 0, token_generation = token_generation + 1
+             WHERE id = ?",
+        )
+            .bind(password_hash)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_must_change_password(&self, user_id: i64, required: bool) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET must_change_password = ? WHERE id = ?")
+            .bind(required as i64)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Change the address, which then needs verifying again
+    pub async fn update_email(&self, user_id: i64, email: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET email = ?, email_verified = 0 WHERE id = ?")
+            .bind(email)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn count_admins(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE is_admin = 1")
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Delete a user, giving their personal repositories to `transfer_to` or
+    /// deleting them along with the user. Repositories they created in
+    /// organizations pass to another owner of the organization, so each must
+    /// have one. Repository directories are left for the caller to remove
+    /// once this has succeeded.
+    pub async fn delete_user_account(&self, user_id: i64, transfer_to: Option<i64>) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if transfer_to.is_none() {
+            let repo_hashes: Vec<String> =
+                sqlx::query_scalar("SELECT repo_hash FROM repositories WHERE owner_id = ? AND org_id IS NULL")
+                    .bind(user_id)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            for repo_hash in &repo_hashes {
+                Self::delete_repository_rows(&mut tx, repo_hash).await?;
+            }
+        }
+
+        if let Some(new_owner_id) = transfer_to {
+            // The new owner no longer needs to be a collaborator
+            sqlx::query(
+                "DELETE FROM repo_collaborators WHERE user_id = ? AND repo_hash IN
+                     (SELECT repo_hash FROM repositories WHERE owner_id = ? AND org_id IS NULL)",
+            )
+            .bind(new_owner_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("UPDATE repositories SET owner_id = ? WHERE owner_id = ? AND org_id IS NULL")
+                .bind(new_owner_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            "UPDATE repositories SET owner_id =
+                 (SELECT m.user_id FROM org_members m
+                  WHERE m.org_id = repositories.org_id AND m.role = 'owner' AND m.user_id != ?
+                  ORDER BY m.created_at LIMIT 1)
+             WHERE owner_id = ? AND org_id IS NOT NULL",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        // Tables from before deletes cascaded
+        for table in ["pins", "repo_stars", "api_keys"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE user_id = ?", table))
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        // Logs are kept without the user
+        for table in ["activity_log", "repo_access_log"] {
+            sqlx::query(&format!("UPDATE {} SET user_id = NULL WHERE user_id = ?", table))
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        match transfer_to {
+            Some(new_owner_id) => self.recount_storage_used(new_owner_id, None).await,
+            None => Ok(()),
+        }
+    }
+
     // Repository operations
     pub async fn create_repository(
         &self,
         repo: &CreateRepoRequest,
         owner_id: i64,
         repo_hash: &str,
+    ) -> Result<Repository, sqlx::Error> {
+        self.insert_repository(repo, owner_id, None, repo_hash).await
+    }
+
+    /// Create a repository owned by an organization; `creator_id` is
+    /// recorded as `owner_id` but gets no access from it
+    pub async fn create_org_repository(
+        &self,
+        repo: &CreateRepoRequest,
+        org_id: i64,
+        creator_id: i64,
+        repo_hash: &str,
+    ) -> Result<Repository, sqlx::Error> {
+        self.insert_repository(repo, creator_id, Some(org_id), repo_hash).await
+    }
+
+    async fn insert_repository(
+        &self,
+        repo: &CreateRepoRequest,
+        owner_id: i64,
+        org_id: Option<i64>,
+        repo_hash: &str,
     ) -> Result<Repository, sqlx::Error> {
         let is_private = if repo.is_private { 1 } else { 0 };
 
+        // A new repository takes its name back from any renamed one
+        match org_id {
+            Some(org_id) => sqlx::query("DELETE FROM repo_redirects WHERE org_id = ? AND name = ?").bind(org_id),
+            None => sqlx::query("DELETE FROM repo_redirects WHERE org_id IS NULL AND owner_id = ? AND name = ?")
+                .bind(owner_id),
+        }
+        .bind(&repo.name)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO repositories (repo_hash, owner_id, name, description, storage_tier, is_private, org_id)
+             VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(repo_hash)
+        .bind(owner_id)
+        .bind(&repo.name)
+        .bind(&repo.description)
+        .bind(&repo.storage_tier)
+        .bind(is_private)
+        .bind(org_id)
+        .execute(&self.pool)
+        .await?;
+
+        self.get_repository(repo_hash).await
+    }
+
+    pub async fn get_repository(&self, repo_hash: &str) -> Result<Repository, sqlx::Error> {
+        sqlx::query_as::<_, Repository>("SELECT * FROM repositories WHERE repo_hash = ?")
+            .bind(repo_hash)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Look a repository up by its owner's username or organization name
+    /// and its name
+    pub async fn get_repository_by_owner_name(
+        &self,
+        owner: &str,
+        name: &str,
+    ) -> Result<Repository, sqlx::Error> {
+        sqlx::query_as::<_, Repository>(
+            "SELECT r.* FROM repositories r
+             JOIN users u ON u.id = r.owner_id
+             LEFT JOIN organizations o ON o.id = r.org_id
+             WHERE COALESCE(o.name, u.username) = ? AND r.name = ?",
+        )
+        .bind(owner)
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Look a repository up by `owner/name`, falling back to names it had
+    /// before being renamed. The flag is true when `name` is an old name.
+    pub async fn resolve_repository(&self, owner: &str, name: &str) -> Result<(Repository, bool), sqlx::Error> {
+        match self.get_repository_by_owner_name(owner, name).await {
+            Ok(repo) => return Ok((repo, false)),
+            Err(sqlx::Error::RowNotFound) => {}
+            Err(e) => return Err(e),
+        }
+
+        let repo = sqlx::query_as::<_, Repository>(
+            "SELECT r.* FROM repo_redirects d
+             LEFT JOIN users u ON u.id = d.owner_id
+             LEFT JOIN organizations o ON o.id = d.org_id
+             JOIN repositories r ON r.repo_hash = d.repo_hash
+             WHERE COALESCE(o.name, u.username) = ? AND d.name = ?",
+        )
+        .bind(owner)
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((repo, true))
+    }
+
+    /// Whether the user already has a personal repository with this name
+    pub async fn repository_name_taken(&self, owner_id: i64, name: &str) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM repositories WHERE org_id IS NULL AND owner_id = ? AND name = ?",
+        )
+        .bind(owner_id)
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count > 0)
+    }
+
+    pub async fn org_repository_name_taken(&self, org_id: i64, name: &str) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM repositories WHERE org_id = ? AND name = ?")
+            .bind(org_id)
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Whether another repository in the same namespace as `repo` uses `name`
+    pub async fn sibling_name_taken(&self, repo: &Repository, name: &str) -> Result<bool, sqlx::Error> {
+        match repo.org_id {
+            Some(org_id) => self.org_repository_name_taken(org_id, name).await,
+            None => self.repository_name_taken(repo.owner_id, name).await,
+        }
+    }
+
+    /// The user or organization name that prefixes the repository's URL
+    pub async fn repository_namespace(&self, repo: &Repository) -> Result<String, sqlx::Error> {
+        match repo.org_id {
+            Some(org_id) => sqlx::query_scalar("SELECT name FROM organizations WHERE id = ?")
+                .bind(org_id)
+                .fetch_one(&self.pool)
+                .await,
+            None => Ok(self.get_user_by_id(repo.owner_id).await?.username),
+        }
+    }
+
+    /// Rename a repository, leaving the old name redirecting to it
+    pub async fn rename_repository(&self, repo_hash: &str, new_name: &str) -> Result<(), sqlx::Error> {
+        let repo = self.get_repository(repo_hash).await?;
+        if repo.name == new_name {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE repositories SET name = ?, last_updated = datetime('now') WHERE repo_hash = ?")
+            .bind(new_name)
+            .bind(repo_hash)
+            .execute(&mut *tx)
+            .await?;
+        for name in [new_name, repo.name.as_str()] {
+            match repo.org_id {
+                Some(org_id) => sqlx::query("DELETE FROM repo_redirects WHERE org_id = ? AND name = ?").bind(org_id),
+                None => sqlx::query("DELETE FROM repo_redirects WHERE org_id IS NULL AND owner_id = ? AND name = ?")
+                    .bind(repo.owner_id),
+            }
+            .bind(name)
+            .execute(&mut *tx)
+            .await?;
+        }
+        // Organization redirects belong to the organization alone
+        sqlx::query("INSERT INTO repo_redirects (owner_id, org_id, name, repo_hash) VALUES (?, ?, ?, ?)")
+            .bind(if repo.org_id.is_some() { None } else { Some(repo.owner_id) })
+            .bind(repo.org_id)
+            .bind(&repo.name)
+            .bind(repo_hash)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    pub async fn list_public_repositories(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<Repository>, sqlx::Error> {
+        sqlx::query_as::<_, Repository>(
+            "SELECT * FROM repositories WHERE is_private = 0 ORDER BY last_updated DESC LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn list_user_repositories(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<Repository>, sqlx::Error> {
+        sqlx::query_as::<_, Repository>(
+            "SELECT * FROM repositories WHERE owner_id = ? AND org_id IS NULL ORDER BY last_updated DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn update_repository_size(
+        &self,
+        repo_hash: &str,
+        size: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE repositories SET size = ?, last_updated = datetime('now') WHERE repo_hash = ?",
+        )
+        .bind(size)
+        .bind(repo_hash)
+        .execute(&self.pool)
+        .await?;
+
+        let repo = self.get_repository(repo_hash).await?;
+        self.recount_storage_used(repo.owner_id, repo.org_id).await
+    }
+
+    /// Recompute `storage_used` for the account a repository counts against:
+    /// the organization if it has one, otherwise the owning user
+    async fn recount_storage_used(&self, owner_id: i64, org_id: Option<i64>) -> Result<(), sqlx::Error> {
+        match org_id {
+            Some(org_id) => sqlx::query(
+                "UPDATE organizations SET storage_used =
+                     (SELECT COALESCE(SUM(size), 0) FROM repositories WHERE org_id = organizations.id)
+                 WHERE id = ?",
+            )
+            .bind(org_id),
+            None => sqlx::query(
+                "UPDATE users SET storage_used =
+                     (SELECT COALESCE(SUM(size), 0) FROM repositories WHERE owner_id = users.id AND org_id IS NULL)
+                 WHERE id = ?",
+            )
+            .bind(owner_id),
+        }
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_repository_require_anchor(
+        &self,
+        repo_hash: &str,
+        require_anchor: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE repositories SET require_anchor = ? WHERE repo_hash = ?")
+            .bind(require_anchor as i64)
+            .bind(repo_hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_repository(&self, repo_hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM repositories WHERE repo_hash = ?")
+            .bind(repo_hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Node operations
+    pub async fn register_node(&self, node: &RegisterNodeRequest) -> Result<Node, sqlx::Error> {
+        let is_anchor = if node.is_anchor { 1 } else { 0 };
+
+        sqlx::query(
+            "INSERT INTO nodes (node_id, address, port, storage_capacity, is_anchor, access_token)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(node_id) DO UPDATE SET
+                address = excluded.address,
+                port = excluded.port,
+                storage_capacity = excluded.storage_capacity,
+                access_token = excluded.access_token,
+                last_seen = datetime('now')",
+        )
+        .bind(&node.node_id)
+        .bind(&node.address)
+        .bind(node.port)
+        .bind(node.storage_capacity)
+        .bind(is_anchor)
+        .bind(&node.access_token)
+        .execute(&self.pool)
+        .await?;
+
+        self.get_node(&node.node_id).await
+    }
+
+    pub async fn get_node(&self, node_id: &str) -> Result<Node, sqlx::Error> {
+        sqlx::query_as::<_, Node>("SELECT * FROM nodes WHERE node_id = ?")
+            .bind(node_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn list_active_nodes(&self, timeout_minutes: i32) -> Result<Vec<Node>, sqlx::Error> {
+        sqlx::query_as::<_, Node>(
+            "SELECT * FROM nodes
+             WHERE datetime(last_seen) > datetime('now', '-' || ? || ' minutes')
+             ORDER BY reputation_score DESC",
+        )
+        .bind(timeout_minutes)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn list_all_nodes(&self) -> Result<Vec<Node>, sqlx::Error> {
+        sqlx::query_as::<_, Node>("SELECT * FROM nodes ORDER BY is_banned ASC, reputation_score DESC")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Active, unbanned nodes with at least `min_reputation`, best first
+    pub async fn list_replication_targets(
+        &self,
+        timeout_minutes: i32,
+        min_reputation: i32,
+    ) -> Result<Vec<Node>, sqlx::Error> {
+        sqlx::query_as::<_, Node>(
+            "SELECT * FROM nodes
+             WHERE datetime(last_seen) > datetime('now', '-' || ? || ' minutes')
+             AND is_banned = 0
+             AND state = 'online'
+             AND reputation_score >= ?
+             ORDER BY reputation_score DESC",
+        )
+        .bind(timeout_minutes)
+        .bind(min_reputation)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// (node_id, state, minutes since last seen) for every unbanned node
+    pub async fn list_node_liveness(&self) -> Result<Vec<(String, String, i64)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT node_id, state,
+                    CAST((julianday('now') - julianday(last_seen)) * 1440 AS INTEGER)
+             FROM nodes WHERE is_banned = 0",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Move a node to a new liveness state and record the transition
+    pub async fn set_node_state(
+        &self,
+        node_id: &str,
+        from_state: &str,
+        to_state: &str,
+        silent_minutes: i64,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // Only transition from the state we observed
+        let result = sqlx::query(
+            "UPDATE nodes SET state = ?, state_changed_at = datetime('now')
+             WHERE node_id = ? AND state = ?",
+        )
+        .bind(to_state)
+        .bind(node_id)
+        .bind(from_state)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            sqlx::query(
+                "INSERT INTO node_state_transitions (node_id, from_state, to_state, silent_minutes)
+                 VALUES (?, ?, ?, ?)",
+            )
+            .bind(node_id)
+            .bind(from_state)
+            .bind(to_state)
+            .bind(silent_minutes)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn list_node_transitions(&self, limit: i64) -> Result<Vec<NodeStateTransition>, sqlx::Error> {
+        sqlx::query_as::<_, NodeStateTransition>(
+            "SELECT * FROM node_state_transitions ORDER BY id DESC LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Whole minutes since the node was last seen
+    pub async fn node_heartbeat_gap_minutes(&self, node_id: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT CAST((julianday('now') - julianday(last_seen)) * 1440 AS INTEGER)
+             FROM nodes WHERE node_id = ?",
+        )
+        .bind(node_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Returns false if the node is not registered
+    pub async fn update_node_heartbeat(
+        &self,
+        node_id: &str,
+        storage_used: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE nodes SET last_seen = datetime('now'), storage_used = ? WHERE node_id = ?",
+        )
+        .bind(storage_used)
+        .bind(node_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Replica operations
+
+    /// Record a verified replica together with the refs the node confirmed
+    /// holding, so a failure leaves neither behind
+    pub async fn create_replica(
+        &self,
+        repo_hash: &str,
+        node_id: &str,
+        ref_state: &str,
+    ) -> Result<Replica, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO replicas (repo_hash, node_id)
+             VALUES (?, ?)
+             ON CONFLICT(repo_hash, node_id) DO UPDATE SET
+                last_verified = datetime('now')",
+        )
+        .bind(repo_hash)
+        .bind(node_id)
+        .execute(&mut *tx)
+        .await?;
+        Self::set_replica_refs(&mut tx, repo_hash, node_id, ref_state).await?;
+
+        let replica = sqlx::query_as::<_, Replica>("SELECT * FROM replicas WHERE repo_hash = ? AND node_id = ?")
+            .bind(repo_hash)
+            .bind(node_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(replica)
+    }
+
+    pub async fn get_replica(&self, repo_hash: &str, node_id: &str) -> Result<Replica, sqlx::Error> {
+        sqlx::query_as::<_, Replica>("SELECT * FROM replicas WHERE repo_hash = ? AND node_id = ?")
+            .bind(repo_hash)
+            .bind(node_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn list_repo_replicas(&self, repo_hash: &str) -> Result<Vec<NodeInfo>, sqlx::Error> {
+        let nodes = sqlx::query(
+            "SELECT n.node_id, n.address, n.port, n.is_anchor
+             FROM replicas r
+             JOIN nodes n ON r.node_id = n.node_id
+             WHERE r.repo_hash = ?
+             AND datetime(n.last_seen) > datetime('now', '-5 minutes')",
+        )
+        .bind(repo_hash)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(nodes
+            .into_iter()
+            .map(|row| NodeInfo {
+                node_id: row.try_get("node_id").unwrap_or_default(),
+                address: row.try_get("address").unwrap_or_default(),
+                port: row.try_get("port").unwrap_or_default(),
+                is_anchor: row.try_get::<i64, _>("is_anchor").unwrap_or(0) != 0,
+            })
+            .collect())
+    }
+
+    /// Replicas of a repository with their node and recorded ref state
+    pub async fn list_replica_sync(&self, repo_hash: &str) -> Result<Vec<ReplicaSync>, sqlx::Error> {
+        sqlx::query_as::<_, ReplicaSync>(
+            "SELECT r.node_id, n.address, n.port, n.is_anchor, n.state AS node_state,
+                    r.ref_state, r.synced_at
+             FROM replicas r
+             JOIN nodes n ON r.node_id = n.node_id
+             WHERE r.repo_hash = ?
+             ORDER BY n.is_anchor DESC, r.created_at ASC",
+        )
+        .bind(repo_hash)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Remember the refs the primary copy holds
+    pub async fn record_primary_refs(&self, repo_hash: &str, ref_state: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE repositories SET ref_state = ? WHERE repo_hash = ?")
+            .bind(ref_state)
+            .bind(repo_hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// The refs last recorded for the primary copy, if any were
+    pub async fn get_primary_refs(&self, repo_hash: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT ref_state FROM repositories WHERE repo_hash = ?")
+            .bind(repo_hash)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Nodes holding a replica of the repository that should answer requests
+    pub async fn list_live_replica_nodes(&self, repo_hash: &str) -> Result<Vec<Node>, sqlx::Error> {
+        sqlx::query_as::<_, Node>(
+            "SELECT n.* FROM replicas r
+             JOIN nodes n ON r.node_id = n.node_id
+             WHERE r.repo_hash = ? AND n.state IN ('online', 'suspect') AND n.is_banned = 0",
+        )
+        .bind(repo_hash)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // Remember which refs a node confirmed holding
+    pub async fn record_replica_refs(&self, repo_hash: &str, node_id: &str, ref_state: &str) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        Self::set_replica_refs(&mut tx, repo_hash, node_id, ref_state).await?;
+        tx.commit().await
+    }
+
+    async fn set_replica_refs(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        repo_hash: &str,
+        node_id: &str,
+        ref_state: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE replicas SET ref_state = ?, synced_at = datetime('now')
+             WHERE repo_hash = ? AND node_id = ?",
+        )
+        .bind(ref_state)
+        .bind(repo_hash)
+        .bind(node_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_node_replica_hashes(&self, node_id: &str) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>("SELECT repo_hash FROM replicas WHERE node_id = ?")
+            .bind(node_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Replicas on nodes that are still expected to answer (online or suspect)
+    pub async fn list_replicas_on_live_nodes(&self) -> Result<Vec<Replica>, sqlx::Error> {
+        sqlx::query_as::<_, Replica>(
+            "SELECT r.* FROM replicas r
+             JOIN nodes n ON r.node_id = n.node_id
+             WHERE n.state IN ('online', 'suspect')
+             ORDER BY r.last_verified ASC",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // Proof-of-storage passed: refresh verification time and reset misses
+    pub async fn mark_replica_verified(&self, repo_hash: &str, node_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE replicas SET last_verified = datetime('now'), failed_challenges = 0
+             WHERE repo_hash = ? AND node_id = ?",
+        )
+        .bind(repo_hash)
+        .bind(node_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // Proof-of-storage failed: returns the consecutive miss count
+    pub async fn record_failed_challenge(&self, repo_hash: &str, node_id: &str) -> Result<i64, sqlx::Error> {
+        sqlx::query(
+            "UPDATE replicas SET failed_challenges = failed_challenges + 1
+             WHERE repo_hash = ? AND node_id = ?",
+        )
+        .bind(repo_hash)
+        .bind(node_id)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query_scalar("SELECT failed_challenges FROM replicas WHERE repo_hash = ? AND node_id = ?")
+            .bind(repo_hash)
+            .bind(node_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Apply a clamped reputation change and return the new score
+    pub async fn adjust_node_reputation(&self, node_id: &str, delta: i32) -> Result<i32, sqlx::Error> {
+        sqlx::query_scalar(
+            "UPDATE nodes SET reputation_score = MAX(0, MIN(100, reputation_score + ?))
+             WHERE node_id = ?
+             RETURNING reputation_score",
+        )
+        .bind(delta)
+        .bind(node_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Reward every active, unbanned node; returns how many were rewarded
+    pub async fn reward_active_nodes(&self, timeout_minutes: i32, delta: i32) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE nodes SET reputation_score = MIN(100, reputation_score + ?)
+             WHERE is_banned = 0
+             AND datetime(last_seen) > datetime('now', '-' || ? || ' minutes')",
+        )
+        .bind(delta)
+        .bind(timeout_minutes)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn set_node_standing(&self, node_id: &str, is_banned: bool, reputation: i32) -> Result<Node, sqlx::Error> {
+        sqlx::query("UPDATE nodes SET is_banned = ?, reputation_score = ? WHERE node_id = ?")
+            .bind(is_banned as i64)
+            .bind(reputation)
+            .bind(node_id)
+            .execute(&self.pool)
+            .await?;
+
+        self.get_node(node_id).await
+    }
+
+    /// Drop every replica recorded on a node and return the affected repos
+    pub async fn evict_node_replicas(&self, node_id: &str) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("DELETE FROM replicas WHERE node_id = ? RETURNING repo_hash")
+            .bind(node_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Drop every replica recorded on a node; returns how many were dropped
+    pub async fn delete_node_replicas(&self, node_id: &str) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM replicas WHERE node_id = ?")
+            .bind(node_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete_node(&self, node_id: &str) -> Result<(), sqlx::Error> {
+        self.delete_node_replicas(node_id).await?;
+
+        sqlx::query("DELETE FROM nodes WHERE node_id = ?")
+            .bind(node_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Replication job queue
+
+    /// Queue a replication job unless one is already pending or running for
+    /// the repo; either way the active job is returned
+    pub async fn enqueue_replication_job(
+        &self,
+        repo_hash: &str,
+        reason: &str,
+        priority: i64,
+    ) -> Result<ReplicationJob, sqlx::Error> {
+        let inserted = sqlx::query(
+            "INSERT OR IGNORE INTO replication_jobs (repo_hash, reason, priority) VALUES (?, ?, ?)",
+        )
+        .bind(repo_hash)
+        .bind(reason)
+        .bind(priority)
+        .execute(&self.pool)
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            // Already queued - make sure it keeps the higher priority
+            sqlx::query(
+                "UPDATE replication_jobs SET priority = MAX(priority, ?), updated_at = datetime('now')
+                 WHERE repo_hash = ? AND state IN ('pending', 'running')",
+            )
+            .bind(priority)
+            .bind(repo_hash)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        sqlx::query_as::<_, ReplicationJob>(
+            "SELECT * FROM replication_jobs WHERE repo_hash = ? AND state IN ('pending', 'running')",
+        )
+        .bind(repo_hash)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Atomically take the most urgent due job and mark it running
+    pub async fn claim_replication_job(&self) -> Result<Option<ReplicationJob>, sqlx::Error> {
+        sqlx::query_as::<_, ReplicationJob>(
+            "UPDATE replication_jobs
+             SET state = 'running', attempts = attempts + 1, updated_at = datetime('now')
+             WHERE id = (
+                 SELECT id FROM replication_jobs
+                 WHERE state = 'pending' AND datetime(next_attempt_at) <= datetime('now')
+                 ORDER BY priority DESC, id ASC
+                 LIMIT 1
+             ) AND state = 'pending'
+             RETURNING *",
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn complete_replication_job(&self, id: i64, node_id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE replication_jobs
+             SET state = 'succeeded', target_node_id = ?, last_error = NULL, updated_at = datetime('now')
+             WHERE id = ?",
+        )
+        .bind(node_id)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt; `retry_in_secs` of None gives up for good
+    pub async fn fail_replication_job(
+        &self,
+        id: i64,
+        error: &str,
+        retry_in_secs: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        let (state, delay) = match retry_in_secs {
+            Some(secs) => ("pending", secs),
+            None => ("failed", 0),
+        };
+
+        sqlx::query(
+            "UPDATE replication_jobs
+             SET state = ?, last_error = ?,
+                 next_attempt_at = datetime('now', '+' || ? || ' seconds'),
+                 updated_at = datetime('now')
+             WHERE id = ?",
+        )
+        .bind(state)
+        .bind(error)
+        .bind(delay)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_replication_job(&self, id: i64) -> Result<ReplicationJob, sqlx::Error> {
+        sqlx::query_as::<_, ReplicationJob>("SELECT * FROM replication_jobs WHERE id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn list_replication_jobs(
+        &self,
+        state: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<ReplicationJob>, sqlx::Error> {
+        sqlx::query_as::<_, ReplicationJob>(
+            "SELECT * FROM replication_jobs
+             WHERE ? IS NULL OR state = ?
+             ORDER BY id DESC
+             LIMIT ?",
+        )
+        .bind(state)
+        .bind(state)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Job counts keyed by state
+    pub async fn count_replication_jobs(&self) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        sqlx::query_as("SELECT state, COUNT(*) FROM replication_jobs GROUP BY state ORDER BY state")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Put a failed job back in the queue with fresh attempts. Returns false
+    /// if the job has not failed or its repo already has an active job.
+    pub async fn retry_replication_job(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE replication_jobs
+             SET state = 'pending', attempts = 0, next_attempt_at = datetime('now'),
+                 updated_at = datetime('now')
+             WHERE id = ? AND state = 'failed'
+             AND NOT EXISTS (
+                 SELECT 1 FROM replication_jobs active
+                 WHERE active.repo_hash = replication_jobs.repo_hash
+                 AND active.state IN ('pending', 'running')
+             )",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Cancel a job that has not started. Returns false if it cannot be cancelled.
+    pub async fn cancel_replication_job(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE replication_jobs SET state = 'cancelled', updated_at = datetime('now')
+             WHERE id = ? AND state = 'pending'",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Whether the repo's most recent job gave up within the last `secs` seconds
+    pub async fn replication_recently_failed(&self, repo_hash: &str, secs: i64) -> Result<bool, sqlx::Error> {
+        let state: Option<String> = sqlx::query_scalar(
+            "SELECT state FROM replication_jobs
+             WHERE repo_hash = ? AND datetime(updated_at) > datetime('now', '-' || ? || ' seconds')
+             ORDER BY id DESC LIMIT 1",
+        )
+        .bind(repo_hash)
+        .bind(secs)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(state.as_deref() == Some("failed"))
+    }
+
+    /// Delete finished jobs older than `days`
+    pub async fn prune_replication_jobs(&self, days: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM replication_jobs
+             WHERE state IN ('succeeded', 'failed', 'cancelled')
+             AND datetime(updated_at) < datetime('now', '-' || ? || ' days')",
+        )
+        .bind(days)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Jobs left running by a previous process go back to pending
+    pub async fn requeue_running_replication_jobs(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE replication_jobs SET state = 'pending', updated_at = datetime('now')
+             WHERE state = 'running'",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // SSH keys
+
+    pub async fn add_ssh_key(
+        &self,
+        user_id: i64,
+        title: &str,
+        fingerprint: &str,
+        public_key: &str,
+    ) -> Result<SshKey, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO ssh_keys (user_id, title, fingerprint, public_key) VALUES (?, ?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(title)
+        .bind(fingerprint)
+        .bind(public_key)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query_as::<_, SshKey>("SELECT * FROM ssh_keys WHERE id = ?")
+            .bind(result.last_insert_rowid())
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn list_ssh_keys(&self, user_id: i64) -> Result<Vec<SshKey>, sqlx::Error> {
+        sqlx::query_as::<_, SshKey>("SELECT * FROM ssh_keys WHERE user_id = ? ORDER BY id")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Returns false if the key does not exist or belongs to someone else
+    pub async fn delete_ssh_key(&self, id: i64, user_id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM ssh_keys WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_ssh_key_by_fingerprint(&self, fingerprint: &str) -> Result<SshKey, sqlx::Error> {
+        sqlx::query_as::<_, SshKey>("SELECT * FROM ssh_keys WHERE fingerprint = ?")
+            .bind(fingerprint)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn touch_ssh_key(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE ssh_keys SET last_used_at = datetime('now') WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Personal access tokens
+
+    pub async fn create_api_key(
+        &self,
+        user_id: i64,
+        name: &str,
+        key_hash: &str,
+        token_prefix: &str,
+        scopes: &str,
+        expires_in_days: Option<i64>,
+    ) -> Result<ApiKey, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(
+            "INSERT INTO api_keys (user_id, key_hash, name, scopes, token_prefix, expires_at)
+             VALUES (?, ?, ?, ?, ?, CASE WHEN ? IS NULL THEN NULL ELSE datetime('now', '+' || ? || ' days') END)
+             RETURNING *",
+        )
+        .bind(user_id)
+        .bind(key_hash)
+        .bind(name)
+        .bind(scopes)
+        .bind(token_prefix)
+        .bind(expires_in_days)
+        .bind(expires_in_days)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// The user's tokens, including revoked and expired ones
+    pub async fn list_api_keys(&self, user_id: i64) -> Result<Vec<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys WHERE user_id = ? ORDER BY created_at DESC, id DESC")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// The token with this hash, if it is neither revoked nor expired
+    pub async fn get_active_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(
+            "SELECT * FROM api_keys
+             WHERE key_hash = ? AND is_active = 1
+               AND (expires_at IS NULL OR expires_at > datetime('now'))",
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn touch_api_key(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE api_keys SET last_used = datetime('now') WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns false if the token does not exist, belongs to someone else
+    /// or was already revoked
+    pub async fn revoke_api_key(&self, id: i64, user_id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE api_keys SET is_active = 0 WHERE id = ? AND user_id = ? AND is_active = 1")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revoke all of the user's tokens. Returns how many were active.
+    pub async fn revoke_user_api_keys(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("UPDATE api_keys SET is_active = 0 WHERE user_id = ? AND is_active = 1")
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Sessions
+
+    pub async fn insert_session(&self, key: &str, session: &Session) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO sessions (key, user_id, created_at, last_seen, expires_at, ip_address, user_agent)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(key)
+        .bind(session.user_id)
+        .bind(session.created_at)
+        .bind(session.last_seen)
+        .bind(session.expires_at)
+        .bind(&session.ip_address)
+        .bind(&session.user_agent)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_session(&self, key: &str) -> Result<Option<Session>, sqlx::Error> {
+        sqlx::query_as::<_, Session>(
+            "SELECT s.*, u.username FROM sessions s
+             JOIN users u ON u.id = s.user_id
+             WHERE s.key = ?",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn touch_session(&self, key: &str, last_seen: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE sessions SET last_seen = ? WHERE key = ?")
+            .bind(last_seen)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_session(&self, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM sessions WHERE key = ?")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// The user's sessions that expire after `now`, most recently used first
+    pub async fn list_user_sessions(&self, user_id: i64, now: DateTime<Utc>) -> Result<Vec<ActiveSession>, sqlx::Error> {
+        sqlx::query_as::<_, ActiveSession>(
+            "SELECT s.*, u.username FROM sessions s
+             JOIN users u ON u.id = s.user_id
+             WHERE s.user_id = ? AND s.expires_at > ?
+             ORDER BY s.last_seen DESC",
+        )
+        .bind(user_id)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn delete_expired_sessions(&self, now: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM sessions WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Two-factor authentication
+
+    pub async fn get_totp_state(&self, user_id: i64) -> Result<TotpState, sqlx::Error> {
+        sqlx::query_as::<_, TotpState>(
+            "SELECT totp_secret, totp_enabled, totp_last_step FROM users WHERE id = ?",
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Start enrolling with a new secret. Two-factor stays off until it
+    /// is confirmed with a code.
+    pub async fn set_pending_totp_secret(&self, user_id: i64, secret: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE users SET totp_secret = ?, totp_last_step = NULL
+             WHERE id = ? AND totp_enabled = 0",
+        )
+        .bind(secret)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Turn two-factor on with a fresh set of recovery codes
+    pub async fn enable_totp(&self, user_id: i64, step: i64, code_hashes: &[String]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE users SET totp_enabled = 1, totp_last_step = ? WHERE id = ?")
+            .bind(step)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        Self::insert_recovery_codes(&mut tx, user_id, code_hashes).await?;
+
+        tx.commit().await
+    }
+
+    pub async fn disable_totp(&self, user_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "UPDATE users SET totp_secret = NULL, totp_enabled = 0, totp_last_step = NULL WHERE id = ?",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    /// Record that a code from `step` was used. Returns false if that step
+    /// or a later one was already used, so each code works only once.
+    pub async fn record_totp_step(&self, user_id: i64, step: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE users SET totp_last_step = ?
+             WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)",
+        )
+        .bind(step)
+        .bind(user_id)
+        .bind(step)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn insert_recovery_codes(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        user_id: i64,
+        code_hashes: &[String],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut **tx)
+            .await?;
+        for hash in code_hashes {
+            sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) VALUES (?, ?)")
+                .bind(user_id)
+                .bind(hash)
+                .execute(&mut **tx)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace the user's recovery codes
+    pub async fn replace_recovery_codes(&self, user_id: i64, code_hashes: &[String]) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        Self::insert_recovery_codes(&mut tx, user_id, code_hashes).await?;
+        tx.commit().await
+    }
+
+    /// Mark an unused recovery code as used. Returns false if there is no
+    /// such code.
+    pub async fn use_recovery_code(&self, user_id: i64, code_hash: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE recovery_codes SET used_at = datetime('now')
+             WHERE user_id = ? AND code_hash = ? AND used_at IS NULL",
+        )
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn count_unused_recovery_codes(&self, user_id: i64) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM recovery_codes WHERE user_id = ? AND used_at IS NULL")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn create_login_challenge(&self, key: &str, user_id: i64, lifetime_secs: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO login_challenges (key, user_id, expires_at)
+             VALUES (?, ?, datetime('now', '+' || ? || ' seconds'))",
+        )
+        .bind(key)
+        .bind(user_id)
+        .bind(lifetime_secs)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The user an unexpired challenge belongs to, counting this as an
+    /// attempt. Challenges past `max_attempts` are dropped.
+    pub async fn attempt_login_challenge(&self, key: &str, max_attempts: i64) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query("DELETE FROM login_challenges WHERE expires_at <= datetime('now') OR attempts >= ?")
+            .bind(max_attempts)
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query_scalar(
+            "UPDATE login_challenges SET attempts = attempts + 1 WHERE key = ? RETURNING user_id",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn delete_login_challenge(&self, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM login_challenges WHERE key = ?")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Login throttling
+
+    /// Count one more attempt against an account or address in a single
+    /// statement, starting over if the last one was before `stale_before`.
+    /// Nothing is counted while it is locked out. Returns the row as counted.
+    pub async fn count_login_attempt(
+        &self,
+        scope: &str,
+        subject: &str,
+        now: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+    ) -> Result<LoginThrottle, sqlx::Error> {
+        sqlx::query_as::<_, LoginThrottle>(
+            "INSERT INTO login_throttles (scope, subject, failures, last_failure_at)
+             VALUES (?1, ?2, 1, ?3)
+             ON CONFLICT(scope, subject) DO UPDATE SET
+                failures = CASE
+                    WHEN locked_until > ?3 THEN failures
+                    WHEN last_failure_at < ?4 THEN 1
+                    ELSE failures + 1
+                END,
+                previous_failure_at = CASE
+                    WHEN locked_until > ?3 THEN previous_failure_at
+                    WHEN last_failure_at < ?4 THEN NULL
+                    ELSE last_failure_at
+                END,
+                last_failure_at = CASE WHEN locked_until > ?3 THEN last_failure_at ELSE ?3 END
+             RETURNING *",
+        )
+        .bind(scope)
+        .bind(subject)
+        .bind(now)
+        .bind(stale_before)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Take back an attempt that was counted at `now` and then refused,
+    /// unless another attempt has been counted since
+    pub async fn uncount_login_attempt(
+        &self,
+        scope: &str,
+        subject: &str,
+        failures: i64,
+        now: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE login_throttles
+             SET failures = failures - 1, last_failure_at = COALESCE(previous_failure_at, last_failure_at)
+             WHERE scope = ? AND subject = ? AND failures = ? AND last_failure_at = ?",
+        )
+        .bind(scope)
+        .bind(subject)
+        .bind(failures)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stop counting an attempt that succeeded
+    pub async fn forgive_login_attempt(&self, scope: &str, subject: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE login_throttles SET failures = MAX(failures - 1, 0) WHERE scope = ? AND subject = ?")
+            .bind(scope)
+            .bind(subject)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Lock out an account or address with at least `limit` failures until
+    /// `until`. Returns false if it had fewer or was already locked.
+    pub async fn lock_login_throttle(
+        &self,
+        scope: &str,
+        subject: &str,
+        limit: i64,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE login_throttles SET failures = 0, previous_failure_at = NULL, locked_until = ?
+             WHERE scope = ? AND subject = ? AND failures >= ? AND (locked_until IS NULL OR locked_until <= ?)",
+        )
+        .bind(until)
+        .bind(scope)
+        .bind(subject)
+        .bind(limit)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Forget failures for an account or address. Returns false if there
+    /// were none.
+    pub async fn clear_login_throttle(&self, scope: &str, subject: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM login_throttles WHERE scope = ? AND subject = ?")
+            .bind(scope)
+            .bind(subject)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Accounts and addresses locked out at `now`
+    pub async fn list_login_lockouts(&self, now: DateTime<Utc>) -> Result<Vec<LoginThrottle>, sqlx::Error> {
+        sqlx::query_as::<_, LoginThrottle>(
+            "SELECT * FROM login_throttles WHERE locked_until > ? ORDER BY scope, subject",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Drop rows with no recent failure and no lockout in force
+    pub async fn delete_stale_login_throttles(
+        &self,
+        failed_before: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM login_throttles
+             WHERE last_failure_at < ? AND (locked_until IS NULL OR locked_until <= ?)",
+        )
+        .bind(failed_before)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Instance settings
+
+    pub async fn get_setting(&self, key: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT value FROM instance_settings WHERE key = ?")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn set_setting(&self, key: &str, value: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO instance_settings (key, value) VALUES (?, ?)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = datetime('now')",
+        )
+        .bind(key)
+        .bind(value)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY username")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    // Push rules
+
+    /// Rules for a repository; repositories without a row get the defaults
+    pub async fn get_push_rules(&self, repo_hash: &str) -> Result<PushRules, sqlx::Error> {
+        let rules = sqlx::query_as::<_, PushRules>("SELECT * FROM push_rules WHERE repo_hash = ?")
+            .bind(repo_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(rules.unwrap_or_else(|| PushRules::none(repo_hash)))
+    }
+
+    pub async fn save_push_rules(&self, rules: &PushRules) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO push_rules (repo_hash, max_blob_size, forbidden_paths, require_verified_email)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(repo_hash) DO UPDATE SET
+                 max_blob_size = excluded.max_blob_size,
+                 forbidden_paths = excluded.forbidden_paths,
+                 require_verified_email = excluded.require_verified_email,
+                 updated_at = datetime('now')",
+        )
+        .bind(&rules.repo_hash)
+        .bind(rules.max_blob_size)
+        .bind(&rules.forbidden_paths)
+        .bind(rules.require_verified_email)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn list_protected_branches(&self, repo_hash: &str) -> Result<Vec<ProtectedBranch>, sqlx::Error> {
+        let mut branches = sqlx::query_as::<_, ProtectedBranch>(
+            "SELECT * FROM protected_branches WHERE repo_hash = ? ORDER BY pattern",
+        )
+        .bind(repo_hash)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let pushers: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT p.branch_id, u.username
+             FROM protected_branch_pushers p
+             JOIN protected_branches b ON b.id = p.branch_id
+             JOIN users u ON u.id = p.user_id
+             WHERE b.repo_hash = ?
+             ORDER BY u.username",
+        )
+        .bind(repo_hash)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (branch_id, username) in pushers {
+            if let Some(branch) = branches.iter_mut().find(|b| b.id == branch_id) {
+                branch.allowed_pushers.push(username);
+            }
+        }
+
+        Ok(branches)
+    }
+
+    pub async fn get_protected_branch(&self, repo_hash: &str, id: i64) -> Result<ProtectedBranch, sqlx::Error> {
+        self.list_protected_branches(repo_hash)
+            .await?
+            .into_iter()
+            .find(|b| b.id == id)
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    /// Protect a branch pattern, replacing the rules if it is already protected
+    pub async fn save_protected_branch(
+        &self,
+        repo_hash: &str,
+        rule: &BranchProtectionRequest,
+        pusher_ids: &[i64],
+    ) -> Result<ProtectedBranch, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO protected_branches
+                 (repo_hash, pattern, allow_deletion, allow_force_push, require_linear_history, restrict_pushes)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(repo_hash, pattern) DO UPDATE SET
+                 allow_deletion = excluded.allow_deletion,
+                 allow_force_push = excluded.allow_force_push,
+                 require_linear_history = excluded.require_linear_history,
+                 restrict_pushes = excluded.restrict_pushes
+             RETURNING id",
+        )
+        .bind(repo_hash)
+        .bind(&rule.pattern)
+        .bind(rule.allow_deletion as i64)
+        .bind(rule.allow_force_push as i64)
+        .bind(rule.require_linear_history as i64)
+        .bind((rule.restrict_pushes || !pusher_ids.is_empty()) as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Self::replace_branch_pushers(&mut tx, id, pusher_ids).await?;
+        tx.commit().await?;
+
+        self.get_protected_branch(repo_hash, id).await
+    }
+
+    /// Change an existing rule, including its pattern
+    pub async fn update_protected_branch(
+        &self,
+        repo_hash: &str,
+        id: i64,
+        rule: &BranchProtectionRequest,
+        pusher_ids: &[i64],
+    ) -> Result<Option<ProtectedBranch>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE protected_branches
+             SET pattern = ?, allow_deletion = ?, allow_force_push = ?, require_linear_history = ?, restrict_pushes = ?
+             WHERE id = ? AND repo_hash = ?",
+        )
+        .bind(&rule.pattern)
+        .bind(rule.allow_deletion as i64)
+        .bind(rule.allow_force_push as i64)
+        .bind(rule.require_linear_history as i64)
+        .bind((rule.restrict_pushes || !pusher_ids.is_empty()) as i64)
+        .bind(id)
+        .bind(repo_hash)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Self::replace_branch_pushers(&mut tx, id, pusher_ids).await?;
+        tx.commit().await?;
+
+        self.get_protected_branch(repo_hash, id).await.map(Some)
+    }
+
+    async fn replace_branch_pushers(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        branch_id: i64,
+        pusher_ids: &[i64],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM protected_branch_pushers WHERE branch_id = ?")
+            .bind(branch_id)
+            .execute(&mut **tx)
+            .await?;
+        for user_id in pusher_ids {
+            sqlx::query("INSERT OR IGNORE INTO protected_branch_pushers (branch_id, user_id) VALUES (?, ?)")
+                .bind(branch_id)
+                .bind(user_id)
+                .execute(&mut **tx)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn delete_protected_branch(&self, repo_hash: &str, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "DELETE FROM protected_branch_pushers
+             WHERE branch_id IN (SELECT id FROM protected_branches WHERE id = ? AND repo_hash = ?)",
+        )
+        .bind(id)
+        .bind(repo_hash)
+        .execute(&mut *tx)
+        .await?;
+        let result = sqlx::query("DELETE FROM protected_branches WHERE id = ? AND repo_hash = ?")
+            .bind(id)
+            .bind(repo_hash)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Whether the address belongs to an account that verified it
+    pub async fn is_email_verified(&self, email: &str) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM users WHERE lower(email) = lower(?) AND email_verified = 1",
+        )
+        .bind(email)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count > 0)
+    }
+
+    // Collaborators
+
+    /// The role granted to a collaborator, or None for everyone else
+    /// (including the owner)
+    pub async fn get_collaborator_role(&self, repo_hash: &str, user_id: i64) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT role FROM repo_collaborators WHERE repo_hash = ? AND user_id = ?")
+            .bind(repo_hash)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn list_collaborators(&self, repo_hash: &str) -> Result<Vec<Collaborator>, sqlx::Error> {
+        sqlx::query_as::<_, Collaborator>(
+            "SELECT c.repo_hash, c.user_id, u.username, c.role, c.created_at
+             FROM repo_collaborators c
+             JOIN users u ON u.id = c.user_id
+             WHERE c.repo_hash = ?
+             ORDER BY u.username",
+        )
+        .bind(repo_hash)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Returns false if the user is not a collaborator
+    pub async fn set_collaborator_role(&self, repo_hash: &str, user_id: i64, role: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE repo_collaborators SET role = ? WHERE repo_hash = ? AND user_id = ?")
+            .bind(role)
+            .bind(repo_hash)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn remove_collaborator(&self, repo_hash: &str, user_id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM repo_collaborators WHERE repo_hash = ? AND user_id = ?")
+            .bind(repo_hash)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Repositories the user collaborates on, most recently updated first
+    pub async fn list_shared_repositories(&self, user_id: i64) -> Result<Vec<(Repository, String)>, sqlx::Error> {
+        let repos = sqlx::query_as::<_, Repository>(
+            "SELECT r.* FROM repositories r
+             JOIN repo_collaborators c ON c.repo_hash = r.repo_hash
+             WHERE c.user_id = ?
+             ORDER BY r.last_updated DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let roles: std::collections::HashMap<String, String> =
+            sqlx::query_as("SELECT repo_hash, role FROM repo_collaborators WHERE user_id = ?")
+                .bind(user_id)
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .collect();
+
+        Ok(repos
+            .into_iter()
+            .map(|repo| {
+                let role = roles.get(&repo.repo_hash).cloned().unwrap_or_default();
+                (repo, role)
+            })
+            .collect())
+    }
+
+    const INVITATION_COLUMNS: &'static str =
+        "SELECT i.id, i.repo_hash, r.name AS repo_name, COALESCE(g.name, o.username) AS owner_username,
+                i.invitee_id, u.username AS invitee_username, i.role,
+                s.username AS inviter_username, i.created_at
+         FROM repo_invitations i
+         JOIN repositories r ON r.repo_hash = i.repo_hash
+         JOIN users o ON o.id = r.owner_id
+         LEFT JOIN organizations g ON g.id = r.org_id
+         JOIN users u ON u.id = i.invitee_id
+         JOIN users s ON s.id = i.inviter_id";
+
+    /// Invite a user, replacing the role of any pending invitation
+    pub async fn create_invitation(
+        &self,
+        repo_hash: &str,
+        invitee_id: i64,
+        role: &str,
+        inviter_id: i64,
+    ) -> Result<RepoInvitation, sqlx::Error> {
+        // Read back on the same connection; another one may not see the
+        // row until the RETURNING statement has been finished
+        let mut tx = self.pool.begin().await?;
+
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO repo_invitations (repo_hash, invitee_id, role, inviter_id)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(repo_hash, invitee_id) DO UPDATE SET
+                 role = excluded.role,
+                 inviter_id = excluded.inviter_id
+             RETURNING id",
+        )
+        .bind(repo_hash)
+        .bind(invitee_id)
+        .bind(role)
+        .bind(inviter_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let invitation = sqlx::query_as::<_, RepoInvitation>(&format!("{} WHERE i.id = ?", Self::INVITATION_COLUMNS))
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(invitation)
+    }
+
+    pub async fn list_repo_invitations(&self, repo_hash: &str) -> Result<Vec<RepoInvitation>, sqlx::Error> {
+        sqlx::query_as::<_, RepoInvitation>(&format!(
+            "{} WHERE i.repo_hash = ? ORDER BY i.created_at",
+            Self::INVITATION_COLUMNS
+        ))
+        .bind(repo_hash)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn list_user_invitations(&self, user_id: i64) -> Result<Vec<RepoInvitation>, sqlx::Error> {
+        sqlx::query_as::<_, RepoInvitation>(&format!(
+            "{} WHERE i.invitee_id = ? ORDER BY i.created_at DESC",
+            Self::INVITATION_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Turn the user's invitation into a collaborator row. Returns the
+    /// repository hash, or None if there is no such invitation for them.
+    pub async fn accept_invitation(&self, id: i64, user_id: i64) -> Result<Option<String>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let invitation: Option<(String, String)> = sqlx::query_as(
+            "DELETE FROM repo_invitations WHERE id = ? AND invitee_id = ? RETURNING repo_hash, role",
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some((repo_hash, role)) = invitation else {
+            return Ok(None);
+        };
+
         sqlx::query(
-            "INSERT INTO repositories (repo_hash, owner_id, name, description, storage_tier, is_private)
-             VALUES (?, ?, ?, ?, ?, ?)"
+            "INSERT INTO repo_collaborators (repo_hash, user_id, role) VALUES (?, ?, ?)
+             ON CONFLICT(repo_hash, user_id) DO UPDATE SET role = excluded.role",
         )
-        .bind(repo_hash)
-        .bind(owner_id)
-        .bind(&repo.name)
-        .bind(&repo.description)
-        .bind(&repo.storage_tier)
-        .bind(is_private)
-        .execute(&self.pool)
+        .bind(&repo_hash)
+        .bind(user_id)
+        .bind(&role)
+        .execute(&mut *tx)
         .await?;
 
-        self.get_repository(repo_hash).await
+        tx.commit().await?;
+        Ok(Some(repo_hash))
     }
 
-    pub async fn get_repository(&self, repo_hash: &str) -> Result<Repository, sqlx::Error> {
-        sqlx::query_as::<_, Repository>("SELECT * FROM repositories WHERE repo_hash = ?")
+    /// Returns false if the invitation does not exist or is for someone else
+    pub async fn decline_invitation(&self, id: i64, user_id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM repo_invitations WHERE id = ? AND invitee_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Withdraw a pending invitation to a repository
+    pub async fn cancel_invitation(&self, repo_hash: &str, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM repo_invitations WHERE id = ? AND repo_hash = ?")
+            .bind(id)
             .bind(repo_hash)
-            .fetch_one(&self.pool)
-            .await
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
     }
 
-    pub async fn list_public_repositories(
+    // Organizations
+
+    /// Create an organization with `creator_id` as its first owner
+    pub async fn create_organization(
         &self,
-        limit: i64,
-    ) -> Result<Vec<Repository>, sqlx::Error> {
-        sqlx::query_as::<_, Repository>(
-            "SELECT * FROM repositories WHERE is_private = 0 ORDER BY last_updated DESC LIMIT ?",
+        name: &str,
+        description: Option<&str>,
+        creator_id: i64,
+    ) -> Result<Organization, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let org = sqlx::query_as::<_, Organization>(
+            "INSERT INTO organizations (name, description) VALUES (?, ?) RETURNING *",
         )
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await
+        .bind(name)
+        .bind(description)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query("INSERT INTO org_members (org_id, user_id, role) VALUES (?, ?, 'owner')")
+            .bind(org.id)
+            .bind(creator_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(org)
     }
 
-    pub async fn list_user_repositories(
-        &self,
-        user_id: i64,
-    ) -> Result<Vec<Repository>, sqlx::Error> {
-        sqlx::query_as::<_, Repository>(
-            "SELECT * FROM repositories WHERE owner_id = ? ORDER BY last_updated DESC",
-        )
-        .bind(user_id)
-        .fetch_all(&self.pool)
-        .await
+    pub async fn get_organization(&self, id: i64) -> Result<Organization, sqlx::Error> {
+        sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
     }
 
-    pub async fn update_repository_size(
-        &self,
-        repo_hash: &str,
-        size: i64,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query(
-            "UPDATE repositories SET size = ?, last_updated = datetime('now') WHERE repo_hash = ?",
+    pub async fn get_organization_by_name(&self, name: &str) -> Result<Organization, sqlx::Error> {
+        sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE name = ?")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Whether a user or organization already goes by this name, ignoring case
+    pub async fn account_name_taken(&self, name: &str) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT (SELECT COUNT(*) FROM users WHERE lower(username) = lower(?1))
+                  + (SELECT COUNT(*) FROM organizations WHERE lower(name) = lower(?1))",
         )
-        .bind(size)
-        .bind(repo_hash)
-        .execute(&self.pool)
+        .bind(name)
+        .fetch_one(&self.pool)
         .await?;
 
-        Ok(())
+        Ok(count > 0)
     }
 
-    pub async fn delete_repository(&self, repo_hash: &str) -> Result<(), sqlx::Error> {
-        sqlx::query("DELETE FROM repositories WHERE repo_hash = ?")
-            .bind(repo_hash)
+    /// Whether an organization goes by this name, ignoring case
+    pub async fn organization_name_taken(&self, name: &str) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM organizations WHERE lower(name) = lower(?)")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    pub async fn update_organization(&self, id: i64, description: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE organizations SET description = ? WHERE id = ?")
+            .bind(description)
+            .bind(id)
             .execute(&self.pool)
             .await?;
 
         Ok(())
     }
 
-    // Node operations
-    pub async fn register_node(&self, node: &RegisterNodeRequest) -> Result<Node, sqlx::Error> {
-        let is_anchor = if node.is_anchor { 1 } else { 0 };
-
-        sqlx::query(
-            "INSERT INTO nodes (node_id, address, port, storage_capacity, is_anchor)
-             VALUES (?, ?, ?, ?, ?)
-             ON CONFLICT(node_id) DO UPDATE SET
-                address = excluded.address,
-                port = excluded.port,
-                storage_capacity = excluded.storage_capacity,
-                last_seen = datetime('now')",
+    /// Organizations the user belongs to, with their role in each
+    pub async fn list_user_organizations(&self, user_id: i64) -> Result<Vec<(Organization, String)>, sqlx::Error> {
+        let orgs = sqlx::query_as::<_, Organization>(
+            "SELECT o.* FROM organizations o
+             JOIN org_members m ON m.org_id = o.id
+             WHERE m.user_id = ?
+             ORDER BY o.name",
         )
-        .bind(&node.node_id)
-        .bind(&node.address)
-        .bind(node.port)
-        .bind(node.storage_capacity)
-        .bind(is_anchor)
-        .execute(&self.pool)
+        .bind(user_id)
+        .fetch_all(&self.pool)
         .await?;
 
-        self.get_node(&node.node_id).await
+        let roles: std::collections::HashMap<i64, String> =
+            sqlx::query_as("SELECT org_id, role FROM org_members WHERE user_id = ?")
+                .bind(user_id)
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .collect();
+
+        Ok(orgs
+            .into_iter()
+            .map(|org| {
+                let role = roles.get(&org.id).cloned().unwrap_or_default();
+                (org, role)
+            })
+            .collect())
     }
 
-    pub async fn get_node(&self, node_id: &str) -> Result<Node, sqlx::Error> {
-        sqlx::query_as::<_, Node>("SELECT * FROM nodes WHERE node_id = ?")
-            .bind(node_id)
-            .fetch_one(&self.pool)
+    /// The user's role in the organization, or None if they are not a member
+    pub async fn get_org_role(&self, org_id: i64, user_id: i64) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT role FROM org_members WHERE org_id = ? AND user_id = ?")
+            .bind(org_id)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
             .await
     }
 
-    pub async fn list_active_nodes(&self, timeout_minutes: i32) -> Result<Vec<Node>, sqlx::Error> {
-        sqlx::query_as::<_, Node>(
-            "SELECT * FROM nodes
-             WHERE datetime(last_seen) > datetime('now', '-' || ? || ' minutes')
-             ORDER BY reputation_score DESC",
+    pub async fn list_org_members(&self, org_id: i64) -> Result<Vec<OrgMember>, sqlx::Error> {
+        sqlx::query_as::<_, OrgMember>(
+            "SELECT m.org_id, m.user_id, u.username, m.role, m.created_at
+             FROM org_members m
+             JOIN users u ON u.id = m.user_id
+             WHERE m.org_id = ?
+             ORDER BY m.role DESC, u.username",
         )
-        .bind(timeout_minutes)
+        .bind(org_id)
         .fetch_all(&self.pool)
         .await
     }
 
-    pub async fn update_node_heartbeat(
-        &self,
-        node_id: &str,
-        storage_used: i64,
-    ) -> Result<(), sqlx::Error> {
+    pub async fn count_org_owners(&self, org_id: i64) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM org_members WHERE org_id = ? AND role = 'owner'")
+            .bind(org_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Add a member or change their role
+    pub async fn set_org_member(&self, org_id: i64, user_id: i64, role: &str) -> Result<(), sqlx::Error> {
         sqlx::query(
-            "UPDATE nodes SET last_seen = datetime('now'), storage_used = ? WHERE node_id = ?",
+            "INSERT INTO org_members (org_id, user_id, role) VALUES (?, ?, ?)
+             ON CONFLICT(org_id, user_id) DO UPDATE SET role = excluded.role",
         )
-        .bind(storage_used)
-        .bind(node_id)
+        .bind(org_id)
+        .bind(user_id)
+        .bind(role)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    // Replica operations
-    pub async fn create_replica(
-        &self,
-        repo_hash: &str,
-        node_id: &str,
-    ) -> Result<Replica, sqlx::Error> {
+    /// Remove a member from the organization and all of its teams
+    pub async fn remove_org_member(&self, org_id: i64, user_id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
         sqlx::query(
-            "INSERT INTO replicas (repo_hash, node_id)
-             VALUES (?, ?)
-             ON CONFLICT(repo_hash, node_id) DO UPDATE SET
-                last_verified = datetime('now')",
+            "DELETE FROM team_members
+             WHERE user_id = ? AND team_id IN (SELECT id FROM teams WHERE org_id = ?)",
         )
-        .bind(repo_hash)
-        .bind(node_id)
-        .execute(&self.pool)
+        .bind(user_id)
+        .bind(org_id)
+        .execute(&mut *tx)
         .await?;
+        let result = sqlx::query("DELETE FROM org_members WHERE org_id = ? AND user_id = ?")
+            .bind(org_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
 
-        sqlx::query_as::<_, Replica>("SELECT * FROM replicas WHERE repo_hash = ? AND node_id = ?")
-            .bind(repo_hash)
-            .bind(node_id)
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_org_repositories(&self, org_id: i64) -> Result<Vec<Repository>, sqlx::Error> {
+        sqlx::query_as::<_, Repository>(
+            "SELECT * FROM repositories WHERE org_id = ? ORDER BY last_updated DESC",
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // Teams
+
+    /// Teams of an organization with their members and repository grants
+    pub async fn list_teams(&self, org_id: i64) -> Result<Vec<Team>, sqlx::Error> {
+        let mut teams = sqlx::query_as::<_, Team>("SELECT * FROM teams WHERE org_id = ? ORDER BY name")
+            .bind(org_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        for team in &mut teams {
+            team.members = sqlx::query_scalar(
+                "SELECT u.username FROM team_members m
+                 JOIN users u ON u.id = m.user_id
+                 WHERE m.team_id = ?
+                 ORDER BY u.username",
+            )
+            .bind(team.id)
+            .fetch_all(&self.pool)
+            .await?;
+            team.repos = sqlx::query_as::<_, TeamRepo>(
+                "SELECT t.team_id, t.repo_hash, r.name AS repo_name, t.role
+                 FROM team_repos t
+                 JOIN repositories r ON r.repo_hash = t.repo_hash
+                 WHERE t.team_id = ?
+                 ORDER BY r.name",
+            )
+            .bind(team.id)
+            .fetch_all(&self.pool)
+            .await?;
+        }
+
+        Ok(teams)
+    }
+
+    pub async fn get_team_by_name(&self, org_id: i64, name: &str) -> Result<Team, sqlx::Error> {
+        sqlx::query_as::<_, Team>("SELECT * FROM teams WHERE org_id = ? AND name = ?")
+            .bind(org_id)
+            .bind(name)
             .fetch_one(&self.pool)
             .await
     }
 
-    pub async fn list_repo_replicas(&self, repo_hash: &str) -> Result<Vec<NodeInfo>, sqlx::Error> {
-        let nodes = sqlx::query(
-            "SELECT n.node_id, n.address, n.port, n.is_anchor
-             FROM replicas r
-             JOIN nodes n ON r.node_id = n.node_id
-             WHERE r.repo_hash = ?
-             AND datetime(n.last_seen) > datetime('now', '-5 minutes')",
+    pub async fn create_team(&self, org_id: i64, name: &str, description: Option<&str>) -> Result<Team, sqlx::Error> {
+        sqlx::query_as::<_, Team>("INSERT INTO teams (org_id, name, description) VALUES (?, ?, ?) RETURNING *")
+            .bind(org_id)
+            .bind(name)
+            .bind(description)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn delete_team(&self, team_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM team_members WHERE team_id = ?")
+            .bind(team_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM team_repos WHERE team_id = ?")
+            .bind(team_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM teams WHERE id = ?")
+            .bind(team_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    pub async fn add_team_member(&self, team_id: i64, user_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR IGNORE INTO team_members (team_id, user_id) VALUES (?, ?)")
+            .bind(team_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_team_member(&self, team_id: i64, user_id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM team_members WHERE team_id = ? AND user_id = ?")
+            .bind(team_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Grant a team access to a repository or change its role
+    pub async fn set_team_repo(&self, team_id: i64, repo_hash: &str, role: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO team_repos (team_id, repo_hash, role) VALUES (?, ?, ?)
+             ON CONFLICT(team_id, repo_hash) DO UPDATE SET role = excluded.role",
         )
+        .bind(team_id)
         .bind(repo_hash)
-        .fetch_all(&self.pool)
+        .bind(role)
+        .execute(&self.pool)
         .await?;
 
-        Ok(nodes
-            .into_iter()
-            .map(|row| NodeInfo {
-                node_id: row.try_get("node_id").unwrap_or_default(),
-                address: row.try_get("address").unwrap_or_default(),
-                port: row.try_get("port").unwrap_or_default(),
-                is_anchor: row.try_get::<i64, _>("is_anchor").unwrap_or(0) != 0,
-            })
-            .collect())
+        Ok(())
+    }
+
+    pub async fn remove_team_repo(&self, team_id: i64, repo_hash: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM team_repos WHERE team_id = ? AND repo_hash = ?")
+            .bind(team_id)
+            .bind(repo_hash)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Roles the user holds on a repository through their teams
+    pub async fn get_team_repo_roles(&self, repo_hash: &str, user_id: i64) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT t.role FROM team_repos t
+             JOIN team_members m ON m.team_id = t.team_id
+             WHERE t.repo_hash = ? AND m.user_id = ?",
+        )
+        .bind(repo_hash)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
     }
 
     pub async fn get_replica_count(&self, repo_hash: &str) -> Result<i64, sqlx::Error> {
@@ -665,50 +2748,106 @@ impl Database {
     }
 // Delete repository with all related data
 pub async fn delete_repository_complete(&self, repo_hash: &str) -> Result<(), sqlx::Error> {
+    let repo = self.get_repository(repo_hash).await?;
+
     // Start a transaction to ensure atomicity
     let mut tx = self.pool.begin().await?;
     
+    Self::delete_repository_rows(&mut tx, repo_hash).await?;
+
+    // Commit the transaction
+    tx.commit().await?;
+
+    self.recount_storage_used(repo.owner_id, repo.org_id).await
+}
+
+/// Everything stored about a repository, for deleting it inside a larger
+/// transaction
+async fn delete_repository_rows(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    repo_hash: &str,
+) -> Result<(), sqlx::Error> {
     // Delete in order to avoid foreign key constraint violations
     
     // 1. Delete replicas
     sqlx::query("DELETE FROM replicas WHERE repo_hash = ?")
         .bind(repo_hash)
-        .execute(&mut *tx)
+        .execute(&mut **tx)
         .await?;
     
     // 2. Delete pins
     sqlx::query("DELETE FROM pins WHERE repo_hash = ?")
         .bind(repo_hash)
-        .execute(&mut *tx)
+        .execute(&mut **tx)
         .await?;
     
     // 3. Delete stars
     sqlx::query("DELETE FROM repo_stars WHERE repo_hash = ?")
         .bind(repo_hash)
-        .execute(&mut *tx)
+        .execute(&mut **tx)
         .await?;
     
     // 4. Delete tags
     sqlx::query("DELETE FROM repo_tags WHERE repo_hash = ?")
         .bind(repo_hash)
-        .execute(&mut *tx)
+        .execute(&mut **tx)
         .await?;
     
     // 5. Delete access logs
     sqlx::query("DELETE FROM repo_access_log WHERE repo_hash = ?")
         .bind(repo_hash)
-        .execute(&mut *tx)
+        .execute(&mut **tx)
         .await?;
     
-    // 6. Finally delete the repository itself
-    sqlx::query("DELETE FROM repositories WHERE repo_hash = ?")
+    // 6. Delete queued replication jobs
+    sqlx::query("DELETE FROM replication_jobs WHERE repo_hash = ?")
         .bind(repo_hash)
-        .execute(&mut *tx)
+        .execute(&mut **tx)
         .await?;
     
-    // Commit the transaction
-    tx.commit().await?;
+    // 7. Delete push rules and branch protection
+    sqlx::query("DELETE FROM push_rules WHERE repo_hash = ?")
+        .bind(repo_hash)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(
+        "DELETE FROM protected_branch_pushers
+         WHERE branch_id IN (SELECT id FROM protected_branches WHERE repo_hash = ?)",
+    )
+    .bind(repo_hash)
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query("DELETE FROM protected_branches WHERE repo_hash = ?")
+        .bind(repo_hash)
+        .execute(&mut **tx)
+        .await?;
+    
+    // 8. Delete redirects from old names
+    sqlx::query("DELETE FROM repo_redirects WHERE repo_hash = ?")
+        .bind(repo_hash)
+        .execute(&mut **tx)
+        .await?;
     
+    // 9. Delete collaborators and pending invitations
+    sqlx::query("DELETE FROM repo_collaborators WHERE repo_hash = ?")
+        .bind(repo_hash)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM repo_invitations WHERE repo_hash = ?")
+        .bind(repo_hash)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM team_repos WHERE repo_hash = ?")
+        .bind(repo_hash)
+        .execute(&mut **tx)
+        .await?;
+
+    // 10. Finally delete the repository itself
+    sqlx::query("DELETE FROM repositories WHERE repo_hash = ?")
+        .bind(repo_hash)
+        .execute(&mut **tx)
+        .await?;
+
     Ok(())
 }
     // Get unhealthy repositories (below minimum replica count)
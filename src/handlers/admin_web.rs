@@ -1,17 +1,19 @@
 // Hyrule/src/handlers/admin_web.rs
 use axum::{
-    extract::State,
+    extract::{Form, State},
     http::StatusCode,
     response::{Html, Redirect},
 };
 use axum_extra::extract::cookie::CookieJar;
+use serde::Deserialize;
 use std::sync::Arc;
 
+use crate::services::{login_throttle, two_factor};
 use crate::templates;
 use crate::AppState;
 
 // Helper to check admin access - MUST be used by all admin endpoints
-async fn check_admin_access(
+pub(crate) async fn check_admin_access(
     state: &Arc<AppState>,
     jar: &CookieJar,
 ) -> Result<(i64, String), (StatusCode, Html<String>)> {
@@ -40,6 +42,12 @@ async fn check_admin_access(
     if user.is_admin == 0 {
         return Err((StatusCode::FORBIDDEN, Html(access_denied())));
     }
+    if user.must_change_password != 0 {
+        return Err((StatusCode::FORBIDDEN, Html(password_change_required())));
+    }
+    if two_factor::must_enroll(&state.db, &user).await {
+        return Err((StatusCode::FORBIDDEN, Html(two_factor_required())));
+    }
 
     Ok((session.user_id, session.username))
 }
@@ -55,6 +63,28 @@ fn redirect_to_login() -> String {
     )
 }
 
+fn password_change_required() -> String {
+    crate::templates::render_page(
+        "Password Change Required",
+        r#"<div class="section">
+            <h1>🔑 Password Change Required</h1>
+            <p>Your password was generated for you. Choose your own before using the admin panel.</p>
+            <a href="/change-password" class="btn btn-primary">Change Password</a>
+        </div>"#,
+    )
+}
+
+fn two_factor_required() -> String {
+    crate::templates::render_page(
+        "Two-Factor Required",
+        r#"<div class="section">
+            <h1>🔐 Two-Factor Authentication Required</h1>
+            <p>Admins must enable two-factor authentication before using the admin panel.</p>
+            <a href="/profile/2fa" class="btn btn-primary">Set Up Two-Factor</a>
+        </div>"#,
+    )
+}
+
 fn access_denied() -> String {
     crate::templates::render_page(
         "Access Denied",
@@ -89,24 +119,61 @@ pub async fn admin_dashboard(
 
     let bandwidth_24h = state.db.get_bandwidth_24h(None).await.unwrap_or(0);
 
+    let job_counts = state.db.count_replication_jobs().await.unwrap_or_default();
+    let jobs = state.db.list_replication_jobs(None, 20).await.unwrap_or_default();
+
     Ok(Html(templates::admin::render_dashboard(
         stats.total_repos,
         stats.total_nodes,
         unhealthy_repos,
         stats.total_storage_used,
         bandwidth_24h,
+        &job_counts,
+        &jobs,
     )))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ReplicationJobAction {
+    pub job_id: i64,
+    pub action: String,
+}
+
+pub async fn replication_job_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<ReplicationJobAction>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (_user_id, username) = check_admin_access(&state, &jar).await?;
+
+    let done = match form.action.as_str() {
+        "retry" => state.db.retry_replication_job(form.job_id).await,
+        "cancel" => state.db.cancel_replication_job(form.job_id).await,
+        _ => return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action")))),
+    }
+    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Html(error_page("Failed to update the job"))))?;
+
+    if !done {
+        return Err((
+            StatusCode::CONFLICT,
+            Html(error_page("The job cannot be changed in its current state")),
+        ));
+    }
+
+    tracing::info!("Admin {} ran '{}' on replication job {}", username, form.action, form.job_id);
+    Ok(Redirect::to("/admin"))
+}
+
 pub async fn admin_nodes_page(
     State(state): State<Arc<AppState>>,
     jar: CookieJar,
 ) -> Result<Html<String>, (StatusCode, Html<String>)> {
     let (_user_id, _username) = check_admin_access(&state, &jar).await?;
 
+    // Offline and banned nodes are listed too so they can be trusted or removed
     let nodes = state
         .db
-        .list_active_nodes(state.config.node_heartbeat_timeout_minutes)
+        .list_all_nodes()
         .await
         .map_err(|_| {
             (
@@ -123,7 +190,9 @@ pub async fn admin_nodes_page(
         nodes_with_counts.push((node, repo_count));
     }
 
-    Ok(Html(templates::admin::render_nodes(&nodes_with_counts)))
+    let transitions = state.db.list_node_transitions(25).await.unwrap_or_default();
+
+    Ok(Html(templates::admin::render_nodes(&nodes_with_counts, &transitions)))
 }
 
 pub async fn admin_repos_page(
@@ -148,10 +217,65 @@ pub async fn admin_users_page(
 ) -> Result<Html<String>, (StatusCode, Html<String>)> {
     let (_user_id, _username) = check_admin_access(&state, &jar).await?;
 
-    Ok(Html(templates::render_page(
-        "User Management",
-        "<h1>User Management</h1><p>Coming soon...</p>",
-    )))
+    let users = state.db.list_users().await.map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html(error_page("Failed to load users")),
+        )
+    })?;
+    let require_admin_2fa = two_factor::admin_2fa_required(&state.db).await;
+    let locked = login_throttle::locked_accounts(&state.db, chrono::Utc::now()).await;
+
+    Ok(Html(templates::admin::render_users(&users, require_admin_2fa, &locked)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserAdminAction {
+    pub action: String,
+    pub value: Option<String>,
+}
+
+pub async fn admin_user_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<UserAdminAction>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (user_id, username) = check_admin_access(&state, &jar).await?;
+
+    match form.action.as_str() {
+        "require_admin_2fa" => {
+            let admin = state.db.get_user_by_id(user_id).await.map_err(|_| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Html(error_page("Failed to verify admin")),
+                )
+            })?;
+            let required = form.value.as_deref() == Some("true");
+            two_factor::set_admin_2fa_required(&state.db, &admin, required)
+                .await
+                .map_err(|(status, message)| (status, Html(error_page(&message))))?;
+            tracing::info!("Admin {} set two-factor required for admins to {}", username, required);
+        }
+        "unlock" => {
+            let user = state
+                .db
+                .get_user_by_username(form.value.as_deref().unwrap_or(""))
+                .await
+                .map_err(|_| (StatusCode::NOT_FOUND, Html(error_page("User not found"))))?;
+            login_throttle::unlock_account(&state.db, &user, &username)
+                .await
+                .map_err(|_| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Html(error_page("Failed to unlock account")),
+                    )
+                })?;
+            tracing::info!("Admin {} unlocked sign-in for {}", username, user.username);
+        }
+        _ => return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action")))),
+    }
+
+    Ok(Redirect::to("/admin/users"))
 }
 
 fn error_page(message: &str) -> String {
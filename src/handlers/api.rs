@@ -1,13 +1,20 @@
 // src/handlers/api.rs
 use axum::{
+    body::Body,
     extract::{Path, State},
-    http::StatusCode,
+    http::{header, StatusCode},
+    response::Response,
     Json,
 };
 use std::sync::Arc;
 
+use crate::auth::repo_access::{authorize, RepoRole};
+use crate::auth::AuthUser;
 use crate::models::*;
-use crate::services::replication::ReplicationService;
+use crate::services::node_protocol::{self, SignedMessage};
+use crate::services::health::NodeState;
+use crate::services::replication_queue::PRIORITY_MANUAL;
+use crate::services::reputation::{ReputationEvent, ReputationService};
 use crate::utils::hash::generate_repo_hash;
 use crate::AppState;
 
@@ -94,29 +101,56 @@ pub async fn get_repo_nodes(
     Ok(Json(nodes))
 }
 
+/// Queue a replication job for a repository the caller administers; the
+/// queue workers do the transfer
 pub async fn request_replication(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(repo_hash): Path<String>,
 ) -> Result<Json<ReplicationResponse>, StatusCode> {
-    let replication_service = ReplicationService::new(state.db.clone());
-    
-    match replication_service.trigger_replication(&repo_hash).await {
-        Ok(target_node) => Ok(Json(ReplicationResponse {
-            success: true,
-            message: format!("Replication requested to node: {}", target_node),
-        })),
-        Err(e) => Ok(Json(ReplicationResponse {
-            success: false,
-            message: format!("Replication failed: {}", e),
-        })),
-    }
+    let repo = state.db
+        .get_repository(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    authorize(&state.db, &repo, Some(user.id), RepoRole::Admin).await?;
+
+    let job = state.db
+        .enqueue_replication_job(&repo.repo_hash, "manual", PRIORITY_MANUAL)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tracing::info!("User {} queued replication job {} for {}", user.username, job.id, job.repo_hash);
+    Ok(Json(ReplicationResponse {
+        success: true,
+        message: format!("Replication job {} queued", job.id),
+        job,
+    }))
 }
 
 // Node endpoints
+
+/// Reject node messages that are unsigned or not signed by the claimed node
+fn verify_node_message<M: SignedMessage>(kind: &str, message: &M) -> Result<(), StatusCode> {
+    node_protocol::verify(message).map_err(|e| {
+        tracing::warn!("Rejected {} from node {}: {}", kind, message.signer(), e);
+        StatusCode::UNAUTHORIZED
+    })
+}
+
 pub async fn register_node(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<RegisterNodeRequest>,
 ) -> Result<Json<Node>, StatusCode> {
+    verify_node_message("registration", &payload)?;
+
+    // Re-registering must not lift a ban
+    if let Ok(existing) = state.db.get_node(&payload.node_id).await {
+        if existing.is_banned != 0 {
+            tracing::warn!("Refused registration from banned node {}", payload.node_id);
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
     let node = state.db
         .register_node(&payload)
         .await
@@ -152,19 +186,115 @@ pub async fn node_heartbeat(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<NodeHeartbeat>,
 ) -> Result<StatusCode, StatusCode> {
+    verify_node_message("heartbeat", &payload)?;
+
+    // Unknown nodes must register first
+    let node = state.db
+        .get_node(&payload.node_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    
+    if node.is_banned != 0 {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    // Long silences cost reputation
+    let timeout = state.config.node_heartbeat_timeout_minutes as i64;
+    let gap = state.db.node_heartbeat_gap_minutes(&node.node_id).await.unwrap_or(0);
+    if gap >= timeout {
+        tracing::info!("Node {} was silent for {} minutes", node.node_id, gap);
+        let event = ReputationEvent::HeartbeatGap { minutes: gap, timeout_minutes: timeout };
+        let _ = ReputationService::new(state.db.clone()).record(&node.node_id, event).await;
+    }
+    
     state.db
         .update_node_heartbeat(&payload.node_id, payload.storage_used)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
-    // Update replicas
-    for repo_hash in &payload.hosted_repos {
-        let _ = state.db.create_replica(repo_hash, &payload.node_id).await;
+    // Only a signed heartbeat brings a suspect, offline or evicted node back
+    if node.state != NodeState::Online.as_str() {
+        tracing::info!("Node {} is back online after {} minutes", node.node_id, gap);
+        let _ = state.db
+            .set_node_state(&node.node_id, &node.state, NodeState::Online.as_str(), gap)
+            .await;
+    }
+    
+    // Reconcile signed replica claims with the replicas we transferred.
+    // Claims alone never create replicas - only a verified transfer does.
+    let recorded = state.db
+        .list_node_replica_hashes(&payload.node_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    for repo_hash in &recorded {
+        if !payload.hosted_repos.contains(repo_hash) {
+            tracing::warn!("Node {} no longer hosts {}, dropping replica", payload.node_id, repo_hash);
+            let _ = state.db.delete_replica(repo_hash, &payload.node_id).await;
+        }
+    }
+    
+    let unverified = payload.hosted_repos.iter().filter(|h| !recorded.contains(h)).count();
+    if unverified > 0 {
+        tracing::debug!("Node {} claims {} replicas without a verified transfer", payload.node_id, unverified);
     }
     
     Ok(StatusCode::OK)
 }
 
+/// Serve a syncing node the objects between its refs and the new tips
+pub async fn node_fetch_pack(
+    State(state): State<Arc<AppState>>,
+    Path(repo_hash): Path<String>,
+    Json(payload): Json<PackRequest>,
+) -> Result<Response, StatusCode> {
+    verify_node_message("pack request", &payload)?;
+
+    if payload.repo_hash != repo_hash || payload.wants.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let node = state.db
+        .get_node(&payload.node_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    if node.is_banned != 0 {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Only nodes we transferred the repository to may pull from it
+    state.db
+        .get_replica(&repo_hash, &node.node_id)
+        .await
+        .map_err(|_| StatusCode::FORBIDDEN)?;
+
+    let is_oid = |s: &String| s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit());
+    if !payload.wants.iter().chain(&payload.haves).all(is_oid) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let storage = state.git_storage.clone();
+    let hash = repo_hash.clone();
+    let pack = tokio::task::spawn_blocking(move || {
+        storage.create_thin_pack(&hash, &payload.wants, &payload.haves)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        tracing::warn!("Failed to build pack for node {}: {}", node.node_id, e);
+        StatusCode::UNPROCESSABLE_ENTITY
+    })?;
+
+    let _ = state.db.log_bandwidth(&repo_hash, pack.len() as i64, "replica-sync").await;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, node_protocol::PACK_CONTENT_TYPE)
+        .body(Body::from(pack))
+        .unwrap())
+}
+
 // Stats endpoint
 pub async fn network_stats(
     State(state): State<Arc<AppState>>,
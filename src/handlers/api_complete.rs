@@ -2,205 +2,1090 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use crate::auth::AdminUser;
-use crate::auth::AuthUser;
+use crate::auth::repo_access::{self, authorize, RepoRole};
+use crate::auth::{AuthUser, OptionalAuthUser};
 use crate::models::*;
+use crate::services::organizations;
+use crate::services::placement::PlacementDecision;
+use crate::services::replication_queue::PRIORITY_MANUAL;
+use crate::services::{accounts, login_throttle, two_factor};
+use crate::services::reputation::ReputationService;
 use crate::AppState;
 // src/handlers/api_complete.rs
 use pulldown_cmark::{Parser, html};
 
+/// Load a repository and check the user has at least `needed` on it
 async fn check_repo_access(
     db: &crate::db::Database,
     repo_hash: &str,
-    user_id: i64,
-    require_ownership: bool,
+    user_id: Option<i64>,
+    needed: RepoRole,
 ) -> Result<crate::models::Repository, StatusCode> {
     let repo = db
         .get_repository(repo_hash)
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
-    
-    // Private repos require authentication
-    if repo.is_private != 0 {
-        if require_ownership && repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-        // Even for non-ownership operations on private repos, verify user has access
-        // For now, only owner can access private repos
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
-    
-    // Public repos with ownership requirement
-    if require_ownership && repo.owner_id != user_id {
-        return Err(StatusCode::FORBIDDEN);
-    }
-    
+
+    authorize(db, &repo, user_id, needed).await?;
+
     Ok(repo)
 }
 
 pub async fn get_repo_readme(
     State(state): State<Arc<AppState>>,
+    OptionalAuthUser(user): OptionalAuthUser,
     Path(repo_hash): Path<String>,
 ) -> Result<String, StatusCode> {
-    let repo_path = state.git_storage.repo_path(&repo_hash);
-    
+    check_repo_access(&state.db, &repo_hash, user.map(|u| u.id), RepoRole::Read).await?;
+
+    render_readme(&state.git_storage.repo_path(&repo_hash)).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// The README at HEAD as HTML; callers check access first
+pub fn render_readme(repo_path: &std::path::Path) -> Option<String> {
     // Try to find README file
     let readme_names = ["README.md", "README.txt", "README", "Readme.md", "readme.md"];
     
     for name in &readme_names {
-        match read_file_from_git(&repo_path, "HEAD", name) {
+        match read_file_from_git(repo_path, "HEAD", name) {
             Ok(content) => {
                 // If it's markdown, convert to HTML
                 if name.ends_with(".md") {
                     let parser = Parser::new(&content);
                     let mut html_output = String::new();
                     html::push_html(&mut html_output, parser);
-                    return Ok(html_output);
+                    return Some(html_output);
                 } else {
                     // Plain text - wrap in <pre>
-                    return Ok(format!("<pre>{}</pre>", html_escape(&content)));
+                    return Some(format!("<pre>{}</pre>", html_escape(&content)));
                 }
             }
             Err(_) => continue,
         }
     }
     
-    Err(StatusCode::NOT_FOUND)
+    None
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+// Fork repository
+#[derive(Debug, Deserialize)]
+pub struct ForkRepoRequest {
+    pub new_name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ForkRepoResponse {
+    pub original_hash: String,
+    pub forked_hash: String,
+    pub message: String,
+}
+
+pub async fn fork_repo(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(repo_hash): Path<String>,
+    Json(payload): Json<ForkRepoRequest>,
+) -> Result<Json<ForkRepoResponse>, StatusCode> {
+    // Get original repository
+    let original_repo = check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Read).await?;
+    
+    // Generate new hash for fork
+    let fork_name = payload.new_name.unwrap_or(format!("{}-fork", original_repo.name));
+    crate::utils::validation::validate_repo_name(&fork_name).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if state.db.repository_name_taken(user.id, &fork_name).await.unwrap_or(false) {
+        return Err(StatusCode::CONFLICT);
+    }
+    let fork_hash = crate::utils::hash::generate_repo_hash(&fork_name, user.id);
+    
+    // Copy repository data
+    let original_path = state.git_storage.repo_path(&repo_hash);
+    let fork_path = state.git_storage.repo_path(&fork_hash);
+    
+    std::fs::create_dir_all(&fork_path)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    // Copy all git objects
+    copy_dir_recursive(&original_path, &fork_path)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    // Create database entry
+    let fork_request = CreateRepoRequest {
+        name: fork_name.clone(),
+        description: payload.description.or(Some(format!("Fork of {}", original_repo.name))),
+        storage_tier: "free".to_string(),
+        is_private: original_repo.is_private != 0,
+    };
+    
+    state.db
+        .create_repository(&fork_request, user.id, &fork_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    Ok(Json(ForkRepoResponse {
+        original_hash: repo_hash,
+        forked_hash: fork_hash.clone(),
+        message: format!("Repository forked successfully as {}", fork_name),
+    }))
+}
+
+fn copy_dir_recursive(src: &std::path::Path, dst: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dst)?;
+    
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+        let dest_path = dst.join(entry.file_name());
+        
+        if path.is_dir() {
+            copy_dir_recursive(&path, &dest_path)?;
+        } else {
+            std::fs::copy(&path, &dest_path)?;
+        }
+    }
+    
+    Ok(())
+}
+
+// Delete repository (proper implementation)
+pub async fn delete_repo_complete(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(repo_hash): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    // Only the owner may delete, not admin collaborators
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Owner).await?;
+    
+    // Delete from storage first (ignore errors as files might not exist)
+    let _ = state.git_storage.delete_repo(&repo_hash);
+    
+    // Delete from database using the complete method
+    state.db
+        .delete_repository_complete(&repo_hash)
+        .await
+        .map_err(|e| {
+            eprintln!("Error deleting repository: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Placement settings
+#[derive(Debug, Deserialize)]
+pub struct PlacementSettings {
+    pub require_anchor: bool,
+}
+
+pub async fn update_repo_placement(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(repo_hash): Path<String>,
+    Json(payload): Json<PlacementSettings>,
+) -> Result<Json<Repository>, StatusCode> {
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+    
+    state.db
+        .set_repository_require_anchor(&repo_hash, payload.require_anchor)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    let repo = state.db
+        .get_repository(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    Ok(Json(repo))
+}
+
+// List user's repositories
+pub async fn list_user_repos(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<Repository>>, StatusCode> {
+    let repos = state.db
+        .list_user_repositories(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    Ok(Json(repos))
+}
+
+// SSH keys used by the git SSH server
+pub async fn list_ssh_keys(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<SshKey>>, StatusCode> {
+    let keys = state.db
+        .list_ssh_keys(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(keys))
+}
+
+pub async fn add_ssh_key(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<AddSshKeyRequest>,
+) -> Result<Json<SshKey>, StatusCode> {
+    let title = payload.title.trim();
+    if title.is_empty() || title.len() > 100 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (fingerprint, public_key) = crate::services::ssh_server::parse_public_key(&payload.key)
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    // Fingerprints are unique across all users
+    let key = state.db
+        .add_ssh_key(user.id, title, &fingerprint, &public_key)
+        .await
+        .map_err(|_| StatusCode::CONFLICT)?;
+
+    Ok(Json(key))
+}
+
+pub async fn delete_ssh_key(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    match state.db.delete_ssh_key(id, user.id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// Personal access tokens
+pub async fn list_tokens(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<ApiKey>>, StatusCode> {
+    let keys = state.db
+        .list_api_keys(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(keys))
+}
+
+/// Create a token; the response is the only time it is shown
+pub async fn create_token(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreateApiKeyResponse>), StatusCode> {
+    let (key, token) = crate::auth::tokens::create_token(
+        &state.db,
+        user.id,
+        &payload.name,
+        &payload.scopes,
+        payload.expires_in_days,
+    )
+    .await
+    .map_err(|(status, _)| status)?;
+
+    Ok((StatusCode::CREATED, Json(CreateApiKeyResponse { token, key })))
+}
+
+pub async fn revoke_token(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    match state.db.revoke_api_key(id, user.id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Email a new verification link to the user's address
+pub async fn resend_verification(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<StatusCode, StatusCode> {
+    let account = state.db
+        .get_user_by_id(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    accounts::send_verification(state.mailer.as_ref(), &state.config.site_origin, &state.config.jwt_secret, &account, chrono::Utc::now())
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Change the password; every web session is signed out and login tokens,
+/// including the caller's, stop working
+pub async fn change_password(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let account = state.db
+        .get_user_by_id(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    accounts::change_password(&state.db, &account, &payload.current_password, &payload.new_password)
+        .await
+        .map_err(|(status, _)| status)?;
+    state.session_store.revoke_other_sessions(user.id, None).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Change the email address and send a link to verify it
+pub async fn change_email(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<ChangeEmailRequest>,
+) -> Result<Json<crate::handlers::api_enhanced::UserInfo>, StatusCode> {
+    let account = state.db
+        .get_user_by_id(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let updated = accounts::change_email(
+        &state.db,
+        state.mailer.as_ref(),
+        &state.config.site_origin,
+        &state.config.jwt_secret,
+        &account,
+        &payload.password,
+        &payload.email,
+        chrono::Utc::now(),
+    )
+    .await
+    .map_err(|(status, _)| status)?;
+
+    Ok(Json(updated.into()))
+}
+
+/// Delete the account, transferring or deleting personal repositories
+pub async fn delete_account(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<DeleteAccountRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let account = state.db
+        .get_user_by_id(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    accounts::delete_account(
+        &state.db,
+        &state.git_storage,
+        &account,
+        &payload.password,
+        payload.transfer_to.as_deref(),
+    )
+    .await
+    .map_err(|(status, _)| status)?;
+    state.session_store.revoke_other_sessions(user.id, None).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Two-factor authentication
+pub async fn two_factor_status(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<TwoFactorStatus>, StatusCode> {
+    let account = state.db
+        .get_user_by_id(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let recovery_codes_remaining = state.db
+        .count_unused_recovery_codes(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(TwoFactorStatus {
+        enabled: account.totp_enabled != 0,
+        recovery_codes_remaining,
+    }))
+}
+
+/// Start enrolling; confirm with a code at /api/user/2fa/enable
+pub async fn two_factor_setup(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<TwoFactorSetupResponse>, StatusCode> {
+    let account = state.db
+        .get_user_by_id(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let enrollment = two_factor::begin_enrollment(&state.db, &account)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok(Json(TwoFactorSetupResponse {
+        secret: enrollment.secret,
+        otpauth_uri: enrollment.otpauth_uri,
+    }))
+}
+
+pub async fn two_factor_enable(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, StatusCode> {
+    let recovery_codes = two_factor::confirm_enrollment(
+        &state.db,
+        user.id,
+        &payload.code,
+        chrono::Utc::now().timestamp(),
+    )
+    .await
+    .map_err(|(status, _)| status)?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+pub async fn two_factor_disable(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let account = state.db
+        .get_user_by_id(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    two_factor::disable(&state.db, &account, &payload.code, chrono::Utc::now().timestamp())
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn regenerate_recovery_codes(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, StatusCode> {
+    let recovery_codes = two_factor::regenerate_recovery_codes(
+        &state.db,
+        user.id,
+        &payload.code,
+        chrono::Utc::now().timestamp(),
+    )
+    .await
+    .map_err(|(status, _)| status)?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+pub async fn admin_security_settings(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminUser,
+) -> Json<SecuritySettings> {
+    Json(SecuritySettings {
+        require_admin_2fa: two_factor::admin_2fa_required(&state.db).await,
+    })
+}
+
+pub async fn admin_update_security_settings(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Json(payload): Json<SecuritySettings>,
+) -> Result<Json<SecuritySettings>, StatusCode> {
+    let account = state.db
+        .get_user_by_id(admin.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    two_factor::set_admin_2fa_required(&state.db, &account, payload.require_admin_2fa)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok(Json(payload))
+}
+
+/// Let a locked out account sign in again
+pub async fn admin_unlock_user(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(username): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let user = state.db
+        .get_user_by_username(&username)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    match login_throttle::unlock_account(&state.db, &user, &admin.username).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// Branch protection, editable by repository admins
+pub async fn list_protected_branches(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(repo_hash): Path<String>,
+) -> Result<Json<Vec<ProtectedBranch>>, StatusCode> {
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+
+    let branches = state.db
+        .list_protected_branches(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(branches))
+}
+
+/// Validate a rule and look up its pushers
+async fn branch_rule(
+    state: &AppState,
+    mut payload: BranchProtectionRequest,
+) -> Result<(BranchProtectionRequest, Vec<i64>), StatusCode> {
+    payload.pattern = crate::services::pre_receive::normalize_branch_pattern(&payload.pattern)
+        .ok_or(StatusCode::BAD_REQUEST)?
+        .to_string();
+    let pusher_ids = crate::services::pre_receive::resolve_pushers(&state.db, &payload.allowed_pushers)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    Ok((payload, pusher_ids))
+}
+
+/// Protect a branch pattern; an existing rule for the pattern is replaced
+pub async fn protect_branch(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(repo_hash): Path<String>,
+    Json(payload): Json<BranchProtectionRequest>,
+) -> Result<Json<ProtectedBranch>, StatusCode> {
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+    let (rule, pusher_ids) = branch_rule(&state, payload).await?;
+
+    let branch = state.db
+        .save_protected_branch(&repo_hash, &rule, &pusher_ids)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(branch))
+}
+
+pub async fn update_protected_branch(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((repo_hash, id)): Path<(String, i64)>,
+    Json(payload): Json<BranchProtectionRequest>,
+) -> Result<Json<ProtectedBranch>, StatusCode> {
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+    let (rule, pusher_ids) = branch_rule(&state, payload).await?;
+
+    // Renaming onto another rule's pattern hits the unique constraint
+    match state.db.update_protected_branch(&repo_hash, id, &rule, &pusher_ids).await {
+        Ok(Some(branch)) => Ok(Json(branch)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::CONFLICT),
+    }
+}
+
+pub async fn unprotect_branch(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((repo_hash, id)): Path<(String, i64)>,
+) -> Result<StatusCode, StatusCode> {
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+
+    match state.db.delete_protected_branch(&repo_hash, id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// Collaborators, managed by repository admins
+#[derive(Debug, Serialize)]
+pub struct CollaboratorsResponse {
+    pub collaborators: Vec<Collaborator>,
+    pub invitations: Vec<RepoInvitation>,
+}
+
+pub async fn list_collaborators(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(repo_hash): Path<String>,
+) -> Result<Json<CollaboratorsResponse>, StatusCode> {
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+
+    let collaborators = state.db
+        .list_collaborators(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let invitations = state.db
+        .list_repo_invitations(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(CollaboratorsResponse { collaborators, invitations }))
+}
+
+/// Invite a user; they become a collaborator once they accept
+pub async fn invite_collaborator(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(repo_hash): Path<String>,
+    Json(payload): Json<InviteCollaboratorRequest>,
+) -> Result<(StatusCode, Json<RepoInvitation>), StatusCode> {
+    let repo = check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+
+    let invitation = repo_access::invite_collaborator(&state.db, &repo, user.id, &payload.username, &payload.role)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok((StatusCode::CREATED, Json(invitation)))
+}
+
+/// The user id of a collaborator on the repository
+async fn collaborator_id(state: &AppState, repo_hash: &str, username: &str) -> Result<i64, StatusCode> {
+    let collaborator = state.db
+        .get_user_by_username(username)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    state.db
+        .get_collaborator_role(repo_hash, collaborator.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(collaborator.id)
+}
+
+pub async fn update_collaborator(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((repo_hash, username)): Path<(String, String)>,
+    Json(payload): Json<UpdateCollaboratorRequest>,
+) -> Result<Json<Collaborator>, StatusCode> {
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+    let role = RepoRole::parse_grantable(&payload.role).ok_or(StatusCode::BAD_REQUEST)?;
+    let collaborator_id = collaborator_id(&state, &repo_hash, &username).await?;
+
+    state.db
+        .set_collaborator_role(&repo_hash, collaborator_id, role.as_str())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    state.db
+        .list_collaborators(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .find(|c| c.user_id == collaborator_id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Remove a collaborator; collaborators may also remove themselves
+pub async fn remove_collaborator(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((repo_hash, username)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let collaborator_id = collaborator_id(&state, &repo_hash, &username).await?;
+    if collaborator_id != user.id {
+        check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+    }
+
+    match state.db.remove_collaborator(&repo_hash, collaborator_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn cancel_invitation(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((repo_hash, id)): Path<(String, i64)>,
+) -> Result<StatusCode, StatusCode> {
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+
+    match state.db.cancel_invitation(&repo_hash, id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// Invitations addressed to the signed-in user
+pub async fn list_invitations(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<RepoInvitation>>, StatusCode> {
+    let invitations = state.db
+        .list_user_invitations(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(invitations))
+}
+
+/// Accept an invitation and return the repository it grants access to
+pub async fn accept_invitation(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<Json<Repository>, StatusCode> {
+    let repo_hash = state.db
+        .accept_invitation(id, user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let repo = state.db
+        .get_repository(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(repo))
+}
+
+pub async fn decline_invitation(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    match state.db.decline_invitation(id, user.id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+// Organizations
+#[derive(Debug, Serialize)]
+pub struct UserOrganization {
+    #[serde(flatten)]
+    pub organization: Organization,
+    pub role: String,
+}
+
+pub async fn create_org(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(payload): Json<CreateOrgRequest>,
+) -> Result<(StatusCode, Json<Organization>), StatusCode> {
+    let org = organizations::create_organization(&state.db, user.id, &payload.name, payload.description.as_deref())
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok((StatusCode::CREATED, Json(org)))
+}
+
+pub async fn list_user_orgs(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<Vec<UserOrganization>>, StatusCode> {
+    let orgs = state.db
+        .list_user_organizations(user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(|(organization, role)| UserOrganization { organization, role })
+        .collect();
+
+    Ok(Json(orgs))
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrgDetails {
+    pub organization: Organization,
+    /// The caller's role, if they are a member
+    pub role: Option<String>,
+    /// Repositories the caller can read
+    pub repositories: Vec<Repository>,
+    /// Only shown to members
+    pub members: Vec<OrgMember>,
+    pub teams: Vec<Team>,
+}
+
+pub async fn get_org(
+    State(state): State<Arc<AppState>>,
+    OptionalAuthUser(user): OptionalAuthUser,
+    Path(org_name): Path<String>,
+) -> Result<Json<OrgDetails>, StatusCode> {
+    let org = state.db
+        .get_organization_by_name(&org_name)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let user_id = user.map(|u| u.id);
+    let role = match user_id {
+        Some(id) => state.db
+            .get_org_role(org.id, id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => None,
+    };
+
+    let mut repositories = Vec::new();
+    for repo in state.db
+        .list_org_repositories(org.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        let visible = repo_access::repo_role(&state.db, &repo, user_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .is_some();
+        if visible {
+            repositories.push(repo);
+        }
+    }
+
+    let (members, teams) = if role.is_some() {
+        (
+            state.db.list_org_members(org.id).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+            state.db.list_teams(org.id).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        )
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    Ok(Json(OrgDetails { organization: org, role, repositories, members, teams }))
+}
+
+pub async fn create_org_repo(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(org_name): Path<String>,
+    Json(payload): Json<CreateRepoRequest>,
+) -> Result<(StatusCode, Json<Repository>), StatusCode> {
+    let org = organizations::owned_organization(&state.db, &org_name, user.id)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    let repo = organizations::create_repository(&state.db, &state.git_storage, &org, user.id, &payload)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok((StatusCode::CREATED, Json(repo)))
 }
 
-fn html_escape(s: &str) -> String {
-    s.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
+/// Add a member or change their role
+pub async fn set_org_member(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((org_name, username)): Path<(String, String)>,
+    Json(payload): Json<OrgMemberRequest>,
+) -> Result<Json<OrgMember>, StatusCode> {
+    let org = organizations::owned_organization(&state.db, &org_name, user.id)
+        .await
+        .map_err(|(status, _)| status)?;
+    let member = organizations::set_member(&state.db, &org, &username, &payload.role)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    state.db
+        .list_org_members(org.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .find(|m| m.user_id == member.id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
 }
-// Fork repository
-#[derive(Debug, Deserialize)]
-pub struct ForkRepoRequest {
-    pub new_name: Option<String>,
-    pub description: Option<String>,
+
+/// Remove a member; members may also leave on their own
+pub async fn remove_org_member(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((org_name, username)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let org = if username == user.username {
+        state.db
+            .get_organization_by_name(&org_name)
+            .await
+            .map_err(|_| StatusCode::NOT_FOUND)?
+    } else {
+        organizations::owned_organization(&state.db, &org_name, user.id)
+            .await
+            .map_err(|(status, _)| status)?
+    };
+
+    organizations::remove_member(&state.db, &org, &username)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
-#[derive(Debug, Serialize)]
-pub struct ForkRepoResponse {
-    pub original_hash: String,
-    pub forked_hash: String,
-    pub message: String,
+pub async fn create_team(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(org_name): Path<String>,
+    Json(payload): Json<CreateTeamRequest>,
+) -> Result<(StatusCode, Json<Team>), StatusCode> {
+    let org = organizations::owned_organization(&state.db, &org_name, user.id)
+        .await
+        .map_err(|(status, _)| status)?;
+    let team = organizations::create_team(&state.db, &org, &payload.name, payload.description.as_deref())
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok((StatusCode::CREATED, Json(team)))
 }
 
-pub async fn fork_repo(
+pub async fn delete_team(
     State(state): State<Arc<AppState>>,
     user: AuthUser,
-    Path(repo_hash): Path<String>,
-    Json(payload): Json<ForkRepoRequest>,
-) -> Result<Json<ForkRepoResponse>, StatusCode> {
-    // Get original repository
-    let original_repo = state.db
-        .get_repository(&repo_hash)
+    Path((org_name, team_name)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let org = organizations::owned_organization(&state.db, &org_name, user.id)
         .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-    
-    // Generate new hash for fork
-    let fork_name = payload.new_name.unwrap_or(format!("{}-fork", original_repo.name));
-    let fork_hash = crate::utils::hash::generate_repo_hash(&fork_name, user.id);
-    
-    // Copy repository data
-    let original_path = state.git_storage.repo_path(&repo_hash);
-    let fork_path = state.git_storage.repo_path(&fork_hash);
-    
-    std::fs::create_dir_all(&fork_path)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    
-    // Copy all git objects
-    copy_dir_recursive(&original_path, &fork_path)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    
-    // Create database entry
-    let fork_request = CreateRepoRequest {
-        name: fork_name.clone(),
-        description: payload.description.or(Some(format!("Fork of {}", original_repo.name))),
-        storage_tier: "free".to_string(),
-        is_private: original_repo.is_private != 0,
-    };
-    
+        .map_err(|(status, _)| status)?;
+    let team = organizations::find_team(&state.db, &org, &team_name)
+        .await
+        .map_err(|(status, _)| status)?;
+
     state.db
-        .create_repository(&fork_request, user.id, &fork_hash)
+        .delete_team(team.id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    
-    Ok(Json(ForkRepoResponse {
-        original_hash: repo_hash,
-        forked_hash: fork_hash.clone(),
-        message: format!("Repository forked successfully as {}", fork_name),
-    }))
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
-fn copy_dir_recursive(src: &std::path::Path, dst: &std::path::Path) -> std::io::Result<()> {
-    std::fs::create_dir_all(dst)?;
-    
-    for entry in std::fs::read_dir(src)? {
-        let entry = entry?;
-        let path = entry.path();
-        let dest_path = dst.join(entry.file_name());
-        
-        if path.is_dir() {
-            copy_dir_recursive(&path, &dest_path)?;
-        } else {
-            std::fs::copy(&path, &dest_path)?;
-        }
-    }
-    
-    Ok(())
+/// The organization, if the user owns it, and one of its teams
+async fn owned_team(
+    state: &AppState,
+    user_id: i64,
+    org_name: &str,
+    team_name: &str,
+) -> Result<(Organization, Team), StatusCode> {
+    let org = organizations::owned_organization(&state.db, org_name, user_id)
+        .await
+        .map_err(|(status, _)| status)?;
+    let team = organizations::find_team(&state.db, &org, team_name)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok((org, team))
 }
 
-// Delete repository (proper implementation)
-pub async fn delete_repo_complete(
+pub async fn add_team_member(
     State(state): State<Arc<AppState>>,
     user: AuthUser,
-    Path(repo_hash): Path<String>,
+    Path((org_name, team_name, username)): Path<(String, String, String)>,
 ) -> Result<StatusCode, StatusCode> {
-    // Verify ownership
-    let repo = state.db
-        .get_repository(&repo_hash)
+    let (org, team) = owned_team(&state, user.id, &org_name, &team_name).await?;
+    organizations::add_team_member(&state.db, &org, &team, &username)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn remove_team_member(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((org_name, team_name, username)): Path<(String, String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let (_, team) = owned_team(&state, user.id, &org_name, &team_name).await?;
+    organizations::remove_team_member(&state.db, &team, &username)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn grant_team_repo(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((org_name, team_name, repo_name)): Path<(String, String, String)>,
+    Json(payload): Json<TeamRepoRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let (org, team) = owned_team(&state, user.id, &org_name, &team_name).await?;
+    organizations::grant_team_repo(&state.db, &org, &team, &repo_name, &payload.role)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn revoke_team_repo(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path((org_name, team_name, repo_name)): Path<(String, String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let (org, team) = owned_team(&state, user.id, &org_name, &team_name).await?;
+    organizations::revoke_team_repo(&state.db, &org, &team, &repo_name)
+        .await
+        .map_err(|(status, _)| status)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `/api/repos/:owner/:repo`; old names redirect to the current one
+pub async fn get_repo_by_name(
+    State(state): State<Arc<AppState>>,
+    user: OptionalAuthUser,
+    Path((owner, name)): Path<(String, String)>,
+) -> Result<Response, StatusCode> {
+    let (repo, renamed) = state.db
+        .resolve_repository(&owner, &name)
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
-    
-    if repo.owner_id != user.id {
-        return Err(StatusCode::FORBIDDEN);
+
+    if renamed {
+        let url = format!("/api/repos{}", crate::utils::paths::repo_url(&owner, &repo.name));
+        return Ok(Redirect::permanent(&url).into_response());
     }
-    
-    // Delete from storage first (ignore errors as files might not exist)
-    let _ = state.git_storage.delete_repo(&repo_hash);
-    
-    // Delete from database using the complete method
-    state.db
-        .delete_repository_complete(&repo_hash)
+
+    get_repo_detailed(State(state), user, Path(repo.repo_hash))
         .await
-        .map_err(|e| {
-            eprintln!("Error deleting repository: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    
-    Ok(StatusCode::NO_CONTENT)
+        .map(IntoResponse::into_response)
 }
 
-// List user's repositories
-pub async fn list_user_repos(
+#[derive(Debug, Deserialize)]
+pub struct RenameRepoRequest {
+    pub name: String,
+}
+
+/// Rename a repository; the old name keeps redirecting
+pub async fn rename_repo(
     State(state): State<Arc<AppState>>,
     user: AuthUser,
-) -> Result<Json<Vec<Repository>>, StatusCode> {
-    let repos = state.db
-        .list_user_repositories(user.id)
+    Path(repo_hash): Path<String>,
+    Json(payload): Json<RenameRepoRequest>,
+) -> Result<Json<Repository>, StatusCode> {
+    let repo = check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
+    crate::utils::validation::validate_repo_name(&payload.name).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    if payload.name != repo.name && state.db.sibling_name_taken(&repo, &payload.name).await.unwrap_or(false) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    state.db
+        .rename_repository(&repo_hash, &payload.name)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    
-    Ok(Json(repos))
+    let repo = state.db
+        .get_repository(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(repo))
 }
 
 // Get detailed repository info
@@ -218,15 +1103,13 @@ pub struct DetailedRepoInfo {
 
 pub async fn get_repo_detailed(
     State(state): State<Arc<AppState>>,
+    OptionalAuthUser(user): OptionalAuthUser,
     Path(repo_hash): Path<String>,
 ) -> Result<Json<DetailedRepoInfo>, StatusCode> {
-    let repo = state.db
-        .get_repository(&repo_hash)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let repo = check_repo_access(&state.db, &repo_hash, user.map(|u| u.id), RepoRole::Read).await?;
     
     let owner = state.db
-        .get_user_by_id(repo.owner_id)
+        .repository_namespace(&repo)
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
     
@@ -253,7 +1136,7 @@ pub async fn get_repo_detailed(
     
     Ok(Json(DetailedRepoInfo {
         repo,
-        owner_username: owner.username,
+        owner_username: owner,
         replica_count,
         nodes,
         health_status,
@@ -269,6 +1152,8 @@ pub async fn star_repo(
     user: AuthUser,
     Path(repo_hash): Path<String>,
 ) -> Result<StatusCode, StatusCode> {
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Read).await?;
+
     state.db
         .star_repository(&repo_hash, user.id)
         .await
@@ -302,15 +1187,7 @@ pub async fn add_tags(
     Path(repo_hash): Path<String>,
     Json(payload): Json<AddTagsRequest>,
 ) -> Result<StatusCode, StatusCode> {
-    // Verify ownership
-    let repo = state.db
-        .get_repository(&repo_hash)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-    
-    if repo.owner_id != user.id {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    check_repo_access(&state.db, &repo_hash, Some(user.id), RepoRole::Admin).await?;
     
     for tag in payload.tags {
         let _ = state.db.add_repo_tag(&repo_hash, &tag).await;
@@ -347,8 +1224,9 @@ pub async fn admin_list_nodes(
 ) -> Result<Json<Vec<AdminNodeInfo>>, StatusCode> {
     // No need to check admin status - extractor handles it
     
+    // Include offline and banned nodes so admins can act on them
     let nodes = state.db
-        .list_active_nodes(state.config.node_heartbeat_timeout_minutes)
+        .list_all_nodes()
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
@@ -370,6 +1248,48 @@ pub async fn admin_list_nodes(
     Ok(Json(admin_nodes))
 }
 
+pub async fn admin_ban_node(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(node_id): Path<String>,
+) -> Result<Json<Node>, StatusCode> {
+    let node = ReputationService::new(state.db.clone())
+        .ban(&node_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    
+    tracing::info!("Admin {} banned node {}", admin.username, node_id);
+    Ok(Json(node))
+}
+
+pub async fn admin_trust_node(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(node_id): Path<String>,
+) -> Result<Json<Node>, StatusCode> {
+    let node = ReputationService::new(state.db.clone())
+        .trust(&node_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    
+    tracing::info!("Admin {} trusted node {}", admin.username, node_id);
+    Ok(Json(node))
+}
+
+pub async fn admin_remove_node(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(node_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    ReputationService::new(state.db.clone())
+        .remove(&node_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    
+    tracing::info!("Admin {} removed node {}", admin.username, node_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn admin_system_health(
     State(state): State<Arc<AppState>>,
     _admin: AdminUser, // Changed
@@ -398,21 +1318,129 @@ pub async fn admin_system_health(
 
 pub async fn admin_trigger_replication(
     State(state): State<Arc<AppState>>,
-    _admin: AdminUser, // Changed
+    admin: AdminUser,
     Json(payload): Json<ManualReplicationRequest>,
-) -> Result<Json<ReplicationResponse>, StatusCode> {
-    let replication_service = crate::services::replication::ReplicationService::new(state.db.clone());
-    
-    match replication_service.trigger_replication(&payload.repo_hash).await {
-        Ok(target_node) => Ok(Json(ReplicationResponse {
-            success: true,
-            message: format!("Replication triggered to node: {}", target_node),
-        })),
-        Err(e) => Ok(Json(ReplicationResponse {
-            success: false,
-            message: format!("Replication failed: {}", e),
-        })),
+) -> Result<Json<QueuedReplication>, StatusCode> {
+    state.db
+        .get_repository(&payload.repo_hash)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    
+    let job = state.db
+        .enqueue_replication_job(&payload.repo_hash, "manual", PRIORITY_MANUAL)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    tracing::info!("Admin {} queued replication job {} for {}", admin.username, job.id, job.repo_hash);
+    
+    // Preview where the job is likely to land
+    let replication_service = crate::services::replication::ReplicationService::new(
+        state.db.clone(),
+        state.git_storage.clone(),
+    );
+    
+    Ok(Json(QueuedReplication {
+        success: true,
+        message: format!("Replication job {} queued", job.id),
+        placement: replication_service.plan(&job.repo_hash).await.ok(),
+        job,
+    }))
+}
+
+// Admin: List replication jobs
+#[derive(Debug, Deserialize)]
+pub struct JobListQuery {
+    pub state: Option<String>,
+    pub limit: Option<i64>,
+}
+
+pub async fn admin_list_replication_jobs(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminUser,
+    Query(params): Query<JobListQuery>,
+) -> Result<Json<Vec<ReplicationJob>>, StatusCode> {
+    let jobs = state.db
+        .list_replication_jobs(params.state.as_deref(), params.limit.unwrap_or(50).clamp(1, 500))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    Ok(Json(jobs))
+}
+
+pub async fn admin_retry_replication_job(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(job_id): Path<i64>,
+) -> Result<Json<ReplicationJob>, StatusCode> {
+    state.db.get_replication_job(job_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    
+    // Only failed jobs of repos without an active job can be retried
+    let retried = state.db
+        .retry_replication_job(job_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !retried {
+        return Err(StatusCode::CONFLICT);
+    }
+    
+    tracing::info!("Admin {} retried replication job {}", admin.username, job_id);
+    state.db
+        .get_replication_job(job_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn admin_cancel_replication_job(
+    State(state): State<Arc<AppState>>,
+    admin: AdminUser,
+    Path(job_id): Path<i64>,
+) -> Result<Json<ReplicationJob>, StatusCode> {
+    state.db.get_replication_job(job_id).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    
+    // Running jobs cannot be interrupted
+    let cancelled = state.db
+        .cancel_replication_job(job_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !cancelled {
+        return Err(StatusCode::CONFLICT);
     }
+    
+    tracing::info!("Admin {} cancelled replication job {}", admin.username, job_id);
+    state.db
+        .get_replication_job(job_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueuedReplication {
+    pub success: bool,
+    pub message: String,
+    pub job: ReplicationJob,
+    /// Where the job would be placed right now
+    pub placement: Option<PlacementDecision>,
+}
+
+// Admin: Explain where a repository would be replicated next
+pub async fn admin_explain_placement(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminUser,
+    Path(repo_hash): Path<String>,
+) -> Result<Json<PlacementDecision>, StatusCode> {
+    let replication_service = crate::services::replication::ReplicationService::new(
+        state.db.clone(),
+        state.git_storage.clone(),
+    );
+    
+    let decision = replication_service
+        .plan(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    
+    Ok(Json(decision))
 }
 
 // Admin: System health overview
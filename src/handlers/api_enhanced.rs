@@ -3,13 +3,15 @@ use axum::{
     extract::{Path, Query, State, Form},
     http::StatusCode,
     Json,
-    response::{Html, Redirect},
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 use crate::auth::AuthUser;
 use crate::models::*;
+use crate::auth::session::ClientInfo;
+use crate::services::{accounts, login_throttle, two_factor};
 use crate::AppState;
 
 // Authentication endpoints
@@ -32,6 +34,23 @@ pub struct UserInfo {
     pub email: String,
     pub storage_used: i64,
     pub storage_quota: i64,
+    pub email_verified: bool,
+    /// Set while a generated password must be replaced
+    pub password_change_required: bool,
+}
+
+impl From<User> for UserInfo {
+    fn from(user: User) -> Self {
+        UserInfo {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            storage_used: user.storage_used,
+            storage_quota: user.storage_quota,
+            email_verified: user.email_verified != 0,
+            password_change_required: user.must_change_password != 0,
+        }
+    }
 }
 
 // Form-based login (no JS required)
@@ -65,15 +84,23 @@ pub async fn login_form(
 // API login (JSON)
 pub async fn login(
     State(state): State<Arc<AppState>>,
+    client: ClientInfo,
     Json(payload): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, (StatusCode, String)> {
-    let user = state.db
-        .get_user_by_username(&payload.username)
+) -> Result<Response, (StatusCode, String)> {
+    let ip = client.ip_address.as_deref();
+    let now = chrono::Utc::now();
+    login_throttle::begin_attempt(&state.db, &payload.username, ip, now)
         .await
-        .map_err(|e| {
+        .map_err(|throttled| (StatusCode::TOO_MANY_REQUESTS, throttled.message()))?;
+
+    let user = match state.db.get_user_by_username(&payload.username).await {
+        Ok(user) => user,
+        Err(e) => {
             eprintln!("User not found: {}", e);
-            (StatusCode::UNAUTHORIZED, "Invalid username or password".to_string())
-        })?;
+            login_throttle::record_failure(&state.db, &payload.username, ip, now).await;
+            return Err((StatusCode::UNAUTHORIZED, "Invalid username or password".to_string()));
+        }
+    };
     
     let is_valid = crate::auth::password::verify_password(&payload.password, &user.password_hash)
         .map_err(|e| {
@@ -83,10 +110,57 @@ pub async fn login(
     
     if !is_valid {
         eprintln!("Invalid password for user: {}", payload.username);
+        login_throttle::record_failure(&state.db, &payload.username, ip, now).await;
         return Err((StatusCode::UNAUTHORIZED, "Invalid username or password".to_string()));
     }
-    
-    let token = crate::auth::jwt::generate_token(user.id, &user.username)
+
+    // The client answers the challenge at /api/auth/login/2fa
+    if user.totp_enabled != 0 {
+        let challenge = two_factor::start_login_challenge(&state.db, user.id).await?;
+        return Ok(Json(TwoFactorChallenge {
+            two_factor_required: true,
+            challenge,
+        })
+        .into_response());
+    }
+
+    login_throttle::record_success(&state.db, &user, ip).await;
+    Ok(login_response(&state.config.jwt_secret, user)?.into_response())
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub challenge: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge: String,
+    pub code: String,
+}
+
+/// Second step of an API login for users with two-factor enabled
+pub async fn login_two_factor(
+    State(state): State<Arc<AppState>>,
+    client: ClientInfo,
+    Json(payload): Json<TwoFactorLoginRequest>,
+) -> Result<Json<LoginResponse>, (StatusCode, String)> {
+    let user = two_factor::complete_login_challenge(
+        &state.db,
+        &payload.challenge,
+        &payload.code,
+        chrono::Utc::now().timestamp(),
+    )
+    .await?;
+
+    login_throttle::record_success(&state.db, &user, client.ip_address.as_deref()).await;
+
+    login_response(&state.config.jwt_secret, user)
+}
+
+fn login_response(secret: &str, user: User) -> Result<Json<LoginResponse>, (StatusCode, String)> {
+    let token = crate::auth::jwt::generate_token(secret, user.id, &user.username, user.token_generation)
         .map_err(|e| {
             eprintln!("Token generation error: {}", e);
             (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate token".to_string())
@@ -94,13 +168,7 @@ pub async fn login(
     
     Ok(Json(LoginResponse {
         token,
-        user: UserInfo {
-            id: user.id,
-            username: user.username,
-            email: user.email,
-            storage_used: user.storage_used,
-            storage_quota: user.storage_quota,
-        },
+        user: user.into(),
     }))
 }
 
@@ -156,20 +224,25 @@ pub async fn signup_form(
     Ok(Redirect::to("/login?success=Account created successfully"))
 }
 
-// API signup (JSON) - also no email required
+// API signup (JSON) - email optional, verified by a link if given
 #[derive(Debug, Deserialize)]
 pub struct SignupRequest {
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub email: Option<String>,
 }
 
 pub async fn signup(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<SignupRequest>,
 ) -> Result<Json<LoginResponse>, StatusCode> {
-    if payload.username.len() < 3 || payload.username.len() > 32 {
+    if crate::utils::validation::validate_username(&payload.username).is_err() {
         return Err(StatusCode::BAD_REQUEST);
     }
+    if state.db.organization_name_taken(&payload.username).await.unwrap_or(false) {
+        return Err(StatusCode::CONFLICT);
+    }
     
     if payload.password.len() < 8 {
         return Err(StatusCode::BAD_REQUEST);
@@ -178,8 +251,10 @@ pub async fn signup(
     let password_hash = crate::auth::password::hash_password(&payload.password)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
+    let email = accounts::signup_email(&state.db, &payload.username, payload.email.as_deref())
+        .await
+        .map_err(|(status, _)| status)?;
     let public_key = hex::encode(blake3::hash(payload.username.as_bytes()).as_bytes());
-    let email = format!("{}@hyrule.local", payload.username);
     
     let user_req = CreateUserRequest {
         username: payload.username,
@@ -193,22 +268,72 @@ pub async fn signup(
         .create_user(&user_req)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    accounts::spawn_verification(state.mailer.clone(), state.config.site_origin.clone(), state.config.jwt_secret.clone(), user.clone());
     
-    let token = crate::auth::jwt::generate_token(user.id, &user.username)
+    let token = crate::auth::jwt::generate_token(&state.config.jwt_secret, user.id, &user.username, user.token_generation)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
     Ok(Json(LoginResponse {
         token,
-        user: UserInfo {
-            id: user.id,
-            username: user.username,
-            email: user.email,
-            storage_used: user.storage_used,
-            storage_quota: user.storage_quota,
-        },
+        user: user.into(),
     }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+/// Email a reset link. Always accepted, so addresses can't be probed.
+pub async fn forgot_password(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> StatusCode {
+    let state = state.clone();
+    tokio::spawn(async move {
+        accounts::request_password_reset(
+            &state.db,
+            state.mailer.as_ref(),
+            &state.config.site_origin,
+            &state.config.jwt_secret,
+            &payload.email,
+            chrono::Utc::now(),
+        )
+        .await;
+    });
+
+    StatusCode::ACCEPTED
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+pub async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = accounts::reset_password(&state.db, &state.config.jwt_secret, &payload.token, &payload.new_password, chrono::Utc::now()).await?;
+    state.session_store.revoke_other_sessions(user.id, None).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+pub async fn verify_email(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> Result<Json<UserInfo>, (StatusCode, String)> {
+    let user = accounts::verify_email(&state.db, &state.config.jwt_secret, &payload.token, chrono::Utc::now()).await?;
+    Ok(Json(user.into()))
+}
+
 fn render_error(message: &str) -> String {
     use crate::templates::render_page;
     let content = format!(
@@ -234,6 +359,10 @@ pub async fn create_repo_authenticated(
     user: AuthUser,
     Json(payload): Json<CreateRepoRequest>,
 ) -> Result<Json<CreateRepoResponse>, StatusCode> {
+    crate::utils::validation::validate_repo_name(&payload.name).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if state.db.repository_name_taken(user.id, &payload.name).await.unwrap_or(false) {
+        return Err(StatusCode::CONFLICT);
+    }
     let repo_hash = crate::utils::hash::generate_repo_hash(&payload.name, user.id);
     
     state.db
@@ -291,13 +420,7 @@ pub async fn get_profile(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
     
-    Ok(Json(UserInfo {
-        id: db_user.id,
-        username: db_user.username,
-        email: db_user.email,
-        storage_used: db_user.storage_used,
-        storage_quota: db_user.storage_quota,
-    }))
+    Ok(Json(db_user.into()))
 }
 
 // Repository statistics
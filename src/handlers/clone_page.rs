@@ -7,6 +7,7 @@ use axum::{
 use axum_extra::extract::cookie::CookieJar;
 use std::sync::Arc;
 
+use crate::auth::repo_access::{authorize, RepoRole};
 use crate::AppState;
 use crate::templates;
 
@@ -32,19 +33,28 @@ pub async fn show_clone_page(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
     
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user_id = get_session_user_id(&state, &jar).await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let user_id = get_session_user_id(&state, &jar).await;
+    authorize(&state.db, &repo, user_id, RepoRole::Read).await?;
     
     let server_url = format!("http://{}:{}", 
         state.config.host, 
         state.config.port
     );
     
-    Ok(Html(templates::clone_page::render(&repo, &server_url)))
+    let owner = state.db
+        .repository_namespace(&repo)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    // git@host:owner/name.git only works on the default SSH port
+    let ssh_url = match state.config.ssh_port {
+        0 => None,
+        22 => Some(format!("git@{}:{}/{}.git", state.config.host, owner, repo.name)),
+        port => Some(format!(
+            "ssh://git@{}:{}/{}/{}.git",
+            state.config.host, port, owner, repo.name
+        )),
+    };
+
+    Ok(Html(templates::clone_page::render(&repo, &owner, &server_url, ssh_url.as_deref())))
 }
@@ -9,6 +9,7 @@ use axum::{
 use crate::utils::validation;
 use std::sync::Arc;
 
+use crate::auth::repo_access::{authorize, RepoRole};
 use crate::auth::{AuthUser, OptionalAuthUser};
 use crate::models::*;
 use crate::AppState;
@@ -61,10 +62,9 @@ pub async fn upload_object(
     let repo = state.db.get_repository(&repo_hash).await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    if repo.owner_id != user.id {
+    authorize(&state.db, &repo, Some(user.id), RepoRole::Write).await.inspect_err(|_| {
         tracing::warn!("Unauthorized upload attempt by user {} to repo {}", user.id, repo_hash);
-        return Err(StatusCode::FORBIDDEN);
-    }
+    })?;
 
     use base64::{engine::general_purpose, Engine as _};
     let data = general_purpose::STANDARD
@@ -129,11 +129,9 @@ pub async fn batch_upload_objects(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check ownership
-    if repo.owner_id != user.id {
+    authorize(&state.db, &repo, Some(user.id), RepoRole::Write).await.inspect_err(|_| {
         tracing::warn!("Unauthorized batch upload attempt by user {} to repo {}", user.id, repo_hash);
-        return Err(StatusCode::FORBIDDEN);
-    }
+    })?;
 
     let mut uploaded = 0;
     let mut failed = Vec::new();
@@ -212,14 +210,7 @@ pub async fn download_object(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user = maybe_user.ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user.id {
-            tracing::warn!("Unauthorized download attempt by user {} for repo {}", user.id, repo_hash);
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    authorize(&state.db, &repo, maybe_user.map(|u| u.id), RepoRole::Read).await?;
 
     let data = state
         .git_storage
@@ -265,11 +256,9 @@ pub async fn update_ref(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check ownership
-    if repo.owner_id != user.id {
+    authorize(&state.db, &repo, Some(user.id), RepoRole::Write).await.inspect_err(|_| {
         tracing::warn!("Unauthorized ref update attempt by user {} to repo {}", user.id, repo_hash);
-        return Err(StatusCode::FORBIDDEN);
-    }
+    })?;
 
     state
         .git_storage
@@ -301,13 +290,7 @@ pub async fn get_ref(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user = maybe_user.ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user.id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    authorize(&state.db, &repo, maybe_user.map(|u| u.id), RepoRole::Read).await?;
 
     let ref_name = urlencoding::decode(&ref_name)
         .map_err(|_| StatusCode::BAD_REQUEST)?;
@@ -348,13 +331,7 @@ pub async fn list_objects(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user = maybe_user.ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user.id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    authorize(&state.db, &repo, maybe_user.map(|u| u.id), RepoRole::Read).await?;
 
     let objects = state
         .git_storage
@@ -383,13 +360,7 @@ pub async fn get_packfile(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user = maybe_user.ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user.id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    authorize(&state.db, &repo, maybe_user.map(|u| u.id), RepoRole::Read).await?;
 
     let pack_data = state
         .git_storage
@@ -425,13 +396,7 @@ pub async fn git_info_refs(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user = maybe_user.ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user.id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    authorize(&state.db, &repo, maybe_user.map(|u| u.id), RepoRole::Read).await?;
 
     let repo_path = state.git_storage.repo_path(&repo_hash);
     if !repo_path.exists() {
@@ -1,5 +1,5 @@
 // Hyrule/src/handlers/git_http_complete.rs
-use axum::body::Bytes;
+use async_compression::tokio::bufread::GzipDecoder;
 use axum::{
     body::Body,
 
@@ -7,23 +7,42 @@ use axum::{
     http::{header, StatusCode, HeaderMap},
     response::{IntoResponse, Response},
 };
+use futures_util::TryStreamExt;
 use serde::Deserialize;
-use std::io::Write;
-use std::process::{Command, Stdio};
+use std::io;
+use std::pin::Pin;
+use std::process::Stdio;
 use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio_util::io::{ReaderStream, StreamReader};
 
+use crate::auth::repo_access::{authorize, RepoRole};
+use crate::auth::tokens;
+use crate::models::Repository;
+use crate::services::pre_receive::{self, PushPolicy, Screening};
+use crate::services::replication::{ReplicationService, RestoredPrimary};
 use crate::AppState;
 
+/// Response header naming the node a missing repository was restored from
+const RESTORED_FROM_HEADER: &str = "x-hyrule-restored-from";
+/// Set when the restored copy could not be shown to match the lost primary
+const RESTORED_STALE_HEADER: &str = "x-hyrule-restored-stale";
+/// Read size when streaming git output to the client
+const RPC_CHUNK_SIZE: usize = 64 * 1024;
+
 #[derive(Debug, Deserialize)]
 pub struct GitService {
     service: String,
 }
 
-/// Helper to extract user_id from authentication
+/// Helper to extract the user from authentication, along with the
+/// strongest role their credential may use. Sessions and JWTs act with the
+/// user's full role; personal access tokens are capped by their scopes.
 async fn get_authenticated_user_id(
     state: &Arc<AppState>,
     headers: &HeaderMap,
-) -> Option<i64> {
+) -> Option<(i64, RepoRole)> {
     // Try cookie-based session first
     if let Some(cookie_header) = headers.get(header::COOKIE) {
         if let Ok(cookie_str) = cookie_header.to_str() {
@@ -31,21 +50,26 @@ async fn get_authenticated_user_id(
                 let parts: Vec<&str> = cookie.trim().splitn(2, '=').collect();
                 if parts.len() == 2 && parts[0] == "session_id" {
                     if let Some(session) = state.session_store.get_session(parts[1]).await {
-                        return Some(session.user_id);
+                        return Some((session.user_id, RepoRole::Owner));
                     }
                 }
             }
         }
     }
     
-    // Try Authorization header (Bearer token)
+    // Try Authorization header: a JWT or token as Bearer, or a token as
+    // the password of HTTP Basic auth, which is what git clients send
     if let Some(auth) = headers.get("Authorization") {
         if let Ok(auth_str) = auth.to_str() {
-            if auth_str.starts_with("Bearer ") {
-                let token = &auth_str[7..];
-                if let Ok(claims) = crate::auth::jwt::validate_token(token) {
-                    return Some(claims.sub);
-                }
+            let token = match auth_str.strip_prefix("Bearer ") {
+                Some(token) => token.to_string(),
+                None => tokens::basic_auth_password(auth_str)?,
+            };
+            if let Some(key) = tokens::authenticate(&state.db, &token).await {
+                return Some((key.user_id, tokens::max_repo_role(&key)?));
+            }
+            if let Some(claims) = crate::auth::jwt::authenticate(&state.db, &state.config.jwt_secret, &token).await {
+                return Some((claims.sub, RepoRole::Owner));
             }
         }
     }
@@ -53,6 +77,82 @@ async fn get_authenticated_user_id(
     None
 }
 
+/// `authorize` for git requests, also refusing anything beyond what the
+/// caller's credential allows
+async fn authorize_git(
+    state: &Arc<AppState>,
+    repo: &Repository,
+    caller: Option<(i64, RepoRole)>,
+    needed: RepoRole,
+) -> Result<RepoRole, StatusCode> {
+    if caller.is_some_and(|(_, ceiling)| ceiling < needed) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    authorize(&state.db, repo, caller.map(|(user_id, _)| user_id), needed).await
+}
+
+/// Ask git clients for credentials when a request needs them; without the
+/// challenge git gives up instead of trying the user's token
+pub async fn challenge_unauthorized(mut response: Response) -> Response {
+    if response.status() == StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static("Basic realm=\"Hyrule\""),
+        );
+    }
+    response
+}
+
+/// Make sure the primary copy is on disk, restoring it from a replica if
+/// the local repository is gone. Returns where it was restored from.
+pub(crate) async fn ensure_primary(
+    state: &Arc<AppState>,
+    repo_hash: &str,
+) -> Result<Option<RestoredPrimary>, StatusCode> {
+    if state.git_storage.repo_path(repo_hash).exists() {
+        return Ok(None);
+    }
+
+    tracing::warn!("Primary copy of {} is missing, restoring from a replica", repo_hash);
+    let restored = ReplicationService::new(state.db.clone(), state.git_storage.clone())
+        .restore_primary(repo_hash)
+        .await
+        .map_err(|e| {
+            tracing::error!("Repository {} is unavailable: {}", repo_hash, e);
+            StatusCode::SERVICE_UNAVAILABLE
+        })?;
+
+    match restored.refs_behind {
+        Some(0) => {}
+        Some(behind) => tracing::error!(
+            "Restored {} from {}, which is {} refs behind the lost primary; later pushes are gone",
+            repo_hash, restored.node_id, behind
+        ),
+        None => tracing::error!(
+            "Restored {} from {} without known primary refs to check it against",
+            repo_hash, restored.node_id
+        ),
+    }
+
+    Ok(Some(restored))
+}
+
+/// Tell the client which replica a restored repository came from, and
+/// whether it may be missing pushes
+fn restored_headers(
+    mut response: axum::http::response::Builder,
+    restored: Option<RestoredPrimary>,
+) -> axum::http::response::Builder {
+    if let Some(restored) = restored {
+        if restored.is_stale() {
+            response = response.header(RESTORED_STALE_HEADER, "1");
+        }
+        response = response.header(RESTORED_FROM_HEADER, restored.node_id);
+    }
+    response
+}
+
 /// Git info/refs endpoint - handles auth for private repos
 pub async fn git_info_refs(
     State(state): State<Arc<AppState>>,
@@ -66,21 +166,13 @@ pub async fn git_info_refs(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check authentication for private repos or receive-pack
-    if repo.is_private != 0 || params.service == "git-receive-pack" {
-        let user_id = get_authenticated_user_id(&state, &headers)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    // Pushes need write access, fetches read access
+    let needed = if params.service == "git-receive-pack" { RepoRole::Write } else { RepoRole::Read };
+    let caller = get_authenticated_user_id(&state, &headers).await;
+    authorize_git(&state, &repo, caller, needed).await?;
 
+    let restored_from = ensure_primary(&state, &repo_hash).await?;
     let repo_path = state.git_storage.repo_path(&repo_hash);
-    if !repo_path.exists() {
-        return Err(StatusCode::NOT_FOUND);
-    }
 
     let service = &params.service;
     let git_command = if service == "git-upload-pack" {
@@ -91,39 +183,51 @@ pub async fn git_info_refs(
         return Err(StatusCode::BAD_REQUEST);
     };
 
+    let protocol = git_protocol(&headers);
+    // Protocol v2 only exists for fetches; pushes stay on v0
+    let v2 = git_command == "upload-pack" && is_protocol_v2(protocol.as_deref());
+
     let output = Command::new("git")
         .arg(git_command)
         .arg("--stateless-rpc")
         .arg("--advertise-refs")
         .arg(&repo_path)
+        .envs(protocol.as_deref().map(|p| ("GIT_PROTOCOL", p)))
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .output()
+        .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     if !output.status.success() {
-        eprintln!(
-            "Git command failed: {}",
-            String::from_utf8_lossy(&output.stderr)
+        tracing::warn!(
+            "git-{} --advertise-refs failed for {}: {}",
+            git_command,
+            repo_hash,
+            String::from_utf8_lossy(&output.stderr).trim()
         );
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
 
+    // v2 clients expect the capability advertisement without a service line
     let mut response_body = Vec::new();
-    let service_line = format!("# service={}\n", service);
-    response_body.extend_from_slice(format_pkt_line(&service_line).as_bytes());
-    response_body.extend_from_slice(b"0000");
+    if !v2 {
+        let service_line = format!("# service={}\n", service);
+        response_body.extend_from_slice(format_pkt_line(&service_line).as_bytes());
+        response_body.extend_from_slice(b"0000");
+    }
     response_body.extend_from_slice(&output.stdout);
 
-    Ok(Response::builder()
+    let response = Response::builder()
         .status(StatusCode::OK)
         .header(
             header::CONTENT_TYPE,
             format!("application/x-{}-advertisement", service),
         )
-        .header(header::CACHE_CONTROL, "no-cache")
-        .body(Body::from(response_body))
-        .unwrap())
+        .header(header::CACHE_CONTROL, "no-cache");
+    let response = restored_headers(response, restored_from);
+
+    Ok(response.body(Body::from(response_body)).unwrap())
 }
 
 /// Handle git-upload-pack (clone/fetch) - respects privacy
@@ -131,7 +235,7 @@ pub async fn git_upload_pack(
     State(state): State<Arc<AppState>>,
     Path(repo_hash): Path<String>,
     headers: HeaderMap,
-    body: Bytes, // Changed from Vec<u8>
+    body: Body,
 ) -> Result<Response, StatusCode> {
     let repo = state
         .db
@@ -139,53 +243,21 @@ pub async fn git_upload_pack(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check authentication for private repos
-    if repo.is_private != 0 {
-        let user_id = get_authenticated_user_id(&state, &headers)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let caller = get_authenticated_user_id(&state, &headers).await;
+    authorize_git(&state, &repo, caller, RepoRole::Read).await?;
 
+    let restored_from = ensure_primary(&state, &repo_hash).await?;
     let repo_path = state.git_storage.repo_path(&repo_hash);
 
-    let mut child = Command::new("git")
-        .arg("upload-pack")
-        .arg("--stateless-rpc")
-        .arg(&repo_path)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let protocol = git_protocol(&headers);
+    let mut child = spawn_rpc("upload-pack", &repo_path, protocol, request_reader(&headers, body))?;
+    let stdout = child.stdout.take().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(&body) // body is already &[u8] via Bytes deref
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    }
-
-    let output = child
-        .wait_with_output()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tokio::spawn(async move {
+        finish_rpc("upload-pack", &repo_hash, child).await;
+    });
 
-    if !output.status.success() {
-        eprintln!(
-            "git-upload-pack failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "application/x-git-upload-pack-result")
-        .header(header::CACHE_CONTROL, "no-cache")
-        .body(Body::from(output.stdout))
-        .unwrap())
+    Ok(rpc_response("application/x-git-upload-pack-result", stdout, restored_from))
 }
 
 /// Handle git-receive-pack (push) - REQUIRES AUTHENTICATION
@@ -193,12 +265,13 @@ pub async fn git_receive_pack(
     State(state): State<Arc<AppState>>,
     Path(repo_hash): Path<String>,
     headers: HeaderMap,
-    body: Bytes, // Changed from Vec<u8>
+    body: Body,
 ) -> Result<Response, StatusCode> {
     // Push always requires authentication
-    let user_id = get_authenticated_user_id(&state, &headers)
+    let caller = get_authenticated_user_id(&state, &headers)
         .await
         .ok_or(StatusCode::UNAUTHORIZED)?;
+    let user_id = caller.0;
 
     let repo = state
         .db
@@ -206,57 +279,231 @@ pub async fn git_receive_pack(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check ownership
-    if repo.owner_id != user_id {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    authorize_git(&state, &repo, Some(caller), RepoRole::Write).await?;
 
+    // Never accept a push into an empty directory while replicas hold the history
+    ensure_primary(&state, &repo_hash).await?;
     let repo_path = state.git_storage.repo_path(&repo_hash);
 
-    let mut child = Command::new("git")
-        .arg("receive-pack")
-        .arg("--stateless-rpc")
-        .arg(&repo_path)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let protocol = git_protocol(&headers);
+    let mut input = request_reader(&headers, body);
 
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(&body) // body is already &[u8] via Bytes deref
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Repositories with push rules get the request checked before git sees it
+    let policy = PushPolicy::load(&state.db, &repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !policy.is_empty() {
+        let screening = pre_receive::screen(&state.db, &state.git_storage, &repo_hash, &policy, user_id, input)
+            .await
+            .map_err(|e| {
+                tracing::warn!("Failed to check push to {}: {}", repo_hash, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        input = match screening {
+            Screening::Accept(replay) => replay,
+            Screening::Reject(response) => {
+                return Ok((
+                    [
+                        (header::CONTENT_TYPE, "application/x-git-receive-pack-result"),
+                        (header::CACHE_CONTROL, "no-cache"),
+                    ],
+                    response,
+                )
+                    .into_response());
+            }
+        };
     }
 
-    let output = child
-        .wait_with_output()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut child = spawn_rpc("receive-pack", &repo_path, protocol, input)?;
+    let stdout = child.stdout.take().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    if !output.status.success() {
-        eprintln!(
-            "git-receive-pack failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
+    // Post-push bookkeeping runs once git has finished writing the refs
+    tokio::spawn(async move {
+        if finish_rpc("receive-pack", &repo_hash, child).await {
+            after_push(&state, &repo_hash).await;
+        }
+    });
+
+    Ok(rpc_response("application/x-git-receive-pack-result", stdout, None))
+}
+
+/// Resolve the `/:owner/:repo.git` form of a smart HTTP URL to a repo hash.
+/// Old names left by renames are served directly: git only follows
+/// redirects on the initial info/refs request.
+async fn resolve_named_repo(state: &Arc<AppState>, owner: &str, repo: &str) -> Result<String, StatusCode> {
+    let (name, _) = crate::utils::paths::strip_git_suffix(repo);
+    state
+        .db
+        .resolve_repository(owner, name)
+        .await
+        .map(|(repo, _)| repo.repo_hash)
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
 
+pub async fn named_info_refs(
+    State(state): State<Arc<AppState>>,
+    Path((owner, repo)): Path<(String, String)>,
+    query: Query<GitService>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let repo_hash = resolve_named_repo(&state, &owner, &repo).await?;
+    git_info_refs(State(state), Path(repo_hash), query, headers).await
+}
+
+pub async fn named_upload_pack(
+    State(state): State<Arc<AppState>>,
+    Path((owner, repo)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, StatusCode> {
+    let repo_hash = resolve_named_repo(&state, &owner, &repo).await?;
+    git_upload_pack(State(state), Path(repo_hash), headers, body).await
+}
+
+pub async fn named_receive_pack(
+    State(state): State<Arc<AppState>>,
+    Path((owner, repo)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, StatusCode> {
+    let repo_hash = resolve_named_repo(&state, &owner, &repo).await?;
+    git_receive_pack(State(state), Path(repo_hash), headers, body).await
+}
+
+/// Bookkeeping after a successful push, whichever transport it came over
+pub(crate) async fn after_push(state: &Arc<AppState>, repo_hash: &str) {
     // Update repository size after push
-    if let Ok(size) = state.git_storage.get_repo_size(&repo_hash) {
+    if let Ok(size) = state.git_storage.get_repo_size(repo_hash) {
         let _ = state
             .db
-            .update_repository_size(&repo_hash, size as i64)
+            .update_repository_size(repo_hash, size as i64)
             .await;
     }
 
-    Ok(Response::builder()
+    // Let nodes hosting replicas fetch the new objects
+    let replication = ReplicationService::new(state.db.clone(), state.git_storage.clone());
+    match replication.sync_replicas(repo_hash).await {
+        Ok((0, 0)) => {}
+        Ok((synced, failed)) => tracing::info!(
+            "Propagated push to {}: {} replicas synced, {} behind",
+            repo_hash, synced, failed
+        ),
+        Err(e) => tracing::warn!("Failed to propagate push to {}: {}", repo_hash, e),
+    }
+}
+
+/// The `Git-Protocol` header, passed to git as `GIT_PROTOCOL`
+///
+/// Values are colon-separated `key=value` pairs such as `version=2`;
+/// anything else is dropped so clients fall back to protocol v0.
+fn git_protocol(headers: &HeaderMap) -> Option<String> {
+    parse_git_protocol(headers.get("git-protocol")?.to_str().ok()?)
+}
+
+/// Validate a `GIT_PROTOCOL` value received from a client
+pub(crate) fn parse_git_protocol(value: &str) -> Option<String> {
+    let well_formed = !value.is_empty()
+        && value.len() <= 256
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '=' | ':' | '.' | '-' | '_'));
+
+    well_formed.then(|| value.to_string())
+}
+
+fn is_protocol_v2(protocol: Option<&str>) -> bool {
+    protocol.is_some_and(|p| p.split(':').any(|param| param == "version=2"))
+}
+
+/// The request body as a byte stream, gunzipped if the client compressed it
+fn request_reader(headers: &HeaderMap, body: Body) -> Pin<Box<dyn AsyncRead + Send>> {
+    let reader = StreamReader::new(
+        body.into_data_stream()
+            .map_err(io::Error::other),
+    );
+
+    // git clients gzip large negotiation requests
+    let gzipped = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|h| h.to_str().ok())
+        .map(|v| v.eq_ignore_ascii_case("gzip"))
+        .unwrap_or(false);
+
+    if gzipped {
+        Box::pin(GzipDecoder::new(reader))
+    } else {
+        Box::pin(reader)
+    }
+}
+
+/// Start `git <service> --stateless-rpc` and stream the request into it
+///
+/// stdin is fed from a background task, so a slow git process slows down
+/// reading the upload instead of the upload piling up in memory.
+fn spawn_rpc(
+    service: &'static str,
+    repo_path: &std::path::Path,
+    protocol: Option<String>,
+    mut input: Pin<Box<dyn AsyncRead + Send>>,
+) -> Result<Child, StatusCode> {
+    let mut child = Command::new("git")
+        .arg(service)
+        .arg("--stateless-rpc")
+        .arg(repo_path)
+        .envs(protocol.map(|p| ("GIT_PROTOCOL", p)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut stdin = child.stdin.take().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    tokio::spawn(async move {
+        // Dropping stdin afterwards signals end of request to git
+        if let Err(e) = tokio::io::copy(&mut input, &mut stdin).await {
+            tracing::warn!("git-{} request aborted: {}", service, e);
+        }
+    });
+
+    Ok(child)
+}
+
+/// Stream a git process's stdout back to the client as it is produced
+fn rpc_response(content_type: &str, stdout: ChildStdout, restored_from: Option<RestoredPrimary>) -> Response {
+    let response = Response::builder()
         .status(StatusCode::OK)
-        .header(
-            header::CONTENT_TYPE,
-            "application/x-git-receive-pack-result",
-        )
-        .body(Body::from(output.stdout))
-        .unwrap())
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "no-cache");
+    let response = restored_headers(response, restored_from);
+
+    let stream = ReaderStream::with_capacity(stdout, RPC_CHUNK_SIZE);
+    response.body(Body::from_stream(stream)).unwrap()
+}
+
+/// Wait for a git process to exit, logging its stderr if it failed
+async fn finish_rpc(service: &str, repo_hash: &str, mut child: Child) -> bool {
+    let mut stderr = child.stderr.take();
+    let drain = async {
+        let mut buf = Vec::new();
+        if let Some(stderr) = stderr.as_mut() {
+            let _ = stderr.read_to_end(&mut buf).await;
+        }
+        buf
+    };
+
+    match tokio::join!(child.wait(), drain) {
+        (Ok(status), _) if status.success() => true,
+        (status, stderr) => {
+            tracing::warn!(
+                "git-{} failed for {} ({:?}): {}",
+                service,
+                repo_hash,
+                status.map(|s| s.code()),
+                String::from_utf8_lossy(&stderr).trim()
+            );
+            false
+        }
+    }
 }
 
 fn format_pkt_line(data: &str) -> String {
@@ -276,16 +523,8 @@ pub async fn dumb_clone(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check authentication for private repos
-    if repo.is_private != 0 {
-        let user_id = get_authenticated_user_id(&state, &headers)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let caller = get_authenticated_user_id(&state, &headers).await;
+    authorize_git(&state, &repo, caller, RepoRole::Read).await?;
 
     let repo_path = state.git_storage.repo_path(&repo_hash);
 
@@ -296,6 +535,7 @@ pub async fn dumb_clone(
         .arg("--all")
         .current_dir(&repo_path)
         .output()
+        .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     if !output.status.success() {
@@ -312,3 +552,943 @@ pub async fn dumb_clone(
         .body(Body::from(output.stdout))
         .unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protocol_header(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("Git-Protocol", value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_git_protocol_header() {
+        let v2 = git_protocol(&protocol_header("version=2"));
+        assert_eq!(v2.as_deref(), Some("version=2"));
+        assert!(is_protocol_v2(v2.as_deref()));
+        assert!(is_protocol_v2(Some("object-format=sha1:version=2")));
+
+        assert!(!is_protocol_v2(Some("version=1")));
+        assert!(!is_protocol_v2(None));
+        assert_eq!(git_protocol(&HeaderMap::new()), None);
+
+        // Nothing that could smuggle extra environment or arguments through
+        assert_eq!(git_protocol(&protocol_header("version=2 --upload-pack=x")), None);
+        assert_eq!(git_protocol(&protocol_header("")), None);
+    }
+
+    /// A coordinator serving one public repository on a random local port
+    struct TestServer {
+        dir: std::path::PathBuf,
+        url: String,
+        repo_hash: String,
+        git_storage: Arc<crate::storage::GitStorage>,
+        db: crate::db::Database,
+        /// `http.extraHeader` value that signs git in as the owner
+        auth_header: String,
+        session_store: Arc<crate::auth::session::SessionStore>,
+        state: Arc<AppState>,
+    }
+
+    impl Drop for TestServer {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    async fn git(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .await
+            .expect("failed to run git");
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    /// Run git expecting it to fail, returning what it printed
+    async fn git_fails(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .await
+            .expect("failed to run git");
+        assert!(!output.status.success(), "git {:?} succeeded", args);
+        String::from_utf8_lossy(&output.stderr).to_string()
+    }
+
+    /// Serve a repository holding three commits that each rewrite the same file
+    async fn start_server() -> TestServer {
+        use crate::auth::session::{ClientInfo, SessionStore};
+        use crate::middleware::{cache::CacheService, csrf::CsrfProtection, rate_limit::RateLimiter};
+        use crate::models::{CreateRepoRequest, CreateUserRequest};
+
+        let dir = std::env::temp_dir().join(format!("hyrule-test-{:016x}", rand::random::<u64>()));
+        let work = dir.join("work");
+        std::fs::create_dir_all(&work).unwrap();
+
+        let db = crate::db::Database::new(&format!("sqlite://{}?mode=rwc", dir.join("hyrule.db").display()))
+            .await
+            .unwrap();
+        db.migrate().await.unwrap();
+        let user = db
+            .create_user(&CreateUserRequest {
+                username: "alice".to_string(),
+                email: "alice@example.com".to_string(),
+                password_hash: String::new(),
+                public_key: String::new(),
+                storage_quota: 1 << 30,
+            })
+            .await
+            .unwrap();
+        let repo_hash = crate::utils::hash::generate_repo_hash("demo", user.id);
+        let request = CreateRepoRequest {
+            name: "demo".to_string(),
+            description: None,
+            storage_tier: "free".to_string(),
+            is_private: false,
+        };
+        db.create_repository(&request, user.id, &repo_hash).await.unwrap();
+
+        let git_storage = Arc::new(crate::storage::GitStorage::new(dir.join("repos")).unwrap());
+        git_storage.init_repo(&repo_hash).unwrap();
+        let bare = git_storage.repo_path(&repo_hash);
+
+        git(&work, &["init", "--quiet", "--initial-branch=main"]).await;
+        for round in 1..=3 {
+            std::fs::write(work.join("README.md"), format!("revision {}\n", round)).unwrap();
+            git(&work, &["add", "README.md"]).await;
+            git(&work, &["commit", "--quiet", "-m", &format!("commit {}", round)]).await;
+        }
+        git(&work, &["push", "--quiet", bare.to_str().unwrap(), "main"]).await;
+        git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]).await;
+
+        let config = crate::config::Config {
+            database_url: String::new(),
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            jwt_secret: "test_secret_key_minimum_32_characters_long_12345".to_string(),
+            default_storage_quota: 1 << 30,
+            min_replica_count: 3,
+            node_heartbeat_timeout_minutes: 10,
+            node_eviction_grace_minutes: 60,
+            replication_workers: 1,
+            ssh_port: 0,
+            ssh_host_key_path: String::new(),
+            session_backend: "memory".to_string(),
+            site_origin: "http://127.0.0.1".to_string(),
+            mailer: "log".to_string(),
+            mail_dir: String::new(),
+            mail_from: "Hyrule <noreply@hyrule.local>".to_string(),
+            smtp_host: String::new(),
+            smtp_port: 0,
+            smtp_tls: "none".to_string(),
+            smtp_username: None,
+            smtp_password: None,
+        };
+        let session_store = Arc::new(SessionStore::new());
+        let session_id = session_store.create_session(user.id, user.username.clone(), &ClientInfo::default()).await;
+        let auth_header = format!("http.extraHeader=Cookie: session_id={}", session_id);
+
+        let state = Arc::new(AppState {
+            db: db.clone(),
+            config,
+            cache: CacheService::new(),
+            git_storage: git_storage.clone(),
+            session_store: session_store.clone(),
+            rate_limiter: Arc::new(RateLimiter::new(100, 60)),
+            csrf_protection: Arc::new(CsrfProtection::new()),
+            mailer: Arc::new(crate::services::mailer::LogMailer),
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/git/{}", listener.local_addr().unwrap(), repo_hash);
+        // Extractors find the state where main.rs layers it
+        let router = crate::routes::create_router(state.clone()).layer(axum::Extension(state.clone()));
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        TestServer { dir, url, repo_hash, git_storage, db, auth_header, session_store, state }
+    }
+
+    /// A storage node registered with the server's database that keeps
+    /// replicas in its own storage and signs what it holds, like `hyrule-node`
+    struct FakeNode {
+        node_id: String,
+        storage: Arc<crate::storage::GitStorage>,
+        /// Acknowledge a ref the node doesn't hold, as a faulty node would
+        misreport: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    async fn start_node(server: &TestServer) -> FakeNode {
+        use crate::services::node_protocol::{self, RegisterNodeRequest, ReplicaAck};
+        use axum::{body::Bytes, routing::put, Json, Router};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let key = ed25519_dalek::SigningKey::from_bytes(&rand::random::<[u8; 32]>());
+        let node_id = node_protocol::node_id_from_key(&key.verifying_key());
+        let storage = Arc::new(crate::storage::GitStorage::new(server.dir.join("node")).unwrap());
+        let misreport = Arc::new(AtomicBool::new(false));
+
+        let receive = {
+            let (storage, misreport, node_id) = (storage.clone(), misreport.clone(), node_id.clone());
+            move |Path(repo_hash): Path<String>, bundle: Bytes| async move {
+                storage
+                    .restore_from_bundle(&repo_hash, &bundle, None)
+                    .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+                let mut refs = storage.list_refs(&repo_hash).unwrap();
+                if misreport.load(Ordering::SeqCst) {
+                    refs.insert("refs/heads/phantom".to_string(), "0".repeat(40));
+                }
+                let mut ack = ReplicaAck {
+                    node_id,
+                    repo_hash,
+                    refs,
+                    timestamp: node_protocol::unix_now(),
+                    signature: None,
+                };
+                node_protocol::sign(&mut ack, &key);
+                Ok::<_, (StatusCode, String)>(Json(ack))
+            }
+        };
+        let send = {
+            let storage = storage.clone();
+            move |Path(repo_hash): Path<String>| async move {
+                storage.create_bundle(&repo_hash).map_err(|_| StatusCode::NOT_FOUND)
+            }
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let router = Router::new().route("/replicas/:hash", put(receive).get(send));
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        server
+            .db
+            .register_node(&RegisterNodeRequest {
+                node_id: node_id.clone(),
+                address: "127.0.0.1".to_string(),
+                port: port as i32,
+                storage_capacity: 1 << 40,
+                is_anchor: false,
+                access_token: None,
+                timestamp: 0,
+                signature: None,
+            })
+            .await
+            .unwrap();
+
+        FakeNode { node_id, storage, misreport }
+    }
+
+    #[tokio::test]
+    async fn test_partial_clone() {
+        let server = start_server().await;
+        assert!(server.git_storage.upload_pack_settings(&server.repo_hash).unwrap().partial_clone());
+
+        for version in ["0", "2"] {
+            let dest = format!("partial-v{}", version);
+            let protocol = format!("protocol.version={}", version);
+            git(&server.dir, &["-c", &protocol, "clone", "--quiet", "--filter=blob:none", &server.url, &dest]).await;
+            let clone = server.dir.join(&dest);
+
+            assert_eq!(git(&clone, &["config", "remote.origin.promisor"]).await.trim(), "true");
+            // Only the checked-out blob was fetched; older revisions stay missing
+            let missing = git(&clone, &["rev-list", "--objects", "--all", "--missing=print"]).await;
+            assert_eq!(missing.lines().filter(|l| l.starts_with('?')).count(), 2);
+
+            // Missing blobs are fetched on demand
+            let old = git(&clone, &["show", "HEAD~2:README.md"]).await;
+            assert_eq!(old, "revision 1\n");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shallow_clone() {
+        let server = start_server().await;
+
+        git(&server.dir, &["clone", "--quiet", "--depth=1", &server.url, "shallow"]).await;
+        let clone = server.dir.join("shallow");
+
+        assert_eq!(git(&clone, &["rev-list", "--count", "HEAD"]).await.trim(), "1");
+        assert_eq!(git(&clone, &["rev-parse", "--is-shallow-repository"]).await.trim(), "true");
+        assert_eq!(std::fs::read_to_string(clone.join("README.md")).unwrap(), "revision 3\n");
+
+        // Deepening goes through the same endpoint
+        git(&clone, &["fetch", "--quiet", "--deepen=1"]).await;
+        assert_eq!(git(&clone, &["rev-list", "--count", "HEAD"]).await.trim(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_existing_repos_are_configured() {
+        let server = start_server().await;
+        let bare = server.git_storage.repo_path(&server.repo_hash);
+
+        // A repository created before filters were enabled
+        git(&bare, &["config", "--unset", "uploadpack.allowFilter"]).await;
+        assert!(!server.git_storage.upload_pack_settings(&server.repo_hash).unwrap().partial_clone());
+
+        assert_eq!(server.git_storage.configure_all_repos().unwrap(), 1);
+        assert!(server.git_storage.upload_pack_settings(&server.repo_hash).unwrap().partial_clone());
+    }
+
+    #[tokio::test]
+    async fn test_push_rules() {
+        use crate::models::{BranchProtectionRequest, CreateUserRequest, PushRules};
+
+        let server = start_server().await;
+        git(&server.dir, &["clone", "--quiet", &server.url, "pusher"]).await;
+        let clone = server.dir.join("pusher");
+        let push = ["-c", &server.auth_header, "push", "--quiet", "origin", "HEAD:main"];
+        let force_push = ["-c", &server.auth_header, "push", "--quiet", "--force", "origin", "HEAD:main"];
+
+        let main = BranchProtectionRequest {
+            pattern: "main".to_string(),
+            allow_deletion: false,
+            allow_force_push: false,
+            require_linear_history: false,
+            restrict_pushes: false,
+            allowed_pushers: Vec::new(),
+        };
+        server.db.save_protected_branch(&server.repo_hash, &main, &[]).await.unwrap();
+        server
+            .db
+            .save_push_rules(&PushRules {
+                max_blob_size: Some(1024),
+                forbidden_paths: "*.pem\nsecrets/**".to_string(),
+                ..PushRules::none(&server.repo_hash)
+            })
+            .await
+            .unwrap();
+
+        // Pushes that follow the rules go through unchanged
+        std::fs::write(clone.join("README.md"), "revision 4\n").unwrap();
+        git(&clone, &["commit", "--quiet", "-am", "commit 4"]).await;
+        git(&clone, &push).await;
+        let bare = server.git_storage.repo_path(&server.repo_hash);
+        assert_eq!(git(&bare, &["log", "-1", "--format=%s", "main"]).await.trim(), "commit 4");
+
+        // Rewriting a protected branch is refused
+        git(&clone, &["reset", "--quiet", "--hard", "HEAD~1"]).await;
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "rewrite"]).await;
+        let stderr = git_fails(&clone, &force_push).await;
+        assert!(stderr.contains("main is protected and cannot be force-pushed"), "{}", stderr);
+        assert!(stderr.contains("push rejected by repository rules"), "{}", stderr);
+        git(&clone, &["reset", "--quiet", "--hard", "origin/main"]).await;
+
+        // So are large and forbidden files, even when a later commit removes them
+        std::fs::create_dir_all(clone.join("secrets")).unwrap();
+        std::fs::write(clone.join("secrets/prod.yml"), "password: hunter2\n").unwrap();
+        std::fs::write(clone.join("big.bin"), vec![b'x'; 4096]).unwrap();
+        git(&clone, &["add", "."]).await;
+        git(&clone, &["commit", "--quiet", "-m", "oops"]).await;
+        git(&clone, &["rm", "--quiet", "-r", "secrets", "big.bin"]).await;
+        git(&clone, &["commit", "--quiet", "-m", "remove"]).await;
+        let stderr = git_fails(&clone, &push).await;
+        assert!(stderr.contains("secrets/prod.yml matches forbidden path secrets/**"), "{}", stderr);
+        assert!(stderr.contains("big.bin is 4096 bytes, over the 1024 byte limit"), "{}", stderr);
+        git(&clone, &["reset", "--quiet", "--hard", "origin/main"]).await;
+
+        // Authors must have verified their email when the repository asks for it
+        server
+            .db
+            .save_push_rules(&PushRules { require_verified_email: 1, ..PushRules::none(&server.repo_hash) })
+            .await
+            .unwrap();
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "unverified"]).await;
+        let stderr = git_fails(&clone, &push).await;
+        assert!(stderr.contains("author email test@example.com is not verified"), "{}", stderr);
+
+        assert_eq!(git(&bare, &["log", "-1", "--format=%s", "main"]).await.trim(), "commit 4");
+        // Nothing from the rejected pushes is left behind
+        assert!(!git(&bare, &["cat-file", "--batch-all-objects", "--batch-check"]).await.contains(" blob 4096"));
+
+        // Once the address belongs to a verified account the commit goes in
+        let author = server
+            .db
+            .create_user(&CreateUserRequest {
+                username: "tester".to_string(),
+                email: "test@example.com".to_string(),
+                password_hash: String::new(),
+                public_key: String::new(),
+                storage_quota: 1 << 30,
+            })
+            .await
+            .unwrap();
+        server.db.mark_email_verified(author.id, "test@example.com").await.unwrap();
+        git(&clone, &push).await;
+        assert_eq!(git(&bare, &["log", "-1", "--format=%s", "main"]).await.trim(), "unverified");
+    }
+
+    #[tokio::test]
+    async fn test_branch_protection() {
+        use crate::models::{BranchProtectionRequest, CreateUserRequest};
+
+        let server = start_server().await;
+        git(&server.dir, &["clone", "--quiet", &server.url, "pusher"]).await;
+        let clone = server.dir.join("pusher");
+        let push = |refspec: &'static str| ["-c", &server.auth_header, "push", "--quiet", "origin", refspec];
+        let protect = |pattern: &str, allowed_pushers: Vec<String>| BranchProtectionRequest {
+            pattern: pattern.to_string(),
+            allow_deletion: false,
+            allow_force_push: true,
+            require_linear_history: true,
+            restrict_pushes: false,
+            allowed_pushers,
+        };
+
+        git(&clone, &push("HEAD:release/1.0")).await;
+        server.db.save_protected_branch(&server.repo_hash, &protect("release/*", Vec::new()), &[]).await.unwrap();
+
+        // Deletion is refused unless the rule allows it
+        let stderr = git_fails(&clone, &push(":release/1.0")).await;
+        assert!(stderr.contains("release/1.0 is protected and cannot be deleted"), "{}", stderr);
+
+        // Merge commits are refused when linear history is required
+        git(&clone, &["checkout", "--quiet", "-b", "topic", "HEAD~1"]).await;
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "topic"]).await;
+        git(&clone, &["checkout", "--quiet", "main"]).await;
+        git(&clone, &["merge", "--quiet", "--no-ff", "-m", "merge topic", "topic"]).await;
+        let stderr = git_fails(&clone, &push("HEAD:release/1.0")).await;
+        assert!(stderr.contains("release/1.0 requires linear history"), "{}", stderr);
+
+        // This rule allows force-pushes, so a rebased branch goes through
+        git(&clone, &["reset", "--quiet", "--hard", "topic"]).await;
+        git(&clone, &["-c", &server.auth_header, "push", "--quiet", "--force", "origin", "HEAD:release/1.0"]).await;
+
+        // Only listed users may push
+        let bob = server
+            .db
+            .create_user(&CreateUserRequest {
+                username: "bob".to_string(),
+                email: "bob@example.com".to_string(),
+                password_hash: String::new(),
+                public_key: String::new(),
+                storage_quota: 1 << 30,
+            })
+            .await
+            .unwrap();
+        server
+            .db
+            .save_protected_branch(&server.repo_hash, &protect("release/*", vec!["bob".to_string()]), &[bob.id])
+            .await
+            .unwrap();
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "more"]).await;
+        let stderr = git_fails(&clone, &push("HEAD:release/1.0")).await;
+        assert!(stderr.contains("release/1.0 is protected and alice may not push to it"), "{}", stderr);
+
+        // Deleting the last allowed pusher doesn't open the branch up
+        server.db.delete_user_account(bob.id, None).await.unwrap();
+        let stderr = git_fails(&clone, &push("HEAD:release/1.0")).await;
+        assert!(stderr.contains("release/1.0 is protected and alice may not push to it"), "{}", stderr);
+
+        // Unprotected branches are unaffected
+        git(&clone, &push("HEAD:feature")).await;
+        git(&clone, &push(":feature")).await;
+    }
+
+    #[tokio::test]
+    async fn test_owner_name_urls() {
+        let server = start_server().await;
+        let base = server.url.split("/git/").next().unwrap().to_string();
+
+        // Smart HTTP works with and without .git
+        git(&server.dir, &["clone", "--quiet", &format!("{}/alice/demo.git", base), "named"]).await;
+        git(&server.dir, &["clone", "--quiet", &format!("{}/alice/demo", base), "bare-name"]).await;
+
+        server.db.rename_repository(&server.repo_hash, "renamed").await.unwrap();
+
+        // The old name keeps working for fetches and pushes
+        let clone = server.dir.join("named");
+        git(&clone, &["fetch", "--quiet"]).await;
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "after rename"]).await;
+        git(&clone, &["-c", &server.auth_header, "push", "--quiet", "origin", "HEAD:main"]).await;
+        let bare = server.git_storage.repo_path(&server.repo_hash);
+        assert_eq!(git(&bare, &["log", "-1", "--format=%s", "main"]).await.trim(), "after rename");
+
+        // Web and API URLs redirect to the current name
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let location = |response: reqwest::Response| {
+            assert!(response.status().is_redirection(), "{}", response.status());
+            response.headers()["location"].to_str().unwrap().to_string()
+        };
+        let get = |path: String| client.get(format!("{}{}", base, path)).send();
+
+        assert_eq!(location(get(format!("/r/{}", server.repo_hash)).await.unwrap()), "/alice/renamed");
+        assert_eq!(location(get("/alice/demo".to_string()).await.unwrap()), "/alice/renamed");
+        assert_eq!(location(get("/alice/renamed.git".to_string()).await.unwrap()), "/alice/renamed");
+        assert_eq!(location(get("/api/repos/alice/demo".to_string()).await.unwrap()), "/api/repos/alice/renamed");
+        assert_eq!(get("/alice/renamed".to_string()).await.unwrap().status().as_u16(), 200);
+        assert_eq!(get("/api/repos/alice/renamed".to_string()).await.unwrap().status().as_u16(), 200);
+        assert_eq!(get("/alice/missing".to_string()).await.unwrap().status().as_u16(), 404);
+
+        // A new repository can take the old name back
+        assert!(!server.db.repository_name_taken(1, "demo").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_collaborator_access() {
+        use crate::models::{CreateRepoRequest, CreateUserRequest};
+
+        let server = start_server().await;
+        let base = server.url.split("/git/").next().unwrap().to_string();
+
+        // A private repository holding the demo history
+        let secret_hash = crate::utils::hash::generate_repo_hash("secret", 1);
+        let request = CreateRepoRequest {
+            name: "secret".to_string(),
+            description: None,
+            storage_tier: "free".to_string(),
+            is_private: true,
+        };
+        let secret = server.db.create_repository(&request, 1, &secret_hash).await.unwrap();
+        server.git_storage.init_repo(&secret_hash).unwrap();
+        let secret_url = format!("{}/alice/secret", base);
+        git(&server.dir.join("work"), &["-c", &server.auth_header, "push", "--quiet", &secret_url, "main"]).await;
+        git(&server.git_storage.repo_path(&secret_hash), &["symbolic-ref", "HEAD", "refs/heads/main"]).await;
+
+        let bob = server
+            .db
+            .create_user(&CreateUserRequest {
+                username: "bob".to_string(),
+                email: "bob@example.com".to_string(),
+                password_hash: String::new(),
+                public_key: String::new(),
+                storage_quota: 1 << 30,
+            })
+            .await
+            .unwrap();
+        let session_id = server.session_store.create_session(bob.id, bob.username.clone(), &Default::default()).await;
+        let bob_header = format!("http.extraHeader=Cookie: session_id={}", session_id);
+        let bob_clone = |dest: &'static str| ["-c", bob_header.as_str(), "clone", "--quiet", secret_url.as_str(), dest];
+
+        // Neither anonymous users nor other users can see it
+        git_fails(&server.dir, &["clone", "--quiet", &secret_url, "anonymous"]).await;
+        git_fails(&server.dir, &bob_clone("uninvited")).await;
+
+        // An invitation grants nothing until it is accepted
+        let invitation =
+            crate::auth::repo_access::invite_collaborator(&server.db, &secret, 1, "bob", "write").await.unwrap();
+        git_fails(&server.dir, &bob_clone("invited")).await;
+        assert_eq!(server.db.accept_invitation(invitation.id, bob.id).await.unwrap(), Some(secret_hash.clone()));
+
+        // Writers can clone and push
+        git(&server.dir, &bob_clone("collaborator")).await;
+        let clone = server.dir.join("collaborator");
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "from bob"]).await;
+        git(&clone, &["-c", &bob_header, "push", "--quiet", "origin", "HEAD:main"]).await;
+
+        // Readers can fetch but not push
+        server.db.set_collaborator_role(&secret_hash, bob.id, "read").await.unwrap();
+        git(&clone, &["-c", &bob_header, "fetch", "--quiet"]).await;
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "read only"]).await;
+        git_fails(&clone, &["-c", &bob_header, "push", "--quiet", "origin", "HEAD:main"]).await;
+
+        // Only admins see the settings
+        let client = reqwest::Client::new();
+        let settings = |cookie: &str| {
+            client
+                .get(format!("{}/r/{}/settings", base, secret_hash))
+                .header("Cookie", format!("session_id={}", cookie))
+                .send()
+        };
+        assert_eq!(settings(&session_id).await.unwrap().status().as_u16(), 403);
+        server.db.set_collaborator_role(&secret_hash, bob.id, "admin").await.unwrap();
+        assert_eq!(settings(&session_id).await.unwrap().status().as_u16(), 200);
+
+        // Removing a collaborator takes their access away
+        server.db.remove_collaborator(&secret_hash, bob.id).await.unwrap();
+        git_fails(&clone, &["-c", &bob_header, "fetch", "--quiet"]).await;
+    }
+
+    #[tokio::test]
+    async fn test_organization_access() {
+        use crate::models::{CreateRepoRequest, CreateUserRequest};
+        use crate::services::organizations;
+
+        let server = start_server().await;
+        let base = server.url.split("/git/").next().unwrap().to_string();
+
+        // Organization names share the namespace with usernames
+        let org = organizations::create_organization(&server.db, 1, "acme", None).await.unwrap();
+        let (status, _) = organizations::create_organization(&server.db, 1, "Alice", None).await.unwrap_err();
+        assert_eq!(status.as_u16(), 409);
+
+        // A private organization repository holding the demo history
+        let request = CreateRepoRequest {
+            name: "tools".to_string(),
+            description: None,
+            storage_tier: "free".to_string(),
+            is_private: true,
+        };
+        let tools = organizations::create_repository(&server.db, &server.git_storage, &org, 1, &request)
+            .await
+            .unwrap();
+        let tools_url = format!("{}/acme/tools", base);
+        git(&server.dir.join("work"), &["-c", &server.auth_header, "push", "--quiet", &tools_url, "main"]).await;
+        git(&server.git_storage.repo_path(&tools.repo_hash), &["symbolic-ref", "HEAD", "refs/heads/main"]).await;
+
+        // Storage counts against the organization
+        let size = server.db.get_repository(&tools.repo_hash).await.unwrap().size;
+        assert!(size > 0);
+        assert_eq!(server.db.get_organization(org.id).await.unwrap().storage_used, size);
+
+        let bob = server
+            .db
+            .create_user(&CreateUserRequest {
+                username: "bob".to_string(),
+                email: "bob@example.com".to_string(),
+                password_hash: String::new(),
+                public_key: String::new(),
+                storage_quota: 1 << 30,
+            })
+            .await
+            .unwrap();
+        let session_id = server.session_store.create_session(bob.id, bob.username.clone(), &Default::default()).await;
+        let bob_header = format!("http.extraHeader=Cookie: session_id={}", session_id);
+        let bob_clone = |dest: &'static str| ["-c", bob_header.as_str(), "clone", "--quiet", tools_url.as_str(), dest];
+
+        // Outsiders cannot see it
+        git_fails(&server.dir, &bob_clone("outsider")).await;
+        let client = reqwest::Client::new();
+        let dashboard = |cookie: &str| {
+            client
+                .get(format!("{}/orgs/acme", base))
+                .header("Cookie", format!("session_id={}", cookie))
+                .send()
+        };
+        assert!(!dashboard(&session_id).await.unwrap().text().await.unwrap().contains("tools"));
+
+        // Members can read every repository but not push
+        organizations::set_member(&server.db, &org, "bob", "member").await.unwrap();
+        assert!(dashboard(&session_id).await.unwrap().text().await.unwrap().contains("tools"));
+        git(&server.dir, &bob_clone("member")).await;
+        let clone = server.dir.join("member");
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "from bob"]).await;
+        git_fails(&clone, &["-c", &bob_header, "push", "--quiet", "origin", "HEAD:main"]).await;
+
+        // A team with write access lets them push
+        let team = organizations::create_team(&server.db, &org, "devs", None).await.unwrap();
+        organizations::add_team_member(&server.db, &org, &team, "bob").await.unwrap();
+        organizations::grant_team_repo(&server.db, &org, &team, "tools", "write").await.unwrap();
+        git(&clone, &["-c", &bob_header, "push", "--quiet", "origin", "HEAD:main"]).await;
+
+        // Only owners see the settings, and the last owner cannot step down
+        let settings = client
+            .get(format!("{}/orgs/acme/settings", base))
+            .header("Cookie", format!("session_id={}", session_id))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(settings.status().as_u16(), 403);
+        let (status, _) = organizations::set_member(&server.db, &org, "alice", "member").await.unwrap_err();
+        assert_eq!(status.as_u16(), 400);
+
+        // Leaving the organization also leaves its teams
+        organizations::remove_member(&server.db, &org, "bob").await.unwrap();
+        assert!(server.db.list_teams(org.id).await.unwrap()[0].members.is_empty());
+        git_fails(&clone, &["-c", &bob_header, "fetch", "--quiet"]).await;
+    }
+
+    #[tokio::test]
+    async fn test_personal_access_tokens() {
+        use crate::auth::tokens;
+        use crate::models::CreateRepoRequest;
+
+        let server = start_server().await;
+        let addr = server.url.trim_start_matches("http://").split('/').next().unwrap().to_string();
+
+        // A private repository holding the demo history
+        let vault_hash = crate::utils::hash::generate_repo_hash("vault", 1);
+        let request = CreateRepoRequest {
+            name: "vault".to_string(),
+            description: None,
+            storage_tier: "free".to_string(),
+            is_private: true,
+        };
+        server.db.create_repository(&request, 1, &vault_hash).await.unwrap();
+        server.git_storage.init_repo(&vault_hash).unwrap();
+        let vault_url = format!("http://{}/alice/vault", addr);
+        git(&server.dir.join("work"), &["-c", &server.auth_header, "push", "--quiet", &vault_url, "main"]).await;
+        git(&server.git_storage.repo_path(&vault_hash), &["symbolic-ref", "HEAD", "refs/heads/main"]).await;
+        git_fails(&server.dir, &["clone", "--quiet", &vault_url, "anonymous"]).await;
+
+        // Git answers the Basic challenge with the token from the URL
+        let (read_key, read_token) =
+            tokens::create_token(&server.db, 1, "ci", &["repo:read".to_string()], Some(30)).await.unwrap();
+        assert!(read_key.last_used.is_none());
+        let read_url = format!("http://ci:{}@{}/alice/vault", read_token, addr);
+        git(&server.dir, &["clone", "--quiet", &read_url, "reader"]).await;
+        let keys = server.db.list_api_keys(1).await.unwrap();
+        assert!(keys[0].last_used.is_some());
+
+        // Scopes cap what the token can do, whatever the owner may
+        let clone = server.dir.join("reader");
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "from ci"]).await;
+        git_fails(&clone, &["push", "--quiet", "origin", "HEAD:main"]).await;
+
+        let (_, write_token) =
+            tokens::create_token(&server.db, 1, "deploy", &["repo:write".to_string()], None).await.unwrap();
+        let write_url = format!("http://deploy:{}@{}/alice/vault", write_token, addr);
+        git(&clone, &["push", "--quiet", &write_url, "HEAD:main"]).await;
+
+        // Revoked and unknown tokens get nothing
+        assert!(server.db.revoke_api_key(read_key.id, 1).await.unwrap());
+        git_fails(&clone, &["fetch", "--quiet", "origin"]).await;
+        let bogus_url = format!("http://ci:hyr_{}@{}/alice/vault", "0".repeat(40), addr);
+        git_fails(&server.dir, &["clone", "--quiet", &bogus_url, "bogus"]).await;
+        assert!(tokens::create_token(&server.db, 1, "bad", &["repo:delete".to_string()], None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replication_round_trip() {
+        use std::sync::atomic::Ordering;
+
+        let server = start_server().await;
+        let node = start_node(&server).await;
+        let replication = ReplicationService::new(server.db.clone(), server.git_storage.clone());
+
+        // A node that acknowledges refs it wasn't sent gets no replica
+        node.misreport.store(true, Ordering::SeqCst);
+        let err = replication.replicate(&server.repo_hash).await.unwrap_err();
+        assert!(err.contains("do not match"), "{}", err);
+        assert!(server.db.list_replica_sync(&server.repo_hash).await.unwrap().is_empty());
+
+        node.misreport.store(false, Ordering::SeqCst);
+        let decision = replication.replicate(&server.repo_hash).await.unwrap();
+        assert_eq!(decision.chosen.as_deref(), Some(node.node_id.as_str()));
+
+        // The bundle carried every ref and object across
+        let primary_refs = server.git_storage.list_refs(&server.repo_hash).unwrap();
+        assert_eq!(node.storage.list_refs(&server.repo_hash).unwrap(), primary_refs);
+        let replica = node.storage.repo_path(&server.repo_hash);
+        assert_eq!(git(&replica, &["rev-list", "--count", "main"]).await.trim(), "3");
+        git(&replica, &["fsck", "--no-progress"]).await;
+
+        let replicas = server.db.list_replica_sync(&server.repo_hash).await.unwrap();
+        assert_eq!(replicas.len(), 1);
+        assert_eq!(replicas[0].node_id, node.node_id);
+        let recorded = crate::services::replication::decode_refs(replicas[0].ref_state.as_deref().unwrap());
+        assert_eq!(recorded, primary_refs);
+    }
+
+    #[tokio::test]
+    async fn test_replication_requests_are_queued() {
+        let server = start_server().await;
+        let repo = server.db.get_repository(&server.repo_hash).await.unwrap();
+        let owner = server.db.get_user_by_id(repo.owner_id).await.unwrap();
+        let stranger = server
+            .db
+            .create_user(&crate::models::CreateUserRequest {
+                username: "mallory".to_string(),
+                email: "mallory@example.com".to_string(),
+                password_hash: String::new(),
+                public_key: String::new(),
+                storage_quota: 1 << 30,
+            })
+            .await
+            .unwrap();
+        let bearer = |user: &crate::models::User| {
+            let secret = &server.state.config.jwt_secret;
+            let token = crate::auth::jwt::generate_token(secret, user.id, &user.username, user.token_generation).unwrap();
+            format!("Bearer {}", token)
+        };
+        let http = reqwest::Client::new();
+        let endpoint = server.url.replace("/git/", "/api/repos/") + "/replicate";
+
+        let response = http.post(&endpoint).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        let response = http.post(&endpoint).header("Authorization", bearer(&stranger)).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+        assert!(server.db.list_replication_jobs(None, 10).await.unwrap().is_empty());
+
+        // The owner gets a job for the workers rather than an inline transfer
+        let response = http.post(&endpoint).header("Authorization", bearer(&owner)).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["job"]["repo_hash"], server.repo_hash.as_str());
+        assert_eq!(body["job"]["state"], "pending");
+        assert!(server.db.list_replica_sync(&server.repo_hash).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restore_from_replica() {
+        let server = start_server().await;
+        let node = start_node(&server).await;
+        ReplicationService::new(server.db.clone(), server.git_storage.clone())
+            .replicate(&server.repo_hash)
+            .await
+            .unwrap();
+        let http = reqwest::Client::new();
+        let advertise = format!("{}/info/refs?service=git-upload-pack", server.url);
+
+        // Losing the primary's disk doesn't lose the repository
+        server.git_storage.delete_repo(&server.repo_hash).unwrap();
+        let response = http.get(&advertise).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(response.headers()[RESTORED_FROM_HEADER], node.node_id.as_str());
+        assert!(response.headers().get(RESTORED_STALE_HEADER).is_none());
+        assert!(server.git_storage.repo_path(&server.repo_hash).exists());
+
+        git(&server.dir, &["clone", "--quiet", &server.url, "restored"]).await;
+        let clone = server.dir.join("restored");
+        assert_eq!(git(&clone, &["rev-list", "--count", "HEAD"]).await.trim(), "3");
+        assert_eq!(std::fs::read_to_string(clone.join("README.md")).unwrap(), "revision 3\n");
+
+        // A push the node never received is lost with the primary, and
+        // the restore says so
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "commit 4"]).await;
+        git(&clone, &["-c", &server.auth_header, "push", "--quiet", "origin", "HEAD:main"]).await;
+        let pushed = git(&clone, &["rev-parse", "HEAD"]).await;
+        for _ in 0..100 {
+            let known = server.db.get_primary_refs(&server.repo_hash).await.unwrap().unwrap_or_default();
+            if known.contains(pushed.trim()) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+
+        server.git_storage.delete_repo(&server.repo_hash).unwrap();
+        let response = http.get(&advertise).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(response.headers()[RESTORED_FROM_HEADER], node.node_id.as_str());
+        assert_eq!(response.headers()[RESTORED_STALE_HEADER], "1");
+        let bare = server.git_storage.repo_path(&server.repo_hash);
+        assert_eq!(git(&bare, &["log", "-1", "--format=%s", "main"]).await.trim(), "commit 3");
+
+        // Once the only replica's node goes offline there is nothing to restore from
+        server.git_storage.delete_repo(&server.repo_hash).unwrap();
+        server.db.set_node_state(&node.node_id, "online", "offline", 120).await.unwrap();
+        let response = http.get(&advertise).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_large_push_and_clone() {
+        use rand::RngCore;
+
+        let server = start_server().await;
+        git(&server.dir, &["clone", "--quiet", &server.url, "pusher"]).await;
+        let clone = server.dir.join("pusher");
+
+        // Incompressible, so the pack is as big as the file
+        let mut data = vec![0u8; 24 << 20];
+        rand::thread_rng().fill_bytes(&mut data);
+        std::fs::write(clone.join("large.bin"), &data).unwrap();
+        git(&clone, &["add", "large.bin"]).await;
+        git(&clone, &["commit", "--quiet", "-m", "large"]).await;
+
+        // A small post buffer makes git send the pack chunked, as it does
+        // for any push too big to buffer
+        let push = ["-c", &server.auth_header, "-c", "http.postBuffer=65536", "push", "--quiet", "origin", "HEAD:main"];
+        git(&clone, &push).await;
+        let blob = git(&clone, &["rev-parse", "HEAD:large.bin"]).await;
+        let bare = server.git_storage.repo_path(&server.repo_hash);
+        assert_eq!(git(&bare, &["rev-parse", "main:large.bin"]).await, blob);
+
+        for version in ["0", "2"] {
+            let dest = format!("large-v{}", version);
+            let protocol = format!("protocol.version={}", version);
+            git(&server.dir, &["-c", &protocol, "clone", "--quiet", &server.url, &dest]).await;
+            assert_eq!(std::fs::read(server.dir.join(&dest).join("large.bin")).unwrap(), data);
+        }
+    }
+
+    /// Fail instead of hanging when git waits on a server that never answers
+    async fn within<T>(git: impl std::future::Future<Output = T>) -> T {
+        tokio::time::timeout(std::time::Duration::from_secs(60), git).await.expect("git hung")
+    }
+
+    #[tokio::test]
+    async fn test_ssh_push() {
+        use crate::models::BranchProtectionRequest;
+        use crate::services::ssh_server::{parse_public_key, GitSshServer};
+        use std::time::Duration;
+
+        let server = start_server().await;
+        let key_path = server.dir.join("id_ed25519");
+        let keygen = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&key_path)
+            .output()
+            .await
+            .unwrap();
+        assert!(keygen.status.success());
+        let public_key = std::fs::read_to_string(key_path.with_extension("pub")).unwrap();
+        let (fingerprint, encoded) = parse_public_key(&public_key).unwrap();
+        server.db.add_ssh_key(1, "laptop", &fingerprint, &encoded).await.unwrap();
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let addr: std::net::SocketAddr = ([127, 0, 0, 1], port).into();
+        let ssh_server = GitSshServer::new(server.state.clone());
+        let host_key = server.dir.join("ssh_host_ed25519_key");
+        tokio::spawn(async move {
+            let _ = ssh_server.run(addr, host_key).await;
+        });
+        while tokio::net::TcpStream::connect(addr).await.is_err() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let ssh = format!(
+            "core.sshCommand=ssh -i {} -p {} -o IdentitiesOnly=yes -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null -o LogLevel=ERROR",
+            key_path.display(),
+            port
+        );
+        let remote = "git@127.0.0.1:alice/demo.git";
+        // Rules make every push go through screening
+        let main = BranchProtectionRequest {
+            pattern: "main".to_string(),
+            allow_deletion: false,
+            allow_force_push: false,
+            require_linear_history: false,
+            restrict_pushes: false,
+            allowed_pushers: Vec::new(),
+        };
+        server.db.save_protected_branch(&server.repo_hash, &main, &[]).await.unwrap();
+
+        within(git(&server.dir, &["-c", &ssh, "clone", "--quiet", remote, "over-ssh"])).await;
+        let clone = server.dir.join("over-ssh");
+        let bare = server.git_storage.repo_path(&server.repo_hash);
+
+        std::fs::write(clone.join("README.md"), "revision 4\n").unwrap();
+        git(&clone, &["commit", "--quiet", "-am", "commit 4"]).await;
+        within(git(&clone, &["-c", &ssh, "push", "--quiet", "origin", "HEAD:main", "HEAD:refs/heads/topic"])).await;
+        assert_eq!(git(&bare, &["log", "-1", "--format=%s", "main"]).await.trim(), "commit 4");
+        assert_eq!(git(&bare, &["rev-parse", "topic"]).await, git(&bare, &["rev-parse", "main"]).await);
+
+        // Deleting sends no pack, and the client waits for our answer
+        within(git(&clone, &["-c", &ssh, "push", "--quiet", "origin", "--delete", "topic"])).await;
+        assert!(!git(&bare, &["branch", "--list"]).await.contains("topic"));
+
+        let stderr = within(git_fails(&clone, &["-c", &ssh, "push", "--quiet", "origin", "--delete", "main"])).await;
+        assert!(stderr.contains("push rejected by repository rules"), "{}", stderr);
+        git(&clone, &["reset", "--quiet", "--hard", "HEAD~1"]).await;
+        git(&clone, &["commit", "--quiet", "--allow-empty", "-m", "rewrite"]).await;
+        let stderr = within(git_fails(&clone, &["-c", &ssh, "push", "--quiet", "--force", "origin", "HEAD:main"])).await;
+        assert!(stderr.contains("main is protected and cannot be force-pushed"), "{}", stderr);
+        assert_eq!(git(&bare, &["log", "-1", "--format=%s", "main"]).await.trim(), "commit 4");
+    }
+}
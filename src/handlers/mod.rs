@@ -8,4 +8,6 @@ pub mod web_enhanced;
 pub mod git;
 pub mod repo_browser;
 pub mod clone_page;
+pub mod repo_settings;
+pub mod orgs;
 pub mod admin_web;  // NEW
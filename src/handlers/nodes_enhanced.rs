@@ -4,9 +4,12 @@ use axum::{
     http::StatusCode,
     response::{Html, Redirect},
 };
+use axum_extra::extract::cookie::CookieJar;
 use serde::Deserialize;
 use std::sync::Arc;
 
+use crate::handlers::admin_web::check_admin_access;
+use crate::services::reputation::ReputationService;
 use crate::templates;
 use crate::AppState;
 
@@ -82,8 +85,13 @@ pub struct NodeAction {
 
 pub async fn node_action(
     State(state): State<Arc<AppState>>,
+    jar: CookieJar,
     Form(form): Form<NodeAction>,
 ) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (_user_id, username) = check_admin_access(&state, &jar).await?;
+    let reputation = ReputationService::new(state.db.clone());
+    let failed = |e: String| (StatusCode::NOT_FOUND, Html(error_page(&e)));
+    
     match form.action.as_str() {
         "ping" => {
             // Try to ping the node
@@ -110,30 +118,18 @@ pub async fn node_action(
         }
         "remove" => {
             // Remove node and all its replicas
-            // First get all replicas
-            let replicas = state.db.list_repo_replicas(&form.node_id)
-                .await
-                .unwrap_or_default();
-            
-            // Delete all replicas
-            for replica_node in replicas {
-                let _ = state.db.delete_replica(&replica_node.node_id, &form.node_id).await;
-            }
-            
-            // Delete node (would need to add this method to db)
-            // let _ = state.db.delete_node(&form.node_id).await;
+            reputation.remove(&form.node_id).await.map_err(failed)?;
         }
         "ban" => {
-            // Set reputation to 0 (would need to add this method)
-            // let _ = state.db.update_node_reputation(&form.node_id, 0).await;
+            reputation.ban(&form.node_id).await.map_err(failed)?;
         }
         "trust" => {
-            // Set reputation to 100
-            // let _ = state.db.update_node_reputation(&form.node_id, 100).await;
+            reputation.trust(&form.node_id).await.map_err(failed)?;
         }
         _ => return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action")))),
     }
     
+    tracing::info!("Admin {} ran '{}' on node {}", username, form.action, form.node_id);
     Ok(Redirect::to("/admin/nodes"))
 }
 
@@ -0,0 +1,233 @@
+// src/handlers/orgs.rs
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+    Form,
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::auth::repo_access::repo_role;
+use crate::services::organizations::{self, OrgError};
+use crate::templates;
+use crate::AppState;
+
+async fn get_session_user_id(state: &Arc<AppState>, jar: &CookieJar) -> Option<i64> {
+    if let Some(session_id) = jar.get("session_id") {
+        if let Some(session) = state.session_store.get_session(session_id.value()).await {
+            return Some(session.user_id);
+        }
+    }
+    None
+}
+
+fn error_page(message: &str) -> String {
+    templates::render_page(
+        "Error",
+        &format!(
+            r#"<div class="section">
+                <h1>Error</h1>
+                <p>{}</p>
+                <a href="javascript:history.back()" class="btn btn-secondary">Go Back</a>
+            </div>"#,
+            templates::html_escape(message)
+        ),
+    )
+}
+
+fn page_error((status, message): OrgError) -> (StatusCode, Html<String>) {
+    (status, Html(error_page(&message)))
+}
+
+pub async fn new_org_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Result<Html<String>, StatusCode> {
+    get_session_user_id(&state, &jar)
+        .await
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    Ok(Html(templates::org::render_new()))
+}
+
+#[derive(Deserialize)]
+pub struct CreateOrgForm {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+pub async fn create_org(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<CreateOrgForm>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let user_id = get_session_user_id(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(error_page("Please log in"))))?;
+
+    let org = organizations::create_organization(&state.db, user_id, &form.name, form.description.as_deref())
+        .await
+        .map_err(page_error)?;
+
+    Ok(Redirect::to(&format!("/orgs/{}", org.name)))
+}
+
+/// Organization dashboard. Everyone sees the repositories they can read;
+/// members also see who is in the organization and its teams.
+pub async fn org_dashboard(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(org_name): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let org = state
+        .db
+        .get_organization_by_name(&org_name)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let user_id = get_session_user_id(&state, &jar).await;
+    let role = match user_id {
+        Some(id) => state
+            .db
+            .get_org_role(org.id, id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => None,
+    };
+
+    let mut repos = Vec::new();
+    for repo in state.db.list_org_repositories(org.id).await.unwrap_or_default() {
+        if repo_role(&state.db, &repo, user_id).await.ok().flatten().is_some() {
+            repos.push(repo);
+        }
+    }
+    let members = state.db.list_org_members(org.id).await.unwrap_or_default();
+    let teams = if role.is_some() {
+        state.db.list_teams(org.id).await.unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    Ok(Html(templates::org::render_dashboard(
+        &org,
+        role.as_deref(),
+        &repos,
+        &members,
+        &teams,
+    )))
+}
+
+/// Organization settings, visible to owners only
+pub async fn org_settings(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(org_name): Path<String>,
+) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let user_id = get_session_user_id(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(error_page("Please log in"))))?;
+    let org = organizations::owned_organization(&state.db, &org_name, user_id)
+        .await
+        .map_err(page_error)?;
+
+    let members = state.db.list_org_members(org.id).await.unwrap_or_default();
+    let teams = state.db.list_teams(org.id).await.unwrap_or_default();
+
+    Ok(Html(templates::org::render_settings(&org, &members, &teams)))
+}
+
+#[derive(Deserialize)]
+pub struct OrgSettingsAction {
+    pub action: String,
+    pub description: Option<String>,
+    pub username: Option<String>,
+    pub role: Option<String>,
+    pub team: Option<String>,
+    pub repo: Option<String>,
+}
+
+pub async fn org_settings_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(org_name): Path<String>,
+    Form(form): Form<OrgSettingsAction>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let user_id = get_session_user_id(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(error_page("Please log in"))))?;
+    let org = organizations::owned_organization(&state.db, &org_name, user_id)
+        .await
+        .map_err(page_error)?;
+
+    let db = &state.db;
+    let username = form.username.as_deref().unwrap_or("");
+    let role = form.role.as_deref().unwrap_or("");
+    let team_name = form.team.as_deref().unwrap_or("");
+    let repo_name = form.repo.as_deref().unwrap_or("");
+
+    match form.action.as_str() {
+        "update" => {
+            let description = form.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+            if description.is_some_and(|d| d.len() > crate::models::MAX_DESCRIPTION_LEN) {
+                return Err((StatusCode::BAD_REQUEST, Html(error_page("Description is too long"))));
+            }
+            db.update_organization(org.id, description)
+                .await
+                .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Html(error_page("Failed to save"))))?;
+        }
+        "add_member" | "set_role" => {
+            organizations::set_member(db, &org, username, role)
+                .await
+                .map_err(page_error)?;
+        }
+        "remove_member" => {
+            organizations::remove_member(db, &org, username)
+                .await
+                .map_err(page_error)?;
+        }
+        "create_team" => {
+            organizations::create_team(db, &org, team_name, form.description.as_deref())
+                .await
+                .map_err(page_error)?;
+        }
+        "delete_team" => {
+            let team = organizations::find_team(db, &org, team_name).await.map_err(page_error)?;
+            db.delete_team(team.id)
+                .await
+                .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, Html(error_page("Failed to delete team"))))?;
+        }
+        "add_team_member" => {
+            let team = organizations::find_team(db, &org, team_name).await.map_err(page_error)?;
+            organizations::add_team_member(db, &org, &team, username)
+                .await
+                .map_err(page_error)?;
+        }
+        "remove_team_member" => {
+            let team = organizations::find_team(db, &org, team_name).await.map_err(page_error)?;
+            organizations::remove_team_member(db, &team, username)
+                .await
+                .map_err(page_error)?;
+        }
+        "grant_repo" => {
+            let team = organizations::find_team(db, &org, team_name).await.map_err(page_error)?;
+            organizations::grant_team_repo(db, &org, &team, repo_name, role)
+                .await
+                .map_err(page_error)?;
+        }
+        "revoke_repo" => {
+            let team = organizations::find_team(db, &org, team_name).await.map_err(page_error)?;
+            organizations::revoke_team_repo(db, &org, &team, repo_name)
+                .await
+                .map_err(page_error)?;
+        }
+        _ => {
+            return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action"))));
+        }
+    }
+
+    // An owner who demoted or removed themselves can no longer see settings
+    let still_owner = db.get_org_role(org.id, user_id).await.ok().flatten().as_deref() == Some("owner");
+    if still_owner {
+        Ok(Redirect::to(&format!("/orgs/{}/settings", org.name)))
+    } else {
+        Ok(Redirect::to(&format!("/orgs/{}", org.name)))
+    }
+}
@@ -1,4 +1,5 @@
 // Hyrule/src/handlers/repo_browser.rs - SECURITY FIXES
+use crate::auth::repo_access::{authorize, RepoRole};
 use crate::templates;
 use crate::AppState;
 use axum::{
@@ -58,15 +59,8 @@ pub async fn view_repo(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user_id = get_session_user_id(&state, &jar)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let user_id = get_session_user_id(&state, &jar).await;
+    authorize(&state.db, &repo, user_id, RepoRole::Read).await?;
 
     let replica_count = state.db.get_replica_count(&repo_hash).await.unwrap_or(0);
     let nodes = state
@@ -95,15 +89,8 @@ pub async fn browse_files(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user_id = get_session_user_id(&state, &jar)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let user_id = get_session_user_id(&state, &jar).await;
+    authorize(&state.db, &repo, user_id, RepoRole::Read).await?;
 
     let branch = query.branch.unwrap_or_else(|| "main".to_string());
     
@@ -143,15 +130,8 @@ pub async fn browse_directory(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user_id = get_session_user_id(&state, &jar)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let user_id = get_session_user_id(&state, &jar).await;
+    authorize(&state.db, &repo, user_id, RepoRole::Read).await?;
 
     let branch = query.branch.unwrap_or_else(|| "main".to_string());
     
@@ -190,15 +170,8 @@ pub async fn view_file(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user_id = get_session_user_id(&state, &jar)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let user_id = get_session_user_id(&state, &jar).await;
+    authorize(&state.db, &repo, user_id, RepoRole::Read).await?;
 
     let branch = query.branch.unwrap_or_else(|| "main".to_string());
     
@@ -234,15 +207,8 @@ pub async fn list_commits(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user_id = get_session_user_id(&state, &jar)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let user_id = get_session_user_id(&state, &jar).await;
+    authorize(&state.db, &repo, user_id, RepoRole::Read).await?;
 
     let branch = query.branch.unwrap_or_else(|| "main".to_string());
     
@@ -279,15 +245,8 @@ pub async fn view_commit(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user_id = get_session_user_id(&state, &jar)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let user_id = get_session_user_id(&state, &jar).await;
+    authorize(&state.db, &repo, user_id, RepoRole::Read).await?;
 
     let repo_path = state.git_storage.repo_path(&repo_hash);
     let commit_info =
@@ -320,15 +279,8 @@ pub async fn list_branches(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
-    if repo.is_private != 0 {
-        let user_id = get_session_user_id(&state, &jar)
-            .await
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != user_id {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let user_id = get_session_user_id(&state, &jar).await;
+    authorize(&state.db, &repo, user_id, RepoRole::Read).await?;
 
     let repo_path = state.git_storage.repo_path(&repo_hash);
     let branches = get_branches(&repo_path)
@@ -0,0 +1,378 @@
+// src/handlers/repo_settings.rs
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+    Form,
+};
+use axum_extra::extract::cookie::CookieJar;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::auth::repo_access::{self, authorize, RepoRole};
+use crate::models::{BranchProtectionRequest, PushRules, Repository};
+use crate::services::pre_receive;
+use crate::templates;
+use crate::AppState;
+
+async fn get_session_user_id(state: &Arc<AppState>, jar: &CookieJar) -> Option<i64> {
+    if let Some(session_id) = jar.get("session_id") {
+        if let Some(session) = state.session_store.get_session(session_id.value()).await {
+            return Some(session.user_id);
+        }
+    }
+    None
+}
+
+/// The repository and the signed-in user, if they may administer it
+async fn managed_repo(state: &Arc<AppState>, jar: &CookieJar, repo_hash: &str) -> Result<(Repository, i64), StatusCode> {
+    if !crate::utils::validation::validate_repo_hash(repo_hash) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let repo = state
+        .db
+        .get_repository(repo_hash)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let user_id = get_session_user_id(state, jar)
+        .await
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    authorize(&state.db, &repo, Some(user_id), RepoRole::Admin).await?;
+
+    Ok((repo, user_id))
+}
+
+fn error_page(message: &str) -> String {
+    templates::render_page(
+        "Error",
+        &format!(
+            r#"<div class="section">
+                <h1>Error</h1>
+                <p>{}</p>
+                <a href="javascript:history.back()" class="btn btn-secondary">Go Back</a>
+            </div>"#,
+            templates::html_escape(message)
+        ),
+    )
+}
+
+/// Repository settings, visible to repository admins only
+pub async fn show_settings(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(repo_hash): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let (repo, _) = managed_repo(&state, &jar, &repo_hash).await?;
+
+    let upload_pack = state
+        .git_storage
+        .upload_pack_settings(&repo_hash)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let push_rules = state
+        .db
+        .get_push_rules(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let protected = state
+        .db
+        .list_protected_branches(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Html(templates::repo_settings::render(
+        &repo,
+        &upload_pack,
+        &push_rules,
+        protected.len(),
+        state.config.sends_email(),
+    )))
+}
+
+#[derive(Deserialize)]
+pub struct RenameForm {
+    pub name: String,
+}
+
+/// Rename the repository; the old name keeps redirecting
+pub async fn rename_repo(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(repo_hash): Path<String>,
+    Form(form): Form<RenameForm>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (repo, _) = managed_repo(&state, &jar, &repo_hash)
+        .await
+        .map_err(|status| (status, Html(error_page("Repository not found"))))?;
+
+    let name = form.name.trim();
+    if let Err(e) = crate::utils::validation::validate_repo_name(name) {
+        return Err((StatusCode::BAD_REQUEST, Html(error_page(e))));
+    }
+    if name != repo.name && state.db.sibling_name_taken(&repo, name).await.unwrap_or(false) {
+        return Err((
+            StatusCode::CONFLICT,
+            Html(error_page("A repository with this name already exists here")),
+        ));
+    }
+
+    state.db.rename_repository(&repo_hash, name).await.map_err(|_| {
+        (StatusCode::INTERNAL_SERVER_ERROR, Html(error_page("Failed to rename repository")))
+    })?;
+
+    Ok(Redirect::to(&format!("/r/{}/settings", repo_hash)))
+}
+
+#[derive(Deserialize)]
+pub struct PushRulesForm {
+    /// Empty means no limit
+    pub max_blob_size_mb: Option<String>,
+    #[serde(default)]
+    pub forbidden_paths: String,
+    /// Present when the checkbox is ticked
+    pub require_verified_email: Option<String>,
+}
+
+pub async fn update_push_rules(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(repo_hash): Path<String>,
+    Form(form): Form<PushRulesForm>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    managed_repo(&state, &jar, &repo_hash)
+        .await
+        .map_err(|status| (status, Html(error_page("Repository not found"))))?;
+
+    let max_blob_size = match form.max_blob_size_mb.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(mb) => match mb.parse::<f64>() {
+            Ok(mb) if mb > 0.0 && mb <= 10_240.0 => Some((mb * 1024.0 * 1024.0) as i64),
+            _ => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Html(error_page("Maximum file size must be a number of megabytes")),
+                ))
+            }
+        },
+    };
+
+    // Authors can only verify their addresses if the links reach them
+    if form.require_verified_email.is_some() && !state.config.sends_email() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Html(error_page("Requiring verified emails needs outgoing email; set MAILER first")),
+        ));
+    }
+
+    let forbidden_paths: Vec<&str> = form.forbidden_paths.lines().map(str::trim).filter(|p| !p.is_empty()).collect();
+    if forbidden_paths.len() > 100 || forbidden_paths.iter().any(|p| p.len() > 200) {
+        return Err((StatusCode::BAD_REQUEST, Html(error_page("Too many or too long forbidden paths"))));
+    }
+
+    let rules = PushRules {
+        max_blob_size,
+        forbidden_paths: forbidden_paths.join("\n"),
+        require_verified_email: form.require_verified_email.is_some() as i64,
+        ..PushRules::none(&repo_hash)
+    };
+    state.db.save_push_rules(&rules).await.map_err(|_| {
+        (StatusCode::INTERNAL_SERVER_ERROR, Html(error_page("Failed to save push rules")))
+    })?;
+
+    Ok(Redirect::to(&format!("/r/{}/settings", repo_hash)))
+}
+
+/// Branch protection rules, visible to repository admins only
+pub async fn show_branch_settings(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(repo_hash): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let (repo, _) = managed_repo(&state, &jar, &repo_hash).await?;
+
+    let protected = state
+        .db
+        .list_protected_branches(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Html(templates::branch_settings::render(&repo, &protected)))
+}
+
+#[derive(Deserialize)]
+pub struct BranchProtectionAction {
+    pub action: String,
+    pub branch_id: Option<i64>,
+    pub pattern: Option<String>,
+    /// Checkboxes are present only when ticked
+    pub allow_deletion: Option<String>,
+    pub allow_force_push: Option<String>,
+    pub require_linear_history: Option<String>,
+    pub restrict_pushes: Option<String>,
+    /// Comma-separated usernames
+    #[serde(default)]
+    pub allowed_pushers: String,
+}
+
+impl BranchProtectionAction {
+    fn rule(&self) -> Result<BranchProtectionRequest, String> {
+        let pattern = pre_receive::normalize_branch_pattern(self.pattern.as_deref().unwrap_or(""))
+            .ok_or("Invalid branch name or pattern")?;
+
+        Ok(BranchProtectionRequest {
+            pattern: pattern.to_string(),
+            allow_deletion: self.allow_deletion.is_some(),
+            allow_force_push: self.allow_force_push.is_some(),
+            require_linear_history: self.require_linear_history.is_some(),
+            restrict_pushes: self.restrict_pushes.is_some(),
+            allowed_pushers: self.allowed_pushers.split(',').map(|u| u.trim().to_string()).collect(),
+        })
+    }
+}
+
+pub async fn branch_protection_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(repo_hash): Path<String>,
+    Form(form): Form<BranchProtectionAction>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    managed_repo(&state, &jar, &repo_hash)
+        .await
+        .map_err(|status| (status, Html(error_page("Repository not found"))))?;
+    let bad_request = |message: &str| (StatusCode::BAD_REQUEST, Html(error_page(message)));
+    let save_failed = |_| {
+        (StatusCode::INTERNAL_SERVER_ERROR, Html(error_page("Failed to save branch protection")))
+    };
+
+    match form.action.as_str() {
+        "add" | "update" => {
+            let rule = form.rule().map_err(|e| bad_request(&e))?;
+            let pusher_ids = pre_receive::resolve_pushers(&state.db, &rule.allowed_pushers)
+                .await
+                .map_err(|e| bad_request(&e))?;
+
+            if form.action == "add" {
+                state
+                    .db
+                    .save_protected_branch(&repo_hash, &rule, &pusher_ids)
+                    .await
+                    .map_err(save_failed)?;
+            } else {
+                let updated = state
+                    .db
+                    .update_protected_branch(&repo_hash, form.branch_id.unwrap_or_default(), &rule, &pusher_ids)
+                    .await
+                    .map_err(save_failed)?;
+                if updated.is_none() {
+                    return Err((StatusCode::NOT_FOUND, Html(error_page("Protected branch not found"))));
+                }
+            }
+        }
+        "delete" => {
+            let deleted = state
+                .db
+                .delete_protected_branch(&repo_hash, form.branch_id.unwrap_or_default())
+                .await
+                .unwrap_or(false);
+            if !deleted {
+                return Err((StatusCode::NOT_FOUND, Html(error_page("Protected branch not found"))));
+            }
+        }
+        _ => {
+            return Err(bad_request("Invalid action"));
+        }
+    }
+
+    Ok(Redirect::to(&format!("/r/{}/settings/branches", repo_hash)))
+}
+
+/// Collaborators and pending invitations, visible to repository admins only
+pub async fn show_collaborators(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(repo_hash): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let (repo, _) = managed_repo(&state, &jar, &repo_hash).await?;
+
+    let collaborators = state
+        .db
+        .list_collaborators(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let invitations = state
+        .db
+        .list_repo_invitations(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Html(templates::collaborators::render(&repo, &collaborators, &invitations)))
+}
+
+#[derive(Deserialize)]
+pub struct CollaboratorAction {
+    pub action: String,
+    pub username: Option<String>,
+    pub role: Option<String>,
+    pub user_id: Option<i64>,
+    pub invitation_id: Option<i64>,
+}
+
+pub async fn collaborator_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Path(repo_hash): Path<String>,
+    Form(form): Form<CollaboratorAction>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (repo, user_id) = managed_repo(&state, &jar, &repo_hash)
+        .await
+        .map_err(|status| (status, Html(error_page("Repository not found"))))?;
+    let role = form.role.as_deref().unwrap_or("");
+
+    match form.action.as_str() {
+        "invite" => {
+            let username = form.username.as_deref().unwrap_or("");
+            repo_access::invite_collaborator(&state.db, &repo, user_id, username, role)
+                .await
+                .map_err(|(status, message)| (status, Html(error_page(&message))))?;
+        }
+        "update" => {
+            if RepoRole::parse_grantable(role).is_none() {
+                return Err((StatusCode::BAD_REQUEST, Html(error_page("Role must be read, write or admin"))));
+            }
+            let updated = state
+                .db
+                .set_collaborator_role(&repo_hash, form.user_id.unwrap_or_default(), role)
+                .await
+                .unwrap_or(false);
+            if !updated {
+                return Err((StatusCode::NOT_FOUND, Html(error_page("Collaborator not found"))));
+            }
+        }
+        "remove" => {
+            let removed = state
+                .db
+                .remove_collaborator(&repo_hash, form.user_id.unwrap_or_default())
+                .await
+                .unwrap_or(false);
+            if !removed {
+                return Err((StatusCode::NOT_FOUND, Html(error_page("Collaborator not found"))));
+            }
+        }
+        "cancel" => {
+            let cancelled = state
+                .db
+                .cancel_invitation(&repo_hash, form.invitation_id.unwrap_or_default())
+                .await
+                .unwrap_or(false);
+            if !cancelled {
+                return Err((StatusCode::NOT_FOUND, Html(error_page("Invitation not found"))));
+            }
+        }
+        _ => {
+            return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action"))));
+        }
+    }
+
+    Ok(Redirect::to(&format!("/r/{}/settings/collaborators", repo_hash)))
+}
@@ -2,13 +2,16 @@
 use axum::{
     extract::{Form, Path, Query, State},
     http::StatusCode,
-    response::{Html, Redirect},
+    response::{Html, IntoResponse, Redirect, Response},
 };
-use axum_extra::extract::cookie::CookieJar;
+use axum_extra::extract::cookie::{Cookie, CookieJar};
 use serde::Deserialize;
 use std::sync::Arc;
 
-use crate::auth::session::SessionUser;
+use crate::auth::repo_access::{authorize, RepoRole};
+use crate::auth::session::SessionStore;
+use crate::services::replication::ReplicationService;
+use crate::services::{accounts, login_throttle, two_factor};
 use crate::templates;
 use crate::AppState;
 
@@ -73,6 +76,49 @@ fn redirect_to_login() -> String {
 }
 
 // Enhanced repository view with actions
+/// Hash links from before owner/name URLs point at `/:owner/:repo` now
+pub async fn repo_hash_redirect(
+    State(state): State<Arc<AppState>>,
+    Path(repo_hash): Path<String>,
+) -> Result<Redirect, StatusCode> {
+    let repo = state
+        .db
+        .get_repository(&repo_hash)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let owner = state
+        .db
+        .repository_namespace(&repo)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    Ok(Redirect::permanent(&crate::utils::paths::repo_url(&owner, &repo.name)))
+}
+
+/// Repository page at `/:owner/:repo`; old names and `.git` redirect to
+/// the current name
+pub async fn repo_by_name(
+    State(state): State<Arc<AppState>>,
+    Path((owner, name)): Path<(String, String)>,
+    jar: CookieJar,
+) -> Result<Response, StatusCode> {
+    let (name, git_suffix) = crate::utils::paths::strip_git_suffix(&name);
+    let (repo, renamed) = state
+        .db
+        .resolve_repository(&owner, name)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    if renamed || git_suffix {
+        let url = crate::utils::paths::repo_url(&owner, &repo.name);
+        return Ok(Redirect::permanent(&url).into_response());
+    }
+
+    repo_enhanced(State(state), Path(repo.repo_hash), jar)
+        .await
+        .map(IntoResponse::into_response)
+}
+
 pub async fn repo_enhanced(
     State(state): State<Arc<AppState>>,
     Path(repo_hash): Path<String>,
@@ -84,34 +130,25 @@ pub async fn repo_enhanced(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    // Check access for private repos
     let maybe_user = get_session_user(&state, &jar).await;
-    if repo.is_private != 0 {
-        let (user_id, _) = maybe_user.as_ref().ok_or(StatusCode::UNAUTHORIZED)?;
-        if repo.owner_id != *user_id {
-            // Note the dereference here
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
+    let role = authorize(&state.db, &repo, maybe_user.as_ref().map(|(id, _)| *id), RepoRole::Read).await?;
 
     let owner = state
         .db
-        .get_user_by_id(repo.owner_id)
+        .repository_namespace(&repo)
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
     let replica_count = state.db.get_replica_count(&repo_hash).await.unwrap_or(0);
-    let nodes = state
-        .db
-        .list_repo_replicas(&repo_hash)
+    let replicas = ReplicationService::new(state.db.clone(), state.git_storage.clone())
+        .replica_status(&repo_hash)
         .await
         .unwrap_or_default();
     let tags = state.db.get_repo_tags(&repo_hash).await.unwrap_or_default();
     let star_count = state.db.get_repo_star_count(&repo_hash).await.unwrap_or(0);
 
-    // Check if user is logged in and owns repo
-    let (is_owner, is_starred, is_pinned) = if let Some((user_id, _)) = maybe_user {
-        let owns = repo.owner_id == user_id;
+    // Check if user is logged in and has starred or pinned the repo
+    let (role, is_starred, is_pinned) = if let Some((user_id, _)) = maybe_user {
         let starred = state
             .db
             .has_starred(&repo_hash, user_id)
@@ -122,32 +159,23 @@ pub async fn repo_enhanced(
             .has_pinned(&repo_hash, user_id)
             .await
             .unwrap_or(false);
-        (owns, starred, pinned)
+        (Some(role), starred, pinned)
     } else {
-        (false, false, false)
+        (None, false, false)
     };
 
-    // Try to fetch README
-    let readme_html = {
-        use crate::auth::OptionalAuthUser;
-        use crate::handlers::api_complete::get_repo_readme;
-
-        // For readme, pass None for auth since we already checked access above
-        match get_repo_readme(State(state.clone()), Path(repo_hash.clone())).await {
-            Ok(html) => Some(html),
-            Err(_) => None,
-        }
-    };
+    // Access was checked above
+    let readme_html = crate::handlers::api_complete::render_readme(&state.git_storage.repo_path(&repo_hash));
 
     Ok(Html(
         templates::repo_enhanced::render_with_readme(
             &repo,
-            &owner.username,
+            &owner,
             replica_count,
-            &nodes,
+            &replicas,
             &tags,
             star_count,
-            is_owner,
+            role,
             is_starred,
             is_pinned,
             readme_html,
@@ -172,6 +200,35 @@ pub async fn repo_action(
         .await
         .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
 
+    let repo = state
+        .db
+        .get_repository(&form.repo_hash)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                Html(error_page("Repository not found")),
+            )
+        })?;
+
+    // Starring and pinning need read access, deleting needs ownership.
+    // Unstarring and unpinning stay possible after losing access.
+    let needed = match form.action.as_str() {
+        "delete" => Some(RepoRole::Owner),
+        "star" | "pin" => Some(RepoRole::Read),
+        _ => None,
+    };
+    if let Some(needed) = needed {
+        authorize(&state.db, &repo, Some(user_id), needed)
+            .await
+            .map_err(|status| {
+                (
+                    status,
+                    Html(error_page("You don't have permission to do that")),
+                )
+            })?;
+    }
+
     match form.action.as_str() {
         "star" => {
             state
@@ -222,25 +279,6 @@ pub async fn repo_action(
                 })?;
         }
         "delete" => {
-            // Verify ownership
-            let repo = state
-                .db
-                .get_repository(&form.repo_hash)
-                .await
-                .map_err(|_| {
-                    (
-                        StatusCode::NOT_FOUND,
-                        Html(error_page("Repository not found")),
-                    )
-                })?;
-
-            if repo.owner_id != user_id {
-                return Err((
-                    StatusCode::FORBIDDEN,
-                    Html(error_page("You don't own this repository")),
-                ));
-            }
-
             state
                 .db
                 .delete_repository_complete(&form.repo_hash)
@@ -277,16 +315,33 @@ fn error_page(message: &str) -> String {
 }
 
 // Create repository form
+#[derive(Debug, Deserialize)]
+pub struct CreateRepoQuery {
+    pub owner: Option<String>,
+}
+
 pub async fn create_repo_page(
     State(state): State<Arc<AppState>>,
     jar: CookieJar,
+    Query(query): Query<CreateRepoQuery>,
 ) -> Result<Html<String>, StatusCode> {
     // Check if logged in
-    if get_session_user(&state, &jar).await.is_none() {
-        return Err(StatusCode::UNAUTHORIZED);
-    }
+    let (user_id, username) = get_session_user(&state, &jar)
+        .await
+        .ok_or(StatusCode::UNAUTHORIZED)?;
 
-    Ok(Html(templates::create_repo::render()))
+    // Only organization owners can create repositories in it
+    let orgs: Vec<_> = state
+        .db
+        .list_user_organizations(user_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, role)| role == "owner")
+        .map(|(org, _)| org)
+        .collect();
+
+    Ok(Html(templates::create_repo::render(&username, &orgs, query.owner.as_deref())))
 }
 
 #[derive(Debug, Deserialize)]
@@ -316,11 +371,23 @@ pub async fn fork_repo_form(
                 Html(error_page("Repository not found")),
             )
         })?;
+    authorize(&state.db, &original_repo, Some(user_id), RepoRole::Read)
+        .await
+        .map_err(|status| (status, Html(error_page("Repository not found"))))?;
 
     // Generate new hash for fork
     let fork_name = form
         .new_name
         .unwrap_or(format!("{}-fork", original_repo.name));
+    if let Err(e) = crate::utils::validation::validate_repo_name(&fork_name) {
+        return Err((StatusCode::BAD_REQUEST, Html(error_page(e))));
+    }
+    if state.db.repository_name_taken(user_id, &fork_name).await.unwrap_or(false) {
+        return Err((
+            StatusCode::CONFLICT,
+            Html(error_page("You already have a repository with this name")),
+        ));
+    }
     let fork_hash = crate::utils::hash::generate_repo_hash(&fork_name, user_id);
 
     // Copy repository data
@@ -389,6 +456,8 @@ pub struct CreateRepoForm {
     pub name: String,
     pub description: Option<String>,
     pub is_private: Option<String>,
+    /// Organization to create the repository in; empty for a personal one
+    pub owner: Option<String>,
 }
 
 pub async fn create_repo_submit(
@@ -400,11 +469,37 @@ pub async fn create_repo_submit(
         .await
         .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
 
+    if let Some(org_name) = form.owner.as_deref().filter(|o| !o.is_empty()) {
+        let org = crate::services::organizations::owned_organization(&state.db, org_name, user_id)
+            .await
+            .map_err(|(status, message)| (status, Html(error_page(&message))))?;
+        let create_req = crate::models::CreateRepoRequest {
+            name: form.name,
+            description: form.description,
+            storage_tier: "free".to_string(),
+            is_private: form.is_private.is_some(),
+        };
+        let repo = crate::services::organizations::create_repository(
+            &state.db,
+            &state.git_storage,
+            &org,
+            user_id,
+            &create_req,
+        )
+        .await
+        .map_err(|(status, message)| (status, Html(error_page(&message))))?;
+
+        return Ok(Redirect::to(&format!("/r/{}", repo.repo_hash)));
+    }
+
     // Validate
-    if form.name.len() < 3 || form.name.len() > 64 {
+    if let Err(e) = crate::utils::validation::validate_repo_name(&form.name) {
+        return Err((StatusCode::BAD_REQUEST, Html(error_page(e))));
+    }
+    if state.db.repository_name_taken(user_id, &form.name).await.unwrap_or(false) {
         return Err((
-            StatusCode::BAD_REQUEST,
-            Html(error_page("Repository name must be 3-64 characters")),
+            StatusCode::CONFLICT,
+            Html(error_page("You already have a repository with this name")),
         ));
     }
 
@@ -499,11 +594,540 @@ pub async fn profile_page(
         .await
         .unwrap_or_default();
 
+    let ssh_keys = state.db.list_ssh_keys(user_id).await.unwrap_or_default();
+    let invitations = state.db.list_user_invitations(user_id).await.unwrap_or_default();
+    let shared = state.db.list_shared_repositories(user_id).await.unwrap_or_default();
+    let orgs = state.db.list_user_organizations(user_id).await.unwrap_or_default();
+
     Ok(Html(templates::profile::render(
-        &user, &repos, &starred, &pinned,
+        &user, &repos, &starred, &pinned, &ssh_keys, &invitations, &shared, &orgs,
     )))
 }
 
+#[derive(Deserialize)]
+pub struct EmailAction {
+    pub action: String,
+}
+
+pub async fn email_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<EmailAction>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+    let user = state
+        .db
+        .get_user_by_id(user_id)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, Html(error_page("User not found"))))?;
+
+    match form.action.as_str() {
+        "resend" => {
+            accounts::send_verification(
+                state.mailer.as_ref(),
+                &state.config.site_origin,
+                &state.config.jwt_secret,
+                &user,
+                chrono::Utc::now(),
+            )
+            .await
+            .map_err(|(status, message)| (status, Html(error_page(&message))))?;
+        }
+        _ => {
+            return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action"))));
+        }
+    }
+
+    Ok(Redirect::to("/profile"))
+}
+
+#[derive(Deserialize)]
+pub struct SshKeyAction {
+    pub action: String,
+    pub title: Option<String>,
+    pub key: Option<String>,
+    pub key_id: Option<i64>,
+}
+
+pub async fn ssh_key_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<SshKeyAction>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+
+    match form.action.as_str() {
+        "add" => {
+            let title = form.title.as_deref().unwrap_or("").trim();
+            if title.is_empty() || title.len() > 100 {
+                return Err((StatusCode::BAD_REQUEST, Html(error_page("Please give the key a title"))));
+            }
+
+            let (fingerprint, public_key) =
+                crate::services::ssh_server::parse_public_key(form.key.as_deref().unwrap_or(""))
+                    .map_err(|e| (StatusCode::BAD_REQUEST, Html(error_page(&e))))?;
+
+            state
+                .db
+                .add_ssh_key(user_id, title, &fingerprint, &public_key)
+                .await
+                .map_err(|_| {
+                    (
+                        StatusCode::CONFLICT,
+                        Html(error_page("This key is already in use")),
+                    )
+                })?;
+        }
+        "delete" => {
+            let deleted = state
+                .db
+                .delete_ssh_key(form.key_id.unwrap_or_default(), user_id)
+                .await
+                .unwrap_or(false);
+            if !deleted {
+                return Err((StatusCode::NOT_FOUND, Html(error_page("SSH key not found"))));
+            }
+        }
+        _ => {
+            return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action"))));
+        }
+    }
+
+    Ok(Redirect::to("/profile"))
+}
+
+/// Personal access tokens
+pub async fn tokens_page(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+
+    let keys = state.db.list_api_keys(user_id).await.unwrap_or_default();
+    Ok(Html(templates::tokens::render(&keys, None)))
+}
+
+/// Create or revoke a token. A new token is rendered straight away since
+/// it can never be shown again. The form is read as pairs because every
+/// ticked scope arrives under the same name.
+pub async fn token_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<Vec<(String, String)>>,
+) -> Result<Response, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+    let field = |name: &str| {
+        form.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or("")
+    };
+
+    match field("action") {
+        "create" => {
+            let scopes: Vec<String> = form
+                .iter()
+                .filter(|(key, _)| key == "scopes")
+                .map(|(_, value)| value.clone())
+                .collect();
+            let expires_in_days = match field("expires_in_days") {
+                "" => None,
+                days => Some(days.parse().map_err(|_| {
+                    (StatusCode::BAD_REQUEST, Html(error_page("Invalid expiry")))
+                })?),
+            };
+
+            let (_, token) = crate::auth::tokens::create_token(
+                &state.db,
+                user_id,
+                field("name"),
+                &scopes,
+                expires_in_days,
+            )
+            .await
+            .map_err(|(status, message)| (status, Html(error_page(&message))))?;
+
+            let keys = state.db.list_api_keys(user_id).await.unwrap_or_default();
+            Ok(Html(templates::tokens::render(&keys, Some(&token))).into_response())
+        }
+        "revoke" => {
+            let id = field("token_id").parse().unwrap_or_default();
+            let revoked = state.db.revoke_api_key(id, user_id).await.unwrap_or(false);
+            if !revoked {
+                return Err((StatusCode::NOT_FOUND, Html(error_page("Token not found"))));
+            }
+
+            Ok(Redirect::to("/profile/tokens").into_response())
+        }
+        _ => Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action")))),
+    }
+}
+
+pub async fn sessions_page(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+    let current_key = jar
+        .get("session_id")
+        .map(|cookie| SessionStore::session_key(cookie.value()))
+        .unwrap_or_default();
+
+    let sessions = state.session_store.list_user_sessions(user_id).await;
+    let activity: Vec<_> = state
+        .db
+        .get_user_activity(user_id, 100)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|entry| login_throttle::SIGN_IN_ACTIONS.contains(&entry.action.as_str()))
+        .take(20)
+        .collect();
+
+    Ok(Html(templates::sessions::render(&sessions, &current_key, &activity)))
+}
+
+#[derive(Deserialize)]
+pub struct SessionAction {
+    pub action: String,
+    pub session_key: Option<String>,
+}
+
+/// Revoke one of the user's other sessions, or all of them
+pub async fn session_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<SessionAction>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+    let current_key = jar
+        .get("session_id")
+        .map(|cookie| SessionStore::session_key(cookie.value()));
+
+    match form.action.as_str() {
+        "revoke" => {
+            let key = form.session_key.unwrap_or_default();
+            if !state.session_store.revoke_session(user_id, &key).await {
+                return Err((StatusCode::NOT_FOUND, Html(error_page("Session not found"))));
+            }
+        }
+        "revoke_others" => {
+            state
+                .session_store
+                .revoke_other_sessions(user_id, current_key.as_deref())
+                .await;
+        }
+        _ => return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action")))),
+    }
+
+    Ok(Redirect::to("/profile/sessions"))
+}
+
+async fn render_account_settings(
+    state: &Arc<AppState>,
+    user_id: i64,
+    error: Option<&str>,
+    success: Option<&str>,
+) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let user = state
+        .db
+        .get_user_by_id(user_id)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, Html(error_page("User not found"))))?;
+    let repo_count = state.db.list_user_repositories(user_id).await.unwrap_or_default().len();
+
+    Ok(Html(templates::account_settings::render(&templates::account_settings::AccountSettingsView {
+        user: &user,
+        repo_count,
+        error,
+        success,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct AccountSettingsQuery {
+    pub updated: Option<String>,
+}
+
+pub async fn account_settings_page(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Query(query): Query<AccountSettingsQuery>,
+) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+    let success = match query.updated.as_deref() {
+        Some("password") => Some("Your password was changed and your other sessions were signed out."),
+        Some("email") => Some("Your email address was changed. Open the link we emailed to verify it."),
+        _ => None,
+    };
+
+    render_account_settings(&state, user_id, None, success).await
+}
+
+#[derive(Deserialize)]
+pub struct AccountSettingsAction {
+    pub action: String,
+    pub current_password: Option<String>,
+    pub new_password: Option<String>,
+    pub confirm_password: Option<String>,
+    pub email: Option<String>,
+    pub password: Option<String>,
+    pub transfer_to: Option<String>,
+    pub confirm_username: Option<String>,
+}
+
+/// Change the password or email, or delete the account. Failures re-render
+/// the page with the error next to the forms.
+pub async fn account_settings_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<AccountSettingsAction>,
+) -> Result<Response, (StatusCode, Html<String>)> {
+    let (user_id, username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+    let user = state
+        .db
+        .get_user_by_id(user_id)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, Html(error_page("User not found"))))?;
+    let current_key = jar
+        .get("session_id")
+        .map(|cookie| SessionStore::session_key(cookie.value()));
+    let password = form.password.as_deref().unwrap_or("");
+
+    let result = match form.action.as_str() {
+        "change_password" => {
+            let new_password = form.new_password.as_deref().unwrap_or("");
+            if form.confirm_password.as_deref() != Some(new_password) {
+                Err((StatusCode::BAD_REQUEST, "Passwords don't match".to_string()))
+            } else {
+                accounts::change_password(
+                    &state.db,
+                    &user,
+                    form.current_password.as_deref().unwrap_or(""),
+                    new_password,
+                )
+                .await
+            }
+            .map(|()| "password")
+        }
+        "change_email" => accounts::change_email(
+            &state.db,
+            state.mailer.as_ref(),
+            &state.config.site_origin,
+            &state.config.jwt_secret,
+            &user,
+            password,
+            form.email.as_deref().unwrap_or(""),
+            chrono::Utc::now(),
+        )
+        .await
+        .map(|_| "email"),
+        "delete_account" => {
+            if form.confirm_username.as_deref().map(str::trim) != Some(username.as_str()) {
+                Err((StatusCode::BAD_REQUEST, "Type your username to confirm".to_string()))
+            } else {
+                match accounts::delete_account(
+                    &state.db,
+                    &state.git_storage,
+                    &user,
+                    password,
+                    form.transfer_to.as_deref(),
+                )
+                .await
+                {
+                    Ok(()) => {
+                        state.session_store.revoke_other_sessions(user_id, None).await;
+                        let cookie = Cookie::build(("session_id", ""))
+                            .path("/")
+                            .max_age(time::Duration::seconds(0))
+                            .build();
+                        return Ok((jar.add(cookie), Redirect::to("/")).into_response());
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+        }
+        _ => return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action")))),
+    };
+
+    match result {
+        Ok(updated) => {
+            if updated == "password" {
+                state
+                    .session_store
+                    .revoke_other_sessions(user_id, current_key.as_deref())
+                    .await;
+            }
+            Ok(Redirect::to(&format!("/profile/settings?updated={}", updated)).into_response())
+        }
+        Err((status, message)) => {
+            let page = render_account_settings(&state, user_id, Some(&message), None).await?;
+            Ok((status, page).into_response())
+        }
+    }
+}
+
+/// Render the two-factor page for the user's current state
+async fn render_two_factor(
+    state: &Arc<AppState>,
+    user_id: i64,
+    recovery_codes: Option<&[String]>,
+) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let user = state
+        .db
+        .get_user_by_id(user_id)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, Html(error_page("User not found"))))?;
+    let setup = two_factor::pending_enrollment(&state.db, &user).await;
+    let unused_recovery_codes = state.db.count_unused_recovery_codes(user_id).await.unwrap_or(0);
+
+    Ok(Html(templates::two_factor::render(&templates::two_factor::TwoFactorView {
+        enabled: user.totp_enabled != 0,
+        setup: setup.as_ref(),
+        recovery_codes,
+        unused_recovery_codes,
+        required: two_factor::must_enroll(&state.db, &user).await,
+    })))
+}
+
+pub async fn two_factor_page(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Result<Html<String>, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+
+    render_two_factor(&state, user_id, None).await
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorAction {
+    pub action: String,
+    pub code: Option<String>,
+}
+
+/// Enroll, disable or replace recovery codes. New recovery codes are
+/// rendered straight away since they can never be shown again.
+pub async fn two_factor_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<TwoFactorAction>,
+) -> Result<Response, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+    let user = state
+        .db
+        .get_user_by_id(user_id)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, Html(error_page("User not found"))))?;
+    let code = form.code.as_deref().unwrap_or("");
+    let now = chrono::Utc::now().timestamp();
+    let page_error = |(status, message): two_factor::TwoFactorError| (status, Html(error_page(&message)));
+
+    match form.action.as_str() {
+        "begin" => {
+            two_factor::begin_enrollment(&state.db, &user)
+                .await
+                .map_err(page_error)?;
+        }
+        "confirm" => {
+            let codes = two_factor::confirm_enrollment(&state.db, user_id, code, now)
+                .await
+                .map_err(page_error)?;
+            return Ok(render_two_factor(&state, user_id, Some(&codes)).await?.into_response());
+        }
+        "regenerate" => {
+            let codes = two_factor::regenerate_recovery_codes(&state.db, user_id, code, now)
+                .await
+                .map_err(page_error)?;
+            return Ok(render_two_factor(&state, user_id, Some(&codes)).await?.into_response());
+        }
+        "disable" => {
+            two_factor::disable(&state.db, &user, code, now)
+                .await
+                .map_err(page_error)?;
+        }
+        _ => return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action")))),
+    }
+
+    Ok(Redirect::to("/profile/2fa").into_response())
+}
+
+#[derive(Deserialize)]
+pub struct InvitationAction {
+    pub action: String,
+    pub invitation_id: Option<i64>,
+    pub repo_hash: Option<String>,
+}
+
+/// Accept or decline an invitation, or leave a shared repository
+pub async fn invitation_action(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Form(form): Form<InvitationAction>,
+) -> Result<Redirect, (StatusCode, Html<String>)> {
+    let (user_id, _username) = get_session_user(&state, &jar)
+        .await
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Html(redirect_to_login())))?;
+    let invitation_id = form.invitation_id.unwrap_or_default();
+
+    match form.action.as_str() {
+        "accept" => {
+            let repo_hash = state
+                .db
+                .accept_invitation(invitation_id, user_id)
+                .await
+                .ok()
+                .flatten()
+                .ok_or_else(|| (StatusCode::NOT_FOUND, Html(error_page("Invitation not found"))))?;
+            return Ok(Redirect::to(&format!("/r/{}", repo_hash)));
+        }
+        "decline" => {
+            let declined = state
+                .db
+                .decline_invitation(invitation_id, user_id)
+                .await
+                .unwrap_or(false);
+            if !declined {
+                return Err((StatusCode::NOT_FOUND, Html(error_page("Invitation not found"))));
+            }
+        }
+        "leave" => {
+            let left = state
+                .db
+                .remove_collaborator(form.repo_hash.as_deref().unwrap_or(""), user_id)
+                .await
+                .unwrap_or(false);
+            if !left {
+                return Err((StatusCode::NOT_FOUND, Html(error_page("Repository not found"))));
+            }
+        }
+        _ => {
+            return Err((StatusCode::BAD_REQUEST, Html(error_page("Invalid action"))));
+        }
+    }
+
+    Ok(Redirect::to("/profile"))
+}
+
 // Tags page
 pub async fn tags_page(State(state): State<Arc<AppState>>) -> Result<Html<String>, StatusCode> {
     let tags = state.db.get_all_tags().await.unwrap_or_default();
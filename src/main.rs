@@ -9,9 +9,11 @@ mod routes;
 mod services;
 mod storage;
 mod templates;
+#[cfg(test)]
+mod test_support;
 mod utils;
 
-use crate::auth::session::SessionStore;
+use crate::auth::session::{SessionStore, SqliteSessionBackend};
 use crate::config::Config;
 use crate::db::Database;
 use crate::middleware::cache::CacheService;
@@ -19,6 +21,8 @@ use crate::middleware::rate_limit::RateLimiter;
 use crate::middleware::csrf::CsrfProtection;
 use crate::routes::create_router;
 use crate::services::health::HealthMonitor;
+use crate::services::replication_queue::ReplicationQueue;
+use crate::services::ssh_server::GitSshServer;
 use crate::storage::git::GitStorage;
 use axum::Extension;
 use std::path::PathBuf;
@@ -35,6 +39,7 @@ pub struct AppState {
     pub session_store: Arc<SessionStore>,
     pub rate_limiter: Arc<RateLimiter>,
     pub csrf_protection: Arc<CsrfProtection>,
+    pub mailer: Arc<dyn crate::services::mailer::Mailer>,
 }
 
 async fn create_admin_if_not_exists(db: &Database) -> Result<(), Box<dyn std::error::Error>> {
@@ -59,6 +64,8 @@ async fn create_admin_if_not_exists(db: &Database) -> Result<(), Box<dyn std::er
             
             let password_hash = crate::auth::password::hash_password(&password)
                 .map_err(|e| e.to_string())?;
+            // The 20250210000000 migration recognises the bootstrap admin by
+            // this address and key
             let public_key = hex::encode(blake3::hash(b"admin").as_bytes());
 
             let admin_req = crate::models::CreateUserRequest {
@@ -69,7 +76,10 @@ async fn create_admin_if_not_exists(db: &Database) -> Result<(), Box<dyn std::er
                 storage_quota: 10737418240,
             };
 
-            db.create_user(&admin_req).await?;
+            let admin = db.create_user(&admin_req).await?;
+            // The migration that flags admins ran before this user existed
+            db.set_user_admin(admin.id, true).await?;
+            db.set_must_change_password(admin.id, true).await?;
             
             println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
             println!("✓ Admin user created");
@@ -77,7 +87,7 @@ async fn create_admin_if_not_exists(db: &Database) -> Result<(), Box<dyn std::er
             println!("   Username: admin");
             println!("   Password: {}", password);
             println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-            println!("⚠️  CHANGE THIS PASSWORD IMMEDIATELY AFTER FIRST LOGIN!");
+            println!("⚠️  You will be asked to change this password at first login");
             
             Ok(())
         }
@@ -85,12 +95,13 @@ async fn create_admin_if_not_exists(db: &Database) -> Result<(), Box<dyn std::er
 }
 
 /// Validate critical security configuration
-fn validate_security_config() -> Result<(), Box<dyn std::error::Error>> {
+fn validate_security_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     // Check JWT_SECRET is set
-    let jwt_secret = std::env::var("JWT_SECRET")
-        .map_err(|_| "CRITICAL: JWT_SECRET environment variable must be set")?;
+    if config.jwt_secret.is_empty() {
+        return Err("CRITICAL: JWT_SECRET environment variable must be set".into());
+    }
     
-    if jwt_secret.len() < 32 {
+    if config.jwt_secret.len() < 32 {
         return Err("JWT_SECRET must be at least 32 characters long".into());
     }
     
@@ -130,11 +141,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load configuration
     dotenvy::dotenv().ok();
     
-    // Validate security configuration BEFORE starting
-    validate_security_config()?;
-    
     let config = Config::from_env()?;
 
+    // Validate security configuration BEFORE starting
+    validate_security_config(&config)?;
+
     tracing::info!("📊 Connecting to database: {}", config.database_url);
 
     // Initialize database
@@ -150,10 +161,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("📦 Initializing Git storage...");
     let storage_path = PathBuf::from("storage/repos");
     let git_storage = Arc::new(GitStorage::new(storage_path)?);
-    tracing::info!("✓ Git storage ready");
+    match git_storage.configure_all_repos() {
+        Ok(count) => tracing::info!("✓ Git storage ready ({} repositories)", count),
+        Err(e) => tracing::warn!("Failed to apply upload-pack settings: {}", e),
+    }
 
     // Initialize session store
-    let session_store = Arc::new(SessionStore::new());
+    let session_store = Arc::new(match config.session_backend.as_str() {
+        "sqlite" => SessionStore::with_backend(Arc::new(SqliteSessionBackend::new(db.clone()))),
+        "memory" => SessionStore::new(),
+        other => return Err(format!("Unknown SESSION_BACKEND {}", other).into()),
+    });
+    tracing::info!("✓ Sessions stored in {}", config.session_backend);
+
+    // Initialize mailer
+    let mailer = crate::services::mailer::from_config(&config)?;
+    tracing::info!("✓ Email sent with the {} mailer", config.mailer);
 
     // Initialize rate limiter
     let rate_limiter = Arc::new(RateLimiter::new(100, 60));
@@ -170,6 +193,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         session_store: session_store.clone(),
         rate_limiter: rate_limiter.clone(),
         csrf_protection: csrf_protection.clone(),
+        mailer,
     });
 
     // Start background tasks
@@ -185,6 +209,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             tracing::debug!("Session cleanup completed");
         }
     });
+
+    // Forget stale failed logins
+    let throttle_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            crate::services::login_throttle::cleanup(&throttle_db, chrono::Utc::now()).await;
+            tracing::debug!("Login throttle cleanup completed");
+        }
+    });
     
     // Rate limiter cleanup task
     let rate_limiter_clone = rate_limiter.clone();
@@ -193,6 +228,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         loop {
             interval.tick().await;
             rate_limiter_clone.cleanup().await;
+            crate::middleware::rate_limit::cleanup_auth_limiter().await;
             tracing::debug!("Rate limiter cleanup completed");
         }
     });
@@ -212,16 +248,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("💚 Starting health monitoring service...");
     let health_monitor = HealthMonitor::new(
         db.clone(),
+        state.git_storage.clone(),
         config.min_replica_count,
         10, // Check every 10 minutes
+        config.node_heartbeat_timeout_minutes,
+        config.node_eviction_grace_minutes,
     );
 
     tokio::spawn(async move {
         health_monitor.start().await;
     });
 
+    // Start replication workers (jobs persist across restarts)
+    ReplicationQueue::new(db.clone(), state.git_storage.clone(), config.replication_workers)
+        .start()
+        .await;
+
+    // Start the git SSH server
+    if config.ssh_port != 0 {
+        let addr = std::net::SocketAddr::new(config.server_addr().ip(), config.ssh_port);
+        let host_key = PathBuf::from(&config.ssh_host_key_path);
+        let ssh_server = GitSshServer::new(state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = ssh_server.run(addr, host_key).await {
+                tracing::error!("{}", e);
+            }
+        });
+    }
+
     // Create router with all security middleware
     let app = create_router(state.clone())
+        // AdminUser looks the database up through this extension
+        .layer(Extension(state.clone()))
         .layer(Extension(state.session_store.clone()))
         .layer(Extension(state.csrf_protection.clone()))
         .layer(axum::middleware::from_fn(
@@ -258,7 +316,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Peer addresses are recorded on sessions
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
 
     Ok(())
 }
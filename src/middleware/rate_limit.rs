@@ -1,13 +1,13 @@
 // src/middleware/rate_limit.rs - Enhanced Security Version
 use axum::{
-    extract::Request,
-    http::{StatusCode, HeaderMap},
+    extract::{ConnectInfo, Request},
+    http::{Extensions, StatusCode, HeaderMap},
     middleware::Next,
     response::Response,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 use tokio::sync::RwLock;
 use std::time::{Duration, Instant};
 
@@ -72,26 +72,111 @@ impl RateLimiter {
     }
 }
 
-/// Extract client identifier from request
-fn extract_client_id(headers: &HeaderMap) -> String {
-    // Try to get real IP from headers (for reverse proxy setups)
-    if let Some(forwarded) = headers.get("x-forwarded-for") {
-        if let Ok(forwarded_str) = forwarded.to_str() {
-            // Take the first IP (client's original IP)
-            if let Some(ip) = forwarded_str.split(',').next() {
-                return ip.trim().to_string();
+/// An address or CIDR range of reverse proxies allowed to say who the
+/// client is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrustedProxy {
+    network: IpAddr,
+    prefix_len: u32,
+}
+
+impl TrustedProxy {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (addr, prefix_len) = match value.trim().split_once('/') {
+            Some((addr, len)) => (addr.parse::<IpAddr>().ok()?, len.parse::<u32>().ok()?),
+            None => {
+                let addr = value.trim().parse::<IpAddr>().ok()?;
+                (addr, if addr.is_ipv4() { 32 } else { 128 })
             }
+        };
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_len {
+            return None;
         }
+        Some(Self { network: addr, prefix_len })
     }
-    
-    if let Some(real_ip) = headers.get("x-real-ip") {
-        if let Ok(ip_str) = real_ip.to_str() {
-            return ip_str.to_string();
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        fn masked(bits: u128, width: u32, prefix_len: u32) -> u128 {
+            if prefix_len == 0 {
+                0
+            } else {
+                bits >> (width - prefix_len)
+            }
+        }
+
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                masked(u32::from(network) as u128, 32, self.prefix_len)
+                    == masked(u32::from(ip) as u128, 32, self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                masked(u128::from(network), 128, self.prefix_len) == masked(u128::from(ip), 128, self.prefix_len)
+            }
+            _ => false,
         }
     }
-    
+}
+
+lazy_static::lazy_static! {
+    /// Proxies from TRUSTED_PROXIES (comma-separated addresses or CIDR
+    /// ranges). Forwarding headers from anyone else are ignored, since
+    /// clients can set them to whatever they like.
+    static ref TRUSTED_PROXIES: Vec<TrustedProxy> = std::env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|value| !value.trim().is_empty())
+        .filter_map(|value| {
+            let proxy = TrustedProxy::parse(value);
+            if proxy.is_none() {
+                tracing::warn!("Ignoring invalid TRUSTED_PROXIES entry: {}", value.trim());
+            }
+            proxy
+        })
+        .collect();
+}
+
+/// The client's address: the peer address the server records, or when the
+/// peer is a trusted proxy, the last X-Forwarded-For hop that isn't one
+/// (or X-Real-IP)
+pub fn client_ip(headers: &HeaderMap, extensions: &Extensions) -> Option<String> {
+    let peer = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())?;
+    Some(forwarded_client_ip(headers, peer, &TRUSTED_PROXIES).to_string())
+}
+
+fn forwarded_client_ip(headers: &HeaderMap, peer: IpAddr, trusted: &[TrustedProxy]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted.iter().any(|proxy| proxy.contains(ip));
+    if !is_trusted(peer) {
+        return peer;
+    }
+
+    // Each proxy appends the address it got the request from, so only the
+    // hops after the last untrusted one can be believed
+    let forwarded: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|hop| hop.trim().parse::<IpAddr>())
+        .collect::<Result<_, _>>()
+        .unwrap_or_default();
+    if let Some(client) = forwarded.iter().rev().find(|ip| !is_trusted(**ip)) {
+        return *client;
+    }
+
+    headers
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(peer)
+}
+
+/// Extract client identifier from request
+fn extract_client_id(request: &Request) -> String {
     // Fallback to a generic identifier
-    "unknown".to_string()
+    client_ip(request.headers(), request.extensions()).unwrap_or_else(|| "unknown".to_string())
 }
 
 /// Global rate limiter middleware
@@ -99,7 +184,7 @@ pub async fn global_rate_limit(
     request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let client_id = extract_client_id(request.headers());
+    let client_id = extract_client_id(&request);
     
     // Different rate limits for different endpoints
     let path = request.uri().path();
@@ -122,17 +207,21 @@ pub async fn global_rate_limit(
     }
 }
 
+lazy_static::lazy_static! {
+    /// Shared by all auth requests: 20 per 15 minutes per client. Password
+    /// guessing is throttled per account by `services::login_throttle`;
+    /// this only caps how fast one client can hit the auth endpoints.
+    static ref AUTH_LIMITER: RateLimiter = RateLimiter::new(20, 900);
+}
+
 /// Auth-specific rate limiter with stricter limits
 pub async fn auth_rate_limit(
     request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
-    let client_id = extract_client_id(request.headers());
-    
-    // Very strict limit for auth attempts: 5 per 15 minutes
-    let limiter = RateLimiter::new(5, 900);
-    
-    match limiter.check(&client_id).await {
+    let client_id = extract_client_id(&request);
+
+    match AUTH_LIMITER.check(&client_id).await {
         Ok(_) => Ok(next.run(request).await),
         Err(_) => {
             tracing::warn!("Auth rate limit exceeded for client: {}", client_id);
@@ -141,6 +230,11 @@ pub async fn auth_rate_limit(
     }
 }
 
+/// Forget expired auth limiter windows
+pub async fn cleanup_auth_limiter() {
+    AUTH_LIMITER.cleanup().await;
+}
+
 /// Per-user rate limiter (for authenticated requests)
 pub struct PerUserRateLimiter {
     limiters: Arc<RwLock<HashMap<i64, RateLimiter>>>,
@@ -186,6 +280,39 @@ mod tests {
         assert!(limiter.check("test_client").await.is_err());
     }
 
+    #[test]
+    fn test_forwarded_headers_need_a_trusted_proxy() {
+        let trusted = vec![
+            TrustedProxy::parse("10.0.0.0/8").unwrap(),
+            TrustedProxy::parse("::1").unwrap(),
+        ];
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "198.51.100.7, 203.0.113.9, 10.1.2.3".parse().unwrap());
+
+        // Anyone else can write whatever they like in the header
+        let stranger: IpAddr = "192.0.2.1".parse().unwrap();
+        assert_eq!(forwarded_client_ip(&headers, stranger, &trusted), stranger);
+
+        // Behind the proxy, the client is the hop before the trusted ones;
+        // earlier hops were supplied by the client
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        assert_eq!(
+            forwarded_client_ip(&headers, proxy, &trusted),
+            "203.0.113.9".parse::<IpAddr>().unwrap()
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-real-ip", "203.0.113.9".parse().unwrap());
+        assert_eq!(
+            forwarded_client_ip(&headers, "::1".parse().unwrap(), &trusted),
+            "203.0.113.9".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(forwarded_client_ip(&HeaderMap::new(), proxy, &trusted), proxy);
+
+        assert!(TrustedProxy::parse("10.0.0.0/33").is_none());
+        assert!(TrustedProxy::parse("proxy.example.com").is_none());
+    }
+
     #[tokio::test]
     async fn test_window_reset() {
         let limiter = RateLimiter::new(2, 1); // 2 requests per 1 second
@@ -21,6 +21,21 @@ pub struct User {
     pub storage_quota: i64,
     pub storage_used: i64,
     pub created_at: String,
+    pub totp_enabled: i64,
+    pub email_verified: i64,
+    pub must_change_password: i64,
+    /// Login tokens issued before the last password change carry an older
+    /// generation and are refused
+    pub token_generation: i64,
+}
+
+/// A user's authenticator secret. The secret is set while enrolling and
+/// only checked once `totp_enabled` is set.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TotpState {
+    pub totp_secret: Option<String>,
+    pub totp_enabled: i64,
+    pub totp_last_step: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -34,6 +49,10 @@ pub struct Repository {
     pub is_private: i64,
     pub created_at: String,
     pub last_updated: String,
+    /// Keep at least one replica on an anchor node
+    pub require_anchor: i64,
+    /// Owning organization; `owner_id` is then the user who created it
+    pub org_id: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -46,6 +65,313 @@ pub struct Node {
     pub storage_capacity: i64,
     pub storage_used: i64,
     pub is_anchor: i64,
+    pub is_banned: i64,
+    /// online, suspect, offline or evicted
+    pub state: String,
+    pub state_changed_at: Option<String>,
+    /// Bearer token the node expects on coordinator requests
+    #[serde(skip)]
+    pub access_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct NodeStateTransition {
+    pub id: i64,
+    pub node_id: String,
+    pub from_state: String,
+    pub to_state: String,
+    pub silent_minutes: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PushRules {
+    pub repo_hash: String,
+    /// Largest blob a push may add, in bytes
+    pub max_blob_size: Option<i64>,
+    /// Glob patterns, one per line
+    pub forbidden_paths: String,
+    pub require_verified_email: i64,
+    pub updated_at: String,
+}
+
+impl PushRules {
+    pub fn none(repo_hash: &str) -> Self {
+        Self {
+            repo_hash: repo_hash.to_string(),
+            max_blob_size: None,
+            forbidden_paths: String::new(),
+            require_verified_email: 0,
+            updated_at: String::new(),
+        }
+    }
+
+    pub fn forbidden_path_patterns(&self) -> impl Iterator<Item = &str> {
+        self.forbidden_paths.lines().map(str::trim).filter(|p| !p.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ProtectedBranch {
+    pub id: i64,
+    pub repo_hash: String,
+    /// Branch name without `refs/heads/`, may contain * and ?
+    pub pattern: String,
+    pub allow_deletion: i64,
+    pub allow_force_push: i64,
+    /// Refuse merge commits
+    pub require_linear_history: i64,
+    pub created_at: String,
+    /// Only `allowed_pushers` may push; otherwise anyone with push access
+    pub restrict_pushes: i64,
+    /// Usernames allowed to push when pushes are restricted
+    #[sqlx(skip)]
+    pub allowed_pushers: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BranchProtectionRequest {
+    pub pattern: String,
+    #[serde(default)]
+    pub allow_deletion: bool,
+    #[serde(default)]
+    pub allow_force_push: bool,
+    #[serde(default)]
+    pub require_linear_history: bool,
+    /// Only `allowed_pushers` may push, so with none nobody can. Listing
+    /// any pushers implies it.
+    #[serde(default)]
+    pub restrict_pushes: bool,
+    #[serde(default)]
+    pub allowed_pushers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Organization {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub storage_quota: i64,
+    pub storage_used: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OrgMember {
+    pub org_id: i64,
+    pub user_id: i64,
+    pub username: String,
+    /// owner or member
+    pub role: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Team {
+    pub id: i64,
+    pub org_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: String,
+    /// Usernames
+    #[sqlx(skip)]
+    pub members: Vec<String>,
+    #[sqlx(skip)]
+    pub repos: Vec<TeamRepo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TeamRepo {
+    pub team_id: i64,
+    pub repo_hash: String,
+    pub repo_name: String,
+    /// read, write or admin
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrgRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrgMemberRequest {
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTeamRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TeamRepoRequest {
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Collaborator {
+    pub repo_hash: String,
+    pub user_id: i64,
+    pub username: String,
+    /// read, write or admin
+    pub role: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RepoInvitation {
+    pub id: i64,
+    pub repo_hash: String,
+    pub repo_name: String,
+    pub owner_username: String,
+    pub invitee_id: i64,
+    pub invitee_username: String,
+    pub role: String,
+    pub inviter_username: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InviteCollaboratorRequest {
+    pub username: String,
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCollaboratorRequest {
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SshKey {
+    pub id: i64,
+    pub user_id: i64,
+    pub title: String,
+    /// SHA256 fingerprint as printed by `ssh-keygen -l`
+    pub fingerprint: String,
+    pub public_key: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddSshKeyRequest {
+    pub title: String,
+    pub key: String,
+}
+
+/// A personal access token; the token itself is only shown once
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    /// Space-separated, e.g. `repo:read repo:write`
+    pub scopes: String,
+    /// First characters of the token, enough to recognise it
+    pub token_prefix: String,
+    pub created_at: String,
+    pub last_used: Option<String>,
+    pub expires_at: Option<String>,
+    pub is_active: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Days until the token expires; None for never
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateApiKeyResponse {
+    /// The only time the token is returned
+    pub token: String,
+    #[serde(flatten)]
+    pub key: ApiKey,
+}
+
+/// Failed logins for one account or client address. Attempts count as
+/// failures until they succeed.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct LoginThrottle {
+    pub scope: String,
+    pub subject: String,
+    pub failures: i64,
+    pub last_failure_at: chrono::DateTime<chrono::Utc>,
+    pub locked_until: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the attempt before the latest was made, while it is recent
+    pub previous_failure_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_remaining: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorSetupResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    /// An authenticator code, or a recovery code where one is accepted
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    /// The only time the codes are returned
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeEmailRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+    /// A user to take over personal repositories, which are deleted otherwise
+    #[serde(default)]
+    pub transfer_to: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecuritySettings {
+    pub require_admin_2fa: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReplicationJob {
+    pub id: i64,
+    pub repo_hash: String,
+    /// pending, running, succeeded, failed or cancelled
+    pub state: String,
+    pub reason: String,
+    pub priority: i64,
+    pub attempts: i64,
+    pub max_attempts: i64,
+    pub next_attempt_at: String,
+    pub last_error: Option<String>,
+    pub target_node_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -54,6 +380,24 @@ pub struct Replica {
     pub node_id: String,
     pub created_at: String,
     pub last_verified: Option<String>,
+    pub failed_challenges: i64,
+    pub ref_state: Option<String>,
+    pub synced_at: Option<String>,
+}
+
+/// A replica as shown on the repository page
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ReplicaSync {
+    pub node_id: String,
+    pub address: String,
+    pub port: i32,
+    pub is_anchor: i64,
+    pub node_state: String,
+    pub ref_state: Option<String>,
+    pub synced_at: Option<String>,
+    /// Refs that differ from the primary; None if never recorded
+    #[sqlx(skip)]
+    pub refs_behind: Option<usize>,
 }
 
 // Request/Response types
@@ -80,21 +424,7 @@ pub struct CreateRepoResponse {
     pub message: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct RegisterNodeRequest {
-    pub node_id: String,
-    pub address: String,
-    pub port: i32,
-    pub storage_capacity: i64,
-    pub is_anchor: bool,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct NodeHeartbeat {
-    pub node_id: String,
-    pub storage_used: i64,
-    pub hosted_repos: Vec<String>,
-}
+pub use crate::services::node_protocol::{NodeHeartbeat, PackRequest, RegisterNodeRequest};
 
 #[derive(Debug, Serialize)]
 pub struct RepoMetadata {
@@ -125,6 +455,8 @@ pub struct ReplicationRequest {
 pub struct ReplicationResponse {
     pub success: bool,
     pub message: String,
+    /// The queued (or already active) job for the repository
+    pub job: ReplicationJob,
 }
 
 #[derive(Debug, Serialize)]
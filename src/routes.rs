@@ -4,26 +4,30 @@
 use axum::{
     extract::{DefaultBodyLimit, Path, State},
     http::StatusCode,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use serde::Serialize;
 use std::sync::Arc;
 use tower_http::{
-    compression::CompressionLayer,
+    compression::{
+        predicate::{DefaultPredicate, NotForContentType, Predicate},
+        CompressionLayer,
+    },
     cors::{Any, CorsLayer},
     services::ServeDir,
 };
 
 use crate::handlers::{
-    admin_web, api, api_complete, api_enhanced, git, git_http_complete, repo_browser, web,
-    web_enhanced,
+    admin_web, api, api_complete, api_enhanced, git, git_http_complete, nodes_enhanced, orgs,
+    repo_browser, web, web_enhanced,
 };
 use crate::models::{NetworkStats, Repository};
 use crate::AppState;
 
 pub fn create_router(state: Arc<AppState>) -> Router {
-    Router::new()
+    // Git clients only send credentials after a Basic challenge
+    let git_routes = Router::new()
         // ==================
         // GIT SMART HTTP PROTOCOL (RFC)
         // ==================
@@ -44,6 +48,68 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         )
         // Dumb HTTP fallback
         .route("/git/:hash/download", get(git_http_complete::dumb_clone))
+        // The same endpoints under /:owner/:repo.git
+        .route(
+            "/:owner/:repo/info/refs",
+            get(git_http_complete::named_info_refs),
+        )
+        .route(
+            "/:owner/:repo/git-upload-pack",
+            post(git_http_complete::named_upload_pack),
+        )
+        .route(
+            "/:owner/:repo/git-receive-pack",
+            post(git_http_complete::named_receive_pack),
+        )
+        .route_layer(axum::middleware::map_response(
+            git_http_complete::challenge_unauthorized,
+        ));
+
+    // Credential submissions share a per-client rate limit
+    let auth_routes = Router::new()
+        .route(
+            "/login",
+            post(crate::auth::auth_session::login_form_handler),
+        )
+        .route(
+            "/login/2fa",
+            post(crate::auth::auth_session::two_factor_handler),
+        )
+        .route(
+            "/signup",
+            post(crate::auth::auth_session::signup_form_handler),
+        )
+        .route("/api/auth/login", post(api_enhanced::login))
+        .route("/api/auth/login/2fa", post(api_enhanced::login_two_factor))
+        .route("/api/auth/signup", post(api_enhanced::signup))
+        .route(
+            "/forgot-password",
+            post(crate::auth::auth_session::forgot_password_handler),
+        )
+        .route(
+            "/reset-password",
+            post(crate::auth::auth_session::reset_password_handler),
+        )
+        .route(
+            "/change-password",
+            post(crate::auth::auth_session::change_password_handler),
+        )
+        .route("/api/auth/password/forgot", post(api_enhanced::forgot_password))
+        .route("/api/auth/password/reset", post(api_enhanced::reset_password))
+        .route(
+            "/profile/settings",
+            post(web_enhanced::account_settings_action),
+        )
+        .route("/api/user/password", put(api_complete::change_password))
+        .route("/api/user/email", put(api_complete::change_email))
+        .route("/api/user", delete(api_complete::delete_account))
+        .route_layer(axum::middleware::from_fn(
+            crate::middleware::rate_limit::auth_rate_limit,
+        ));
+
+    Router::new()
+        .merge(git_routes)
+        .merge(auth_routes)
         // ==================
         // WEB UI ROUTES
         // ==================
@@ -53,6 +119,22 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/about", get(web::about))
         .route("/dashboard", get(web_enhanced::dashboard_enhanced))
         .route("/profile", get(web_enhanced::profile_page))
+        .route("/profile/keys", post(web_enhanced::ssh_key_action))
+        .route(
+            "/profile/tokens",
+            get(web_enhanced::tokens_page).post(web_enhanced::token_action),
+        )
+        .route(
+            "/profile/2fa",
+            get(web_enhanced::two_factor_page).post(web_enhanced::two_factor_action),
+        )
+        .route(
+            "/profile/sessions",
+            get(web_enhanced::sessions_page).post(web_enhanced::session_action),
+        )
+        .route("/profile/settings", get(web_enhanced::account_settings_page))
+        .route("/profile/email", post(web_enhanced::email_action))
+        .route("/profile/invitations", post(web_enhanced::invitation_action))
         .route("/search", get(web_enhanced::search_page))
         .route("/tags", get(web_enhanced::tags_page))
         .route("/starred", get(web_enhanced::starred_page))
@@ -61,8 +143,16 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/repos/new", post(web_enhanced::create_repo_submit))
         .route("/repos/action", post(web_enhanced::repo_action))
         .route("/repos/fork", post(web_enhanced::fork_repo_form))
+        // Organizations
+        .route("/orgs/new", get(orgs::new_org_page).post(orgs::create_org))
+        .route("/orgs/:org", get(orgs::org_dashboard))
+        .route(
+            "/orgs/:org/settings",
+            get(orgs::org_settings).post(orgs::org_settings_action),
+        )
         // Repository views
-        .route("/r/:hash", get(web_enhanced::repo_enhanced))
+        .route("/r/:hash", get(web_enhanced::repo_hash_redirect))
+        .route("/:owner/:repo", get(web_enhanced::repo_by_name))
         .route("/r/:hash/files", get(repo_browser::browse_files))
         .route("/r/:hash/files/*path", get(repo_browser::browse_directory))
         .route("/r/:hash/file/*path", get(repo_browser::view_file))
@@ -76,46 +166,137 @@ pub fn create_router(state: Arc<AppState>) -> Router {
             "/r/:hash/clone",
             get(crate::handlers::clone_page::show_clone_page),
         )
-        // Auth
-        .route("/login", get(web::login_page))
-        .route("/signup", get(web::signup_page))
         .route(
-            "/login",
-            post(crate::auth::auth_session::login_form_handler),
+            "/r/:hash/settings",
+            get(crate::handlers::repo_settings::show_settings),
         )
         .route(
-            "/signup",
-            post(crate::auth::auth_session::signup_form_handler),
+            "/r/:hash/settings/rename",
+            post(crate::handlers::repo_settings::rename_repo),
+        )
+        .route(
+            "/r/:hash/settings/push-rules",
+            post(crate::handlers::repo_settings::update_push_rules),
+        )
+        .route(
+            "/r/:hash/settings/branches",
+            get(crate::handlers::repo_settings::show_branch_settings)
+                .post(crate::handlers::repo_settings::branch_protection_action),
         )
+        .route(
+            "/r/:hash/settings/collaborators",
+            get(crate::handlers::repo_settings::show_collaborators)
+                .post(crate::handlers::repo_settings::collaborator_action),
+        )
+        // Auth
+        .route("/login", get(web::login_page))
+        .route("/signup", get(web::signup_page))
+        .route("/login/2fa", get(crate::auth::auth_session::two_factor_page))
+        .route("/forgot-password", get(crate::auth::auth_session::forgot_password_page))
+        .route("/reset-password", get(crate::auth::auth_session::reset_password_page))
+        .route("/change-password", get(crate::auth::auth_session::change_password_page))
+        .route("/verify-email", get(crate::auth::auth_session::verify_email_handler))
         .route("/logout", post(crate::auth::auth_session::logout_handler))
         // Admin
         // ==================
         // API ROUTES
         // ==================
         // Auth API
-        .route("/api/auth/login", post(api_enhanced::login))
-        .route("/api/auth/signup", post(api_enhanced::signup))
         .route("/api/auth/profile", get(api_enhanced::get_profile))
+        .route("/api/auth/verify-email", post(api_enhanced::verify_email))
         // Repository API
         .route("/api/repos", get(list_public_repos))
         .route("/api/repos/user", get(api_complete::list_user_repos))
+        .route("/api/user/keys", get(api_complete::list_ssh_keys))
+        .route("/api/user/keys", post(api_complete::add_ssh_key))
+        .route("/api/user/keys/:id", delete(api_complete::delete_ssh_key))
+        .route(
+            "/api/user/tokens",
+            get(api_complete::list_tokens).post(api_complete::create_token),
+        )
+        .route("/api/user/tokens/:id", delete(api_complete::revoke_token))
+        .route(
+            "/api/user/email/verification",
+            post(api_complete::resend_verification),
+        )
+        .route("/api/user/2fa", get(api_complete::two_factor_status))
+        .route("/api/user/2fa/setup", post(api_complete::two_factor_setup))
+        .route("/api/user/2fa/enable", post(api_complete::two_factor_enable))
+        .route("/api/user/2fa/disable", post(api_complete::two_factor_disable))
+        .route(
+            "/api/user/2fa/recovery-codes",
+            post(api_complete::regenerate_recovery_codes),
+        )
+        .route("/api/user/invitations", get(api_complete::list_invitations))
+        .route(
+            "/api/user/invitations/:id",
+            post(api_complete::accept_invitation).delete(api_complete::decline_invitation),
+        )
+        .route("/api/user/orgs", get(api_complete::list_user_orgs))
+        // Organizations
+        .route("/api/orgs", post(api_complete::create_org))
+        .route("/api/orgs/:org", get(api_complete::get_org))
+        .route("/api/orgs/:org/repos", post(api_complete::create_org_repo))
+        .route(
+            "/api/orgs/:org/members/:username",
+            put(api_complete::set_org_member).delete(api_complete::remove_org_member),
+        )
+        .route("/api/orgs/:org/teams", post(api_complete::create_team))
+        .route("/api/orgs/:org/teams/:team", delete(api_complete::delete_team))
+        .route(
+            "/api/orgs/:org/teams/:team/members/:username",
+            put(api_complete::add_team_member).delete(api_complete::remove_team_member),
+        )
+        .route(
+            "/api/orgs/:org/teams/:team/repos/:repo",
+            put(api_complete::grant_team_repo).delete(api_complete::revoke_team_repo),
+        )
         .route("/api/repos/search", get(api_enhanced::search_repos))
         .route("/api/repos/trending", get(get_trending_repos))
         .route("/api/repos/popular", get(get_popular_repos))
         .route("/api/repos", post(api_enhanced::create_repo_authenticated))
-        .route("/api/repos/:hash", get(api_complete::get_repo_detailed))
+        .route(
+            "/api/repos/:hash",
+            get(api_complete::get_repo_detailed).patch(api_complete::rename_repo),
+        )
+        // The router needs one name per segment, so the owner shares `:hash`
+        .route("/api/repos/:hash/:repo", get(api_complete::get_repo_by_name))
         .route(
             "/api/repos/:hash",
             delete(api_complete::delete_repo_complete),
         )
         .route("/api/repos/:hash/fork", post(api_complete::fork_repo))
         .route("/api/repos/:hash/stats", get(api_enhanced::get_repo_stats))
+        .route(
+            "/api/repos/:hash/protected-branches",
+            get(api_complete::list_protected_branches).post(api_complete::protect_branch),
+        )
+        .route(
+            "/api/repos/:hash/protected-branches/:id",
+            put(api_complete::update_protected_branch).delete(api_complete::unprotect_branch),
+        )
+        .route(
+            "/api/repos/:hash/collaborators",
+            get(api_complete::list_collaborators).post(api_complete::invite_collaborator),
+        )
+        .route(
+            "/api/repos/:hash/collaborators/:username",
+            put(api_complete::update_collaborator).delete(api_complete::remove_collaborator),
+        )
+        .route(
+            "/api/repos/:hash/invitations/:id",
+            delete(api_complete::cancel_invitation),
+        )
         .route("/api/repos/:hash/nodes", get(api::get_repo_nodes))
         .route(
             "/api/repos/:hash/readme",
             get(api_complete::get_repo_readme),
         )
         .route("/api/repos/:hash/replicate", post(api::request_replication))
+        .route(
+            "/api/repos/:hash/placement",
+            put(api_complete::update_repo_placement),
+        )
         // User interactions
         .route("/api/repos/:hash/star", post(api_complete::star_repo))
         .route("/api/repos/:hash/star", delete(api_complete::unstar_repo))
@@ -144,17 +325,47 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/api/nodes", post(api::register_node))
         .route("/api/nodes/:id", get(api::get_node))
         .route("/api/nodes/heartbeat", post(api::node_heartbeat))
+        .route("/api/nodes/replicas/:hash/pack", post(api::node_fetch_pack))
         // Admin API
         .route("/admin", get(admin_web::admin_dashboard))
         .route("/admin/nodes", get(admin_web::admin_nodes_page))
+        .route("/admin/nodes/action", post(nodes_enhanced::node_action))
+        .route("/admin/replication/action", post(admin_web::replication_job_action))
         .route("/admin/repos", get(admin_web::admin_repos_page))
-        .route("/admin/users", get(admin_web::admin_users_page))
+        .route(
+            "/admin/users",
+            get(admin_web::admin_users_page).post(admin_web::admin_user_action),
+        )
         // Admin API routes
         .route("/api/admin/nodes", get(api_complete::admin_list_nodes))
+        .route("/api/admin/nodes/:id", delete(api_complete::admin_remove_node))
+        .route("/api/admin/nodes/:id/ban", post(api_complete::admin_ban_node))
+        .route("/api/admin/nodes/:id/trust", post(api_complete::admin_trust_node))
         .route("/api/admin/health", get(api_complete::admin_system_health))
+        .route(
+            "/api/admin/users/:username/lockout",
+            delete(api_complete::admin_unlock_user),
+        )
+        .route(
+            "/api/admin/security",
+            get(api_complete::admin_security_settings).put(api_complete::admin_update_security_settings),
+        )
         .route(
             "/api/admin/replicate",
-            post(api_complete::admin_trigger_replication),
+            get(api_complete::admin_list_replication_jobs)
+                .post(api_complete::admin_trigger_replication),
+        )
+        .route(
+            "/api/admin/replicate/:id/retry",
+            post(api_complete::admin_retry_replication_job),
+        )
+        .route(
+            "/api/admin/replicate/:id/cancel",
+            post(api_complete::admin_cancel_replication_job),
+        )
+        .route(
+            "/api/admin/placement/:hash",
+            get(api_complete::admin_explain_placement),
         )
         .route("/api/admin/health-check", post(admin_trigger_health_check))
         // Stats
@@ -167,7 +378,10 @@ pub fn create_router(state: Arc<AppState>) -> Router {
             crate::middleware::security::security_headers,
         ))
         .layer(DefaultBodyLimit::disable())
-        .layer(CompressionLayer::new())
+        // Packs are already zlib-compressed; gzipping them again only costs CPU
+        .layer(CompressionLayer::new().compress_when(
+            DefaultPredicate::new().and(NotForContentType::const_new("application/x-git-")),
+        ))
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -183,11 +397,18 @@ pub async fn admin_trigger_health_check(
     _admin: crate::auth::AdminUser, // Add admin check
 ) -> Result<StatusCode, StatusCode> {
     let db = state.db.clone();
+    let git_storage = state.git_storage.clone();
     let config = state.config.clone();
 
     tokio::spawn(async move {
-        let health_monitor =
-            crate::services::health::HealthMonitor::new(db, config.min_replica_count, 1);
+        let health_monitor = crate::services::health::HealthMonitor::new(
+            db,
+            git_storage,
+            config.min_replica_count,
+            1,
+            config.node_heartbeat_timeout_minutes,
+            config.node_eviction_grace_minutes,
+        );
         let _ = health_monitor.check_network_health().await;
     });
 
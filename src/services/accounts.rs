@@ -0,0 +1,725 @@
+// src/services/accounts.rs
+//! Email verification, password resets, password and email changes and
+//! account deletion, shared by the web pages and the API. The links in
+//! emails carry signed tokens from `auth::account_tokens`, so nothing about
+//! them is stored.
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::auth::account_tokens::{self, Purpose};
+use crate::auth::repo_access::RepoRole;
+use crate::db::Database;
+use crate::models::User;
+use crate::services::mailer::{Email, Mailer};
+use crate::storage::git::GitStorage;
+
+pub type AccountError = (StatusCode, String);
+
+/// Accounts created without an email get an address in this domain, which
+/// is never mailed
+const PLACEHOLDER_EMAIL_DOMAIN: &str = "hyrule.local";
+
+fn internal(message: &str) -> AccountError {
+    (StatusCode::INTERNAL_SERVER_ERROR, message.to_string())
+}
+
+fn invalid_link() -> AccountError {
+    (StatusCode::BAD_REQUEST, "This link is invalid or has expired".to_string())
+}
+
+/// The stand-in address for an account that signed up without one
+pub fn placeholder_email(username: &str) -> String {
+    format!("{}@{}", username, PLACEHOLDER_EMAIL_DOMAIN)
+}
+
+/// Whether the account has an address we can send mail to
+pub fn has_real_email(user: &User) -> bool {
+    !user
+        .email
+        .to_lowercase()
+        .ends_with(&format!("@{}", PLACEHOLDER_EMAIL_DOMAIN))
+}
+
+pub fn needs_verification(user: &User) -> bool {
+    user.email_verified == 0 && has_real_email(user)
+}
+
+/// The address to store for a new account: the one given, or a
+/// placeholder when the user didn't give one
+pub async fn signup_email(db: &Database, username: &str, email: Option<&str>) -> Result<String, AccountError> {
+    let email = match email.map(str::trim).filter(|email| !email.is_empty()) {
+        Some(email) => email,
+        None => return Ok(placeholder_email(username)),
+    };
+    crate::utils::validation::validate_email(email).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    if email.to_lowercase().ends_with(&format!("@{}", PLACEHOLDER_EMAIL_DOMAIN)) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid email address".to_string()));
+    }
+    if db.get_user_by_email(email).await.map_err(|_| internal("Failed to check email"))?.is_some() {
+        return Err((StatusCode::CONFLICT, "Email address already in use".to_string()));
+    }
+
+    Ok(email.to_string())
+}
+
+/// Send a verification email without making the request wait on the mail
+/// server. Failures are logged; the user can ask for another.
+pub fn spawn_verification(mailer: Arc<dyn Mailer>, site_origin: String, secret: String, user: User) {
+    if !needs_verification(&user) {
+        return;
+    }
+    tokio::spawn(async move {
+        let _ = send_verification(mailer.as_ref(), &site_origin, &secret, &user, Utc::now()).await;
+    });
+}
+
+/// Email the user a link that verifies their address
+pub async fn send_verification(
+    mailer: &dyn Mailer,
+    site_origin: &str,
+    secret: &str,
+    user: &User,
+    now: DateTime<Utc>,
+) -> Result<(), AccountError> {
+    if !has_real_email(user) {
+        return Err((StatusCode::BAD_REQUEST, "Add an email address first".to_string()));
+    }
+    if user.email_verified != 0 {
+        return Err((StatusCode::BAD_REQUEST, "Your email is already verified".to_string()));
+    }
+
+    let token = account_tokens::issue(secret, Purpose::VerifyEmail, user.id, &user.email.to_lowercase(), now)
+        .map_err(|_| internal("Failed to create verification link"))?;
+    let email = Email {
+        to: user.email.clone(),
+        subject: "Verify your Hyrule email address".to_string(),
+        body: format!(
+            "Hi {},\n\n\
+             Confirm that {} is your email address by opening this link:\n\n\
+             {}/verify-email?token={}\n\n\
+             The link expires in {} hours. If you didn't sign up for Hyrule, ignore this email.\n",
+            user.username,
+            user.email,
+            site_origin,
+            token,
+            Purpose::VerifyEmail.lifetime().num_hours()
+        ),
+    };
+
+    mailer.send(&email).await.map_err(|e| {
+        tracing::error!("Failed to send verification email to user {}: {}", user.id, e);
+        (StatusCode::SERVICE_UNAVAILABLE, "Could not send email, try again later".to_string())
+    })
+}
+
+/// Mark the address in a verification link as verified
+pub async fn verify_email(db: &Database, secret: &str, token: &str, now: DateTime<Utc>) -> Result<User, AccountError> {
+    let verified = account_tokens::verify(secret, token, Purpose::VerifyEmail, now).ok_or_else(invalid_link)?;
+    let user = db.get_user_by_id(verified.user_id).await.map_err(|_| invalid_link())?;
+    // The address changed since the link was sent
+    if !verified.is_bound_to(&user.email.to_lowercase()) {
+        return Err(invalid_link());
+    }
+
+    if user.email_verified == 0 {
+        db.mark_email_verified(user.id, &user.email)
+            .await
+            .map_err(|_| internal("Failed to verify email"))?;
+        let _ = db
+            .log_activity(user.id, "email_verified", "user", &user.username, Some(&user.email), None)
+            .await;
+    }
+
+    db.get_user_by_id(user.id)
+        .await
+        .map_err(|_| internal("Failed to verify email"))
+}
+
+/// Email a reset link to the account with this address, if there is one.
+/// Callers say the same thing either way, so addresses can't be probed.
+pub async fn request_password_reset(
+    db: &Database,
+    mailer: &dyn Mailer,
+    site_origin: &str,
+    secret: &str,
+    email: &str,
+    now: DateTime<Utc>,
+) {
+    let user = match db.get_user_by_email(email.trim()).await {
+        Ok(Some(user)) if has_real_email(&user) => user,
+        Ok(_) => return,
+        Err(e) => {
+            tracing::error!("Failed to look up account for password reset: {}", e);
+            return;
+        }
+    };
+
+    // Bound to the current hash, so the link works once
+    let token = match account_tokens::issue(secret, Purpose::ResetPassword, user.id, &user.password_hash, now) {
+        Ok(token) => token,
+        Err(e) => {
+            tracing::error!("Failed to create password reset link: {}", e);
+            return;
+        }
+    };
+    let message = Email {
+        to: user.email.clone(),
+        subject: "Reset your Hyrule password".to_string(),
+        body: format!(
+            "Hi {},\n\n\
+             Someone asked to reset the password for your Hyrule account. Choose a new one here:\n\n\
+             {}/reset-password?token={}\n\n\
+             The link expires in {} minutes and works once. If you didn't ask for this, ignore this email; your password is unchanged.\n",
+            user.username,
+            site_origin,
+            token,
+            Purpose::ResetPassword.lifetime().num_minutes()
+        ),
+    };
+
+    match mailer.send(&message).await {
+        Ok(()) => {
+            let _ = db
+                .log_activity(user.id, "password_reset_requested", "user", &user.username, None, None)
+                .await;
+        }
+        Err(e) => tracing::error!("Failed to send password reset email to user {}: {}", user.id, e),
+    }
+}
+
+/// Whether a reset link can still be used, so the form is only shown for
+/// links that work
+pub async fn check_reset_token(db: &Database, secret: &str, token: &str, now: DateTime<Utc>) -> Result<User, AccountError> {
+    let verified = account_tokens::verify(secret, token, Purpose::ResetPassword, now).ok_or_else(invalid_link)?;
+    let user = db.get_user_by_id(verified.user_id).await.map_err(|_| invalid_link())?;
+    if !verified.is_bound_to(&user.password_hash) {
+        return Err(invalid_link());
+    }
+
+    Ok(user)
+}
+
+async fn set_password(db: &Database, user: &User, password: &str) -> Result<(), AccountError> {
+    crate::utils::validation::validate_password(password)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let password_hash = crate::auth::password::hash_password(password)
+        .map_err(|_| internal("Password hashing failed"))?;
+
+    db.update_password(user.id, &password_hash)
+        .await
+        .map_err(|_| internal("Failed to update password"))
+}
+
+/// Set a new password from a reset link, which also invalidates login
+/// tokens and revokes access tokens. The caller should end the user's
+/// sessions, since whoever held them may be why the password was reset.
+pub async fn reset_password(
+    db: &Database,
+    secret: &str,
+    token: &str,
+    new_password: &str,
+    now: DateTime<Utc>,
+) -> Result<User, AccountError> {
+    let user = check_reset_token(db, secret, token, now).await?;
+    set_password(db, &user, new_password).await?;
+    let revoked = db
+        .revoke_user_api_keys(user.id)
+        .await
+        .map_err(|_| internal("Failed to revoke access tokens"))?;
+
+    // Following the link proved the address is theirs
+    let _ = db.mark_email_verified(user.id, &user.email).await;
+    let details = match revoked {
+        0 => "Password reset by email".to_string(),
+        n => format!("Password reset by email; {} access token{} revoked", n, if n == 1 { "" } else { "s" }),
+    };
+    let _ = db
+        .log_activity(user.id, "password_reset", "user", &user.username, Some(&details), None)
+        .await;
+
+    Ok(user)
+}
+
+/// Change a signed in user's password after checking their current one
+pub async fn change_password(
+    db: &Database,
+    user: &User,
+    current_password: &str,
+    new_password: &str,
+) -> Result<(), AccountError> {
+    if !crate::auth::password::verify_password(current_password, &user.password_hash).unwrap_or(false) {
+        return Err((StatusCode::UNAUTHORIZED, "Current password is incorrect".to_string()));
+    }
+    if current_password == new_password {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Choose a password different from your current one".to_string(),
+        ));
+    }
+
+    set_password(db, user, new_password).await?;
+    let _ = db
+        .log_activity(user.id, "password_changed", "user", &user.username, None, None)
+        .await;
+
+    Ok(())
+}
+
+fn check_current_password(user: &User, password: &str) -> Result<(), AccountError> {
+    if crate::auth::password::verify_password(password, &user.password_hash).unwrap_or(false) {
+        Ok(())
+    } else {
+        Err((StatusCode::UNAUTHORIZED, "Password is incorrect".to_string()))
+    }
+}
+
+/// Move a signed in user to a new address, which must be verified again.
+/// The old address, if it was real, is told about the change.
+#[allow(clippy::too_many_arguments)]
+pub async fn change_email(
+    db: &Database,
+    mailer: &dyn Mailer,
+    site_origin: &str,
+    secret: &str,
+    user: &User,
+    password: &str,
+    new_email: &str,
+    now: DateTime<Utc>,
+) -> Result<User, AccountError> {
+    check_current_password(user, password)?;
+    let new_email = new_email.trim();
+    if new_email.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Enter an email address".to_string()));
+    }
+    if new_email.eq_ignore_ascii_case(&user.email) {
+        return Err((StatusCode::BAD_REQUEST, "That is already your email address".to_string()));
+    }
+    // Same rules as at signup, including that no one else uses it
+    let new_email = signup_email(db, &user.username, Some(new_email)).await?;
+
+    db.update_email(user.id, &new_email)
+        .await
+        .map_err(|_| internal("Failed to change email"))?;
+    let _ = db
+        .log_activity(
+            user.id,
+            "email_changed",
+            "user",
+            &user.username,
+            Some(&format!("{} → {}", user.email, new_email)),
+            None,
+        )
+        .await;
+
+    if has_real_email(user) {
+        let notice = Email {
+            to: user.email.clone(),
+            subject: "Your Hyrule email address was changed".to_string(),
+            body: format!(
+                "Hi {},\n\n\
+                 The email address for your Hyrule account was changed to {}. \
+                 If you didn't do this, contact an administrator.\n",
+                user.username, new_email
+            ),
+        };
+        if let Err(e) = mailer.send(&notice).await {
+            tracing::error!("Failed to send email change notice to user {}: {}", user.id, e);
+        }
+    }
+
+    let updated = db
+        .get_user_by_id(user.id)
+        .await
+        .map_err(|_| internal("Failed to change email"))?;
+    // The change stands even if this fails; the user can ask for another link
+    let _ = send_verification(mailer, site_origin, secret, &updated, now).await;
+
+    Ok(updated)
+}
+
+/// Delete a user's account after checking their password. Their personal
+/// repositories go to `transfer_to` when given, and are deleted otherwise.
+/// Only someone who already accepted admin access to every one of them can
+/// take them over, since the repositories then count against their quota.
+/// The caller should end the user's sessions.
+pub async fn delete_account(
+    db: &Database,
+    git_storage: &GitStorage,
+    user: &User,
+    password: &str,
+    transfer_to: Option<&str>,
+) -> Result<(), AccountError> {
+    check_current_password(user, password)?;
+
+    if user.is_admin != 0 && db.count_admins().await.map_err(|_| internal("Failed to check admins"))? <= 1 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "You are the only administrator. Make another user an admin first".to_string(),
+        ));
+    }
+
+    let orgs = db
+        .list_user_organizations(user.id)
+        .await
+        .map_err(|_| internal("Failed to check organizations"))?;
+    let mut sole_owner_of = Vec::new();
+    for (org, role) in orgs {
+        if role == "owner" && db.count_org_owners(org.id).await.map_err(|_| internal("Failed to check organizations"))? <= 1 {
+            sole_owner_of.push(org.name);
+        }
+    }
+    if !sole_owner_of.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "You are the only owner of {}. Add another owner first",
+                sole_owner_of.join(", ")
+            ),
+        ));
+    }
+
+    let repos = db
+        .list_user_repositories(user.id)
+        .await
+        .map_err(|_| internal("Failed to list repositories"))?;
+
+    let new_owner = match transfer_to.map(str::trim).filter(|name| !name.is_empty()) {
+        Some(username) => {
+            let new_owner = db
+                .get_user_by_username(username)
+                .await
+                .map_err(|_| (StatusCode::NOT_FOUND, format!("User '{}' not found", username)))?;
+            if new_owner.id == user.id {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Choose someone else to take over your repositories".to_string(),
+                ));
+            }
+
+            let mut not_admin = Vec::new();
+            for repo in &repos {
+                let role = db
+                    .get_collaborator_role(&repo.repo_hash, new_owner.id)
+                    .await
+                    .map_err(|_| internal("Failed to check collaborators"))?;
+                if role.as_deref().and_then(RepoRole::parse_grantable) != Some(RepoRole::Admin) {
+                    not_admin.push(repo.name.clone());
+                }
+            }
+            if !not_admin.is_empty() {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    format!(
+                        "{} must be an admin collaborator on {} to take them over",
+                        new_owner.username,
+                        not_admin.join(", ")
+                    ),
+                ));
+            }
+
+            let mut conflicts = Vec::new();
+            for repo in &repos {
+                if db
+                    .repository_name_taken(new_owner.id, &repo.name)
+                    .await
+                    .map_err(|_| internal("Failed to check repository names"))?
+                {
+                    conflicts.push(repo.name.clone());
+                }
+            }
+            if !conflicts.is_empty() {
+                return Err((
+                    StatusCode::CONFLICT,
+                    format!("{} already has repositories named {}", new_owner.username, conflicts.join(", ")),
+                ));
+            }
+
+            let size: i64 = repos.iter().map(|repo| repo.size).sum();
+            if new_owner.storage_used + size > new_owner.storage_quota {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("{} doesn't have enough storage for your repositories", new_owner.username),
+                ));
+            }
+
+            Some(new_owner)
+        }
+        None => None,
+    };
+
+    db.delete_user_account(user.id, new_owner.as_ref().map(|new_owner| new_owner.id))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to delete user {}: {}", user.id, e);
+            internal("Failed to delete account")
+        })?;
+
+    match new_owner {
+        Some(new_owner) => {
+            for repo in &repos {
+                let _ = db
+                    .log_activity(
+                        new_owner.id,
+                        "repo_transferred",
+                        "repository",
+                        &repo.repo_hash,
+                        Some(&format!("{} from {}", repo.name, user.username)),
+                        None,
+                    )
+                    .await;
+            }
+        }
+        // Only once the account is gone, so a failure here leaves stray
+        // directories rather than repositories with nothing on disk
+        None => {
+            for repo in &repos {
+                if let Err(e) = git_storage.delete_repo(&repo.repo_hash) {
+                    tracing::warn!("Failed to remove repository {} of deleted user {}: {}", repo.repo_hash, user.id, e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateRepoRequest, Repository};
+    use crate::test_support::TestDb;
+    use crate::services::mailer::MailError;
+    use axum::async_trait;
+    use tokio::sync::Mutex;
+
+    /// Keeps messages so tests can follow their links
+    #[derive(Default)]
+    struct MemoryMailer {
+        sent: Mutex<Vec<Email>>,
+    }
+
+    #[async_trait]
+    impl Mailer for MemoryMailer {
+        async fn send(&self, email: &Email) -> Result<(), MailError> {
+            self.sent.lock().await.push(email.clone());
+            Ok(())
+        }
+    }
+
+    impl MemoryMailer {
+        async fn last_token(&self) -> String {
+            let sent = self.sent.lock().await;
+            let body = &sent.last().expect("no email sent").body;
+            let start = body.find("token=").unwrap() + "token=".len();
+            body[start..].split_whitespace().next().unwrap().to_string()
+        }
+    }
+
+    const SITE: &str = "http://hyrule.test";
+
+    const SECRET: &str = "test_secret_key_minimum_32_characters_long_12345";
+
+    async fn setup(email: &str) -> (TestDb, User) {
+        let db = TestDb::new().await;
+        let password_hash = crate::auth::password::hash_password("Original123").unwrap();
+        let user = db.add_user("alice", email, &password_hash).await;
+        (db, user)
+    }
+
+    async fn add_repo(db: &Database, owner_id: i64, name: &str) -> Repository {
+        let request = CreateRepoRequest {
+            name: name.to_string(),
+            description: None,
+            storage_tier: "free".to_string(),
+            is_private: false,
+        };
+        db.create_repository(&request, owner_id, &format!("{:040x}", rand::random::<u64>()))
+            .await
+            .unwrap()
+    }
+
+    /// Invite `user` on the owner's behalf and have them accept
+    async fn add_collaborator(db: &Database, repo: &Repository, user: &User, role: &str) {
+        let invitation = crate::auth::repo_access::invite_collaborator(db, repo, repo.owner_id, &user.username, role)
+            .await
+            .unwrap();
+        db.accept_invitation(invitation.id, user.id).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn verification_link_verifies_the_address() {
+        let (db, user) = setup("Alice@Example.com").await;
+        let mailer = MemoryMailer::default();
+        let now = Utc::now();
+        assert!(needs_verification(&user));
+
+        send_verification(&mailer, SITE, SECRET, &user, now).await.unwrap();
+        let token = mailer.last_token().await;
+        assert!(mailer.sent.lock().await[0].body.contains(&format!("{}/verify-email?token=", SITE)));
+
+        let verified = verify_email(&db, SECRET, &token, now).await.unwrap();
+        assert_eq!(verified.email_verified, 1);
+        assert!(db.is_email_verified("alice@example.com").await.unwrap());
+        assert!(!needs_verification(&verified));
+
+        assert_eq!(send_verification(&mailer, SITE, SECRET, &verified, now).await.unwrap_err().0, StatusCode::BAD_REQUEST);
+        assert!(verify_email(&db, SECRET, "not-a-token", now).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn placeholder_addresses_are_never_mailed() {
+        let (db, user) = setup(&placeholder_email("alice")).await;
+        let mailer = MemoryMailer::default();
+
+        assert!(!needs_verification(&user));
+        assert!(send_verification(&mailer, SITE, SECRET, &user, Utc::now()).await.is_err());
+        request_password_reset(&db, &mailer, SITE, SECRET, &user.email, Utc::now()).await;
+        assert!(mailer.sent.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reset_links_work_once() {
+        let (db, user) = setup("alice@example.com").await;
+        let mailer = MemoryMailer::default();
+        let now = Utc::now();
+
+        request_password_reset(&db, &mailer, SITE, SECRET, "nobody@example.com", now).await;
+        assert!(mailer.sent.lock().await.is_empty());
+
+        request_password_reset(&db, &mailer, SITE, SECRET, "ALICE@example.com", now).await;
+        let token = mailer.last_token().await;
+        assert!(check_reset_token(&db, SECRET, &token, now).await.is_ok());
+        let login = crate::auth::jwt::generate_token(SECRET, user.id, &user.username, user.token_generation).unwrap();
+        let (_, access_token) = crate::auth::tokens::create_token(&db, user.id, "ci", &["repo:read".to_string()], None).await.unwrap();
+
+        assert_eq!(reset_password(&db, SECRET, &token, "weak", now).await.unwrap_err().0, StatusCode::BAD_REQUEST);
+        reset_password(&db, SECRET, &token, "Replaced456", now).await.unwrap();
+
+        let user = db.get_user_by_id(user.id).await.unwrap();
+        assert!(crate::auth::password::verify_password("Replaced456", &user.password_hash).unwrap());
+        assert_eq!(user.email_verified, 1);
+        assert!(reset_password(&db, SECRET, &token, "Another789", now).await.is_err());
+
+        // Whoever got in before the reset is out
+        assert!(crate::auth::jwt::authenticate(&db, SECRET, &login).await.is_none());
+        assert!(crate::auth::tokens::authenticate(&db, &access_token).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn changing_password_needs_the_current_one() {
+        let (db, user) = setup("alice@example.com").await;
+        db.set_must_change_password(user.id, true).await.unwrap();
+        let user = db.get_user_by_id(user.id).await.unwrap();
+
+        let wrong = change_password(&db, &user, "Wrong123", "Replaced456").await.unwrap_err();
+        assert_eq!(wrong.0, StatusCode::UNAUTHORIZED);
+        let same = change_password(&db, &user, "Original123", "Original123").await.unwrap_err();
+        assert_eq!(same.0, StatusCode::BAD_REQUEST);
+
+        let login = crate::auth::jwt::generate_token(SECRET, user.id, &user.username, user.token_generation).unwrap();
+        assert!(crate::auth::jwt::authenticate(&db, SECRET, &login).await.is_some());
+
+        change_password(&db, &user, "Original123", "Replaced456").await.unwrap();
+        let user = db.get_user_by_id(user.id).await.unwrap();
+        assert!(crate::auth::password::verify_password("Replaced456", &user.password_hash).unwrap());
+        assert_eq!(user.must_change_password, 0);
+
+        // Login tokens from before the change stop working
+        assert!(crate::auth::jwt::authenticate(&db, SECRET, &login).await.is_none());
+        let login = crate::auth::jwt::generate_token(SECRET, user.id, &user.username, user.token_generation).unwrap();
+        assert!(crate::auth::jwt::authenticate(&db, SECRET, &login).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn changing_email_needs_verifying_again() {
+        let (db, user) = setup("alice@example.com").await;
+        db.mark_email_verified(user.id, &user.email).await.unwrap();
+        let user = db.get_user_by_id(user.id).await.unwrap();
+        db.add_named_user("bob").await;
+        let mailer = MemoryMailer::default();
+        let now = Utc::now();
+
+        let wrong = change_email(&db, &mailer, SITE, SECRET, &user, "Wrong123", "new@example.com", now).await;
+        assert_eq!(wrong.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        let same = change_email(&db, &mailer, SITE, SECRET, &user, "Original123", "ALICE@example.com", now).await;
+        assert_eq!(same.unwrap_err().0, StatusCode::BAD_REQUEST);
+        let taken = change_email(&db, &mailer, SITE, SECRET, &user, "Original123", "bob@example.com", now).await;
+        assert_eq!(taken.unwrap_err().0, StatusCode::CONFLICT);
+        assert!(mailer.sent.lock().await.is_empty());
+
+        let changed = change_email(&db, &mailer, SITE, SECRET, &user, "Original123", "new@example.com", now)
+            .await
+            .unwrap();
+        assert_eq!(changed.email, "new@example.com");
+        assert_eq!(changed.email_verified, 0);
+        {
+            let sent = mailer.sent.lock().await;
+            assert_eq!(sent.len(), 2);
+            assert_eq!(sent[0].to, "alice@example.com");
+            assert_eq!(sent[1].to, "new@example.com");
+        }
+
+        let token = mailer.last_token().await;
+        assert_eq!(verify_email(&db, SECRET, &token, now).await.unwrap().email_verified, 1);
+    }
+
+    #[tokio::test]
+    async fn deleting_an_account_transfers_or_deletes_repositories() {
+        let (db, alice) = setup("alice@example.com").await;
+        let storage = GitStorage::new(db.dir().join("repos")).unwrap();
+        let bob = db.add_named_user("bob").await;
+        let carol = db.add_named_user("carol").await;
+        let alpha = add_repo(&db, alice.id, "alpha").await;
+        add_repo(&db, bob.id, "alpha").await;
+
+        let wrong = delete_account(&db, &storage, &alice, "Wrong123", Some("carol")).await;
+        assert_eq!(wrong.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        let unknown = delete_account(&db, &storage, &alice, "Original123", Some("nobody")).await;
+        assert_eq!(unknown.unwrap_err().0, StatusCode::NOT_FOUND);
+
+        // Repositories only go to someone who already administers them
+        let unasked = delete_account(&db, &storage, &alice, "Original123", Some("carol")).await;
+        assert_eq!(unasked.unwrap_err().0, StatusCode::FORBIDDEN);
+        add_collaborator(&db, &alpha, &carol, "write").await;
+        let writer = delete_account(&db, &storage, &alice, "Original123", Some("carol")).await;
+        assert_eq!(writer.unwrap_err().0, StatusCode::FORBIDDEN);
+        db.set_collaborator_role(&alpha.repo_hash, carol.id, "admin").await.unwrap();
+        add_collaborator(&db, &alpha, &bob, "admin").await;
+
+        let conflict = delete_account(&db, &storage, &alice, "Original123", Some("bob")).await;
+        assert_eq!(conflict.unwrap_err().0, StatusCode::CONFLICT);
+
+        let org = db.create_organization("hylians", None, alice.id).await.unwrap();
+        let sole_owner = delete_account(&db, &storage, &alice, "Original123", Some("carol")).await;
+        assert_eq!(sole_owner.unwrap_err().0, StatusCode::BAD_REQUEST);
+        db.set_org_member(org.id, bob.id, "owner").await.unwrap();
+
+        delete_account(&db, &storage, &alice, "Original123", Some("carol")).await.unwrap();
+        assert!(db.get_user_by_id(alice.id).await.is_err());
+        assert_eq!(db.get_repository(&alpha.repo_hash).await.unwrap().owner_id, carol.id);
+
+        // Without someone to take them, the repositories go too
+        db.update_password(carol.id, &crate::auth::password::hash_password("Carol123").unwrap())
+            .await
+            .unwrap();
+        let carol = db.get_user_by_id(carol.id).await.unwrap();
+        storage.init_repo(&alpha.repo_hash).unwrap();
+        delete_account(&db, &storage, &carol, "Carol123", None).await.unwrap();
+        assert!(db.get_user_by_id(carol.id).await.is_err());
+        assert!(db.get_repository(&alpha.repo_hash).await.is_err());
+        assert!(!storage.repo_path(&alpha.repo_hash).exists());
+    }
+
+    #[tokio::test]
+    async fn the_only_admin_cannot_delete_their_account() {
+        let (db, user) = setup("alice@example.com").await;
+        let storage = GitStorage::new(db.dir().join("repos")).unwrap();
+        db.set_user_admin(user.id, true).await.unwrap();
+        let user = db.get_user_by_id(user.id).await.unwrap();
+
+        let refused = delete_account(&db, &storage, &user, "Original123", None).await;
+        assert_eq!(refused.unwrap_err().0, StatusCode::BAD_REQUEST);
+        assert!(db.get_user_by_id(user.id).await.is_ok());
+    }
+}
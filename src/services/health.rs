@@ -1,22 +1,52 @@
 // src/services/health.rs
 use crate::db::Database;
-use crate::services::replication::ReplicationService;
+use crate::models::Replica;
+use crate::services::node_client::NodeClient;
+use crate::services::node_protocol::{self, StorageChallenge};
+use crate::services::replication::{refs_behind, ReplicationService};
+use crate::services::replication_queue::{PRIORITY_EVICTION, PRIORITY_UNDER_REPLICATED};
+use crate::services::reputation::{ReputationEvent, ReputationService};
+use crate::storage::GitStorage;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time;
 use tracing::{info, warn, error};
 
+/// Objects sampled per proof-of-storage challenge
+const CHALLENGE_SAMPLE_SIZE: usize = 8;
+/// Consecutive failed challenges before a replica is dropped
+const MAX_FAILED_CHALLENGES: i64 = 3;
+/// Wait after a replication job gives up before queueing the repo again
+const FAILED_JOB_COOLDOWN_SECS: i64 = 3600;
+/// Finished replication jobs are kept this long for the admin pages
+const FINISHED_JOB_RETENTION_DAYS: i64 = 7;
+
 pub struct HealthMonitor {
     db: Database,
+    git_storage: Arc<GitStorage>,
     min_replica_count: i32,
     check_interval_minutes: u64,
+    node_timeout_minutes: i32,
+    eviction_grace_minutes: i32,
 }
 
 impl HealthMonitor {
-    pub fn new(db: Database, min_replica_count: i32, check_interval_minutes: u64) -> Self {
+    pub fn new(
+        db: Database,
+        git_storage: Arc<GitStorage>,
+        min_replica_count: i32,
+        check_interval_minutes: u64,
+        node_timeout_minutes: i32,
+        eviction_grace_minutes: i32,
+    ) -> Self {
         Self {
             db,
+            git_storage,
             min_replica_count,
             check_interval_minutes,
+            node_timeout_minutes,
+            // Eviction never happens before a node is considered offline
+            eviction_grace_minutes: eviction_grace_minutes.max(node_timeout_minutes),
         }
     }
     
@@ -39,34 +69,54 @@ impl HealthMonitor {
     pub async fn check_network_health(&self) -> Result<(), String> {
         info!("Starting network health check...");
         
+        // Update node liveness first so evicted replicas are queued below
+        let sweep = self.cleanup_stale_nodes().await?;
+        if sweep.transitions > 0 {
+            info!(
+                "Node sweep: {} suspect, {} offline, {} evicted ({} replicas removed)",
+                sweep.suspect, sweep.offline, sweep.evicted, sweep.evicted_repos.len()
+            );
+        }
+        
+        // Nodes that kept heartbeating since the last check earn back reputation
+        let reputation = ReputationService::new(self.db.clone());
+        if let Err(e) = reputation.reward_uptime(self.node_timeout_minutes).await {
+            warn!("{}", e);
+        }
+        
+        // Challenge replicas first so dropped ones are queued below
+        let (passed, failed) = self.verify_replicas(&reputation).await?;
+        if passed + failed > 0 {
+            info!("Proof-of-storage: {} passed, {} failed", passed, failed);
+        }
+        
         // Check for unhealthy repositories
         let unhealthy_repos = self.db
             .get_unhealthy_repos(self.min_replica_count)
             .await
             .map_err(|e| format!("Failed to get unhealthy repos: {}", e))?;
         
+        // Queue re-replication; repos that just lost replicas to eviction go first
+        for repo_hash in &sweep.evicted_repos {
+            if unhealthy_repos.contains(repo_hash) {
+                self.enqueue(repo_hash, "eviction", PRIORITY_EVICTION).await;
+            }
+        }
+        
         if !unhealthy_repos.is_empty() {
             warn!("Found {} repositories below minimum replica count", unhealthy_repos.len());
             
-            // Trigger replication for unhealthy repos
-            let replication_service = ReplicationService::new(self.db.clone());
-            
-            for repo_hash in unhealthy_repos.iter().take(10) {  // Process 10 at a time
-                match replication_service.trigger_replication(repo_hash).await {
-                    Ok(node_id) => {
-                        info!("Triggered replication for {} to node {}", repo_hash, node_id);
-                    }
-                    Err(e) => {
-                        warn!("Failed to replicate {}: {}", repo_hash, e);
-                    }
+            for repo_hash in &unhealthy_repos {
+                // Give repos whose last job just gave up a rest before trying again
+                if self.db.replication_recently_failed(repo_hash, FAILED_JOB_COOLDOWN_SECS).await.unwrap_or(false) {
+                    continue;
                 }
+                self.enqueue(repo_hash, "under-replicated", PRIORITY_UNDER_REPLICATED).await;
             }
         }
         
-        // Clean up stale nodes
-        let stale_count = self.cleanup_stale_nodes().await?;
-        if stale_count > 0 {
-            info!("Cleaned up {} stale nodes", stale_count);
+        if let Err(e) = self.db.prune_replication_jobs(FINISHED_JOB_RETENTION_DAYS).await {
+            warn!("Failed to prune replication jobs: {}", e);
         }
         
         // Log network statistics
@@ -86,11 +136,179 @@ impl HealthMonitor {
         Ok(())
     }
     
-    /// Remove nodes that haven't been seen recently
-    async fn cleanup_stale_nodes(&self) -> Result<usize, String> {
-        // This would delete nodes last seen more than 1 hour ago
-        // Implementation would depend on your database structure
-        Ok(0)  // Placeholder
+    async fn enqueue(&self, repo_hash: &str, reason: &str, priority: i64) {
+        if let Err(e) = self.db.enqueue_replication_job(repo_hash, reason, priority).await {
+            warn!("Failed to queue replication for {}: {}", repo_hash, e);
+        }
+    }
+
+    /// Send a proof-of-storage challenge for every replica on a live node
+    ///
+    /// Offline nodes are left to `cleanup_stale_nodes`. Returns the number
+    /// of (passed, failed) challenges.
+    pub async fn verify_replicas(&self, reputation: &ReputationService) -> Result<(usize, usize), String> {
+        let replicas = self.db
+            .list_replicas_on_live_nodes()
+            .await
+            .map_err(|e| format!("Failed to list replicas: {}", e))?;
+
+        let client = NodeClient::new()?;
+        let replication = ReplicationService::new(self.db.clone(), self.git_storage.clone());
+        let (mut passed, mut failed) = (0, 0);
+
+        for replica in replicas {
+            // A replica that missed a push cannot answer for the new objects
+            let result = match self.catch_up(&replication, &client, &replica).await {
+                Ok(()) => self.challenge_replica(&client, &replica).await,
+                Err(e) => Err(format!("could not catch up: {}", e)),
+            };
+
+            match result {
+                Ok(()) => {
+                    passed += 1;
+                    let _ = self.db.mark_replica_verified(&replica.repo_hash, &replica.node_id).await;
+                    let _ = reputation.record(&replica.node_id, ReputationEvent::ChallengePassed).await;
+                }
+                Err(e) => {
+                    failed += 1;
+                    warn!("Replica {} on {} failed verification: {}", replica.repo_hash, replica.node_id, e);
+                    let _ = reputation.record(&replica.node_id, ReputationEvent::ChallengeFailed).await;
+                    self.drop_after_misses(&replica).await;
+                }
+            }
+        }
+
+        Ok((passed, failed))
+    }
+
+    /// Sync a replica whose recorded refs differ from the primary
+    async fn catch_up(
+        &self,
+        replication: &ReplicationService,
+        client: &NodeClient,
+        replica: &Replica,
+    ) -> Result<(), String> {
+        let refs = replication.current_refs(&replica.repo_hash).await?;
+        if replica.ref_state.as_deref().map(|state| refs_behind(state, &refs)) == Some(0) {
+            return Ok(());
+        }
+
+        let node = self.db
+            .get_node(&replica.node_id)
+            .await
+            .map_err(|e| format!("Unknown node: {}", e))?;
+
+        replication.sync_replica(client, &node, &replica.repo_hash, &refs).await
+    }
+
+    async fn challenge_replica(&self, client: &NodeClient, replica: &Replica) -> Result<(), String> {
+        let node = self.db
+            .get_node(&replica.node_id)
+            .await
+            .map_err(|e| format!("Unknown node: {}", e))?;
+
+        // Sample objects and compute the expected answer locally
+        let storage = self.git_storage.clone();
+        let repo_hash = replica.repo_hash.clone();
+        let (object_ids, objects) = tokio::task::spawn_blocking(move || {
+            let ids = storage.sample_object_ids(&repo_hash, CHALLENGE_SAMPLE_SIZE)?;
+            let objects = storage.read_objects_raw(&repo_hash, &ids)?;
+            Ok::<_, anyhow::Error>((ids, objects))
+        })
+        .await
+        .map_err(|e| format!("Sampling task failed: {}", e))?
+        .map_err(|e| format!("Failed to sample objects: {}", e))?;
+
+        if object_ids.is_empty() {
+            return Ok(()); // Nothing stored yet, nothing to prove
+        }
+
+        let mut nonce = [0u8; 32];
+        rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut nonce);
+        let challenge = StorageChallenge {
+            repo_hash: replica.repo_hash.clone(),
+            object_ids,
+            nonce: hex::encode(nonce),
+        };
+        let expected = node_protocol::challenge_digest(&challenge.nonce, &objects);
+
+        let response = client.challenge(&node, &challenge).await?;
+
+        if response.node_id != node.node_id {
+            return Err(format!("response signed by {}", response.node_id));
+        }
+        node_protocol::verify(&response).map_err(|e| e.to_string())?;
+
+        if response.repo_hash != challenge.repo_hash || response.nonce != challenge.nonce {
+            return Err("response does not match the challenge".to_string());
+        }
+        if response.digest != expected {
+            return Err("digest mismatch".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Drop the replica once it has missed too many challenges in a row
+    async fn drop_after_misses(&self, replica: &Replica) {
+        match self.db.record_failed_challenge(&replica.repo_hash, &replica.node_id).await {
+            Ok(misses) if misses >= MAX_FAILED_CHALLENGES => {
+                warn!(
+                    "Dropping replica {} on {} after {} failed challenges",
+                    replica.repo_hash, replica.node_id, misses
+                );
+                let _ = self.db.delete_replica(&replica.repo_hash, &replica.node_id).await;
+            }
+            Ok(_) => {}
+            Err(e) => error!("Failed to record challenge miss: {}", e),
+        }
+    }
+    
+    /// Move silent nodes through online -> suspect -> offline -> evicted
+    ///
+    /// Evicted nodes lose their replica records; the affected repositories
+    /// are returned so they can be re-replicated first.
+    pub async fn cleanup_stale_nodes(&self) -> Result<NodeSweep, String> {
+        let nodes = self.db
+            .list_node_liveness()
+            .await
+            .map_err(|e| format!("Failed to list nodes: {}", e))?;
+
+        let mut sweep = NodeSweep::default();
+
+        for (node_id, state, silent_minutes) in nodes {
+            let current = NodeState::parse(&state);
+            let next = current.after_silence(silent_minutes, self.node_timeout_minutes as i64, self.eviction_grace_minutes as i64);
+            if next == current {
+                continue;
+            }
+
+            self.db
+                .set_node_state(&node_id, current.as_str(), next.as_str(), silent_minutes)
+                .await
+                .map_err(|e| format!("Failed to update node state: {}", e))?;
+            sweep.transitions += 1;
+
+            match next {
+                NodeState::Suspect => sweep.suspect += 1,
+                NodeState::Offline => {
+                    sweep.offline += 1;
+                    warn!("Node {} is offline ({} minutes silent)", node_id, silent_minutes);
+                }
+                NodeState::Evicted => {
+                    sweep.evicted += 1;
+                    let repos = self.db
+                        .evict_node_replicas(&node_id)
+                        .await
+                        .map_err(|e| format!("Failed to evict replicas: {}", e))?;
+                    warn!("Evicted node {} and its {} replicas", node_id, repos.len());
+                    sweep.evicted_repos.extend(repos);
+                }
+                NodeState::Online => {}
+            }
+        }
+
+        Ok(sweep)
     }
     
     /// Check if a specific repository needs replication
@@ -119,6 +337,77 @@ impl HealthMonitor {
     }
 }
 
+/// Outcome of one `cleanup_stale_nodes` pass
+#[derive(Debug, Default)]
+pub struct NodeSweep {
+    pub transitions: usize,
+    pub suspect: usize,
+    pub offline: usize,
+    pub evicted: usize,
+    /// Repositories that lost a replica through eviction
+    pub evicted_repos: Vec<String>,
+}
+
+/// Liveness of a node as tracked in `nodes.state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeState {
+    Online,
+    Suspect,   // missed heartbeats for half the timeout
+    Offline,   // silent beyond the heartbeat timeout
+    Evicted,   // silent beyond the grace period, replicas removed
+}
+
+impl NodeState {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "suspect" => NodeState::Suspect,
+            "offline" => NodeState::Offline,
+            "evicted" => NodeState::Evicted,
+            _ => NodeState::Online,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeState::Online => "online",
+            NodeState::Suspect => "suspect",
+            NodeState::Offline => "offline",
+            NodeState::Evicted => "evicted",
+        }
+    }
+
+    /// State after `silent_minutes` without a heartbeat; only heartbeats
+    /// bring a node back, so the sweep never moves a node backwards
+    pub fn after_silence(self, silent_minutes: i64, timeout_minutes: i64, grace_minutes: i64) -> Self {
+        let observed = if silent_minutes >= grace_minutes {
+            NodeState::Evicted
+        } else if silent_minutes >= timeout_minutes {
+            NodeState::Offline
+        } else if silent_minutes * 2 >= timeout_minutes.max(1) {
+            NodeState::Suspect
+        } else {
+            NodeState::Online
+        };
+
+        if observed.rank() > self.rank() { observed } else { self }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            NodeState::Online => 0,
+            NodeState::Suspect => 1,
+            NodeState::Offline => 2,
+            NodeState::Evicted => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for NodeState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RepoHealth {
     pub repo_hash: String,
@@ -145,3 +434,27 @@ impl std::fmt::Display for HealthStatus {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_state_progression() {
+        let (timeout, grace) = (10, 60);
+        let after = |state: NodeState, minutes| state.after_silence(minutes, timeout, grace);
+
+        assert_eq!(after(NodeState::Online, 2), NodeState::Online);
+        assert_eq!(after(NodeState::Online, 5), NodeState::Suspect);
+        assert_eq!(after(NodeState::Suspect, 10), NodeState::Offline);
+        assert_eq!(after(NodeState::Online, 61), NodeState::Evicted);
+        assert_eq!(after(NodeState::Offline, 59), NodeState::Offline);
+    }
+
+    #[test]
+    fn test_sweep_never_revives_nodes() {
+        // A fresh last_seen without a heartbeat (e.g. admin ping) is not enough
+        assert_eq!(NodeState::Offline.after_silence(0, 10, 60), NodeState::Offline);
+        assert_eq!(NodeState::Evicted.after_silence(0, 10, 60), NodeState::Evicted);
+    }
+}
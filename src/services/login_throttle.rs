@@ -0,0 +1,396 @@
+// src/services/login_throttle.rs
+//! Failed login tracking per account and per client address. After a few
+//! failures each attempt must wait longer than the last, and past a limit
+//! the account or address is locked out for a while. Admin accounts get
+//! less room since they are the usual target of credential stuffing.
+//! Users see failures and lockouts in their activity log.
+//!
+//! Every attempt is counted before the password is checked and forgiven
+//! once it succeeds, so parallel guesses can't slip in under the limits.
+use chrono::{DateTime, Duration, Utc};
+
+use crate::db::Database;
+use crate::models::User;
+
+const ACCOUNT_SCOPE: &str = "account";
+const IP_SCOPE: &str = "ip";
+
+/// Failures after which attempts are delayed, and after which the
+/// subject is locked out
+struct Policy {
+    delay_after: i64,
+    lock_after: i64,
+}
+
+const ACCOUNT_POLICY: Policy = Policy { delay_after: 3, lock_after: 10 };
+const ADMIN_ACCOUNT_POLICY: Policy = Policy { delay_after: 1, lock_after: 5 };
+/// Addresses get more room since many users can share one
+const IP_POLICY: Policy = Policy { delay_after: 5, lock_after: 30 };
+
+const MAX_DELAY_SECS: i64 = 60;
+const LOCKOUT_MINUTES: i64 = 15;
+/// Failures older than this are forgotten
+const FAILURE_WINDOW_MINUTES: i64 = 60;
+
+/// Why a login attempt was refused before the password was checked
+#[derive(Debug, PartialEq, Eq)]
+pub struct Throttled {
+    pub retry_after_secs: i64,
+    pub locked: bool,
+}
+
+impl Throttled {
+    pub fn message(&self) -> String {
+        fn plural(n: i64, unit: &str) -> String {
+            format!("{} {}{}", n, unit, if n == 1 { "" } else { "s" })
+        }
+
+        if self.locked {
+            format!(
+                "Too many failed logins. Sign-in is locked for {} more.",
+                plural((self.retry_after_secs + 59) / 60, "minute")
+            )
+        } else {
+            format!(
+                "Too many failed logins. Try again in {}.",
+                plural(self.retry_after_secs, "second")
+            )
+        }
+    }
+}
+
+fn account_subject(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+fn delay_secs(failures: i64, policy: &Policy) -> i64 {
+    if failures < policy.delay_after {
+        return 0;
+    }
+    let doublings = (failures - policy.delay_after).min(6) as u32;
+    (1i64 << doublings).min(MAX_DELAY_SECS)
+}
+
+fn seconds_until(when: DateTime<Utc>, now: DateTime<Utc>) -> i64 {
+    ((when - now).num_milliseconds() + 999) / 1000
+}
+
+async fn account_policy(db: &Database, username: &str) -> (Option<User>, &'static Policy) {
+    // Failures are counted case-insensitively, so "Admin" must get the
+    // admin policy too
+    let user = match db.get_user_by_username(username.trim()).await {
+        Ok(user) => Some(user),
+        Err(_) => db.get_user_by_username(&account_subject(username)).await.ok(),
+    };
+    let policy = match &user {
+        Some(user) if user.is_admin != 0 => &ADMIN_ACCOUNT_POLICY,
+        _ => &ACCOUNT_POLICY,
+    };
+    (user, policy)
+}
+
+/// Count an attempt against an account or address and decide whether it
+/// may go ahead. Refused attempts are taken back so waiting out the delay
+/// is enough. Returns the count the attempt got.
+async fn admit(
+    db: &Database,
+    scope: &str,
+    subject: &str,
+    policy: &Policy,
+    now: DateTime<Utc>,
+) -> Result<Result<i64, Throttled>, sqlx::Error> {
+    let stale_before = now - Duration::minutes(FAILURE_WINDOW_MINUTES);
+    let throttle = db.count_login_attempt(scope, subject, now, stale_before).await?;
+
+    if let Some(locked_until) = throttle.locked_until.filter(|until| *until > now) {
+        return Ok(Err(Throttled {
+            retry_after_secs: seconds_until(locked_until, now),
+            locked: true,
+        }));
+    }
+    // Only possible if recording an earlier failure went wrong
+    if throttle.failures > policy.lock_after {
+        let locked_until = now + Duration::minutes(LOCKOUT_MINUTES);
+        if db.lock_login_throttle(scope, subject, policy.lock_after, now, locked_until).await? {
+            tracing::warn!("Locked out {} {} after repeated failed logins", scope, subject);
+        }
+        return Ok(Err(Throttled {
+            retry_after_secs: seconds_until(locked_until, now),
+            locked: true,
+        }));
+    }
+
+    if let Some(previous) = throttle.previous_failure_at {
+        let next_attempt = previous + Duration::seconds(delay_secs(throttle.failures - 1, policy));
+        if next_attempt > now {
+            db.uncount_login_attempt(scope, subject, throttle.failures, now).await?;
+            return Ok(Err(Throttled {
+                retry_after_secs: seconds_until(next_attempt, now),
+                locked: false,
+            }));
+        }
+    }
+
+    Ok(Ok(throttle.failures))
+}
+
+/// Start a login for `username` from `ip`, refusing it if the account or
+/// address has to wait. Every started login must end in `record_failure`
+/// or `record_success`.
+pub async fn begin_attempt(
+    db: &Database,
+    username: &str,
+    ip: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<(), Throttled> {
+    let (_, policy) = account_policy(db, username).await;
+    let subject = account_subject(username);
+
+    let account_failures = match admit(db, ACCOUNT_SCOPE, &subject, policy, now).await {
+        Ok(admitted) => admitted?,
+        Err(e) => {
+            tracing::error!("Failed to count login attempt for {}: {}", subject, e);
+            return Ok(());
+        }
+    };
+    let Some(ip) = ip else {
+        return Ok(());
+    };
+    match admit(db, IP_SCOPE, ip, &IP_POLICY, now).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(throttled)) => {
+            if let Err(e) = db.uncount_login_attempt(ACCOUNT_SCOPE, &subject, account_failures, now).await {
+                tracing::warn!("Failed to take back login attempt for {}: {}", subject, e);
+            }
+            Err(throttled)
+        }
+        Err(e) => {
+            tracing::error!("Failed to count login attempt from {}: {}", ip, e);
+            Ok(())
+        }
+    }
+}
+
+/// Lock the subject out if it has run out of attempts. Returns whether this
+/// call locked it.
+async fn lock_if_exhausted(
+    db: &Database,
+    scope: &str,
+    subject: &str,
+    policy: &Policy,
+    now: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    // Delays start over once the lockout ends
+    let locked_until = now + Duration::minutes(LOCKOUT_MINUTES);
+    db.lock_login_throttle(scope, subject, policy.lock_after, now, locked_until).await
+}
+
+/// Record that a started login failed, telling the account owner if there
+/// is one
+pub async fn record_failure(db: &Database, username: &str, ip: Option<&str>, now: DateTime<Utc>) {
+    let (user, policy) = account_policy(db, username).await;
+    let subject = account_subject(username);
+
+    let account_locked = match lock_if_exhausted(db, ACCOUNT_SCOPE, &subject, policy, now).await {
+        Ok(locked) => locked,
+        Err(e) => {
+            tracing::error!("Failed to record login failure for {}: {}", subject, e);
+            false
+        }
+    };
+    if let Some(ip) = ip {
+        match lock_if_exhausted(db, IP_SCOPE, ip, &IP_POLICY, now).await {
+            Ok(true) => tracing::warn!("Locked out logins from {} after repeated failures", ip),
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to record login failure from {}: {}", ip, e),
+        }
+    }
+
+    let Some(user) = user else {
+        return;
+    };
+    let _ = db
+        .log_activity(user.id, "login_failed", "user", &user.username, Some("Failed sign-in attempt"), ip)
+        .await;
+    if account_locked {
+        tracing::warn!("Locked account {} after repeated failed logins", user.username);
+        let details = format!("Sign-in locked for {} minutes after repeated failed attempts", LOCKOUT_MINUTES);
+        let _ = db
+            .log_activity(user.id, "account_locked", "user", &user.username, Some(&details), ip)
+            .await;
+    }
+}
+
+/// Activity log actions about signing in, shown to the account owner
+pub const SIGN_IN_ACTIONS: &[&str] = &["login", "login_failed", "account_locked", "account_unlocked"];
+
+/// Forget the account's failures after it signed in. The address only
+/// gets this attempt back, since others may be guessing from it.
+pub async fn record_success(db: &Database, user: &User, ip: Option<&str>) {
+    if let Err(e) = db.clear_login_throttle(ACCOUNT_SCOPE, &account_subject(&user.username)).await {
+        tracing::warn!("Failed to clear login failures for {}: {}", user.username, e);
+    }
+    if let Some(ip) = ip {
+        if let Err(e) = db.forgive_login_attempt(IP_SCOPE, ip).await {
+            tracing::warn!("Failed to forgive login attempt from {}: {}", ip, e);
+        }
+    }
+    let _ = db
+        .log_activity(user.id, "login", "user", &user.username, Some("Signed in"), ip)
+        .await;
+}
+
+/// Lift a lockout on an account. Returns false if it was not throttled.
+pub async fn unlock_account(db: &Database, user: &User, admin_username: &str) -> Result<bool, sqlx::Error> {
+    let unlocked = db
+        .clear_login_throttle(ACCOUNT_SCOPE, &account_subject(&user.username))
+        .await?;
+    if unlocked {
+        let details = format!("Sign-in unlocked by {}", admin_username);
+        let _ = db
+            .log_activity(user.id, "account_unlocked", "user", &user.username, Some(&details), None)
+            .await;
+    }
+
+    Ok(unlocked)
+}
+
+/// Usernames of accounts that are locked out now
+pub async fn locked_accounts(db: &Database, now: DateTime<Utc>) -> Vec<String> {
+    db.list_login_lockouts(now)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|throttle| throttle.scope == ACCOUNT_SCOPE)
+        .map(|throttle| throttle.subject)
+        .collect()
+}
+
+/// Drop failures nobody needs to remember
+pub async fn cleanup(db: &Database, now: DateTime<Utc>) {
+    let failed_before = now - Duration::minutes(FAILURE_WINDOW_MINUTES);
+    if let Err(e) = db.delete_stale_login_throttles(failed_before, now).await {
+        tracing::warn!("Failed to clean up login throttles: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDb;
+
+    async fn setup() -> (TestDb, User) {
+        let db = TestDb::new().await;
+        let user = db.add_named_user("alice").await;
+        (db, user)
+    }
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn delays_grow_and_cap() {
+        let delays: Vec<i64> = (0..12).map(|f| delay_secs(f, &ACCOUNT_POLICY)).collect();
+        assert_eq!(delays, vec![0, 0, 0, 1, 2, 4, 8, 16, 32, 60, 60, 60]);
+    }
+
+    #[tokio::test]
+    async fn account_is_delayed_then_locked() {
+        let (db, user) = setup().await;
+        let mut now = start();
+
+        for _ in 0..ACCOUNT_POLICY.delay_after {
+            begin_attempt(&db, "alice", None, now).await.unwrap();
+            record_failure(&db, "alice", None, now).await;
+        }
+        // Now each attempt has to wait
+        let throttled = begin_attempt(&db, "Alice", None, now).await.unwrap_err();
+        assert_eq!(throttled, Throttled { retry_after_secs: 1, locked: false });
+        now += Duration::seconds(1);
+        begin_attempt(&db, "alice", None, now).await.unwrap();
+        record_failure(&db, "alice", None, now).await;
+
+        for _ in ACCOUNT_POLICY.delay_after + 1..ACCOUNT_POLICY.lock_after {
+            now += Duration::seconds(MAX_DELAY_SECS);
+            begin_attempt(&db, "alice", None, now).await.unwrap();
+            record_failure(&db, "alice", None, now).await;
+        }
+        let throttled = begin_attempt(&db, "alice", None, now + Duration::minutes(5)).await.unwrap_err();
+        assert!(throttled.locked);
+        assert_eq!(locked_accounts(&db, now).await, vec!["alice".to_string()]);
+
+        let activity = db.get_user_activity(user.id, 50).await.unwrap();
+        assert!(activity.iter().any(|entry| entry.action == "account_locked"));
+        assert_eq!(
+            activity.iter().filter(|entry| entry.action == "login_failed").count() as i64,
+            ACCOUNT_POLICY.lock_after
+        );
+
+        // The lockout ends by itself
+        begin_attempt(&db, "alice", None, now + Duration::minutes(LOCKOUT_MINUTES)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn parallel_guesses_are_all_counted() {
+        let (db, _) = setup().await;
+        let now = start();
+
+        let attempts = (0..20).map(|_| {
+            let db = db.clone();
+            tokio::spawn(async move { begin_attempt(&db, "alice", None, now).await.is_ok() })
+        });
+        let mut admitted = 0;
+        for attempt in attempts.collect::<Vec<_>>() {
+            if attempt.await.unwrap() {
+                admitted += 1;
+            }
+        }
+        assert_eq!(admitted, ACCOUNT_POLICY.delay_after);
+    }
+
+    #[tokio::test]
+    async fn admins_lock_sooner_and_can_be_unlocked() {
+        let (db, user) = setup().await;
+        db.set_user_admin(user.id, true).await.unwrap();
+        let mut now = start();
+
+        for _ in 0..ADMIN_ACCOUNT_POLICY.lock_after {
+            now += Duration::seconds(MAX_DELAY_SECS);
+            begin_attempt(&db, "alice", Some("198.51.100.4"), now).await.unwrap();
+            record_failure(&db, "alice", Some("198.51.100.4"), now).await;
+        }
+        assert!(begin_attempt(&db, "alice", Some("203.0.113.9"), now).await.unwrap_err().locked);
+
+        assert!(unlock_account(&db, &user, "admin").await.unwrap());
+        assert!(!unlock_account(&db, &user, "admin").await.unwrap());
+        begin_attempt(&db, "alice", Some("203.0.113.9"), now).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn addresses_are_throttled_across_accounts() {
+        let (db, user) = setup().await;
+        let now = start();
+        let ip = Some("198.51.100.4");
+
+        // Signing in doesn't use up the address
+        begin_attempt(&db, "alice", ip, now).await.unwrap();
+        record_success(&db, &user, ip).await;
+
+        for i in 0..IP_POLICY.delay_after {
+            let guess = format!("guess{}", i);
+            begin_attempt(&db, &guess, ip, now).await.unwrap();
+            record_failure(&db, &guess, ip, now).await;
+        }
+        assert!(begin_attempt(&db, "someone-else", ip, now).await.is_err());
+        begin_attempt(&db, "alice", Some("203.0.113.9"), now).await.unwrap();
+
+        // Success clears the account, not the address
+        record_success(&db, &user, Some("203.0.113.9")).await;
+        assert!(begin_attempt(&db, "alice", ip, now).await.is_err());
+
+        let later = now + Duration::minutes(FAILURE_WINDOW_MINUTES + 1);
+        cleanup(&db, later).await;
+        begin_attempt(&db, "alice", ip, later).await.unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+// src/services/mailer.rs
+//! Outgoing email. `Mailer` hides how messages leave the server: over SMTP
+//! in production, or into a directory or the log when testing locally.
+use axum::async_trait;
+use lettre::message::{header::ContentType, Mailbox, Message};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::Config;
+
+/// A plain text message to one recipient
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct MailError(pub String);
+
+impl std::fmt::Display for MailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for MailError {}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), MailError>;
+}
+
+fn build_message(from: &Mailbox, email: &Email) -> Result<Message, MailError> {
+    let to: Mailbox = email
+        .to
+        .parse()
+        .map_err(|e| MailError(format!("Invalid recipient {}: {}", email.to, e)))?;
+
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(email.subject.as_str())
+        .header(ContentType::TEXT_PLAIN)
+        .body(email.body.clone())
+        .map_err(|e| MailError(format!("Failed to build message: {}", e)))
+}
+
+/// Sends through an SMTP relay
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &Config, from: Mailbox) -> Result<Self, MailError> {
+        let builder = match config.smtp_tls.as_str() {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host),
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.smtp_host),
+            "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)),
+            other => return Err(MailError(format!("Unknown SMTP_TLS {}", other))),
+        }
+        .map_err(|e| MailError(format!("Invalid SMTP relay {}: {}", config.smtp_host, e)))?
+        .port(config.smtp_port);
+
+        let builder = match (&config.smtp_username, &config.smtp_password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username.clone(), password.clone()))
+            }
+            _ => builder,
+        };
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<(), MailError> {
+        let message = build_message(&self.from, email)?;
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| MailError(format!("SMTP delivery to {} failed: {}", email.to, e)))?;
+        Ok(())
+    }
+}
+
+/// Writes each message to a `.eml` file, for local testing
+pub struct FileMailer {
+    dir: PathBuf,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(dir: PathBuf, from: Mailbox) -> Result<Self, MailError> {
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| MailError(format!("Failed to create {}: {}", dir.display(), e)))?;
+        Ok(Self { dir, from })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<(), MailError> {
+        let message = build_message(&self.from, email)?;
+        let name = format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            hex::encode(&blake3::hash(email.to.as_bytes()).as_bytes()[..4])
+        );
+        let path = self.dir.join(name);
+        tokio::fs::write(&path, message.formatted())
+            .await
+            .map_err(|e| MailError(format!("Failed to write {}: {}", path.display(), e)))?;
+
+        tracing::info!("📧 Wrote email to {} at {}", email.to, path.display());
+        Ok(())
+    }
+}
+
+/// Prints messages to the log instead of sending them. Links in them are
+/// live, so this is only for local testing.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> Result<(), MailError> {
+        tracing::info!(
+            "📧 Email to {}\nSubject: {}\n\n{}",
+            email.to,
+            email.subject,
+            email.body
+        );
+        Ok(())
+    }
+}
+
+/// The mailer selected by `MAILER`
+pub fn from_config(config: &Config) -> Result<Arc<dyn Mailer>, MailError> {
+    let from: Mailbox = config
+        .mail_from
+        .parse()
+        .map_err(|e| MailError(format!("Invalid MAIL_FROM {}: {}", config.mail_from, e)))?;
+
+    Ok(match config.mailer.as_str() {
+        "smtp" => Arc::new(SmtpMailer::new(config, from)?),
+        "file" => Arc::new(FileMailer::new(PathBuf::from(&config.mail_dir), from)?),
+        "log" => Arc::new(LogMailer),
+        other => return Err(MailError(format!("Unknown MAILER {}", other))),
+    })
+}
@@ -1,4 +1,18 @@
 // src/services/mod.rs
+pub mod accounts;
+pub mod login_throttle;
+pub mod mailer;
+pub mod node_client;
+pub mod organizations;
+// Signing helpers are used by the hyrule-node binary
+#[allow(dead_code)]
+pub mod node_protocol;
+pub mod placement;
+pub mod pre_receive;
 pub mod replication;
+pub mod replication_queue;
+pub mod reputation;
+pub mod ssh_server;
+pub mod two_factor;
 
 pub mod health;
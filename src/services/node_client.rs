@@ -0,0 +1,158 @@
+// src/services/node_client.rs
+use crate::models::Node;
+use crate::services::node_protocol::{self, ChallengeResponse, ReplicaAck, StorageChallenge, SyncRequest};
+use std::time::Duration;
+
+const TRANSFER_TIMEOUT_SECS: u64 = 300;
+const CHALLENGE_TIMEOUT_SECS: u64 = 30;
+
+/// HTTP client used by the coordinator to talk to storage nodes
+#[derive(Clone)]
+pub struct NodeClient {
+    http: reqwest::Client,
+}
+
+impl NodeClient {
+    pub fn new() -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(TRANSFER_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+
+        Ok(Self { http })
+    }
+
+    fn base_url(node: &Node) -> String {
+        format!("http://{}:{}", node.address, node.port)
+    }
+
+    fn authorized(request: reqwest::RequestBuilder, node: &Node) -> reqwest::RequestBuilder {
+        match &node.access_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Upload a repository bundle and wait for the node's ref acknowledgement
+    pub async fn push_replica(
+        &self,
+        node: &Node,
+        repo_hash: &str,
+        is_private: bool,
+        bundle: Vec<u8>,
+    ) -> Result<ReplicaAck, String> {
+        let url = format!("{}{}", Self::base_url(node), node_protocol::replica_path(repo_hash));
+
+        let response = Self::authorized(self.http.put(&url), node)
+            .header(reqwest::header::CONTENT_TYPE, node_protocol::BUNDLE_CONTENT_TYPE)
+            .header(node_protocol::PRIVATE_HEADER, if is_private { "1" } else { "0" })
+            .body(bundle)
+            .send()
+            .await
+            .map_err(|e| format!("Transfer to {} failed: {}", node.node_id, e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!(
+                "Node {} rejected replica ({}): {}",
+                node.node_id,
+                status,
+                body.trim()
+            ));
+        }
+
+        response
+            .json::<ReplicaAck>()
+            .await
+            .map_err(|e| format!("Invalid acknowledgement from {}: {}", node.node_id, e))
+    }
+
+    /// Download a node's full copy of a replica as a bundle
+    pub async fn fetch_replica(&self, node: &Node, repo_hash: &str) -> Result<Vec<u8>, String> {
+        let url = format!("{}{}", Self::base_url(node), node_protocol::replica_path(repo_hash));
+
+        let response = Self::authorized(self.http.get(&url), node)
+            .send()
+            .await
+            .map_err(|e| format!("Fetch from {} failed: {}", node.node_id, e))?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Node {} could not send replica: {}",
+                node.node_id,
+                response.status()
+            ));
+        }
+
+        response
+            .bytes()
+            .await
+            .map(|b| b.to_vec())
+            .map_err(|e| format!("Download from {} failed: {}", node.node_id, e))
+    }
+
+    /// Tell a node to catch its replica up with `request.refs`
+    pub async fn sync_replica(&self, node: &Node, request: &SyncRequest) -> Result<ReplicaAck, String> {
+        let url = format!(
+            "{}{}",
+            Self::base_url(node),
+            node_protocol::sync_path(&request.repo_hash)
+        );
+
+        let response = Self::authorized(self.http.post(&url), node)
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| format!("Sync with {} failed: {}", node.node_id, e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!(
+                "Node {} could not sync replica ({}): {}",
+                node.node_id,
+                status,
+                body.trim()
+            ));
+        }
+
+        response
+            .json::<ReplicaAck>()
+            .await
+            .map_err(|e| format!("Invalid acknowledgement from {}: {}", node.node_id, e))
+    }
+
+    /// Ask a node to prove it still holds the given objects
+    pub async fn challenge(
+        &self,
+        node: &Node,
+        challenge: &StorageChallenge,
+    ) -> Result<ChallengeResponse, String> {
+        let url = format!(
+            "{}{}",
+            Self::base_url(node),
+            node_protocol::challenge_path(&challenge.repo_hash)
+        );
+
+        let response = Self::authorized(self.http.post(&url), node)
+            .timeout(Duration::from_secs(CHALLENGE_TIMEOUT_SECS))
+            .json(challenge)
+            .send()
+            .await
+            .map_err(|e| format!("Challenge to {} failed: {}", node.node_id, e))?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Node {} could not answer challenge: {}",
+                node.node_id,
+                response.status()
+            ));
+        }
+
+        response
+            .json::<ChallengeResponse>()
+            .await
+            .map_err(|e| format!("Invalid challenge response from {}: {}", node.node_id, e))
+    }
+}
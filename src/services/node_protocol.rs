@@ -0,0 +1,496 @@
+// src/services/node_protocol.rs
+// Wire types exchanged between the coordinator and storage nodes.
+//
+// This file is also compiled into the `hyrule-node` binary, so it must only
+// depend on external crates and never on `crate::` paths.
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Content type of a replica transfer body
+pub const BUNDLE_CONTENT_TYPE: &str = "application/x-git-bundle";
+
+/// Content type of a thin pack sent to bring a replica up to date
+pub const PACK_CONTENT_TYPE: &str = "application/x-git-packfile";
+
+/// Header telling the node whether a replica may be served publicly
+pub const PRIVATE_HEADER: &str = "x-hyrule-private";
+
+/// Maximum clock difference accepted on signed messages
+pub const MAX_CLOCK_SKEW_SECS: i64 = 300;
+
+/// Path on a node that accepts (PUT) or hands back (GET) a full repository bundle
+pub fn replica_path(repo_hash: &str) -> String {
+    format!("/replicas/{}", repo_hash)
+}
+
+/// Path on a node that answers proof-of-storage challenges
+pub fn challenge_path(repo_hash: &str) -> String {
+    format!("/replicas/{}/challenge", repo_hash)
+}
+
+/// Path on a node that is told to catch a replica up with new refs
+pub fn sync_path(repo_hash: &str) -> String {
+    format!("/replicas/{}/sync", repo_hash)
+}
+
+/// Path on the coordinator that serves thin packs to syncing nodes
+pub fn pack_path(repo_hash: &str) -> String {
+    format!("/api/nodes/replicas/{}/pack", repo_hash)
+}
+
+/// A node's id is the hex encoding of its ed25519 public key
+pub fn node_id_from_key(key: &VerifyingKey) -> String {
+    hex::encode(key.as_bytes())
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureError {
+    Missing,
+    MalformedNodeId,
+    MalformedSignature,
+    Invalid,
+    Stale,
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SignatureError::Missing => write!(f, "message is not signed"),
+            SignatureError::MalformedNodeId => write!(f, "node id is not an ed25519 public key"),
+            SignatureError::MalformedSignature => write!(f, "signature is malformed"),
+            SignatureError::Invalid => write!(f, "signature does not verify"),
+            SignatureError::Stale => write!(f, "timestamp outside the accepted window"),
+        }
+    }
+}
+
+/// A message signed by the node named in it
+pub trait SignedMessage {
+    /// Canonical bytes covered by the signature
+    fn signing_payload(&self) -> Vec<u8>;
+    fn signer(&self) -> &str;
+    fn timestamp(&self) -> i64;
+    fn signature(&self) -> Option<&str>;
+    fn set_signature(&mut self, signature: String);
+}
+
+/// Stamp and sign a message with the node's key
+pub fn sign<M: SignedMessage>(message: &mut M, key: &SigningKey) {
+    let signature = key.sign(&message.signing_payload());
+    message.set_signature(hex::encode(signature.to_bytes()));
+}
+
+/// Verify a message against the public key encoded in its signer id
+pub fn verify<M: SignedMessage>(message: &M) -> Result<(), SignatureError> {
+    verify_at(message, unix_now())
+}
+
+pub fn verify_at<M: SignedMessage>(message: &M, now: i64) -> Result<(), SignatureError> {
+    let signature_hex = message.signature().ok_or(SignatureError::Missing)?;
+
+    let key_bytes: [u8; 32] = hex::decode(message.signer())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or(SignatureError::MalformedNodeId)?;
+    let key = VerifyingKey::from_bytes(&key_bytes).map_err(|_| SignatureError::MalformedNodeId)?;
+
+    let signature_bytes: [u8; 64] = hex::decode(signature_hex)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or(SignatureError::MalformedSignature)?;
+    let signature = Signature::from_bytes(&signature_bytes);
+
+    key.verify(&message.signing_payload(), &signature)
+        .map_err(|_| SignatureError::Invalid)?;
+
+    if (now - message.timestamp()).abs() > MAX_CLOCK_SKEW_SECS {
+        return Err(SignatureError::Stale);
+    }
+
+    Ok(())
+}
+
+/// Build a domain-separated payload from a message kind and its fields.
+/// Fields are length-prefixed, so no field contents can pass for a
+/// different set of fields.
+fn payload(kind: &str, fields: &[&str]) -> Vec<u8> {
+    let mut out = format!("hyrule-{}-v2", kind);
+    for field in fields {
+        out.push_str(&format!("\n{}:{}", field.len(), field));
+    }
+    out.into_bytes()
+}
+
+/// Encode a list as one payload field, length-prefixing each item
+fn list<'a>(items: impl IntoIterator<Item = &'a str>) -> String {
+    items
+        .into_iter()
+        .map(|item| format!("{}:{}", item.len(), item))
+        .collect()
+}
+
+/// Sent by a node when it joins the network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterNodeRequest {
+    pub node_id: String,
+    pub address: String,
+    pub port: i32,
+    pub storage_capacity: i64,
+    pub is_anchor: bool,
+    /// Token the coordinator must present when pushing replicas
+    #[serde(default)]
+    pub access_token: Option<String>,
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl SignedMessage for RegisterNodeRequest {
+    fn signing_payload(&self) -> Vec<u8> {
+        payload(
+            "register",
+            &[
+                &self.node_id,
+                &self.address,
+                &self.port.to_string(),
+                &self.storage_capacity.to_string(),
+                if self.is_anchor { "anchor" } else { "p2p" },
+                self.access_token.as_deref().unwrap_or(""),
+                &self.timestamp.to_string(),
+            ],
+        )
+    }
+    fn signer(&self) -> &str {
+        &self.node_id
+    }
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+    fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+    fn set_signature(&mut self, signature: String) {
+        self.signature = Some(signature);
+    }
+}
+
+/// Periodic liveness report from a node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeHeartbeat {
+    pub node_id: String,
+    pub storage_used: i64,
+    pub hosted_repos: Vec<String>,
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl SignedMessage for NodeHeartbeat {
+    fn signing_payload(&self) -> Vec<u8> {
+        payload(
+            "heartbeat",
+            &[
+                &self.node_id,
+                &self.storage_used.to_string(),
+                &list(self.hosted_repos.iter().map(String::as_str)),
+                &self.timestamp.to_string(),
+            ],
+        )
+    }
+    fn signer(&self) -> &str {
+        &self.node_id
+    }
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+    fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+    fn set_signature(&mut self, signature: String) {
+        self.signature = Some(signature);
+    }
+}
+
+/// Sent by a node once it has stored a replica and checked its refs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicaAck {
+    pub node_id: String,
+    pub repo_hash: String,
+    /// refname -> object id, as reported by `git for-each-ref` on the node
+    pub refs: BTreeMap<String, String>,
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl SignedMessage for ReplicaAck {
+    fn signing_payload(&self) -> Vec<u8> {
+        let refs = list(self.refs.iter().flat_map(|(name, oid)| [name.as_str(), oid.as_str()]));
+        payload(
+            "replica-ack",
+            &[&self.node_id, &self.repo_hash, &refs, &self.timestamp.to_string()],
+        )
+    }
+    fn signer(&self) -> &str {
+        &self.node_id
+    }
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+    fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+    fn set_signature(&mut self, signature: String) {
+        self.signature = Some(signature);
+    }
+}
+
+/// Sent by the coordinator after a push: the refs a replica should now hold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncRequest {
+    pub repo_hash: String,
+    /// refname -> object id of every ref on the primary
+    pub refs: BTreeMap<String, String>,
+}
+
+/// Sent by a node to fetch the objects between its refs and the new tips
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackRequest {
+    pub node_id: String,
+    pub repo_hash: String,
+    /// New tips the node is missing
+    pub wants: Vec<String>,
+    /// Tips the node already holds; the pack may delta against them
+    pub haves: Vec<String>,
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl SignedMessage for PackRequest {
+    fn signing_payload(&self) -> Vec<u8> {
+        payload(
+            "pack-request",
+            &[
+                &self.node_id,
+                &self.repo_hash,
+                &list(self.wants.iter().map(String::as_str)),
+                &list(self.haves.iter().map(String::as_str)),
+                &self.timestamp.to_string(),
+            ],
+        )
+    }
+    fn signer(&self) -> &str {
+        &self.node_id
+    }
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+    fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+    fn set_signature(&mut self, signature: String) {
+        self.signature = Some(signature);
+    }
+}
+
+/// Proof-of-storage challenge for one replica
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageChallenge {
+    pub repo_hash: String,
+    pub object_ids: Vec<String>,
+    pub nonce: String,
+}
+
+/// Signed answer to a `StorageChallenge`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    pub node_id: String,
+    pub repo_hash: String,
+    pub nonce: String,
+    /// `challenge_digest` over the requested objects
+    pub digest: String,
+    #[serde(default)]
+    pub timestamp: i64,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl SignedMessage for ChallengeResponse {
+    fn signing_payload(&self) -> Vec<u8> {
+        payload(
+            "challenge-response",
+            &[
+                &self.node_id,
+                &self.repo_hash,
+                &self.nonce,
+                &self.digest,
+                &self.timestamp.to_string(),
+            ],
+        )
+    }
+    fn signer(&self) -> &str {
+        &self.node_id
+    }
+    fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+    fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+    fn set_signature(&mut self, signature: String) {
+        self.signature = Some(signature);
+    }
+}
+
+/// blake3 over the nonce followed by each object's id, length and contents
+pub fn challenge_digest(nonce: &str, objects: &[(String, Vec<u8>)]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(nonce.as_bytes());
+    for (oid, content) in objects {
+        hasher.update(oid.as_bytes());
+        hasher.update(&(content.len() as u64).to_le_bytes());
+        hasher.update(content);
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Parse `git cat-file --batch` output into (object id, contents) pairs
+pub fn parse_cat_file_batch(output: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut objects = Vec::new();
+    let mut rest = output;
+
+    while !rest.is_empty() {
+        let newline = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or("truncated object header")?;
+        let header = String::from_utf8_lossy(&rest[..newline]).to_string();
+        rest = &rest[newline + 1..];
+
+        let mut parts = header.split(' ');
+        let oid = parts.next().unwrap_or_default().to_string();
+        let kind = parts.next().unwrap_or_default();
+        if kind == "missing" || kind == "ambiguous" {
+            return Err(format!("object {} is {}", oid, kind));
+        }
+        let size: usize = parts
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| format!("bad object header: {}", header))?;
+
+        // Contents are followed by a single newline
+        if rest.len() < size + 1 {
+            return Err(format!("truncated contents for {}", oid));
+        }
+        objects.push((oid, rest[..size].to_vec()));
+        rest = &rest[size + 1..];
+    }
+
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(key: &SigningKey) -> NodeHeartbeat {
+        let mut hb = NodeHeartbeat {
+            node_id: node_id_from_key(&key.verifying_key()),
+            storage_used: 1024,
+            hosted_repos: vec!["a".repeat(40)],
+            timestamp: 1_700_000_000,
+            signature: None,
+        };
+        sign(&mut hb, key);
+        hb
+    }
+
+    #[test]
+    fn test_signed_heartbeat_verifies() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let hb = heartbeat(&key);
+        assert_eq!(verify_at(&hb, 1_700_000_010), Ok(()));
+    }
+
+    #[test]
+    fn test_tampered_claims_rejected() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let mut hb = heartbeat(&key);
+        hb.hosted_repos.push("b".repeat(40));
+        assert_eq!(verify_at(&hb, 1_700_000_010), Err(SignatureError::Invalid));
+    }
+
+    #[test]
+    fn test_field_contents_cannot_shift_fields() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let ack = |refs: &[(&str, &str)]| {
+            let mut ack = ReplicaAck {
+                node_id: node_id_from_key(&key.verifying_key()),
+                repo_hash: "a".repeat(40),
+                refs: refs.iter().map(|(name, oid)| (name.to_string(), oid.to_string())).collect(),
+                timestamp: 1_700_000_000,
+                signature: None,
+            };
+            sign(&mut ack, &key);
+            ack
+        };
+
+        // Ref names may contain ',' and '=', so joining them used to sign
+        // both of these the same
+        let one = ack(&[("refs/heads/a=1,refs/heads/b", "2")]);
+        let mut two = ack(&[("refs/heads/a", "1"), ("refs/heads/b", "2")]);
+        assert_ne!(one.signing_payload(), two.signing_payload());
+        two.signature = one.signature.clone();
+        assert_eq!(verify_at(&two, 1_700_000_010), Err(SignatureError::Invalid));
+
+        let mut joined = heartbeat(&key);
+        joined.hosted_repos = vec!["a,b".to_string()];
+        let mut split = heartbeat(&key);
+        split.hosted_repos = vec!["a".to_string(), "b".to_string()];
+        assert_ne!(joined.signing_payload(), split.signing_payload());
+    }
+
+    #[test]
+    fn test_cat_file_batch_digest() {
+        let output = b"aaaa blob 5\nhello\nbbbb tree 0\n\n";
+        let objects = parse_cat_file_batch(output).unwrap();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0], ("aaaa".to_string(), b"hello".to_vec()));
+        assert!(objects[1].1.is_empty());
+
+        // Same objects under a different nonce must not produce the same digest
+        assert_ne!(challenge_digest("n1", &objects), challenge_digest("n2", &objects));
+        assert!(parse_cat_file_batch(b"cccc missing\n").is_err());
+    }
+
+    #[test]
+    fn test_impersonation_and_replay_rejected() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let other = SigningKey::from_bytes(&[9u8; 32]);
+
+        let mut hb = heartbeat(&key);
+        hb.node_id = node_id_from_key(&other.verifying_key());
+        assert_eq!(verify_at(&hb, 1_700_000_010), Err(SignatureError::Invalid));
+
+        let hb = heartbeat(&key);
+        assert_eq!(verify_at(&hb, 1_700_001_000), Err(SignatureError::Stale));
+
+        let mut unsigned = heartbeat(&key);
+        unsigned.signature = None;
+        assert_eq!(verify_at(&unsigned, 1_700_000_010), Err(SignatureError::Missing));
+    }
+}
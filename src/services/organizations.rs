@@ -0,0 +1,249 @@
+// src/services/organizations.rs
+//! Rules for organizations shared by the web pages and the API. Errors
+//! carry the status and a message fit to show the user.
+use axum::http::StatusCode;
+
+use crate::auth::repo_access::RepoRole;
+use crate::db::Database;
+use crate::models::{CreateRepoRequest, Organization, Repository, Team, User};
+use crate::storage::GitStorage;
+
+pub type OrgError = (StatusCode, String);
+
+/// Roles a member can hold in an organization
+pub const ORG_ROLES: [&str; 2] = ["owner", "member"];
+
+fn internal(message: &str) -> OrgError {
+    (StatusCode::INTERNAL_SERVER_ERROR, message.to_string())
+}
+
+/// Create an organization with `creator_id` as its owner. Organization
+/// names share the `/:owner` namespace with usernames.
+pub async fn create_organization(
+    db: &Database,
+    creator_id: i64,
+    name: &str,
+    description: Option<&str>,
+) -> Result<Organization, OrgError> {
+    let name = name.trim();
+    crate::utils::validation::validate_username(name)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.replacen("Username", "Organization name", 1)))?;
+    let description = description.map(str::trim).filter(|d| !d.is_empty());
+    if description.is_some_and(|d| d.len() > crate::models::MAX_DESCRIPTION_LEN) {
+        return Err((StatusCode::BAD_REQUEST, "Description is too long".to_string()));
+    }
+
+    if db
+        .account_name_taken(name)
+        .await
+        .map_err(|_| internal("Failed to create organization"))?
+    {
+        return Err((StatusCode::CONFLICT, format!("The name {} is already taken", name)));
+    }
+
+    db.create_organization(name, description, creator_id)
+        .await
+        .map_err(|_| internal("Failed to create organization"))
+}
+
+/// Look the organization up and check that the user owns it
+pub async fn owned_organization(db: &Database, name: &str, user_id: i64) -> Result<Organization, OrgError> {
+    let org = db
+        .get_organization_by_name(name)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, "Organization not found".to_string()))?;
+    let role = db
+        .get_org_role(org.id, user_id)
+        .await
+        .map_err(|_| internal("Failed to load organization"))?;
+
+    match role.as_deref() {
+        Some("owner") => Ok(org),
+        _ => Err((StatusCode::FORBIDDEN, "Only organization owners can do that".to_string())),
+    }
+}
+
+async fn find_user(db: &Database, username: &str) -> Result<User, OrgError> {
+    db.get_user_by_username(username.trim())
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, format!("Unknown user {}", username.trim())))
+}
+
+/// Refuse to leave the organization without an owner
+async fn ensure_other_owner(db: &Database, org: &Organization, user_id: i64) -> Result<(), OrgError> {
+    let role = db
+        .get_org_role(org.id, user_id)
+        .await
+        .map_err(|_| internal("Failed to update members"))?;
+    if role.as_deref() != Some("owner") {
+        return Ok(());
+    }
+
+    let owners = db
+        .count_org_owners(org.id)
+        .await
+        .map_err(|_| internal("Failed to update members"))?;
+    if owners <= 1 {
+        return Err((StatusCode::BAD_REQUEST, "An organization needs at least one owner".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Add a member or change their role
+pub async fn set_member(db: &Database, org: &Organization, username: &str, role: &str) -> Result<User, OrgError> {
+    if !ORG_ROLES.contains(&role) {
+        return Err((StatusCode::BAD_REQUEST, "Role must be owner or member".to_string()));
+    }
+    let user = find_user(db, username).await?;
+    if role != "owner" {
+        ensure_other_owner(db, org, user.id).await?;
+    }
+
+    db.set_org_member(org.id, user.id, role)
+        .await
+        .map_err(|_| internal("Failed to update members"))?;
+
+    Ok(user)
+}
+
+pub async fn remove_member(db: &Database, org: &Organization, username: &str) -> Result<(), OrgError> {
+    let user = find_user(db, username).await?;
+    ensure_other_owner(db, org, user.id).await?;
+
+    let removed = db
+        .remove_org_member(org.id, user.id)
+        .await
+        .map_err(|_| internal("Failed to update members"))?;
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, format!("{} is not a member", user.username)));
+    }
+
+    Ok(())
+}
+
+pub async fn create_team(
+    db: &Database,
+    org: &Organization,
+    name: &str,
+    description: Option<&str>,
+) -> Result<Team, OrgError> {
+    let name = name.trim();
+    crate::utils::validation::validate_repo_name(name)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.replacen("Repository name", "Team name", 1)))?;
+    if db.get_team_by_name(org.id, name).await.is_ok() {
+        return Err((StatusCode::CONFLICT, format!("Team {} already exists", name)));
+    }
+
+    let description = description.map(str::trim).filter(|d| !d.is_empty());
+    db.create_team(org.id, name, description)
+        .await
+        .map_err(|_| internal("Failed to create team"))
+}
+
+pub async fn find_team(db: &Database, org: &Organization, name: &str) -> Result<Team, OrgError> {
+    db.get_team_by_name(org.id, name)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, format!("Unknown team {}", name)))
+}
+
+/// Add an organization member to a team
+pub async fn add_team_member(db: &Database, org: &Organization, team: &Team, username: &str) -> Result<(), OrgError> {
+    let user = find_user(db, username).await?;
+    let role = db
+        .get_org_role(org.id, user.id)
+        .await
+        .map_err(|_| internal("Failed to update team"))?;
+    if role.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("{} must join {} before joining a team", user.username, org.name),
+        ));
+    }
+
+    db.add_team_member(team.id, user.id)
+        .await
+        .map_err(|_| internal("Failed to update team"))
+}
+
+pub async fn remove_team_member(db: &Database, team: &Team, username: &str) -> Result<(), OrgError> {
+    let user = find_user(db, username).await?;
+    let removed = db
+        .remove_team_member(team.id, user.id)
+        .await
+        .map_err(|_| internal("Failed to update team"))?;
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, format!("{} is not on the team", user.username)));
+    }
+
+    Ok(())
+}
+
+/// Find one of the organization's repositories by name
+pub async fn find_org_repository(db: &Database, org: &Organization, name: &str) -> Result<Repository, OrgError> {
+    db.get_repository_by_owner_name(&org.name, name.trim())
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, format!("{} has no repository {}", org.name, name.trim())))
+}
+
+/// Give a team read, write or admin access to one of the organization's
+/// repositories
+pub async fn grant_team_repo(
+    db: &Database,
+    org: &Organization,
+    team: &Team,
+    repo_name: &str,
+    role: &str,
+) -> Result<Repository, OrgError> {
+    let role = RepoRole::parse_grantable(role)
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Role must be read, write or admin".to_string()))?;
+    let repo = find_org_repository(db, org, repo_name).await?;
+
+    db.set_team_repo(team.id, &repo.repo_hash, role.as_str())
+        .await
+        .map_err(|_| internal("Failed to update team"))?;
+
+    Ok(repo)
+}
+
+pub async fn revoke_team_repo(db: &Database, org: &Organization, team: &Team, repo_name: &str) -> Result<(), OrgError> {
+    let repo = find_org_repository(db, org, repo_name).await?;
+    let removed = db
+        .remove_team_repo(team.id, &repo.repo_hash)
+        .await
+        .map_err(|_| internal("Failed to update team"))?;
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, format!("{} has no access to {}", team.name, repo.name)));
+    }
+
+    Ok(())
+}
+
+/// Create and initialize a repository owned by the organization
+pub async fn create_repository(
+    db: &Database,
+    git_storage: &GitStorage,
+    org: &Organization,
+    creator_id: i64,
+    request: &CreateRepoRequest,
+) -> Result<Repository, OrgError> {
+    crate::utils::validation::validate_repo_name(&request.name)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    if db.org_repository_name_taken(org.id, &request.name).await.unwrap_or(false) {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{} already has a repository with this name", org.name),
+        ));
+    }
+
+    let repo_hash = crate::utils::hash::generate_repo_hash(&format!("{}/{}", org.name, request.name), creator_id);
+    let repo = db
+        .create_org_repository(request, org.id, creator_id, &repo_hash)
+        .await
+        .map_err(|_| internal("Failed to create repository"))?;
+    git_storage
+        .init_repo(&repo_hash)
+        .map_err(|_| internal("Failed to initialize repository storage"))?;
+
+    Ok(repo)
+}
@@ -0,0 +1,256 @@
+// src/services/placement.rs
+use crate::models::{Node, NodeInfo};
+use serde::Serialize;
+use std::net::IpAddr;
+
+/// What a placement policy knows about the repository being replicated
+pub struct PlacementContext<'a> {
+    pub repo_hash: &'a str,
+    /// Bytes the new replica will need on the target
+    pub repo_size: i64,
+    /// Nodes already hosting the repository
+    pub existing: &'a [NodeInfo],
+    /// The repository must end up on at least one anchor node
+    pub require_anchor: bool,
+}
+
+/// One node's standing in a placement decision
+#[derive(Debug, Clone, Serialize)]
+pub struct PlacementCandidate {
+    pub node_id: String,
+    pub failure_domain: String,
+    pub eligible: bool,
+    pub score: f64,
+    pub reasons: Vec<String>,
+}
+
+/// Outcome of a placement run, kept so admins can see why a node was chosen
+#[derive(Debug, Clone, Serialize)]
+pub struct PlacementDecision {
+    pub policy: String,
+    pub repo_hash: String,
+    pub repo_size: i64,
+    pub require_anchor: bool,
+    pub chosen: Option<String>,
+    /// Every candidate, best first
+    pub candidates: Vec<PlacementCandidate>,
+}
+
+/// Strategy for ranking nodes as replication targets
+pub trait PlacementPolicy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Score a single candidate; ineligible candidates are never chosen
+    fn evaluate(&self, ctx: &PlacementContext<'_>, node: &Node) -> PlacementCandidate;
+
+    /// Rank all candidates and pick the best eligible one
+    fn place(&self, ctx: &PlacementContext<'_>, nodes: &[Node]) -> PlacementDecision {
+        let mut candidates: Vec<PlacementCandidate> = nodes
+            .iter()
+            .filter(|node| !ctx.existing.iter().any(|e| e.node_id == node.node_id))
+            .map(|node| self.evaluate(ctx, node))
+            .collect();
+
+        candidates.sort_by(|a, b| {
+            b.eligible
+                .cmp(&a.eligible)
+                .then(b.score.total_cmp(&a.score))
+                .then(a.node_id.cmp(&b.node_id))
+        });
+
+        PlacementDecision {
+            policy: self.name().to_string(),
+            repo_hash: ctx.repo_hash.to_string(),
+            repo_size: ctx.repo_size,
+            require_anchor: ctx.require_anchor,
+            chosen: candidates.first().filter(|c| c.eligible).map(|c| c.node_id.clone()),
+            candidates,
+        }
+    }
+}
+
+/// Weighs free capacity, reputation and anchor status, and penalizes
+/// nodes that share a failure domain with an existing replica
+pub struct WeightedPlacement {
+    pub capacity_weight: f64,
+    pub reputation_weight: f64,
+    pub anchor_bonus: f64,
+    /// Subtracted once per existing replica in the same failure domain
+    pub shared_domain_penalty: f64,
+}
+
+impl Default for WeightedPlacement {
+    fn default() -> Self {
+        Self {
+            capacity_weight: 40.0,
+            reputation_weight: 40.0,
+            anchor_bonus: 10.0,
+            shared_domain_penalty: 50.0,
+        }
+    }
+}
+
+impl PlacementPolicy for WeightedPlacement {
+    fn name(&self) -> &'static str {
+        "weighted"
+    }
+
+    fn evaluate(&self, ctx: &PlacementContext<'_>, node: &Node) -> PlacementCandidate {
+        let domain = failure_domain(&node.address);
+        let mut reasons = Vec::new();
+        let mut eligible = true;
+
+        // Capacity: the replica has to fit, emptier nodes score higher
+        let free = node.storage_capacity - node.storage_used;
+        let capacity_score = if node.storage_capacity <= 0 || free < ctx.repo_size {
+            eligible = false;
+            reasons.push(format!("not enough free space ({} bytes free, {} needed)", free.max(0), ctx.repo_size));
+            0.0
+        } else {
+            let fraction = (free - ctx.repo_size) as f64 / node.storage_capacity as f64;
+            reasons.push(format!("{:.0}% capacity free after placement", fraction * 100.0));
+            fraction * self.capacity_weight
+        };
+
+        let reputation_score = node.reputation_score.clamp(0, 100) as f64 / 100.0 * self.reputation_weight;
+        reasons.push(format!("reputation {}/100", node.reputation_score));
+
+        let has_anchor = ctx.existing.iter().any(|e| e.is_anchor);
+        let anchor_score = if node.is_anchor != 0 {
+            reasons.push("anchor node".to_string());
+            self.anchor_bonus
+        } else {
+            if ctx.require_anchor && !has_anchor {
+                eligible = false;
+                reasons.push("repository requires an anchor replica".to_string());
+            }
+            0.0
+        };
+
+        let shared = ctx
+            .existing
+            .iter()
+            .filter(|e| failure_domain(&e.address) == domain)
+            .count();
+        if shared > 0 {
+            reasons.push(format!("shares failure domain {} with {} replica(s)", domain, shared));
+        } else {
+            reasons.push(format!("new failure domain {}", domain));
+        }
+
+        PlacementCandidate {
+            node_id: node.node_id.clone(),
+            failure_domain: domain,
+            eligible,
+            score: capacity_score + reputation_score + anchor_score
+                - shared as f64 * self.shared_domain_penalty,
+            reasons,
+        }
+    }
+}
+
+/// Group addresses that are likely to fail together: the /24 for IPv4,
+/// the /48 for IPv6 and the host name itself otherwise
+pub fn failure_domain(address: &str) -> String {
+    match address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        }
+        Ok(IpAddr::V6(ip)) => {
+            let s = ip.segments();
+            format!("{:x}:{:x}:{:x}::/48", s[0], s[1], s[2])
+        }
+        Err(_) => address.to_ascii_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str, address: &str, capacity: i64, used: i64, reputation: i32, anchor: bool) -> Node {
+        Node {
+            node_id: id.to_string(),
+            address: address.to_string(),
+            port: 4000,
+            last_seen: String::new(),
+            reputation_score: reputation,
+            storage_capacity: capacity,
+            storage_used: used,
+            is_anchor: anchor as i64,
+            is_banned: 0,
+            state: "online".to_string(),
+            state_changed_at: None,
+            access_token: None,
+        }
+    }
+
+    fn hosted(node: &Node) -> NodeInfo {
+        NodeInfo {
+            node_id: node.node_id.clone(),
+            address: node.address.clone(),
+            port: node.port,
+            is_anchor: node.is_anchor != 0,
+        }
+    }
+
+    fn ctx<'a>(existing: &'a [NodeInfo], require_anchor: bool) -> PlacementContext<'a> {
+        PlacementContext { repo_hash: "repo", repo_size: 100, existing, require_anchor }
+    }
+
+    #[test]
+    fn test_failure_domains() {
+        assert_eq!(failure_domain("10.1.2.3"), "10.1.2.0/24");
+        assert_eq!(failure_domain("10.1.2.200"), failure_domain("10.1.2.3"));
+        assert_eq!(failure_domain("2001:db8:1:2::1"), "2001:db8:1::/48");
+        assert_eq!(failure_domain("Node.Example.com"), "node.example.com");
+    }
+
+    #[test]
+    fn test_prefers_free_space_and_skips_full_nodes() {
+        let nodes = vec![
+            node("full", "10.0.0.1", 1000, 950, 100, false),
+            node("busy", "10.0.1.1", 1000, 600, 100, false),
+            node("empty", "10.0.2.1", 1000, 0, 100, false),
+        ];
+        let decision = WeightedPlacement::default().place(&ctx(&[], false), &nodes);
+
+        assert_eq!(decision.chosen.as_deref(), Some("empty"));
+        assert!(!decision.candidates.iter().find(|c| c.node_id == "full").unwrap().eligible);
+    }
+
+    #[test]
+    fn test_spreads_across_failure_domains() {
+        let first = node("a", "10.0.0.1", 1000, 0, 100, false);
+        let same_subnet = node("b", "10.0.0.2", 1000, 0, 100, false);
+        let other_subnet = node("c", "10.9.0.1", 1000, 300, 80, false);
+        let existing = [hosted(&first)];
+
+        let decision = WeightedPlacement::default()
+            .place(&ctx(&existing, false), &[first, same_subnet, other_subnet]);
+
+        assert_eq!(decision.chosen.as_deref(), Some("c"));
+        assert!(decision.candidates.iter().all(|c| c.node_id != "a"));
+    }
+
+    #[test]
+    fn test_require_anchor() {
+        let nodes = vec![
+            node("p2p", "10.0.0.1", 1000, 0, 100, false),
+            node("anchor", "10.0.1.1", 1000, 900, 60, true),
+        ];
+        let policy = WeightedPlacement::default();
+
+        assert_eq!(policy.place(&ctx(&[], true), &nodes).chosen.as_deref(), Some("anchor"));
+        assert_eq!(policy.place(&ctx(&[], false), &nodes).chosen.as_deref(), Some("p2p"));
+
+        // Once an anchor holds a replica the requirement is met
+        let existing = [hosted(&nodes[1])];
+        assert_eq!(policy.place(&ctx(&existing, true), &nodes).chosen.as_deref(), Some("p2p"));
+
+        // No anchor available means no placement at all
+        let decision = policy.place(&ctx(&[], true), &nodes[..1]);
+        assert_eq!(decision.chosen, None);
+    }
+}
@@ -0,0 +1,705 @@
+// src/services/pre_receive.rs
+use crate::db::Database;
+use crate::models::{ProtectedBranch, PushRules};
+use crate::storage::GitStorage;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process::{Output, Stdio};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+pub const ZERO_OID: &str = "0000000000000000000000000000000000000000";
+
+/// One `<old> <new> <ref>` command from a receive-pack request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefUpdate {
+    pub old: String,
+    pub new: String,
+    pub name: String,
+}
+
+impl RefUpdate {
+    pub fn is_create(&self) -> bool {
+        self.old == ZERO_OID
+    }
+
+    pub fn is_delete(&self) -> bool {
+        self.new == ZERO_OID
+    }
+
+    /// Branch name for updates to `refs/heads/*`
+    pub fn branch(&self) -> Option<&str> {
+        self.name.strip_prefix("refs/heads/")
+    }
+}
+
+/// The command section that precedes the pack in a receive-pack request
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PushCommands {
+    pub updates: Vec<RefUpdate>,
+    /// Capabilities sent after the first command
+    pub capabilities: Vec<String>,
+}
+
+impl PushCommands {
+    fn has_capability(&self, name: &str) -> bool {
+        self.capabilities.iter().any(|c| c == name)
+    }
+
+    /// A pack follows unless every command deletes a ref
+    fn expects_pack(&self) -> bool {
+        self.updates.iter().any(|u| !u.is_delete())
+    }
+}
+
+/// Read one pkt-line; `None` is a flush packet
+async fn read_pkt_line<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>, String> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len).await.map_err(|_| "Truncated push request".to_string())?;
+    let len = std::str::from_utf8(&len)
+        .ok()
+        .and_then(|l| usize::from_str_radix(l, 16).ok())
+        .ok_or("Malformed pkt-line length")?;
+
+    match len {
+        0 => Ok(None),
+        1..=3 => Err("Malformed pkt-line length".to_string()),
+        _ => {
+            let mut data = vec![0u8; len - 4];
+            reader.read_exact(&mut data).await.map_err(|_| "Truncated push request".to_string())?;
+            Ok(Some(data))
+        }
+    }
+}
+
+/// Parse the ref update commands, leaving `reader` at the start of the pack
+pub async fn read_commands<R: AsyncRead + Unpin>(reader: &mut R) -> Result<PushCommands, String> {
+    let mut commands = PushCommands::default();
+
+    while let Some(line) = read_pkt_line(reader).await? {
+        let line = String::from_utf8(line).map_err(|_| "Push command is not UTF-8")?;
+        let line = line.trim_end_matches('\n');
+
+        // Pushes from shallow clones list their shallow commits first
+        if line.starts_with("shallow ") {
+            continue;
+        }
+
+        let (command, capabilities) = line.split_once('\0').unwrap_or((line, ""));
+        if commands.updates.is_empty() {
+            commands.capabilities = capabilities.split(' ').filter(|c| !c.is_empty()).map(String::from).collect();
+        }
+
+        let mut parts = command.splitn(3, ' ');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(old), Some(new), Some(name)) if old.len() == 40 && new.len() == 40 => {
+                commands.updates.push(RefUpdate {
+                    old: old.to_string(),
+                    new: new.to_string(),
+                    name: name.to_string(),
+                });
+            }
+            _ => return Err(format!("Malformed push command: {}", command)),
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Keeps a copy of everything read through it, so the commands can be
+/// replayed to git exactly as they arrived
+struct Recorded<R> {
+    inner: R,
+    bytes: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Recorded<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let start = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if poll.is_ready() {
+            let read = buf.filled()[start..].to_vec();
+            self.bytes.extend_from_slice(&read);
+        }
+        poll
+    }
+}
+
+/// Match `*` (within one path segment), `**` (across segments) and `?`
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(p: &[u8], t: &[u8]) -> bool {
+        match p {
+            [] => t.is_empty(),
+            [b'*', b'*', rest @ ..] => {
+                let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+                (0..=t.len()).any(|i| matches(rest, &t[i..]))
+            }
+            [b'*', rest @ ..] => (0..=t.len())
+                .take_while(|&i| i == 0 || t[i - 1] != b'/')
+                .any(|i| matches(rest, &t[i..])),
+            [b'?', rest @ ..] => !t.is_empty() && t[0] != b'/' && matches(rest, &t[1..]),
+            [c, rest @ ..] => t.first() == Some(c) && matches(rest, &t[1..]),
+        }
+    }
+
+    matches(pattern.as_bytes(), text.as_bytes())
+}
+
+/// Patterns without a slash match the file name anywhere in the tree,
+/// like in `.gitignore`
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_start_matches('/');
+    if pattern.contains('/') {
+        glob_match(pattern, path)
+    } else {
+        path.split('/').any(|segment| glob_match(pattern, segment))
+    }
+}
+
+/// Clean up a branch pattern as typed, or `None` if no branch could match it
+pub fn normalize_branch_pattern(pattern: &str) -> Option<&str> {
+    let pattern = pattern.trim();
+    let pattern = pattern.strip_prefix("refs/heads/").unwrap_or(pattern);
+    let valid = !pattern.is_empty()
+        && pattern.len() <= 100
+        && !pattern.contains("..")
+        && !pattern.starts_with('/')
+        && pattern.chars().all(|c| !c.is_whitespace() && !c.is_control() && !"~^:\\[".contains(c));
+
+    valid.then_some(pattern)
+}
+
+/// Look up the users allowed to push to a protected branch
+pub async fn resolve_pushers(db: &Database, usernames: &[String]) -> Result<Vec<i64>, String> {
+    let mut ids = Vec::new();
+    for username in usernames.iter().map(|u| u.trim()).filter(|u| !u.is_empty()) {
+        let user = db
+            .get_user_by_username(username)
+            .await
+            .map_err(|_| format!("Unknown user '{}'", username))?;
+        ids.push(user.id);
+    }
+    Ok(ids)
+}
+
+/// A rule a push broke, reported against one ref
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub ref_name: String,
+    pub message: String,
+}
+
+/// Everything a repository checks pushes against
+pub struct PushPolicy {
+    pub rules: PushRules,
+    pub protected: Vec<ProtectedBranch>,
+}
+
+impl PushPolicy {
+    pub async fn load(db: &Database, repo_hash: &str) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            rules: db.get_push_rules(repo_hash).await?,
+            protected: db.list_protected_branches(repo_hash).await?,
+        })
+    }
+
+    /// Pushes to repositories without rules skip screening entirely
+    pub fn is_empty(&self) -> bool {
+        self.protected.is_empty()
+            && self.rules.max_blob_size.is_none()
+            && self.rules.forbidden_path_patterns().next().is_none()
+            && self.rules.require_verified_email == 0
+    }
+
+    /// Every protection rule whose pattern matches `branch`
+    pub fn protections<'a>(&'a self, branch: &'a str) -> impl Iterator<Item = &'a ProtectedBranch> + 'a {
+        self.protected.iter().filter(move |p| glob_match(&p.pattern, branch))
+    }
+
+    /// Check every ref update by `pusher` against the rules
+    pub async fn check(&self, db: &Database, push: &IncomingPush, pusher: &str) -> Result<Vec<Violation>, String> {
+        let mut violations = Vec::new();
+        let inspect_objects =
+            self.rules.max_blob_size.is_some() || self.rules.forbidden_path_patterns().next().is_some();
+
+        for update in &push.commands.updates {
+            let mut reject = |message: String| {
+                violations.push(Violation { ref_name: update.name.clone(), message })
+            };
+
+            if let Some(branch) = update.branch() {
+                let rules: Vec<&ProtectedBranch> = self.protections(branch).collect();
+
+                if rules.iter().any(|r| r.restrict_pushes != 0 && !r.allowed_pushers.iter().any(|u| u == pusher)) {
+                    reject(format!("{} is protected and {} may not push to it", branch, pusher));
+                }
+                if update.is_delete() {
+                    if rules.iter().any(|r| r.allow_deletion == 0) {
+                        reject(format!("{} is protected and cannot be deleted", branch));
+                    }
+                } else {
+                    if !update.is_create()
+                        && rules.iter().any(|r| r.allow_force_push == 0)
+                        && !push.is_ancestor(&update.old, &update.new).await?
+                    {
+                        reject(format!("{} is protected and cannot be force-pushed", branch));
+                    }
+                    if rules.iter().any(|r| r.require_linear_history != 0) {
+                        let merges = push.new_merge_commits(&update.new).await?;
+                        if let Some(merge) = merges.first() {
+                            reject(format!("{} requires linear history but {} is a merge commit", branch, merge));
+                        }
+                    }
+                }
+            }
+
+            if update.is_delete() {
+                continue;
+            }
+
+            if inspect_objects {
+                for (path, size) in push.new_blobs(&update.new).await? {
+                    if let Some(limit) = self.rules.max_blob_size.filter(|&limit| size > limit) {
+                        reject(format!("{} is {} bytes, over the {} byte limit", path, size, limit));
+                    }
+                    if let Some(pattern) = self.rules.forbidden_path_patterns().find(|p| path_matches(p, &path)) {
+                        reject(format!("{} matches forbidden path {}", path, pattern));
+                    }
+                }
+            }
+
+            if self.rules.require_verified_email != 0 {
+                for email in push.new_author_emails(&update.new).await? {
+                    let verified = db
+                        .is_email_verified(&email)
+                        .await
+                        .map_err(|e| format!("Failed to look up {}: {}", email, e))?;
+                    if !verified {
+                        reject(format!(
+                            "author email {} is not verified; verify it under Account Settings",
+                            email
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(violations)
+    }
+}
+
+/// A spooled receive-pack request whose pack sits in a quarantine object
+/// directory, so rules can inspect it before anything reaches the repo
+pub struct IncomingPush {
+    pub commands: PushCommands,
+    dir: PathBuf,
+    repo_path: PathBuf,
+}
+
+impl IncomingPush {
+    /// Spool the request to disk and index its pack into quarantine
+    ///
+    /// Only the commands and the pack are read, never up to end of input:
+    /// over SSH the client keeps its side open until it has our response.
+    pub async fn receive<R: AsyncRead + Unpin>(
+        git_storage: &GitStorage,
+        repo_hash: &str,
+        input: R,
+    ) -> Result<Self, String> {
+        let dir = git_storage.incoming_path(repo_hash);
+        let mut push = Self {
+            commands: PushCommands::default(),
+            dir,
+            repo_path: git_storage.repo_path(repo_hash),
+        };
+        tokio::fs::create_dir_all(push.quarantine().join("pack"))
+            .await
+            .map_err(|e| format!("Failed to create quarantine: {}", e))?;
+
+        let mut file = tokio::fs::File::create(push.request_path())
+            .await
+            .map_err(|e| format!("Failed to spool push: {}", e))?;
+        let mut input = Recorded { inner: input, bytes: Vec::new() };
+        push.commands = read_commands(&mut input).await?;
+        file.write_all(&input.bytes)
+            .await
+            .map_err(|e| format!("Failed to spool push: {}", e))?;
+
+        if push.commands.expects_pack() {
+            push.index_pack(input.inner, &mut file).await?;
+        }
+        file.flush().await.map_err(|e| format!("Failed to spool push: {}", e))?;
+
+        Ok(push)
+    }
+
+    fn quarantine(&self) -> PathBuf {
+        self.dir.join("objects")
+    }
+
+    fn request_path(&self) -> PathBuf {
+        self.dir.join("request")
+    }
+
+    /// The original request, to be handed to git-receive-pack unchanged
+    pub async fn replay(&self) -> Result<Pin<Box<dyn AsyncRead + Send>>, String> {
+        let file = tokio::fs::File::open(self.request_path())
+            .await
+            .map_err(|e| format!("Failed to read spooled push: {}", e))?;
+        Ok(Box::pin(file))
+    }
+
+    fn git(&self) -> Command {
+        let mut command = Command::new("git");
+        command
+            .arg("--git-dir")
+            .arg(&self.repo_path)
+            .env("GIT_OBJECT_DIRECTORY", self.quarantine())
+            .env("GIT_ALTERNATE_OBJECT_DIRECTORIES", absolute(&self.repo_path.join("objects")));
+        command
+    }
+
+    async fn output(&self, args: &[&str]) -> Result<Output, String> {
+        self.git()
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run git {}: {}", args[0], e))
+    }
+
+    /// Run git and fail unless it succeeds, so a push that couldn't be
+    /// screened is rejected rather than let through unchecked
+    async fn run(&self, args: &[&str]) -> Result<Output, String> {
+        let output = self.output(args).await?;
+        if !output.status.success() {
+            return Err(format!(
+                "git {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(output)
+    }
+
+    /// Feed the pack to index-pack, copying it into the spooled request.
+    /// index-pack exits once it has read the whole pack, which is how we
+    /// know the pack has ended.
+    async fn index_pack<R: AsyncRead + Unpin>(&self, mut pack: R, spool: &mut tokio::fs::File) -> Result<(), String> {
+        let mut child = self
+            .git()
+            .args(["index-pack", "--stdin", "--fix-thin"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run git index-pack: {}", e))?;
+
+        let mut stdin = Some(child.stdin.take().ok_or("no stdin")?);
+        let mut stderr = child.stderr.take().ok_or("no stderr")?;
+        let mut buf = vec![0u8; 64 * 1024];
+        let status = loop {
+            tokio::select! {
+                status = child.wait() => break status.map_err(|e| e.to_string())?,
+                read = pack.read(&mut buf), if stdin.is_some() => match read {
+                    // A pack cut short makes index-pack fail
+                    Ok(0) | Err(_) => stdin = None,
+                    Ok(n) => {
+                        spool
+                            .write_all(&buf[..n])
+                            .await
+                            .map_err(|e| format!("Failed to spool push: {}", e))?;
+                        if let Some(pipe) = stdin.as_mut() {
+                            if pipe.write_all(&buf[..n]).await.is_err() {
+                                stdin = None;
+                            }
+                        }
+                    }
+                },
+            }
+        };
+
+        if !status.success() {
+            let mut message = String::new();
+            let _ = stderr.read_to_string(&mut message).await;
+            return Err(format!("unpack failed: {}", message.trim()));
+        }
+        Ok(())
+    }
+
+    /// Whether `new` contains `old`, i.e. the update is a fast-forward
+    pub async fn is_ancestor(&self, old: &str, new: &str) -> Result<bool, String> {
+        let output = self.output(&["merge-base", "--is-ancestor", old, new]).await?;
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(format!("git merge-base failed: {}", String::from_utf8_lossy(&output.stderr).trim())),
+        }
+    }
+
+    /// Path and size of every blob the push adds under `new`
+    pub async fn new_blobs(&self, new: &str) -> Result<Vec<(String, i64)>, String> {
+        let output = self.run(&["rev-list", "--objects", new, "--not", "--all"]).await?;
+        let paths: HashMap<String, String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let (oid, path) = line.split_once(' ')?;
+                Some((oid.to_string(), path.to_string()))
+            })
+            .collect();
+        if paths.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut child = self
+            .git()
+            .args(["cat-file", "--batch-check=%(objectname) %(objecttype) %(objectsize)"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run git cat-file: {}", e))?;
+
+        let mut stdin = child.stdin.take().ok_or("no stdin")?;
+        let input: String = paths.keys().map(|oid| format!("{}\n", oid)).collect();
+        let writer = tokio::spawn(async move {
+            let _ = stdin.write_all(input.as_bytes()).await;
+        });
+
+        let stdout = child.stdout.take().ok_or("no stdout")?;
+        let mut lines = BufReader::new(stdout).lines();
+        let mut blobs = Vec::new();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let mut parts = line.split(' ');
+            if let (Some(oid), Some("blob"), Some(size)) = (parts.next(), parts.next(), parts.next()) {
+                if let (Some(path), Ok(size)) = (paths.get(oid), size.parse()) {
+                    blobs.push((path.clone(), size));
+                }
+            }
+        }
+        let _ = writer.await;
+        let status = child.wait().await.map_err(|e| format!("Failed to run git cat-file: {}", e))?;
+        if !status.success() {
+            return Err("git cat-file failed".to_string());
+        }
+
+        blobs.sort();
+        Ok(blobs)
+    }
+
+    /// Merge commits the push adds under `new`
+    pub async fn new_merge_commits(&self, new: &str) -> Result<Vec<String>, String> {
+        let output = self.run(&["rev-list", "--min-parents=2", new, "--not", "--all"]).await?;
+        Ok(String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect())
+    }
+
+    /// Distinct author emails of the commits the push adds under `new`
+    pub async fn new_author_emails(&self, new: &str) -> Result<BTreeSet<String>, String> {
+        let output = self.run(&["log", "--format=%ae", new, "--not", "--all"]).await?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|email| email.trim().to_string())
+            .filter(|email| !email.is_empty())
+            .collect())
+    }
+}
+
+impl Drop for IncomingPush {
+    fn drop(&mut self) {
+        // An open replay keeps reading from the unlinked file
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// What to do with a push after screening it
+pub enum Screening {
+    /// Feed this to git-receive-pack
+    Accept(Pin<Box<dyn AsyncRead + Send>>),
+    /// Send this receive-pack response back without running git
+    Reject(Vec<u8>),
+}
+
+/// Run a push through the repository's rules
+pub async fn screen<R: AsyncRead + Unpin>(
+    db: &Database,
+    git_storage: &GitStorage,
+    repo_hash: &str,
+    policy: &PushPolicy,
+    pusher_id: i64,
+    input: R,
+) -> Result<Screening, String> {
+    let pusher = db
+        .get_user_by_id(pusher_id)
+        .await
+        .map_err(|e| format!("Failed to look up pusher: {}", e))?;
+    let push = IncomingPush::receive(git_storage, repo_hash, input).await?;
+    let violations = policy.check(db, &push, &pusher.username).await?;
+
+    if violations.is_empty() {
+        Ok(Screening::Accept(push.replay().await?))
+    } else {
+        tracing::info!("Rejected push to {}: {:?}", repo_hash, violations);
+        Ok(Screening::Reject(rejection_response(&push.commands, &violations)))
+    }
+}
+
+fn pkt_line(data: &[u8]) -> Vec<u8> {
+    let mut line = format!("{:04x}", data.len() + 4).into_bytes();
+    line.extend_from_slice(data);
+    line
+}
+
+/// A receive-pack response that refuses every ref, explaining why over
+/// the progress sideband so the client prints it as `remote:` lines
+pub fn rejection_response(commands: &PushCommands, violations: &[Violation]) -> Vec<u8> {
+    let sideband = commands.has_capability("side-band-64k") || commands.has_capability("side-band");
+    let mut response = Vec::new();
+
+    let mut report = Vec::new();
+    if commands.has_capability("report-status") || commands.has_capability("report-status-v2") {
+        report.extend(pkt_line(b"unpack ok\n"));
+        for update in &commands.updates {
+            let reason = if violations.iter().any(|v| v.ref_name == update.name) {
+                "push rejected by repository rules"
+            } else {
+                "other refs in this push were rejected"
+            };
+            report.extend(pkt_line(format!("ng {} {}\n", update.name, reason).as_bytes()));
+        }
+        report.extend(b"0000");
+    }
+
+    if !sideband {
+        return report;
+    }
+
+    let mut messages = String::from("\nPush rejected by repository rules:\n");
+    for violation in violations {
+        messages.push_str(&format!("  {}: {}\n", violation.ref_name, violation.message));
+    }
+    messages.push('\n');
+    // Sideband packets are capped at 1000 bytes without side-band-64k
+    for chunk in messages.as_bytes().chunks(900) {
+        response.extend(pkt_line(&[&[2u8], chunk].concat()));
+    }
+    if !report.is_empty() {
+        response.extend(pkt_line(&[&[1u8], report.as_slice()].concat()));
+    }
+    response.extend(b"0000");
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(old: &str, new: &str, name: &str, caps: Option<&str>) -> Vec<u8> {
+        let mut line = format!("{} {} {}", old, new, name);
+        if let Some(caps) = caps {
+            line = format!("{}\0{}", line, caps);
+        }
+        pkt_line(format!("{}\n", line).as_bytes())
+    }
+
+    #[tokio::test]
+    async fn test_read_commands() {
+        let a = "a".repeat(40);
+        let b = "b".repeat(40);
+        let mut request = pkt_line(format!("shallow {}\n", a).as_bytes());
+        request.extend(command(&a, &b, "refs/heads/main", Some("report-status side-band-64k")));
+        request.extend(command(&b, ZERO_OID, "refs/heads/old", None));
+        request.extend(b"0000PACK");
+
+        let mut reader = request.as_slice();
+        let commands = read_commands(&mut reader).await.unwrap();
+
+        assert_eq!(commands.updates.len(), 2);
+        assert_eq!(commands.updates[0].branch(), Some("main"));
+        assert!(commands.updates[1].is_delete());
+        assert!(commands.has_capability("side-band-64k"));
+        assert!(commands.expects_pack());
+        // The pack is left for index-pack
+        assert_eq!(reader, b"PACK");
+
+        let mut garbage: &[u8] = b"0010not a command";
+        assert!(read_commands(&mut garbage).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_git_failures_are_errors() {
+        let dir = std::env::temp_dir().join(format!("hyrule-pre-receive-{:016x}", rand::random::<u64>()));
+        let repo_path = dir.join("repo.git");
+        let status = std::process::Command::new("git")
+            .args(["init", "--bare", "-q"])
+            .arg(&repo_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let push = IncomingPush {
+            commands: PushCommands::default(),
+            dir: dir.join("incoming"),
+            repo_path,
+        };
+        std::fs::create_dir_all(push.quarantine().join("pack")).unwrap();
+
+        // An object the push never sent can't be screened, so nothing passes
+        let missing = "1".repeat(40);
+        assert!(push.new_blobs(&missing).await.is_err());
+        assert!(push.new_merge_commits(&missing).await.is_err());
+        assert!(push.new_author_emails(&missing).await.is_err());
+
+        drop(push);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("main", "main"));
+        assert!(glob_match("release/*", "release/1.0"));
+        assert!(!glob_match("release/*", "release/1.0/hotfix"));
+        assert!(glob_match("release/**", "release/1.0/hotfix"));
+        assert!(glob_match("v?", "v1"));
+        assert!(!glob_match("main", "maintenance"));
+
+        assert!(path_matches("*.pem", "config/certs/server.pem"));
+        assert!(path_matches(".env", ".env"));
+        assert!(path_matches("secrets/**", "secrets/prod/db.yml"));
+        assert!(!path_matches("secrets/**", "app/secrets.rs"));
+        assert!(path_matches("/build/*.o", "build/main.o"));
+    }
+
+    #[test]
+    fn test_normalize_branch_pattern() {
+        assert_eq!(normalize_branch_pattern(" main "), Some("main"));
+        assert_eq!(normalize_branch_pattern("refs/heads/release/*"), Some("release/*"));
+        assert_eq!(normalize_branch_pattern(""), None);
+        assert_eq!(normalize_branch_pattern("a..b"), None);
+        assert_eq!(normalize_branch_pattern("two words"), None);
+        assert_eq!(normalize_branch_pattern("HEAD~1"), None);
+    }
+
+    #[test]
+    fn test_rejection_response() {
+        let a = "a".repeat(40);
+        let commands = PushCommands {
+            updates: vec![RefUpdate { old: a.clone(), new: a.clone(), name: "refs/heads/main".into() }],
+            capabilities: vec!["report-status".into(), "side-band-64k".into()],
+        };
+        let violations = [Violation { ref_name: "refs/heads/main".into(), message: "main is protected".into() }];
+        let response = rejection_response(&commands, &violations);
+        let text = String::from_utf8_lossy(&response);
+
+        // Progress on band 2, status report on band 1, then a flush
+        assert!(text.contains("\u{2}\nPush rejected by repository rules:\n  refs/heads/main: main is protected\n"));
+        assert!(text.contains("\u{1}000eunpack ok\n"));
+        assert!(text.contains("ng refs/heads/main push rejected by repository rules\n0000"));
+        assert!(text.ends_with("0000"));
+    }
+}
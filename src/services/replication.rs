@@ -1,61 +1,512 @@
 // src/services/replication.rs
 use crate::db::Database;
+use crate::services::node_client::NodeClient;
+use crate::models::{Node, ReplicaSync};
+use crate::services::node_protocol::{self, ReplicaAck, SyncRequest};
+use crate::services::placement::{PlacementContext, PlacementDecision, PlacementPolicy, WeightedPlacement};
+use crate::services::reputation::{ReputationEvent, ReputationService, MIN_TARGET_REPUTATION};
+use crate::storage::GitStorage;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 pub struct ReplicationService {
     db: Database,
+    git_storage: Arc<GitStorage>,
+    /// Strategy for choosing targets; any `PlacementPolicy` can be swapped in
+    policy: Box<dyn PlacementPolicy>,
 }
 
 impl ReplicationService {
-    pub fn new(db: Database) -> Self {
-        Self { db }
+    pub fn new(db: Database, git_storage: Arc<GitStorage>) -> Self {
+        Self {
+            db,
+            git_storage,
+            policy: Box::new(WeightedPlacement::default()),
+        }
     }
-    
-    /// Trigger replication for a repository to a new node
-    pub async fn trigger_replication(&self, repo_hash: &str) -> Result<String, String> {
+
+    /// Rank candidate nodes for a repository without transferring anything
+    pub async fn plan(&self, repo_hash: &str) -> Result<PlacementDecision, String> {
+        let repo = self.db
+            .get_repository(repo_hash)
+            .await
+            .map_err(|e| format!("Repository not found: {}", e))?;
+
         // Get current replicas
         let current_replicas = self.db
             .list_repo_replicas(repo_hash)
             .await
             .map_err(|e| format!("Failed to list replicas: {}", e))?;
-        
-        // Get available nodes
+
+        // Get available nodes, skipping banned and low-reputation ones
         let available_nodes = self.db
-            .list_active_nodes(10)
+            .list_replication_targets(10, MIN_TARGET_REPUTATION)
             .await
             .map_err(|e| format!("Failed to list nodes: {}", e))?;
-        
-        // Find node not already hosting this repo
-        let current_node_ids: Vec<String> = current_replicas.iter()
-            .map(|n| n.node_id.clone())
-            .collect();
-        
-        let target_node = available_nodes
-            .into_iter()
-            .find(|node| !current_node_ids.contains(&node.node_id))
-            .ok_or_else(|| "No available nodes for replication".to_string())?;
-        
-        // Create replica record
+
+        let storage = self.git_storage.clone();
+        let hash = repo_hash.to_string();
+        let repo_size = tokio::task::spawn_blocking(move || storage.get_repo_size(&hash))
+            .await
+            .map_err(|e| format!("Sizing task failed: {}", e))?
+            .map_err(|e| format!("Failed to size repository: {}", e))?;
+
+        let ctx = PlacementContext {
+            repo_hash,
+            repo_size: repo_size as i64,
+            existing: &current_replicas,
+            require_anchor: repo.require_anchor != 0,
+        };
+
+        Ok(self.policy.place(&ctx, &available_nodes))
+    }
+
+    /// Trigger replication for a repository to a new node
+    pub async fn trigger_replication(&self, repo_hash: &str) -> Result<String, String> {
+        self.replicate(repo_hash)
+            .await
+            .map(|decision| decision.chosen.unwrap_or_default())
+    }
+
+    /// Replicate a repository to the node picked by the placement policy
+    ///
+    /// The repository is bundled, uploaded to the chosen node and the node's
+    /// reported refs are compared against ours. The replica row is only
+    /// written once the node has acknowledged an identical ref set.
+    pub async fn replicate(&self, repo_hash: &str) -> Result<PlacementDecision, String> {
+        let decision = self.plan(repo_hash).await?;
+
+        let target_id = decision.chosen.clone().ok_or_else(|| {
+            format!(
+                "No available nodes for replication ({} candidates considered)",
+                decision.candidates.len()
+            )
+        })?;
+
+        let target_node = self.db
+            .get_node(&target_id)
+            .await
+            .map_err(|e| format!("Node not found: {}", e))?;
+
+        let repo = self.db
+            .get_repository(repo_hash)
+            .await
+            .map_err(|e| format!("Repository not found: {}", e))?;
+
+        // Package the repository off the async runtime
+        let storage = self.git_storage.clone();
+        let hash = repo_hash.to_string();
+        let (bundle, expected_refs) = tokio::task::spawn_blocking(move || {
+            let refs = storage.list_refs(&hash)?;
+            let bundle = storage.create_bundle(&hash)?;
+            Ok::<_, anyhow::Error>((bundle, refs))
+        })
+        .await
+        .map_err(|e| format!("Packaging task failed: {}", e))?
+        .map_err(|e| format!("Failed to package repository: {}", e))?;
+        self.record_primary_refs(repo_hash, &expected_refs).await;
+
+        // Transfer outcomes feed the target's reputation
+        let client = NodeClient::new()?;
+        let reputation = ReputationService::new(self.db.clone());
+        let transfer = Self::transfer(&client, &target_node, repo_hash, repo.is_private != 0, bundle, &expected_refs)
+            .await;
+        let event = if transfer.is_ok() {
+            ReputationEvent::TransferSucceeded
+        } else {
+            ReputationEvent::TransferFailed
+        };
+        if let Err(e) = reputation.record(&target_node.node_id, event).await {
+            tracing::warn!("{}", e);
+        }
+        transfer?;
+
+        // Transfer verified - record the replica and the refs it holds
         self.db
-            .create_replica(repo_hash, &target_node.node_id)
+            .create_replica(repo_hash, &target_node.node_id, &encode_refs(&expected_refs))
             .await
             .map_err(|e| format!("Failed to create replica: {}", e))?;
-        
-        // TODO: Actually trigger data transfer to node
-        // This would involve:
-        // 1. Packaging the git objects
-        // 2. Sending to target node via HTTP/gRPC
-        // 3. Verifying successful transfer
-        
-        Ok(target_node.node_id)
-    }
-    
+
+        tracing::info!(
+            "Replicated {} to node {} ({} refs verified, placed by {} policy)",
+            repo_hash, target_node.node_id, expected_refs.len(), decision.policy
+        );
+
+        Ok(decision)
+    }
+
+    /// Upload a bundle to a node and check its signed acknowledgement
+    async fn transfer(
+        client: &NodeClient,
+        target_node: &Node,
+        repo_hash: &str,
+        is_private: bool,
+        bundle: Vec<u8>,
+        expected_refs: &BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        // Send to target node and wait for its acknowledgement
+        let ack = client
+            .push_replica(target_node, repo_hash, is_private, bundle)
+            .await?;
+
+        // The acknowledgement must come from the node we picked
+        if ack.node_id != target_node.node_id {
+            return Err(format!(
+                "Acknowledgement signed by {} instead of {}",
+                ack.node_id, target_node.node_id
+            ));
+        }
+
+        Self::check_ack(target_node, repo_hash, &ack, expected_refs)
+    }
+
+    /// An acknowledgement must be signed by the node we talked to and list
+    /// exactly the refs we expect it to hold
+    fn check_ack(
+        target_node: &Node,
+        repo_hash: &str,
+        ack: &ReplicaAck,
+        expected_refs: &BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        node_protocol::verify(ack)
+            .map_err(|e| format!("Node {} sent an unverifiable acknowledgement: {}", target_node.node_id, e))?;
+
+        if ack.repo_hash != repo_hash {
+            return Err(format!(
+                "Node {} acknowledged the wrong repository ({})",
+                target_node.node_id, ack.repo_hash
+            ));
+        }
+
+        if &ack.refs != expected_refs {
+            return Err(format!(
+                "Node {} reported {} refs that do not match the {} refs sent",
+                target_node.node_id,
+                ack.refs.len(),
+                expected_refs.len()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Bring every live replica of a repository up to date after a push
+    ///
+    /// Returns how many replicas were synced and how many failed. Failed
+    /// replicas keep their old ref state and show up as behind until a
+    /// later push or health check catches them up.
+    pub async fn sync_replicas(&self, repo_hash: &str) -> Result<(usize, usize), String> {
+        let refs = self.current_refs(repo_hash).await?;
+        self.record_primary_refs(repo_hash, &refs).await;
+        let nodes = self.db
+            .list_live_replica_nodes(repo_hash)
+            .await
+            .map_err(|e| format!("Failed to list replicas: {}", e))?;
+
+        let client = NodeClient::new()?;
+        let (mut synced, mut failed) = (0, 0);
+
+        for node in nodes {
+            match self.sync_replica(&client, &node, repo_hash, &refs).await {
+                Ok(()) => synced += 1,
+                Err(e) => {
+                    failed += 1;
+                    tracing::warn!("Replica {} on {} is behind: {}", repo_hash, node.node_id, e);
+                }
+            }
+        }
+
+        Ok((synced, failed))
+    }
+
+    /// Have one node fetch the objects it is missing and adopt `refs`
+    pub async fn sync_replica(
+        &self,
+        client: &NodeClient,
+        node: &Node,
+        repo_hash: &str,
+        refs: &BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        let request = SyncRequest {
+            repo_hash: repo_hash.to_string(),
+            refs: refs.clone(),
+        };
+
+        let result = match client.sync_replica(node, &request).await {
+            Ok(ack) => Self::check_ack(node, repo_hash, &ack, refs),
+            Err(e) => Err(e),
+        };
+
+        let event = if result.is_ok() {
+            ReputationEvent::TransferSucceeded
+        } else {
+            ReputationEvent::TransferFailed
+        };
+        if let Err(e) = ReputationService::new(self.db.clone()).record(&node.node_id, event).await {
+            tracing::warn!("{}", e);
+        }
+        result?;
+
+        self.db
+            .record_replica_refs(repo_hash, &node.node_id, &encode_refs(refs))
+            .await
+            .map_err(|e| format!("Failed to record replica refs: {}", e))?;
+
+        tracing::info!("Synced replica {} on {} ({} refs)", repo_hash, node.node_id, refs.len());
+        Ok(())
+    }
+
+    /// Rebuild a primary copy that vanished from local storage
+    ///
+    /// Only replicas with recorded refs are used, and a copy is only
+    /// accepted if its refs match what that node last acknowledged.
+    /// Replicas matching the primary's last known refs are tried first;
+    /// if none can be used, the result says how far behind the restored
+    /// copy is.
+    pub async fn restore_primary(&self, repo_hash: &str) -> Result<RestoredPrimary, String> {
+        let replicas = self.db
+            .list_replica_sync(repo_hash)
+            .await
+            .map_err(|e| format!("Failed to list replicas: {}", e))?;
+        let known_refs = self.db
+            .get_primary_refs(repo_hash)
+            .await
+            .map_err(|e| format!("Failed to look up primary refs: {}", e))?
+            .map(|state| decode_refs(&state));
+
+        let nodes = self.db
+            .list_live_replica_nodes(repo_hash)
+            .await
+            .map_err(|e| format!("Failed to list replica nodes: {}", e))?;
+
+        let client = NodeClient::new()?;
+        let mut last_error = "No live replicas with recorded refs to restore from".to_string();
+
+        for (replica, refs_behind) in restore_candidates(replicas, known_refs.as_ref()) {
+            let Some(node) = nodes.iter().find(|n| n.node_id == replica.node_id) else {
+                continue;
+            };
+
+            let expected = decode_refs(replica.ref_state.as_deref().unwrap_or_default());
+            match self.restore_from(&client, node, repo_hash, expected).await {
+                Ok(()) => {
+                    tracing::warn!("Restored primary copy of {} from node {}", repo_hash, node.node_id);
+                    return Ok(RestoredPrimary {
+                        node_id: node.node_id.clone(),
+                        refs_behind,
+                    });
+                }
+                Err(e) => {
+                    tracing::warn!("Could not restore {} from {}: {}", repo_hash, node.node_id, e);
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    async fn restore_from(
+        &self,
+        client: &NodeClient,
+        node: &Node,
+        repo_hash: &str,
+        expected_refs: BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        let bundle = client.fetch_replica(node, repo_hash).await?;
+
+        let storage = self.git_storage.clone();
+        let hash = repo_hash.to_string();
+        let size = tokio::task::spawn_blocking(move || {
+            storage.restore_from_bundle(&hash, &bundle, Some(&expected_refs))?;
+            storage.get_repo_size(&hash)
+        })
+        .await
+        .map_err(|e| format!("Restore task failed: {}", e))?
+        .map_err(|e| format!("Failed to restore repository: {}", e))?;
+
+        let _ = self.db.update_repository_size(repo_hash, size as i64).await;
+        Ok(())
+    }
+
+    /// Replicas of a repository and how far each is behind the primary
+    pub async fn replica_status(&self, repo_hash: &str) -> Result<Vec<ReplicaSync>, String> {
+        let refs = self.current_refs(repo_hash).await?;
+        let mut replicas = self.db
+            .list_replica_sync(repo_hash)
+            .await
+            .map_err(|e| format!("Failed to list replicas: {}", e))?;
+
+        for replica in &mut replicas {
+            replica.refs_behind = replica.ref_state.as_deref().map(|state| refs_behind(state, &refs));
+        }
+
+        Ok(replicas)
+    }
+
+    /// Remember what the primary holds, to check a restored copy against
+    async fn record_primary_refs(&self, repo_hash: &str, refs: &BTreeMap<String, String>) {
+        if let Err(e) = self.db.record_primary_refs(repo_hash, &encode_refs(refs)).await {
+            tracing::warn!("Failed to record refs of {}: {}", repo_hash, e);
+        }
+    }
+
+    /// Refs of the primary copy
+    pub async fn current_refs(&self, repo_hash: &str) -> Result<BTreeMap<String, String>, String> {
+        let storage = self.git_storage.clone();
+        let hash = repo_hash.to_string();
+        tokio::task::spawn_blocking(move || storage.list_refs(&hash))
+            .await
+            .map_err(|e| format!("Ref listing task failed: {}", e))?
+            .map_err(|e| format!("Failed to list refs: {}", e))
+    }
+
     /// Check health of all repositories and trigger replication if needed
     pub async fn health_check(&self, _min_replicas: i32) -> Result<Vec<String>, String> {
         // TODO: Implement health check that:
         // 1. Queries all repos with replica count < min_replicas
         // 2. Triggers replication for each
         // 3. Returns list of repos that needed replication
-        
+
         Ok(vec![])
     }
-}
\ No newline at end of file
+}
+
+/// Where a missing primary copy was restored from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoredPrimary {
+    pub node_id: String,
+    /// Refs that differ from what the primary last held; None if the
+    /// primary's refs were never recorded
+    pub refs_behind: Option<usize>,
+}
+
+impl RestoredPrimary {
+    /// Whether pushes may have been lost in the restore
+    pub fn is_stale(&self) -> bool {
+        self.refs_behind != Some(0)
+    }
+}
+
+/// Replicas worth restoring from, with how far each is behind `known_refs`.
+/// Replicas that never recorded refs can't be checked and are left out.
+/// Up to date ones come first, then the least behind, most recently synced
+/// first.
+fn restore_candidates(
+    replicas: Vec<ReplicaSync>,
+    known_refs: Option<&BTreeMap<String, String>>,
+) -> Vec<(ReplicaSync, Option<usize>)> {
+    let mut candidates: Vec<(ReplicaSync, Option<usize>)> = replicas
+        .into_iter()
+        .filter_map(|replica| {
+            let state = replica.ref_state.as_deref()?;
+            let behind = known_refs.map(|known| refs_behind(state, known));
+            Some((replica, behind))
+        })
+        .collect();
+    candidates.sort_by(|(a, a_behind), (b, b_behind)| {
+        a_behind
+            .unwrap_or(usize::MAX)
+            .cmp(&b_behind.unwrap_or(usize::MAX))
+            .then_with(|| b.synced_at.cmp(&a.synced_at))
+    });
+
+    candidates
+}
+
+/// Serialize refs the way `replicas.ref_state` stores them
+pub fn encode_refs(refs: &BTreeMap<String, String>) -> String {
+    refs.iter()
+        .map(|(name, oid)| format!("{} {}", oid, name))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse a `replicas.ref_state` value back into refname -> object id
+pub fn decode_refs(ref_state: &str) -> BTreeMap<String, String> {
+    ref_state
+        .lines()
+        .filter_map(|line| {
+            let (oid, name) = line.split_once(' ')?;
+            Some((name.to_string(), oid.to_string()))
+        })
+        .collect()
+}
+
+/// Count refs whose recorded tip differs from `current`, including refs
+/// that only exist on one side
+pub fn refs_behind(recorded: &str, current: &BTreeMap<String, String>) -> usize {
+    let recorded = decode_refs(recorded);
+
+    let changed = current
+        .iter()
+        .filter(|(name, oid)| recorded.get(*name) != Some(*oid))
+        .count();
+    let removed = recorded.keys().filter(|name| !current.contains_key(*name)).count();
+
+    changed + removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refs(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(n, o)| (n.to_string(), o.to_string())).collect()
+    }
+
+    #[test]
+    fn test_ref_state_round_trip() {
+        let current = refs(&[("refs/heads/main", "aaa"), ("refs/tags/v1", "bbb")]);
+        let encoded = encode_refs(&current);
+
+        assert_eq!(encoded, "aaa refs/heads/main\nbbb refs/tags/v1");
+        assert_eq!(decode_refs(&encoded), current);
+        assert_eq!(refs_behind(&encoded, &current), 0);
+    }
+
+    #[test]
+    fn test_refs_behind_counts_moved_new_and_deleted_refs() {
+        let recorded = encode_refs(&refs(&[("refs/heads/main", "aaa"), ("refs/heads/old", "ccc")]));
+        let current = refs(&[("refs/heads/main", "ddd"), ("refs/heads/new", "eee")]);
+
+        assert_eq!(refs_behind(&recorded, &current), 3);
+        assert_eq!(refs_behind("", &current), 2);
+    }
+
+    #[test]
+    fn test_restore_prefers_up_to_date_replicas() {
+        let replica = |node_id: &str, state: Option<&str>, synced_at: &str| ReplicaSync {
+            node_id: node_id.to_string(),
+            address: "127.0.0.1".to_string(),
+            port: 9000,
+            is_anchor: 0,
+            node_state: "online".to_string(),
+            ref_state: state.map(str::to_string),
+            synced_at: Some(synced_at.to_string()),
+            refs_behind: None,
+        };
+        let known = refs(&[("refs/heads/main", "bbb")]);
+        let replicas = vec![
+            replica("unrecorded", None, "2024-06-03 00:00:00"),
+            replica("stale-recent", Some("aaa refs/heads/main"), "2024-06-02 00:00:00"),
+            replica("synced", Some("bbb refs/heads/main"), "2024-06-01 00:00:00"),
+        ];
+
+        let ranked: Vec<(String, Option<usize>)> = restore_candidates(replicas.clone(), Some(&known))
+            .into_iter()
+            .map(|(replica, behind)| (replica.node_id, behind))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![("synced".to_string(), Some(0)), ("stale-recent".to_string(), Some(1))]
+        );
+
+        // Without known refs nothing can be called up to date
+        let ranked: Vec<String> = restore_candidates(replicas, None)
+            .into_iter()
+            .map(|(replica, _)| replica.node_id)
+            .collect();
+        assert_eq!(ranked, vec!["stale-recent".to_string(), "synced".to_string()]);
+    }
+}
@@ -0,0 +1,142 @@
+// src/services/replication_queue.rs
+use crate::db::Database;
+use crate::models::ReplicationJob;
+use crate::services::replication::ReplicationService;
+use crate::storage::GitStorage;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Priority of jobs for repos that just lost a replica to eviction
+pub const PRIORITY_EVICTION: i64 = 10;
+/// Priority of jobs queued by an administrator
+pub const PRIORITY_MANUAL: i64 = 5;
+/// Priority of jobs for repos found below the minimum replica count
+pub const PRIORITY_UNDER_REPLICATED: i64 = 0;
+
+/// How long idle workers wait before polling the queue again
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// First retry delay; doubled on every further attempt
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 3600;
+
+/// Delay before retrying a job that has failed `attempts` times
+pub fn backoff_secs(attempts: i64) -> i64 {
+    let exponent = (attempts.max(1) - 1).min(20) as u32;
+    (BASE_BACKOFF_SECS << exponent).min(MAX_BACKOFF_SECS)
+}
+
+/// Worker pool draining the persistent `replication_jobs` table
+pub struct ReplicationQueue {
+    db: Database,
+    git_storage: Arc<GitStorage>,
+    workers: usize,
+}
+
+impl ReplicationQueue {
+    pub fn new(db: Database, git_storage: Arc<GitStorage>, workers: usize) -> Self {
+        Self {
+            db,
+            git_storage,
+            workers: workers.max(1),
+        }
+    }
+
+    /// Resume interrupted jobs and spawn the workers
+    pub async fn start(self) {
+        match self.db.requeue_running_replication_jobs().await {
+            Ok(0) => {}
+            Ok(n) => info!("Requeued {} replication jobs interrupted by a restart", n),
+            Err(e) => error!("Failed to requeue replication jobs: {}", e),
+        }
+
+        info!("Replication queue started with {} workers", self.workers);
+
+        let queue = Arc::new(self);
+        for worker in 0..queue.workers {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.work(worker).await });
+        }
+    }
+
+    async fn work(&self, worker: usize) {
+        let replication = ReplicationService::new(self.db.clone(), self.git_storage.clone());
+
+        loop {
+            match self.db.claim_replication_job().await {
+                Ok(Some(job)) => self.run(worker, &replication, job).await,
+                Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+                Err(e) => {
+                    error!("Replication worker {} failed to claim a job: {}", worker, e);
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    async fn run(&self, worker: usize, replication: &ReplicationService, job: ReplicationJob) {
+        info!(
+            "Worker {} running replication job {} for {} (attempt {}/{})",
+            worker, job.id, job.repo_hash, job.attempts, job.max_attempts
+        );
+
+        let result = match replication.trigger_replication(&job.repo_hash).await {
+            Ok(node_id) => self.db.complete_replication_job(job.id, &node_id).await,
+            Err(e) => {
+                let retry = (job.attempts < job.max_attempts).then(|| backoff_secs(job.attempts));
+                match retry {
+                    Some(secs) => warn!("Replication job {} failed, retrying in {}s: {}", job.id, secs, e),
+                    None => warn!("Replication job {} failed permanently: {}", job.id, e),
+                }
+                self.db.fail_replication_job(job.id, &e, retry).await
+            }
+        };
+
+        if let Err(e) = result {
+            error!("Failed to record outcome of replication job {}: {}", job.id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        assert_eq!(backoff_secs(1), 30);
+        assert_eq!(backoff_secs(2), 60);
+        assert_eq!(backoff_secs(4), 240);
+        assert_eq!(backoff_secs(8), MAX_BACKOFF_SECS);
+        assert_eq!(backoff_secs(1000), MAX_BACKOFF_SECS);
+    }
+
+    #[tokio::test]
+    async fn test_only_failed_jobs_are_retried() {
+        let db = crate::test_support::TestDb::new().await;
+        let owner = db.add_named_user("alice").await;
+        let request = crate::models::CreateRepoRequest {
+            name: "alpha".to_string(),
+            description: None,
+            storage_tier: "free".to_string(),
+            is_private: false,
+        };
+        let repo = db.create_repository(&request, owner.id, &"a".repeat(40)).await.unwrap();
+
+        let job = db.enqueue_replication_job(&repo.repo_hash, "manual", PRIORITY_MANUAL).await.unwrap();
+        assert!(!db.retry_replication_job(job.id).await.unwrap());
+        let claimed = db.claim_replication_job().await.unwrap().unwrap();
+        assert!(!db.retry_replication_job(claimed.id).await.unwrap());
+        db.fail_replication_job(claimed.id, "no nodes", None).await.unwrap();
+
+        // Not while another job for the repo is active
+        let next = db.enqueue_replication_job(&repo.repo_hash, "manual", PRIORITY_MANUAL).await.unwrap();
+        assert!(!db.retry_replication_job(job.id).await.unwrap());
+        assert!(db.cancel_replication_job(next.id).await.unwrap());
+        assert!(!db.retry_replication_job(next.id).await.unwrap());
+
+        assert!(db.retry_replication_job(job.id).await.unwrap());
+        let retried = db.get_replication_job(job.id).await.unwrap();
+        assert_eq!((retried.state.as_str(), retried.attempts), ("pending", 0));
+    }
+}
@@ -0,0 +1,134 @@
+// src/services/reputation.rs
+use crate::db::Database;
+use crate::models::Node;
+use tracing::{info, warn};
+
+/// Nodes below this score are never picked as replication targets
+pub const MIN_TARGET_REPUTATION: i32 = 50;
+/// Score given to newly registered and explicitly trusted nodes
+pub const MAX_REPUTATION: i32 = 100;
+
+/// Something a node did that moves its reputation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReputationEvent {
+    /// Node stayed online for a whole health check interval
+    Uptime,
+    /// A heartbeat arrived `minutes` after the previous one
+    HeartbeatGap { minutes: i64, timeout_minutes: i64 },
+    ChallengePassed,
+    ChallengeFailed,
+    TransferSucceeded,
+    TransferFailed,
+}
+
+impl ReputationEvent {
+    pub fn delta(&self) -> i32 {
+        match *self {
+            ReputationEvent::Uptime => 1,
+            ReputationEvent::HeartbeatGap { minutes, timeout_minutes } => {
+                // -5 for every missed timeout window, capped at -25
+                let missed = minutes / timeout_minutes.max(1);
+                -((missed * 5).min(25) as i32)
+            }
+            ReputationEvent::ChallengePassed => 1,
+            ReputationEvent::ChallengeFailed => -10,
+            ReputationEvent::TransferSucceeded => 2,
+            ReputationEvent::TransferFailed => -5,
+        }
+    }
+}
+
+/// Keeps `nodes.reputation_score` in line with how nodes actually behave
+pub struct ReputationService {
+    db: Database,
+}
+
+impl ReputationService {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// Apply an event to a node and return its new score
+    pub async fn record(&self, node_id: &str, event: ReputationEvent) -> Result<i32, String> {
+        let delta = event.delta();
+        let score = self.db
+            .adjust_node_reputation(node_id, delta)
+            .await
+            .map_err(|e| format!("Failed to update reputation: {}", e))?;
+
+        if delta < 0 && score < MIN_TARGET_REPUTATION {
+            warn!("Node {} dropped to reputation {} after {:?}", node_id, score, event);
+        }
+
+        Ok(score)
+    }
+
+    /// Reward every node that stayed online since the last health check
+    pub async fn reward_uptime(&self, timeout_minutes: i32) -> Result<u64, String> {
+        self.db
+            .reward_active_nodes(timeout_minutes, ReputationEvent::Uptime.delta())
+            .await
+            .map_err(|e| format!("Failed to reward uptime: {}", e))
+    }
+
+    /// Exclude a node from the network and drop the replicas it holds
+    pub async fn ban(&self, node_id: &str) -> Result<Node, String> {
+        let node = self.db
+            .set_node_standing(node_id, true, 0)
+            .await
+            .map_err(|e| format!("Node not found: {}", e))?;
+
+        let dropped = self.db.delete_node_replicas(node_id).await.unwrap_or(0);
+        info!("Banned node {} ({} replicas dropped)", node_id, dropped);
+
+        Ok(node)
+    }
+
+    /// Lift a ban and restore full reputation
+    pub async fn trust(&self, node_id: &str) -> Result<Node, String> {
+        let node = self.db
+            .set_node_standing(node_id, false, MAX_REPUTATION)
+            .await
+            .map_err(|e| format!("Node not found: {}", e))?;
+
+        info!("Trusted node {}", node_id);
+        Ok(node)
+    }
+
+    /// Forget a node and its replicas; it may register again later
+    pub async fn remove(&self, node_id: &str) -> Result<(), String> {
+        self.db
+            .get_node(node_id)
+            .await
+            .map_err(|e| format!("Node not found: {}", e))?;
+
+        self.db
+            .delete_node(node_id)
+            .await
+            .map_err(|e| format!("Failed to remove node: {}", e))?;
+
+        info!("Removed node {}", node_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_gap_penalty() {
+        let gap = |minutes| ReputationEvent::HeartbeatGap { minutes, timeout_minutes: 10 }.delta();
+
+        assert_eq!(gap(3), 0);
+        assert_eq!(gap(10), -5);
+        assert_eq!(gap(35), -15);
+        assert_eq!(gap(24 * 60), -25);
+    }
+
+    #[test]
+    fn test_failures_outweigh_successes() {
+        assert!(ReputationEvent::ChallengeFailed.delta() + ReputationEvent::ChallengePassed.delta() < 0);
+        assert!(ReputationEvent::TransferFailed.delta() + ReputationEvent::TransferSucceeded.delta() < 0);
+    }
+}
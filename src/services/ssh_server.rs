@@ -0,0 +1,478 @@
+// src/services/ssh_server.rs
+use crate::auth::repo_access::{authorize, RepoRole};
+use crate::handlers::git_http_complete::{after_push, ensure_primary, parse_git_protocol};
+use crate::models::Repository;
+use crate::services::pre_receive::{self, PushPolicy, Screening};
+use crate::AppState;
+use russh::keys::ssh_key::LineEnding;
+use russh::keys::{Algorithm, HashAlg, PrivateKey, PublicKey};
+use russh::server::{Auth, Handler, Msg, Server, Session};
+use russh::{Channel, ChannelId, CryptoVec, MethodKind, MethodSet};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Git service a client asked to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitService {
+    UploadPack,
+    ReceivePack,
+}
+
+impl GitService {
+    fn name(self) -> &'static str {
+        match self {
+            GitService::UploadPack => "upload-pack",
+            GitService::ReceivePack => "receive-pack",
+        }
+    }
+}
+
+/// Split an exec request such as `git-upload-pack 'alice/demo.git'` into
+/// the service and the repository path. Anything else is refused.
+pub fn parse_git_command(command: &str) -> Option<(GitService, &str)> {
+    let (program, arg) = command.trim().split_once(' ')?;
+    let service = match program {
+        "git-upload-pack" => GitService::UploadPack,
+        "git-receive-pack" => GitService::ReceivePack,
+        _ => return None,
+    };
+
+    let arg = arg.trim();
+    let path = arg
+        .strip_prefix('\'')
+        .and_then(|a| a.strip_suffix('\''))
+        .unwrap_or(arg);
+    if path.is_empty() || path.contains('\'') {
+        return None;
+    }
+
+    Some((service, path))
+}
+
+/// Which repository a path names: `owner/name` or a repo hash, with an
+/// optional leading slash and `.git` suffix
+#[derive(Debug, PartialEq, Eq)]
+pub enum RepoPath<'a> {
+    Hash(&'a str),
+    OwnerName(&'a str, &'a str),
+}
+
+pub fn parse_repo_path(path: &str) -> Option<RepoPath<'_>> {
+    let path = path.trim_start_matches('/');
+    let path = path.strip_prefix("git/").unwrap_or(path);
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    match path.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+            Some(RepoPath::OwnerName(owner, name))
+        }
+        Some(_) => None,
+        None => crate::utils::validation::validate_repo_hash(path).then_some(RepoPath::Hash(path)),
+    }
+}
+
+/// Parse a key as pasted from `id_ed25519.pub` into its SHA256 fingerprint
+/// and the OpenSSH encoding without the comment
+pub fn parse_public_key(line: &str) -> Result<(String, String), String> {
+    let mut key = PublicKey::from_openssh(line.trim()).map_err(|_| "Invalid SSH public key".to_string())?;
+    key.set_comment("");
+    let encoded = key.to_openssh().map_err(|_| "Invalid SSH public key".to_string())?;
+
+    Ok((key.fingerprint(HashAlg::Sha256).to_string(), encoded))
+}
+
+/// Load the server's host key, creating one on first start
+pub fn load_host_key(path: &Path) -> Result<PrivateKey, String> {
+    if path.exists() {
+        return PrivateKey::read_openssh_file(path)
+            .map_err(|e| format!("Failed to read SSH host key {}: {}", path.display(), e));
+    }
+
+    let key = PrivateKey::random(&mut rand::rngs::OsRng, Algorithm::Ed25519)
+        .map_err(|e| format!("Failed to generate SSH host key: {}", e))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    key.write_openssh_file(path, LineEnding::LF)
+        .map_err(|e| format!("Failed to write SSH host key {}: {}", path.display(), e))?;
+
+    info!("Generated SSH host key {}", key.public_key().fingerprint(HashAlg::Sha256));
+    Ok(key)
+}
+
+/// Embedded SSH server that only runs git-upload-pack and git-receive-pack
+pub struct GitSshServer {
+    state: Arc<AppState>,
+}
+
+impl GitSshServer {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(mut self, addr: SocketAddr, host_key_path: PathBuf) -> Result<(), String> {
+        let host_key = load_host_key(&host_key_path)?;
+
+        let config = russh::server::Config {
+            methods: MethodSet::from(&[MethodKind::PublicKey][..]),
+            auth_rejection_time: Duration::from_secs(1),
+            // Clients probe with "none" first; don't stall every connection
+            auth_rejection_time_initial: Some(Duration::ZERO),
+            keys: vec![host_key],
+            ..Default::default()
+        };
+
+        info!("Git SSH server listening on {}", addr);
+        self.run_on_address(Arc::new(config), addr)
+            .await
+            .map_err(|e| format!("SSH server failed: {}", e))
+    }
+}
+
+impl Server for GitSshServer {
+    type Handler = GitSshSession;
+
+    fn new_client(&mut self, peer: Option<SocketAddr>) -> GitSshSession {
+        GitSshSession {
+            state: self.state.clone(),
+            peer,
+            user_id: None,
+            protocol: None,
+            channels: HashMap::new(),
+        }
+    }
+}
+
+/// One SSH connection
+pub struct GitSshSession {
+    state: Arc<AppState>,
+    peer: Option<SocketAddr>,
+    user_id: Option<i64>,
+    /// `GIT_PROTOCOL` sent by the client through `SendEnv`
+    protocol: Option<String>,
+    /// Session channels waiting for their exec request
+    channels: HashMap<ChannelId, Channel<Msg>>,
+}
+
+impl GitSshSession {
+    async fn find_key(&self, key: &PublicKey) -> Option<crate::models::SshKey> {
+        let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+        self.state.db.get_ssh_key_by_fingerprint(&fingerprint).await.ok()
+    }
+
+    /// Resolve the repository and apply the same checks as smart HTTP
+    async fn authorize(&self, service: GitService, path: &str) -> Result<Repository, String> {
+        let user_id = self.user_id.ok_or("Not authenticated")?;
+        let not_found = || format!("Repository '{}' not found", path);
+
+        let repo = match parse_repo_path(path).ok_or_else(not_found)? {
+            RepoPath::Hash(hash) => self.state.db.get_repository(hash).await,
+            RepoPath::OwnerName(owner, name) => {
+                self.state.db.resolve_repository(owner, name).await.map(|(repo, _)| repo)
+            }
+        }
+        .map_err(|_| not_found())?;
+
+        let needed = match service {
+            GitService::ReceivePack => RepoRole::Write,
+            GitService::UploadPack => RepoRole::Read,
+        };
+        authorize(&self.state.db, &repo, Some(user_id), needed)
+            .await
+            .map_err(|_| format!("Permission to '{}' denied", path))?;
+
+        ensure_primary(&self.state, &repo.repo_hash)
+            .await
+            .map_err(|_| "Repository is temporarily unavailable".to_string())?;
+
+        Ok(repo)
+    }
+}
+
+impl Handler for GitSshSession {
+    type Error = russh::Error;
+
+    async fn auth_publickey_offered(&mut self, _user: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
+        Ok(match self.find_key(key).await {
+            Some(_) => Auth::Accept,
+            None => Auth::reject(),
+        })
+    }
+
+    async fn auth_publickey(&mut self, _user: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
+        let Some(ssh_key) = self.find_key(key).await else {
+            return Ok(Auth::reject());
+        };
+
+        let _ = self.state.db.touch_ssh_key(ssh_key.id).await;
+        self.user_id = Some(ssh_key.user_id);
+        Ok(Auth::Accept)
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        self.channels.insert(channel.id(), channel);
+        Ok(true)
+    }
+
+    async fn env_request(
+        &mut self,
+        channel: ChannelId,
+        name: &str,
+        value: &str,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        if name == "GIT_PROTOCOL" {
+            self.protocol = parse_git_protocol(value);
+            session.channel_success(channel)
+        } else {
+            session.channel_failure(channel)
+        }
+    }
+
+    async fn exec_request(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let Some(channel_handle) = self.channels.remove(&channel) else {
+            return session.channel_failure(channel);
+        };
+        session.channel_success(channel)?;
+
+        let command = String::from_utf8_lossy(data).to_string();
+        let authorized = match parse_git_command(&command) {
+            Some((service, path)) => self.authorize(service, path).await.map(|repo| (service, repo)),
+            None => Err("Only git fetch and push are supported over SSH".to_string()),
+        };
+
+        match authorized {
+            Ok((service, repo)) => {
+                info!(
+                    "SSH git-{} of {} by user {} from {:?}",
+                    service.name(), repo.repo_hash, self.user_id.unwrap_or_default(), self.peer
+                );
+                let state = self.state.clone();
+                let protocol = self.protocol.clone();
+                let user_id = self.user_id.unwrap_or_default();
+                tokio::spawn(async move {
+                    run_git(state, service, repo.repo_hash, user_id, protocol, channel_handle).await;
+                });
+            }
+            Err(message) => {
+                // git prints whatever arrives on stderr before giving up
+                session.extended_data(channel, 1, CryptoVec::from(format!("ERROR: {}\n", message)))?;
+                session.exit_status_request(channel, 1)?;
+                session.eof(channel)?;
+                session.close(channel)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Run a git service with the channel wired to its stdin, stdout and stderr
+async fn run_git(
+    state: Arc<AppState>,
+    service: GitService,
+    repo_hash: String,
+    user_id: i64,
+    protocol: Option<String>,
+    channel: Channel<Msg>,
+) {
+    let (mut reader, writer) = channel.split();
+    let mut out = writer.make_writer();
+    let mut err = writer.make_writer_ext(Some(1));
+    let repo_path = state.git_storage.repo_path(&repo_hash);
+
+    let code = match service {
+        GitService::UploadPack => {
+            let mut command = Command::new("git");
+            command
+                .arg("upload-pack")
+                .arg(&repo_path)
+                .envs(protocol.map(|p| ("GIT_PROTOCOL", p)));
+            pipe_git(command, reader.make_reader(), &mut out, &mut err).await
+        }
+        GitService::ReceivePack => {
+            let input = reader.make_reader();
+            receive_pack(&state, &repo_hash, &repo_path, user_id, input, &mut out, &mut err).await
+        }
+    };
+
+    let _ = writer.exit_status(code as u32).await;
+    let _ = writer.eof().await;
+    let _ = writer.close().await;
+
+    if code != 0 {
+        warn!("git-{} over SSH exited with {} for {}", service.name(), code, repo_hash);
+    } else if service == GitService::ReceivePack {
+        after_push(&state, &repo_hash).await;
+    }
+}
+
+/// Receive a push in the same two halves smart HTTP uses, so the commands
+/// can be screened against the push rules before git applies them
+async fn receive_pack<R, W, E>(
+    state: &Arc<AppState>,
+    repo_hash: &str,
+    repo_path: &Path,
+    user_id: i64,
+    input: R,
+    out: &mut W,
+    err: &mut E,
+) -> i32
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+    E: AsyncWrite + Unpin,
+{
+    let advertisement = Command::new("git")
+        .args(["receive-pack", "--advertise-refs"])
+        .arg(repo_path)
+        .output()
+        .await;
+    match advertisement {
+        Ok(output) if output.status.success() => {
+            if out.write_all(&output.stdout).await.is_err() || out.flush().await.is_err() {
+                return 1;
+            }
+        }
+        _ => {
+            let _ = err.write_all(b"ERROR: Failed to read repository refs\n").await;
+            return 1;
+        }
+    }
+
+    let mut command = Command::new("git");
+    command.args(["receive-pack", "--stateless-rpc"]).arg(repo_path);
+
+    let policy = match PushPolicy::load(&state.db, repo_hash).await {
+        Ok(policy) => policy,
+        Err(_) => return 1,
+    };
+    if policy.is_empty() {
+        return pipe_git(command, input, out, err).await;
+    }
+
+    match pre_receive::screen(&state.db, &state.git_storage, repo_hash, &policy, user_id, input).await {
+        Ok(Screening::Accept(replay)) => pipe_git(command, replay, out, err).await,
+        Ok(Screening::Reject(response)) => {
+            let _ = out.write_all(&response).await;
+            // git receive-pack also exits cleanly when it refuses refs
+            0
+        }
+        Err(e) => {
+            warn!("Failed to check push to {}: {}", repo_hash, e);
+            let _ = err.write_all(format!("ERROR: {}\n", e).as_bytes()).await;
+            1
+        }
+    }
+}
+
+/// Spawn `command`, feeding it `input` and copying its output to the
+/// channel until it exits
+async fn pipe_git<R, W, E>(mut command: Command, mut input: R, out: &mut W, err: &mut E) -> i32
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+    E: AsyncWrite + Unpin,
+{
+    let child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Failed to start git: {}", e);
+            return 1;
+        }
+    };
+
+    let (mut stdin, mut stdout, mut stderr) = match (child.stdin.take(), child.stdout.take(), child.stderr.take()) {
+        (Some(stdin), Some(stdout), Some(stderr)) => (stdin, stdout, stderr),
+        _ => return 1,
+    };
+
+    // The client's side of the conversation; stdin closes when it sends EOF.
+    // git may finish first (a fetch ends without client EOF), so only the
+    // output decides when we are done.
+    let feed = async {
+        let _ = tokio::io::copy(&mut input, &mut stdin).await;
+        drop(stdin);
+        std::future::pending::<()>().await
+    };
+    let output = async {
+        let _ = tokio::join!(tokio::io::copy(&mut stdout, out), tokio::io::copy(&mut stderr, err));
+    };
+    tokio::select! {
+        _ = feed => {}
+        _ = output => {}
+    }
+
+    let status = child.wait().await;
+    status.as_ref().ok().and_then(|s| s.code()).unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_git_command() {
+        assert_eq!(
+            parse_git_command("git-upload-pack 'alice/demo.git'"),
+            Some((GitService::UploadPack, "alice/demo.git"))
+        );
+        assert_eq!(
+            parse_git_command("git-receive-pack '/alice/demo.git'"),
+            Some((GitService::ReceivePack, "/alice/demo.git"))
+        );
+        assert_eq!(parse_git_command("git-upload-archive 'alice/demo.git'"), None);
+        assert_eq!(parse_git_command("sh -c 'cat /etc/passwd'"), None);
+        assert_eq!(parse_git_command("git-upload-pack"), None);
+        assert_eq!(parse_git_command("git-upload-pack 'a'b'"), None);
+    }
+
+    #[test]
+    fn test_parse_public_key() {
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAINKROwt269rBX2soX3saG7YZbzf22ERzrazEhux0Oj4n alice@laptop";
+        let (fingerprint, encoded) = parse_public_key(&format!("  {}\n", key)).unwrap();
+
+        assert!(fingerprint.starts_with("SHA256:"));
+        assert!(!encoded.contains("alice@laptop"));
+        // Comments don't change the fingerprint
+        assert_eq!(parse_public_key(&encoded).unwrap().0, fingerprint);
+
+        assert!(parse_public_key("ssh-ed25519 not-base64").is_err());
+        assert!(parse_public_key("").is_err());
+    }
+
+    #[test]
+    fn test_parse_repo_path() {
+        let hash = "a".repeat(40);
+        assert_eq!(parse_repo_path("alice/demo.git"), Some(RepoPath::OwnerName("alice", "demo")));
+        assert_eq!(parse_repo_path("/alice/demo"), Some(RepoPath::OwnerName("alice", "demo")));
+        assert_eq!(parse_repo_path(&format!("/git/{}.git", hash)), Some(RepoPath::Hash(&hash)));
+        assert_eq!(parse_repo_path(&format!("{}.git", hash)), Some(RepoPath::Hash(&hash)));
+
+        assert_eq!(parse_repo_path("alice/demo/extra"), None);
+        assert_eq!(parse_repo_path("../../etc"), None);
+        assert_eq!(parse_repo_path("not-a-hash"), None);
+    }
+}
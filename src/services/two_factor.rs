@@ -0,0 +1,370 @@
+// src/services/two_factor.rs
+//! Two-factor authentication shared by the web pages and the API:
+//! enrolling an authenticator, recovery codes, the second login step and
+//! the instance-wide requirement for admins. Functions that check codes
+//! take the current unix time so tests can fix the clock.
+use axum::http::StatusCode;
+use rand::RngCore;
+
+use crate::auth::totp;
+use crate::db::Database;
+use crate::models::User;
+
+pub type TwoFactorError = (StatusCode, String);
+
+/// Shown as the account's issuer in authenticator apps
+pub const ISSUER: &str = "Hyrule";
+/// Setting that makes two-factor mandatory for admins
+pub const REQUIRE_ADMIN_2FA: &str = "require_admin_2fa";
+const RECOVERY_CODE_COUNT: usize = 10;
+/// How long a password check waits for its second factor
+const CHALLENGE_LIFETIME_SECS: i64 = 300;
+/// Wrong codes allowed before the password must be entered again
+const MAX_CHALLENGE_ATTEMPTS: i64 = 5;
+
+fn internal(message: &str) -> TwoFactorError {
+    (StatusCode::INTERNAL_SERVER_ERROR, message.to_string())
+}
+
+fn invalid_code() -> TwoFactorError {
+    (StatusCode::UNAUTHORIZED, "Invalid authentication code".to_string())
+}
+
+fn hash_secret(value: &str) -> String {
+    blake3::hash(value.as_bytes()).to_hex().to_string()
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Recovery codes are compared without case, spaces or dashes
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// New recovery codes like `3f9a1-c07be`, with the hashes to store
+fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code = random_hex(5);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect();
+    let hashes = codes
+        .iter()
+        .map(|code| hash_secret(&normalize_recovery_code(code)))
+        .collect();
+    (codes, hashes)
+}
+
+/// A secret waiting to be confirmed with a code from the app
+pub struct Enrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+impl Enrollment {
+    fn new(user: &User, secret: String) -> Self {
+        let otpauth_uri = totp::otpauth_uri(ISSUER, &user.username, &secret);
+        Self { secret, otpauth_uri }
+    }
+}
+
+/// Start enrolling, replacing any unconfirmed secret
+pub async fn begin_enrollment(db: &Database, user: &User) -> Result<Enrollment, TwoFactorError> {
+    if user.totp_enabled != 0 {
+        return Err((StatusCode::CONFLICT, "Two-factor authentication is already enabled".to_string()));
+    }
+
+    let secret = totp::generate_secret();
+    db.set_pending_totp_secret(user.id, &secret)
+        .await
+        .map_err(|_| internal("Failed to start two-factor setup"))?;
+
+    Ok(Enrollment::new(user, secret))
+}
+
+/// The enrollment in progress, if any
+pub async fn pending_enrollment(db: &Database, user: &User) -> Option<Enrollment> {
+    let state = db.get_totp_state(user.id).await.ok()?;
+    if state.totp_enabled != 0 {
+        return None;
+    }
+    state.totp_secret.map(|secret| Enrollment::new(user, secret))
+}
+
+/// Finish enrolling with a code from the app. Returns the recovery codes,
+/// which are only shown this once.
+pub async fn confirm_enrollment(
+    db: &Database,
+    user_id: i64,
+    code: &str,
+    now: i64,
+) -> Result<Vec<String>, TwoFactorError> {
+    let state = db
+        .get_totp_state(user_id)
+        .await
+        .map_err(|_| internal("Failed to load two-factor settings"))?;
+    if state.totp_enabled != 0 {
+        return Err((StatusCode::CONFLICT, "Two-factor authentication is already enabled".to_string()));
+    }
+    let secret = state
+        .totp_secret
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Start two-factor setup first".to_string()))?;
+    let step = totp::verify(&secret, code, now).ok_or_else(invalid_code)?;
+
+    let (codes, hashes) = generate_recovery_codes();
+    db.enable_totp(user_id, step, &hashes)
+        .await
+        .map_err(|_| internal("Failed to enable two-factor authentication"))?;
+
+    Ok(codes)
+}
+
+/// Check an authenticator or recovery code for a user with two-factor
+/// enabled. Each code is accepted once.
+pub async fn verify_code(db: &Database, user_id: i64, code: &str, now: i64) -> Result<(), TwoFactorError> {
+    let state = db
+        .get_totp_state(user_id)
+        .await
+        .map_err(|_| internal("Failed to load two-factor settings"))?;
+    let secret = match state.totp_secret {
+        Some(secret) if state.totp_enabled != 0 => secret,
+        _ => return Err((StatusCode::BAD_REQUEST, "Two-factor authentication is not enabled".to_string())),
+    };
+
+    if let Some(step) = totp::verify(&secret, code, now) {
+        let fresh = db
+            .record_totp_step(user_id, step)
+            .await
+            .map_err(|_| internal("Failed to verify code"))?;
+        return if fresh { Ok(()) } else { Err(invalid_code()) };
+    }
+
+    let recovery_code = normalize_recovery_code(code);
+    if recovery_code.is_empty() {
+        return Err(invalid_code());
+    }
+    let used = db
+        .use_recovery_code(user_id, &hash_secret(&recovery_code))
+        .await
+        .map_err(|_| internal("Failed to verify code"))?;
+    if used {
+        tracing::info!("User {} signed in with a recovery code", user_id);
+        Ok(())
+    } else {
+        Err(invalid_code())
+    }
+}
+
+/// Turn two-factor off after checking a current code
+pub async fn disable(db: &Database, user: &User, code: &str, now: i64) -> Result<(), TwoFactorError> {
+    if user.is_admin != 0 && admin_2fa_required(db).await {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Admins must keep two-factor authentication enabled".to_string(),
+        ));
+    }
+    verify_code(db, user.id, code, now).await?;
+
+    db.disable_totp(user.id)
+        .await
+        .map_err(|_| internal("Failed to disable two-factor authentication"))
+}
+
+/// Replace the recovery codes after checking a current code
+pub async fn regenerate_recovery_codes(
+    db: &Database,
+    user_id: i64,
+    code: &str,
+    now: i64,
+) -> Result<Vec<String>, TwoFactorError> {
+    verify_code(db, user_id, code, now).await?;
+
+    let (codes, hashes) = generate_recovery_codes();
+    db.replace_recovery_codes(user_id, &hashes)
+        .await
+        .map_err(|_| internal("Failed to create recovery codes"))?;
+
+    Ok(codes)
+}
+
+/// Record a correct password for a user who still owes a second factor.
+/// The returned challenge is handed to the client.
+pub async fn start_login_challenge(db: &Database, user_id: i64) -> Result<String, TwoFactorError> {
+    let challenge = random_hex(32);
+    db.create_login_challenge(&hash_secret(&challenge), user_id, CHALLENGE_LIFETIME_SECS)
+        .await
+        .map_err(|_| internal("Failed to start login"))?;
+
+    Ok(challenge)
+}
+
+/// Finish a login with the challenge and a code. Returns the user whose
+/// login it was.
+pub async fn complete_login_challenge(
+    db: &Database,
+    challenge: &str,
+    code: &str,
+    now: i64,
+) -> Result<User, TwoFactorError> {
+    let key = hash_secret(challenge);
+    let user_id = db
+        .attempt_login_challenge(&key, MAX_CHALLENGE_ATTEMPTS)
+        .await
+        .map_err(|_| internal("Failed to verify code"))?
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Login expired, please sign in again".to_string()))?;
+
+    verify_code(db, user_id, code, now).await?;
+    if let Err(e) = db.delete_login_challenge(&key).await {
+        tracing::warn!("Failed to clear login challenge: {}", e);
+    }
+
+    db.get_user_by_id(user_id)
+        .await
+        .map_err(|_| internal("Failed to load user"))
+}
+
+pub async fn admin_2fa_required(db: &Database) -> bool {
+    db.get_setting(REQUIRE_ADMIN_2FA).await.ok().flatten().as_deref() == Some("true")
+}
+
+/// Whether the user may not use admin features until they enroll
+pub async fn must_enroll(db: &Database, user: &User) -> bool {
+    user.is_admin != 0 && user.totp_enabled == 0 && admin_2fa_required(db).await
+}
+
+/// Require two-factor for every admin, or stop requiring it. An admin
+/// turning it on must have it already, or they would lock themselves out.
+pub async fn set_admin_2fa_required(db: &Database, admin: &User, required: bool) -> Result<(), TwoFactorError> {
+    if required && admin.totp_enabled == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Enable two-factor authentication on your own account first".to_string(),
+        ));
+    }
+
+    db.set_setting(REQUIRE_ADMIN_2FA, if required { "true" } else { "false" })
+        .await
+        .map_err(|_| internal("Failed to save setting"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDb;
+
+    /// A fixed clock, well inside one TOTP step
+    const NOW: i64 = 1_700_000_010;
+
+    async fn setup() -> (TestDb, User) {
+        let db = TestDb::new().await;
+        let user = db.add_named_user("alice").await;
+        (db, user)
+    }
+
+    async fn enroll(db: &Database, user: &User) -> (String, Vec<String>) {
+        let enrollment = begin_enrollment(db, user).await.unwrap();
+        let code = totp::code_at(&enrollment.secret, NOW).unwrap();
+        let recovery = confirm_enrollment(db, user.id, &code, NOW).await.unwrap();
+        (enrollment.secret, recovery)
+    }
+
+    #[tokio::test]
+    async fn enrollment_needs_a_valid_code() {
+        let (db, user) = setup().await;
+        let enrollment = begin_enrollment(&db, &user).await.unwrap();
+        assert!(enrollment.otpauth_uri.contains(&enrollment.secret));
+        assert_eq!(pending_enrollment(&db, &user).await.unwrap().secret, enrollment.secret);
+
+        let wrong = totp::code_at(&enrollment.secret, NOW + 300).unwrap();
+        assert_eq!(confirm_enrollment(&db, user.id, &wrong, NOW).await.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        assert_eq!(db.get_user_by_id(user.id).await.unwrap().totp_enabled, 0);
+
+        let code = totp::code_at(&enrollment.secret, NOW).unwrap();
+        let recovery = confirm_enrollment(&db, user.id, &code, NOW).await.unwrap();
+        assert_eq!(recovery.len(), RECOVERY_CODE_COUNT);
+        let user = db.get_user_by_id(user.id).await.unwrap();
+        assert_eq!(user.totp_enabled, 1);
+        assert!(begin_enrollment(&db, &user).await.is_err());
+        assert!(pending_enrollment(&db, &user).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn codes_work_once() {
+        let (db, user) = setup().await;
+        let (secret, recovery) = enroll(&db, &user).await;
+
+        // The enrollment code itself cannot be replayed
+        let enrolled_code = totp::code_at(&secret, NOW).unwrap();
+        assert!(verify_code(&db, user.id, &enrolled_code, NOW).await.is_err());
+
+        let later = NOW + 60;
+        let code = totp::code_at(&secret, later).unwrap();
+        verify_code(&db, user.id, &code, later).await.unwrap();
+        assert!(verify_code(&db, user.id, &code, later).await.is_err());
+
+        verify_code(&db, user.id, &recovery[0].to_uppercase(), later).await.unwrap();
+        assert!(verify_code(&db, user.id, &recovery[0], later).await.is_err());
+        assert_eq!(db.count_unused_recovery_codes(user.id).await.unwrap(), 9);
+
+        let fresh = regenerate_recovery_codes(&db, user.id, &recovery[1], later).await.unwrap();
+        assert!(verify_code(&db, user.id, &recovery[2], later).await.is_err());
+        verify_code(&db, user.id, &fresh[0], later).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn login_challenge_flow() {
+        let (db, user) = setup().await;
+        let (secret, _) = enroll(&db, &user).await;
+        let later = NOW + 60;
+        let code = totp::code_at(&secret, later).unwrap();
+
+        let challenge = start_login_challenge(&db, user.id).await.unwrap();
+        assert!(complete_login_challenge(&db, "bogus", &code, later).await.is_err());
+        assert!(complete_login_challenge(&db, &challenge, "000000", later).await.is_err());
+        let signed_in = complete_login_challenge(&db, &challenge, &code, later).await.unwrap();
+        assert_eq!(signed_in.id, user.id);
+        // A challenge is spent once it succeeds
+        let next = totp::code_at(&secret, later + 30).unwrap();
+        assert!(complete_login_challenge(&db, &challenge, &next, later + 30).await.is_err());
+
+        // and after too many wrong codes
+        let challenge = start_login_challenge(&db, user.id).await.unwrap();
+        for _ in 0..MAX_CHALLENGE_ATTEMPTS {
+            assert!(complete_login_challenge(&db, &challenge, "000000", later + 30).await.is_err());
+        }
+        assert!(complete_login_challenge(&db, &challenge, &next, later + 30).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn admins_can_be_required_to_enroll() {
+        let (db, mut admin) = setup().await;
+        admin.is_admin = 1;
+        assert!(!must_enroll(&db, &admin).await);
+        assert!(set_admin_2fa_required(&db, &admin, true).await.is_err());
+
+        let (secret, _) = enroll(&db, &admin).await;
+        admin.totp_enabled = 1;
+        set_admin_2fa_required(&db, &admin, true).await.unwrap();
+        assert!(admin_2fa_required(&db).await);
+        assert!(!must_enroll(&db, &admin).await);
+
+        let unenrolled = User { totp_enabled: 0, ..admin.clone() };
+        assert!(must_enroll(&db, &unenrolled).await);
+
+        let later = NOW + 60;
+        let code = totp::code_at(&secret, later).unwrap();
+        assert_eq!(disable(&db, &admin, &code, later).await.unwrap_err().0, StatusCode::FORBIDDEN);
+        set_admin_2fa_required(&db, &admin, false).await.unwrap();
+        let code = totp::code_at(&secret, later + 30).unwrap();
+        disable(&db, &admin, &code, later + 30).await.unwrap();
+        assert_eq!(db.get_user_by_id(admin.id).await.unwrap().totp_enabled, 0);
+    }
+}
@@ -1,8 +1,30 @@
 // Hyrule/src/storage/git.rs
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::fs;
 use anyhow::Result;
 
+/// Settings that let clients use `--filter` and later fetch the blobs a
+/// partial clone left out. Shallow clones need no config.
+const UPLOAD_PACK_CONFIG: &[(&str, &str)] = &[
+    ("uploadpack.allowFilter", "true"),
+    ("uploadpack.allowReachableSHA1InWant", "true"),
+];
+
+/// Upload-pack features enabled on a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadPackSettings {
+    pub allow_filter: bool,
+    pub allow_reachable_sha1_in_want: bool,
+}
+
+impl UploadPackSettings {
+    /// Whether `git clone --filter=...` works and can fetch missing blobs later
+    pub fn partial_clone(&self) -> bool {
+        self.allow_filter && self.allow_reachable_sha1_in_want
+    }
+}
+
 pub struct GitStorage {
     base_path: PathBuf,
 }
@@ -24,6 +46,11 @@ impl GitStorage {
     pub fn refs_path(&self, repo_hash: &str) -> PathBuf {
         self.repo_path(repo_hash).join("refs")
     }
+
+    /// A fresh scratch directory for a push that is still being checked
+    pub fn incoming_path(&self, repo_hash: &str) -> PathBuf {
+        self.base_path.join(format!(".incoming-{}-{:016x}", repo_hash, rand::random::<u64>()))
+    }
     
     /// Initialize a repository storage structure
   /// Initialize a repository storage structure
@@ -42,8 +69,64 @@ pub fn init_repo(&self, repo_hash: &str) -> Result<()> {
             String::from_utf8_lossy(&output.stderr));
     }
     
-    Ok(())
+    Self::apply_upload_pack_config(&repo_path)
 }
+
+    /// Enable partial and shallow clone support on an existing repository
+    pub fn configure_upload_pack(&self, repo_hash: &str) -> Result<()> {
+        Self::apply_upload_pack_config(&self.repo_path(repo_hash))
+    }
+
+    /// Apply the upload-pack settings to every repository in storage
+    pub fn configure_all_repos(&self) -> Result<usize> {
+        let mut configured = 0;
+        for entry in fs::read_dir(&self.base_path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Skip restore and push staging directories and stray files
+            if name.starts_with('.') || !entry.path().join("objects").is_dir() {
+                continue;
+            }
+            self.configure_upload_pack(&name)?;
+            configured += 1;
+        }
+        Ok(configured)
+    }
+
+    /// Read back the upload-pack settings of a repository
+    pub fn upload_pack_settings(&self, repo_hash: &str) -> Result<UploadPackSettings> {
+        let repo_path = self.repo_path(repo_hash);
+        let get = |key: &str| -> Result<bool> {
+            let output = std::process::Command::new("git")
+                .arg("--git-dir")
+                .arg(&repo_path)
+                .args(["config", "--bool", "--get", key])
+                .output()?;
+            // Exit code 1 means the key is unset, which git treats as false
+            Ok(output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "true")
+        };
+
+        Ok(UploadPackSettings {
+            allow_filter: get("uploadpack.allowFilter")?,
+            allow_reachable_sha1_in_want: get("uploadpack.allowReachableSHA1InWant")?,
+        })
+    }
+
+    fn apply_upload_pack_config(git_dir: &Path) -> Result<()> {
+        for (key, value) in UPLOAD_PACK_CONFIG {
+            let output = std::process::Command::new("git")
+                .arg("--git-dir")
+                .arg(git_dir)
+                .args(["config", key, value])
+                .output()?;
+
+            if !output.status.success() {
+                anyhow::bail!("Failed to set {}: {}", key,
+                    String::from_utf8_lossy(&output.stderr));
+            }
+        }
+        Ok(())
+    }
     
     /// Store a Git object
     pub fn store_object(&self, repo_hash: &str, object_id: &str, data: &[u8]) -> Result<()> {
@@ -170,6 +253,260 @@ pub fn init_repo(&self, repo_hash: &str) -> Result<()> {
         Ok(total_size)
     }
     
+    /// List all refs in a repository as refname -> object id
+    pub fn list_refs(&self, repo_hash: &str) -> Result<BTreeMap<String, String>> {
+        let output = std::process::Command::new("git")
+            .arg("--git-dir")
+            .arg(self.repo_path(repo_hash))
+            .arg("for-each-ref")
+            .arg("--format=%(objectname) %(refname)")
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!("Failed to list refs: {}",
+                String::from_utf8_lossy(&output.stderr));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let (oid, name) = line.split_once(' ')?;
+                Some((name.to_string(), oid.to_string()))
+            })
+            .collect())
+    }
+
+    /// Package every ref of a repository into a git bundle
+    pub fn create_bundle(&self, repo_hash: &str) -> Result<Vec<u8>> {
+        let repo_path = self.repo_path(repo_hash);
+
+        if !repo_path.exists() {
+            anyhow::bail!("Repository not found in storage: {}", repo_hash);
+        }
+
+        if self.list_refs(repo_hash)?.is_empty() {
+            anyhow::bail!("Repository has no refs to replicate");
+        }
+
+        let output = std::process::Command::new("git")
+            .arg("--git-dir")
+            .arg(&repo_path)
+            .arg("bundle")
+            .arg("create")
+            .arg("-")
+            .arg("--all")
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!("Failed to create bundle: {}",
+                String::from_utf8_lossy(&output.stderr));
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// Recreate a missing repository from a bundle
+    ///
+    /// The bundle is unpacked next to the final location and moved into
+    /// place only once complete, so readers never see a partial repository.
+    /// With `expected_refs` the restore is rejected unless the refs match.
+    pub fn restore_from_bundle(
+        &self,
+        repo_hash: &str,
+        bundle: &[u8],
+        expected_refs: Option<&BTreeMap<String, String>>,
+    ) -> Result<()> {
+        let repo_path = self.repo_path(repo_hash);
+        if repo_path.exists() {
+            return Ok(());
+        }
+
+        let suffix = format!("{:016x}", rand::random::<u64>());
+        let staging = self.base_path.join(format!(".restore-{}-{}", repo_hash, suffix));
+        let bundle_path = self.base_path.join(format!(".restore-{}-{}.bundle", repo_hash, suffix));
+
+        fs::write(&bundle_path, bundle)?;
+        let result = Self::unpack_bundle(&staging, &bundle_path).and_then(|refs| {
+            if expected_refs.is_some_and(|expected| *expected != refs) {
+                anyhow::bail!("Bundle refs do not match the expected refs");
+            }
+            Ok(fs::rename(&staging, &repo_path)?)
+        });
+        let _ = fs::remove_file(&bundle_path);
+
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&staging);
+            // Another request may have restored it first
+            if repo_path.exists() {
+                return Ok(());
+            }
+        }
+
+        result
+    }
+
+    fn unpack_bundle(git_dir: &Path, bundle_path: &Path) -> Result<BTreeMap<String, String>> {
+        let git = |args: &[&str]| -> Result<String> {
+            let output = std::process::Command::new("git")
+                .arg("--git-dir")
+                .arg(git_dir)
+                .args(args)
+                .output()?;
+            if !output.status.success() {
+                anyhow::bail!("git {} failed: {}", args[0],
+                    String::from_utf8_lossy(&output.stderr));
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        };
+        let bundle = bundle_path.to_string_lossy();
+
+        git(&["init", "--bare", "--quiet"])?;
+        Self::apply_upload_pack_config(git_dir)?;
+        git(&["bundle", "verify", &bundle])?;
+        git(&["fetch", "--quiet", &bundle, "+refs/*:refs/*"])?;
+
+        // Point HEAD at the branch the bundle's HEAD resolves to
+        let heads = git(&["bundle", "list-heads", &bundle])?;
+        let heads: Vec<(&str, &str)> = heads.lines().filter_map(|l| l.split_once(' ')).collect();
+        let branch = heads
+            .iter()
+            .find(|(_, name)| *name == "HEAD")
+            .and_then(|(oid, _)| {
+                heads
+                    .iter()
+                    .find(|(o, name)| o == oid && name.starts_with("refs/heads/"))
+            });
+        if let Some((_, branch)) = branch {
+            git(&["symbolic-ref", "HEAD", branch])?;
+        }
+
+        Ok(git(&["for-each-ref", "--format=%(objectname) %(refname)"])?
+            .lines()
+            .filter_map(|line| {
+                let (oid, name) = line.split_once(' ')?;
+                Some((name.to_string(), oid.to_string()))
+            })
+            .collect())
+    }
+
+    /// Pick up to `count` random object ids from a repository
+    pub fn sample_object_ids(&self, repo_hash: &str, count: usize) -> Result<Vec<String>> {
+        use rand::seq::SliceRandom;
+
+        let output = std::process::Command::new("git")
+            .arg("--git-dir")
+            .arg(self.repo_path(repo_hash))
+            .arg("cat-file")
+            .arg("--batch-all-objects")
+            .arg("--batch-check=%(objectname)")
+            .output()?;
+
+        if !output.status.success() {
+            anyhow::bail!("Failed to enumerate objects: {}",
+                String::from_utf8_lossy(&output.stderr));
+        }
+
+        let all: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+
+        Ok(all
+            .choose_multiple(&mut rand::thread_rng(), count)
+            .cloned()
+            .collect())
+    }
+
+    /// Read the raw contents of several objects, in the order given
+    pub fn read_objects_raw(&self, repo_hash: &str, object_ids: &[String]) -> Result<Vec<(String, Vec<u8>)>> {
+        use std::io::Write;
+        use std::process::Stdio;
+
+        let mut child = std::process::Command::new("git")
+            .arg("--git-dir")
+            .arg(self.repo_path(repo_hash))
+            .arg("cat-file")
+            .arg("--batch")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(format!("{}\n", object_ids.join("\n")).as_bytes())?;
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            anyhow::bail!("Failed to read objects: {}",
+                String::from_utf8_lossy(&output.stderr));
+        }
+
+        crate::services::node_protocol::parse_cat_file_batch(&output.stdout)
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+
+    /// Build a thin pack with everything reachable from `wants` but not from `haves`
+    ///
+    /// Haves unknown to this repository (e.g. refs deleted since) are ignored.
+    pub fn create_thin_pack(&self, repo_hash: &str, wants: &[String], haves: &[String]) -> Result<Vec<u8>> {
+        let known_haves: Vec<&String> = if haves.is_empty() {
+            Vec::new()
+        } else {
+            let present = self.run_with_input(
+                repo_hash,
+                &["cat-file", "--batch-check=%(objectname)"],
+                format!("{}\n", haves.join("\n")).as_bytes(),
+            )?;
+            let present = String::from_utf8_lossy(&present).to_string();
+            haves.iter().filter(|h| present.lines().any(|l| l == h.as_str())).collect()
+        };
+
+        let mut revs = String::new();
+        for want in wants {
+            revs.push_str(&format!("{}\n", want));
+        }
+        for have in known_haves {
+            revs.push_str(&format!("^{}\n", have));
+        }
+
+        self.run_with_input(
+            repo_hash,
+            &["pack-objects", "--revs", "--thin", "--stdout", "--delta-base-offset", "-q"],
+            revs.as_bytes(),
+        )
+    }
+
+    /// Run a git command in a repository with `input` on stdin
+    fn run_with_input(&self, repo_hash: &str, args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+        use std::io::Write;
+        use std::process::Stdio;
+
+        let mut child = std::process::Command::new("git")
+            .arg("--git-dir")
+            .arg(self.repo_path(repo_hash))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Feed stdin from a thread so a large stdout cannot deadlock us
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("no stdin"))?;
+        let input = input.to_vec();
+        let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+        let output = child.wait_with_output()?;
+        let _ = writer.join();
+
+        if !output.status.success() {
+            anyhow::bail!("git {} failed: {}", args[0],
+                String::from_utf8_lossy(&output.stderr));
+        }
+
+        Ok(output.stdout)
+    }
+
     /// Create a packfile from loose objects
     pub fn create_pack(&self, repo_hash: &str) -> Result<Vec<u8>> {
         // Simplified pack creation - just concatenate objects
@@ -1,4 +1,4 @@
 // Hyrule/src/storage/mod.rs
 pub mod git;
 
-pub use git::GitStorage;
+pub use git::{GitStorage, UploadPackSettings};
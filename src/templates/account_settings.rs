@@ -0,0 +1,152 @@
+// src/templates/account_settings.rs
+use super::{html_escape, render_page};
+use crate::models::User;
+use crate::services::accounts;
+
+/// What the account settings page shows
+pub struct AccountSettingsView<'a> {
+    pub user: &'a User,
+    /// Personal repositories, which deleting the account deletes or transfers
+    pub repo_count: usize,
+    pub error: Option<&'a str>,
+    pub success: Option<&'a str>,
+}
+
+pub fn render(view: &AccountSettingsView) -> String {
+    let user = view.user;
+    let notice = match (view.error, view.success) {
+        (Some(message), _) => format!(r#"<div class="error-message">{}</div>"#, html_escape(message)),
+        (None, Some(message)) => format!(r#"<div class="success-message">✅ {}</div>"#, html_escape(message)),
+        (None, None) => String::new(),
+    };
+
+    let current_email = if !accounts::has_real_email(user) {
+        "You haven't added an email address yet.".to_string()
+    } else if user.email_verified != 0 {
+        format!("Your address is <strong>{}</strong> (verified).", html_escape(&user.email))
+    } else {
+        format!("Your address is <strong>{}</strong> (not verified).", html_escape(&user.email))
+    };
+
+    let repos_html = if view.repo_count == 0 {
+        "<p>You have no personal repositories.</p>".to_string()
+    } else {
+        format!(
+            r#"<p>You have {} personal {}. They are deleted with your account unless you name a user to take them over, who must already be an admin collaborator on each of them.</p>
+            <div class="form-group">
+                <label for="transfer_to">Transfer repositories to (optional)</label>
+                <input type="text" id="transfer_to" name="transfer_to" placeholder="username" autocomplete="off">
+            </div>"#,
+            view.repo_count,
+            if view.repo_count == 1 { "repository" } else { "repositories" }
+        )
+    };
+
+    let content = format!(
+        r#"
+    <div class="breadcrumb">
+        <a href="/profile">← Back to Profile</a>
+    </div>
+
+    <h1>Account Settings</h1>
+
+    {}
+
+    <div class="section">
+        <h2>Change Password</h2>
+        <p>Other sessions are signed out and API login tokens stop working when your password changes. Personal access tokens keep working.</p>
+        <form method="POST" action="/profile/settings" class="settings-form">
+            <input type="hidden" name="action" value="change_password">
+            <div class="form-group">
+                <label for="current_password">Current password</label>
+                <input type="password" id="current_password" name="current_password" required autocomplete="current-password">
+            </div>
+            <div class="form-group">
+                <label for="new_password">New password</label>
+                <input type="password" id="new_password" name="new_password" required minlength="8" autocomplete="new-password">
+                <small>At least 8 characters, with a letter and a number</small>
+            </div>
+            <div class="form-group">
+                <label for="confirm_password">Confirm new password</label>
+                <input type="password" id="confirm_password" name="confirm_password" required minlength="8" autocomplete="new-password">
+            </div>
+            <button type="submit" class="btn btn-primary">Change Password</button>
+        </form>
+    </div>
+
+    <div class="section">
+        <h2>Email Address</h2>
+        <p>{} We'll email a link to verify the new address.</p>
+        <form method="POST" action="/profile/settings" class="settings-form">
+            <input type="hidden" name="action" value="change_email">
+            <div class="form-group">
+                <label for="email">New email address</label>
+                <input type="email" id="email" name="email" required autocomplete="email">
+            </div>
+            <div class="form-group">
+                <label for="email_password">Password</label>
+                <input type="password" id="email_password" name="password" required autocomplete="current-password">
+            </div>
+            <button type="submit" class="btn btn-primary">Change Email</button>
+        </form>
+    </div>
+
+    <div class="section danger-zone">
+        <h2>Delete Account</h2>
+        <p>This can't be undone. Your keys, tokens, stars and pins are deleted, and you leave your organizations.</p>
+        <form method="POST" action="/profile/settings" class="settings-form">
+            <input type="hidden" name="action" value="delete_account">
+            {}
+            <div class="form-group">
+                <label for="confirm_username">Type <strong>{}</strong> to confirm</label>
+                <input type="text" id="confirm_username" name="confirm_username" required autocomplete="off">
+            </div>
+            <div class="form-group">
+                <label for="delete_password">Password</label>
+                <input type="password" id="delete_password" name="password" required autocomplete="current-password">
+            </div>
+            <button type="submit" class="btn btn-danger">Delete My Account</button>
+        </form>
+    </div>
+
+    <style>
+        .settings-form {{
+            max-width: 480px;
+        }}
+
+        .settings-form small {{
+            color: var(--text-muted);
+            display: block;
+            margin-top: 0.5rem;
+        }}
+
+        .danger-zone {{
+            border-color: rgba(255, 68, 68, 0.3);
+        }}
+
+        .error-message {{
+            background: rgba(255, 68, 68, 0.1);
+            border: 2px solid rgba(255, 68, 68, 0.3);
+            border-radius: 15px;
+            padding: 1rem 2rem;
+            margin: 0 0 2rem 0;
+        }}
+
+        .success-message {{
+            background: rgba(0, 255, 136, 0.1);
+            border: 2px solid rgba(0, 255, 136, 0.3);
+            border-radius: 15px;
+            padding: 1rem 2rem;
+            margin: 0 0 2rem 0;
+            color: var(--primary-color);
+        }}
+    </style>
+    "#,
+        notice,
+        current_email,
+        repos_html,
+        html_escape(&user.username)
+    );
+
+    render_page("Account Settings", &content)
+}
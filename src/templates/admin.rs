@@ -1,6 +1,7 @@
 // src/templates/admin.rs
-use super::render_page;
-use crate::models::{Node, Repository};
+use super::{html_escape, render_page};
+use crate::models::{Node, NodeStateTransition, ReplicationJob, Repository, User};
+use crate::services::reputation::MIN_TARGET_REPUTATION;
 
 pub fn render_dashboard(
     total_repos: i64,
@@ -8,7 +9,61 @@ pub fn render_dashboard(
     unhealthy_repos: i64,
     total_storage: i64,
     bandwidth_24h: i64,
+    job_counts: &[(String, i64)],
+    jobs: &[ReplicationJob],
 ) -> String {
+    let counts_html = if job_counts.is_empty() {
+        "no jobs".to_string()
+    } else {
+        job_counts
+            .iter()
+            .map(|(state, count)| format!(r#"<span class="job-state job-{0}">{0}: {1}</span>"#, html_escape(state), count))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    
+    let jobs_html = if jobs.is_empty() {
+        r#"<p class="empty-state">No replication jobs yet.</p>"#.to_string()
+    } else {
+        let rows = jobs.iter().map(|job| {
+            let actions = match job.state.as_str() {
+                "pending" => job_action_form(job.id, "cancel", "Cancel", "btn-danger"),
+                "failed" => job_action_form(job.id, "retry", "Retry", "btn-primary"),
+                _ => String::new(),
+            };
+            
+            format!(
+                r#"<tr>
+                    <td>#{}</td>
+                    <td><code>{}</code></td>
+                    <td><span class="job-state job-{state}">{state}</span></td>
+                    <td>{}</td>
+                    <td>{}/{}</td>
+                    <td>{}</td>
+                    <td title="{err}">{err}</td>
+                    <td>{}</td>
+                </tr>"#,
+                job.id,
+                html_escape(job.repo_hash.get(..12).unwrap_or(&job.repo_hash)),
+                html_escape(&job.reason),
+                job.attempts,
+                job.max_attempts,
+                job.updated_at,
+                actions,
+                state = html_escape(&job.state),
+                err = html_escape(job.last_error.as_deref().unwrap_or("")),
+            )
+        }).collect::<Vec<_>>().join("\n");
+        
+        format!(
+            r#"<table class="jobs-table">
+                <thead><tr><th>Job</th><th>Repository</th><th>State</th><th>Reason</th><th>Attempts</th><th>Updated</th><th>Last Error</th><th></th></tr></thead>
+                <tbody>{}</tbody>
+            </table>"#,
+            rows
+        )
+    };
+    
     let content = format!(
         r#"
     <h1> Admin Dashboard</h1>
@@ -65,6 +120,12 @@ pub fn render_dashboard(
         </div>
     </div>
     
+    <div class="admin-section">
+        <h2>Replication Queue</h2>
+        <p class="job-counts">{}</p>
+        {}
+    </div>
+    
     <div class="admin-section">
         <h2>Recent Activity</h2>
         <p class="empty-state">Activity log coming soon...</p>
@@ -130,6 +191,38 @@ pub fn render_dashboard(
             border-radius: var(--border-radius);
             margin: 2rem 0;
         }}
+        
+        .job-state {{
+            padding: 0.2rem 0.6rem;
+            border-radius: 8px;
+            font-size: 0.85rem;
+            background: var(--bg-glass);
+        }}
+        
+        .job-succeeded {{ background: rgba(0, 255, 136, 0.2); }}
+        .job-running {{ background: rgba(0, 170, 255, 0.25); }}
+        .job-pending {{ background: rgba(255, 200, 0, 0.25); }}
+        .job-failed {{ background: rgba(255, 60, 60, 0.25); }}
+        
+        .jobs-table {{
+            width: 100%;
+            border-collapse: collapse;
+            margin-top: 1rem;
+        }}
+        
+        .jobs-table th,
+        .jobs-table td {{
+            padding: 0.75rem;
+            text-align: left;
+            border-bottom: 1px solid var(--border-color);
+        }}
+        
+        .jobs-table td:nth-child(7) {{
+            max-width: 300px;
+            overflow: hidden;
+            text-overflow: ellipsis;
+            white-space: nowrap;
+        }}
     </style>
     
     <script>
@@ -165,15 +258,35 @@ pub fn render_dashboard(
         unhealthy_repos,
         total_storage / (1024 * 1024 * 1024),
         bandwidth_24h / (1024 * 1024 * 1024),
+        counts_html,
+        jobs_html,
     );
     
     render_page("Admin Dashboard", &content)
 }
 
-pub fn render_nodes(nodes: &[(Node, i64)]) -> String {
+pub fn render_nodes(nodes: &[(Node, i64)], transitions: &[NodeStateTransition]) -> String {
     let nodes_html = nodes.iter().map(|(node, repo_count)| {
         let status = if node.is_anchor != 0 { " Anchor" } else { " P2P" };
-        let health = if node.reputation_score >= 80 { "Healthy" } else { "Degraded" };
+        let health = if node.is_banned != 0 {
+            "Banned"
+        } else if node.reputation_score < MIN_TARGET_REPUTATION {
+            "Untrusted"
+        } else if node.reputation_score >= 80 {
+            "Healthy"
+        } else {
+            "Degraded"
+        };
+        // Banned nodes can only be trusted again or removed
+        let standing_action = if node.is_banned != 0 {
+            node_action_form(&node.node_id, "trust", "Trust", "btn-primary", "")
+        } else {
+            format!(
+                "{}{}",
+                node_action_form(&node.node_id, "trust", "Trust", "btn-secondary", ""),
+                node_action_form(&node.node_id, "ban", "Ban", "btn-warning", "Ban this node and drop its replicas?"),
+            )
+        };
         
         format!(
             r#"
@@ -203,14 +316,19 @@ pub fn render_nodes(nodes: &[(Node, i64)]) -> String {
                     <span class="detail-label">Last Seen:</span>
                     <span class="detail-value">{}</span>
                 </div>
+                <div class="detail-row">
+                    <span class="detail-label">State:</span>
+                    <span class="detail-value"><span class="node-state state-{}">{}</span> since {}</span>
+                </div>
                 <div class="detail-row">
                     <span class="detail-label">Health:</span>
                     <span class="detail-value">{}</span>
                 </div>
             </div>
             <div class="node-actions">
-                <button class="btn btn-secondary" onclick="pingNode('{}')">Ping</button>
-                <button class="btn btn-warning">Ban</button>
+                {}
+                {}
+                {}
             </div>
         </div>
         "#,
@@ -223,11 +341,44 @@ pub fn render_nodes(nodes: &[(Node, i64)]) -> String {
             node.storage_capacity / (1024 * 1024 * 1024),
             node.reputation_score,
             &node.last_seen[..19],
+            html_escape(&node.state),
+            html_escape(&node.state),
+            node.state_changed_at.as_deref().unwrap_or(&node.last_seen),
             health,
-            node.node_id,
+            node_action_form(&node.node_id, "ping", "Ping", "btn-secondary", ""),
+            standing_action,
+            node_action_form(&node.node_id, "remove", "Remove", "btn-danger", "Remove this node and its replicas?"),
         )
     }).collect::<Vec<_>>().join("\n");
     
+    let transitions_html = if transitions.is_empty() {
+        r#"<p class="empty-state">No state changes recorded yet.</p>"#.to_string()
+    } else {
+        let rows = transitions.iter().map(|t| {
+            format!(
+                r#"<tr>
+                    <td>{}</td>
+                    <td><code>{}</code></td>
+                    <td><span class="node-state state-{from}">{from}</span> → <span class="node-state state-{to}">{to}</span></td>
+                    <td>{} min</td>
+                </tr>"#,
+                t.created_at,
+                html_escape(t.node_id.get(..12).unwrap_or(&t.node_id)),
+                t.silent_minutes,
+                from = html_escape(&t.from_state),
+                to = html_escape(&t.to_state),
+            )
+        }).collect::<Vec<_>>().join("\n");
+        
+        format!(
+            r#"<table class="transitions-table">
+                <thead><tr><th>When</th><th>Node</th><th>Change</th><th>Silent</th></tr></thead>
+                <tbody>{}</tbody>
+            </table>"#,
+            rows
+        )
+    };
+    
     let content = format!(
         r#"
     <h1> Node Management</h1>
@@ -241,6 +392,9 @@ pub fn render_nodes(nodes: &[(Node, i64)]) -> String {
         {}
     </div>
     
+    <h2>Recent State Changes</h2>
+    {}
+    
     <style>
         .admin-toolbar {{
             display: flex;
@@ -331,21 +485,72 @@ pub fn render_nodes(nodes: &[(Node, i64)]) -> String {
             display: flex;
             gap: 1rem;
         }}
+        
+        .node-state {{
+            padding: 0.15rem 0.6rem;
+            border-radius: 12px;
+            font-weight: 600;
+        }}
+        
+        .state-online {{ background: rgba(0, 255, 136, 0.2); }}
+        .state-suspect {{ background: rgba(255, 200, 0, 0.25); }}
+        .state-offline {{ background: rgba(255, 120, 0, 0.25); }}
+        .state-evicted {{ background: rgba(255, 60, 60, 0.25); }}
+        
+        .transitions-table {{
+            width: 100%;
+            border-collapse: collapse;
+            margin-top: 1rem;
+        }}
+        
+        .transitions-table th,
+        .transitions-table td {{
+            padding: 0.75rem;
+            text-align: left;
+            border-bottom: 1px solid var(--border-color);
+        }}
     </style>
     
-    <script>
-        async function pingNode(nodeId) {{
-            console.log('Pinging node:', nodeId);
-            // Implement ping functionality
-        }}
-    </script>
     "#,
-        nodes_html
+        nodes_html,
+        transitions_html
     );
     
     render_page("Node Management", &content)
 }
 
+fn job_action_form(job_id: i64, action: &str, label: &str, class: &str) -> String {
+    format!(
+        r#"<form method="POST" action="/admin/replication/action" style="display:inline;">
+                    <input type="hidden" name="job_id" value="{}">
+                    <input type="hidden" name="action" value="{}">
+                    <button type="submit" class="btn {}">{}</button>
+                </form>"#,
+        job_id, action, class, label,
+    )
+}
+
+fn node_action_form(node_id: &str, action: &str, label: &str, class: &str, confirm: &str) -> String {
+    let onsubmit = if confirm.is_empty() {
+        String::new()
+    } else {
+        format!(r#" onsubmit="return confirm('{}')""#, confirm)
+    };
+
+    format!(
+        r#"<form method="POST" action="/admin/nodes/action" style="display:inline;"{}>
+                    <input type="hidden" name="node_id" value="{}">
+                    <input type="hidden" name="action" value="{}">
+                    <button type="submit" class="btn {}">{}</button>
+                </form>"#,
+        onsubmit,
+        html_escape(node_id),
+        action,
+        class,
+        label,
+    )
+}
+
 pub fn render_repos(repos: &[Repository]) -> String {
     let repos_html = repos.iter().map(|repo| {
         let visibility = if repo.is_private != 0 { " Private" } else { " Public" };
@@ -501,3 +706,99 @@ pub fn render_repos(repos: &[Repository]) -> String {
     
     render_page("Repository Management", &content)
 }
+
+/// `locked` holds the lowercased usernames of locked out accounts
+pub fn render_users(users: &[User], require_admin_2fa: bool, locked: &[String]) -> String {
+    let rows = users
+        .iter()
+        .map(|user| {
+            let sign_in = if locked.contains(&user.username.to_lowercase()) {
+                format!(
+                    r#"🔒 Locked
+                    <form method="POST" action="/admin/users" class="inline-form">
+                        <input type="hidden" name="action" value="unlock">
+                        <input type="hidden" name="value" value="{}">
+                        <button type="submit" class="btn btn-secondary">Unlock</button>
+                    </form>"#,
+                    html_escape(&user.username)
+                )
+            } else {
+                "Active".to_string()
+            };
+
+            format!(
+                r#"<tr>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+                <td>{}</td>
+            </tr>"#,
+                html_escape(&user.username),
+                html_escape(&user.email),
+                if user.is_admin != 0 { "Admin" } else { "User" },
+                if user.totp_enabled != 0 { "✅ Enabled" } else { "Off" },
+                sign_in,
+                html_escape(&user.created_at)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let (status, toggle_value, toggle_label) = if require_admin_2fa {
+        ("required", "false", "Stop Requiring")
+    } else {
+        ("not required", "true", "Require for All Admins")
+    };
+
+    let content = format!(
+        r#"
+    <h1>User Management</h1>
+
+    <div class="section">
+        <h2>Security</h2>
+        <p>Two-factor authentication is <strong>{}</strong> for admins. Admins without it are sent to set it up and cannot use admin pages until they do.</p>
+        <form method="POST" action="/admin/users">
+            <input type="hidden" name="action" value="require_admin_2fa">
+            <input type="hidden" name="value" value="{}">
+            <button type="submit" class="btn btn-primary">{}</button>
+        </form>
+    </div>
+
+    <div class="section">
+        <h2>Users ({})</h2>
+        <table class="users-table">
+            <thead>
+                <tr><th>Username</th><th>Email</th><th>Role</th><th>Two-Factor</th><th>Sign-In</th><th>Joined</th></tr>
+            </thead>
+            <tbody>{}</tbody>
+        </table>
+    </div>
+
+    <style>
+        .users-table {{
+            width: 100%;
+            border-collapse: collapse;
+        }}
+
+        .users-table th, .users-table td {{
+            padding: 0.75rem 1rem;
+            text-align: left;
+            border-bottom: 1px solid var(--border-color);
+        }}
+
+        .inline-form {{
+            display: inline;
+        }}
+    </style>
+    "#,
+        status,
+        toggle_value,
+        toggle_label,
+        users.len(),
+        rows
+    );
+
+    render_page("User Management", &content)
+}
@@ -0,0 +1,150 @@
+// src/templates/branch_settings.rs
+use super::{html_escape, render_page};
+use crate::models::{ProtectedBranch, Repository};
+
+fn checked(on: bool) -> &'static str {
+    if on {
+        "checked"
+    } else {
+        ""
+    }
+}
+
+/// The rule fields shared by the add and edit forms
+fn rule_fields(branch: Option<&ProtectedBranch>) -> String {
+    format!(
+        r#"<div class="form-group">
+                <label>Branch or pattern</label>
+                <input type="text" name="pattern" required maxlength="100" value="{}" placeholder="main or release/*">
+            </div>
+            <div class="rule-options">
+                <label><input type="checkbox" name="allow_force_push" value="1" {}> Allow force-pushes</label>
+                <label><input type="checkbox" name="allow_deletion" value="1" {}> Allow deletion</label>
+                <label><input type="checkbox" name="require_linear_history" value="1" {}> Require linear history (no merge commits)</label>
+            </div>
+            <div class="rule-options">
+                <label><input type="checkbox" name="restrict_pushes" value="1" {}> Restrict who can push</label>
+            </div>
+            <div class="form-group">
+                <label>Allowed pushers</label>
+                <input type="text" name="allowed_pushers" value="{}" placeholder="{}">
+                <small>Comma-separated usernames. Listing anyone restricts pushes; restricted with nobody listed, nobody can push.</small>
+            </div>"#,
+        branch.map(|b| html_escape(&b.pattern)).unwrap_or_default(),
+        checked(branch.is_some_and(|b| b.allow_force_push != 0)),
+        checked(branch.is_some_and(|b| b.allow_deletion != 0)),
+        checked(branch.is_some_and(|b| b.require_linear_history != 0)),
+        checked(branch.is_some_and(|b| b.restrict_pushes != 0)),
+        branch.map(|b| html_escape(&b.allowed_pushers.join(", "))).unwrap_or_default(),
+        if branch.is_some_and(|b| b.restrict_pushes != 0) { "Nobody" } else { "Anyone with push access" },
+    )
+}
+
+fn rule_card(repo: &Repository, branch: &ProtectedBranch) -> String {
+    format!(
+        r#"<div class="rule-card">
+            <form method="POST" action="/r/{}/settings/branches">
+                <input type="hidden" name="action" value="update">
+                <input type="hidden" name="branch_id" value="{}">
+                {}
+                <button type="submit" class="btn btn-primary">Save</button>
+            </form>
+            <form method="POST" action="/r/{}/settings/branches" class="rule-delete">
+                <input type="hidden" name="action" value="delete">
+                <input type="hidden" name="branch_id" value="{}">
+                <button type="submit" class="btn btn-danger"
+                    onclick="return confirm('Remove protection from {}?')">Unprotect</button>
+            </form>
+        </div>"#,
+        repo.repo_hash,
+        branch.id,
+        rule_fields(Some(branch)),
+        repo.repo_hash,
+        branch.id,
+        html_escape(&branch.pattern),
+    )
+}
+
+pub fn render(repo: &Repository, protected: &[ProtectedBranch]) -> String {
+    let rules_html = if protected.is_empty() {
+        "<p class='empty-state'>No protected branches</p>".to_string()
+    } else {
+        protected
+            .iter()
+            .map(|branch| rule_card(repo, branch))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let content = format!(
+        r#"
+    <div class="breadcrumb">
+        <a href="/r/{}">← Back to Repository</a>
+    </div>
+
+    <div class="repo-header">
+        <h1>{}</h1>
+        <p class="repo-description">Branch Protection</p>
+    </div>
+
+    <div class="repo-nav">
+        <a href="/r/{}/files" class="nav-tab">Files</a>
+        <a href="/r/{}/commits" class="nav-tab">Commits</a>
+        <a href="/r/{}/branches" class="nav-tab">Branches</a>
+        <a href="/r/{}/clone" class="nav-tab">Clone</a>
+        <a href="/r/{}/settings" class="nav-tab">Settings</a>
+        <a href="/r/{}/settings/branches" class="nav-tab active">Branch Protection</a>
+        <a href="/r/{}/settings/collaborators" class="nav-tab">Collaborators</a>
+    </div>
+
+    <div class="section">
+        <h2>Protected Branches</h2>
+        <p>Protected branches refuse deletion and force-pushes unless allowed below. When several rules match a branch, all of them apply.</p>
+        {}
+    </div>
+
+    <div class="section">
+        <h2>Protect a Branch</h2>
+        <form method="POST" action="/r/{}/settings/branches" class="rule-card">
+            <input type="hidden" name="action" value="add">
+            {}
+            <button type="submit" class="btn btn-primary">Protect Branch</button>
+        </form>
+    </div>
+
+    <style>
+        .rule-card {{
+            border: 1px solid var(--border-color);
+            border-radius: 8px;
+            padding: 1rem 1.25rem;
+            margin-top: 1rem;
+        }}
+
+        .rule-options {{
+            display: flex;
+            flex-wrap: wrap;
+            gap: 1.5rem;
+            margin: 0.75rem 0;
+        }}
+
+        .rule-delete {{
+            margin-top: 0.5rem;
+        }}
+    </style>
+    "#,
+        repo.repo_hash,
+        html_escape(&repo.name),
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        rules_html,
+        repo.repo_hash,
+        rule_fields(None),
+    );
+
+    render_page(&format!("Branch Protection - {}", repo.name), &content)
+}
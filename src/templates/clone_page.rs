@@ -1,10 +1,28 @@
 // Hyrule/src/templates/clone_page.rs
-use super::render_page;
+use super::{html_escape, render_page};
 use crate::models::Repository;
 
-pub fn render(repo: &Repository, server_url: &str) -> String {
-    let git_url = format!("{}/git/{}.git", server_url, repo.repo_hash);
+pub fn render(repo: &Repository, owner: &str, server_url: &str, ssh_url: Option<&str>) -> String {
+    let git_url = format!("{}{}.git", server_url, crate::utils::paths::repo_url(owner, &repo.name));
     let http_url = format!("{}/r/{}/download", server_url, repo.repo_hash);
+
+    let ssh_section = match ssh_url {
+        Some(url) => {
+            let url = html_escape(url);
+            format!(
+                r#"<h2> Clone via SSH</h2>
+        <div class="clone-method">
+            <p>Uses the SSH keys from your <a href="/profile">profile</a>:</p>
+            <div class="code-block">
+                <pre><code>git clone {}</code></pre>
+            </div>
+            <button onclick="navigator.clipboard.writeText('git clone {}')" class="btn btn-primary">Copy Command</button>
+        </div>"#,
+                url, url
+            )
+        }
+        None => String::new(),
+    };
     
     let content = format!(
         r#"
@@ -35,6 +53,8 @@ pub fn render(repo: &Repository, server_url: &str) -> String {
             <noscript><p class="hint"> Copy the command above manually</p></noscript>
         </div>
         
+        {}
+        
         <h2> Direct Download (No Git Required)</h2>
         <div class="clone-method">
             <p>Download repository as a bundle:</p>
@@ -161,6 +181,7 @@ git push origin main</code></pre>
         repo.repo_hash,
         repo.repo_hash,
         git_url, git_url,
+        ssh_section,
         http_url,
         repo.name,
         git_url,
@@ -0,0 +1,197 @@
+// src/templates/collaborators.rs
+use super::{html_escape, render_page};
+use crate::auth::repo_access::RepoRole;
+use crate::models::{Collaborator, RepoInvitation, Repository};
+
+fn role_options(selected: &str) -> String {
+    RepoRole::GRANTABLE
+        .iter()
+        .map(|role| {
+            format!(
+                r#"<option value="{}" {}>{}</option>"#,
+                role.as_str(),
+                if role.as_str() == selected { "selected" } else { "" },
+                role.as_str()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("")
+}
+
+fn collaborator_row(repo: &Repository, collaborator: &Collaborator) -> String {
+    format!(
+        r#"<tr>
+            <td>{}</td>
+            <td>
+                <form method="POST" action="/r/{}/settings/collaborators" class="inline-form">
+                    <input type="hidden" name="action" value="update">
+                    <input type="hidden" name="user_id" value="{}">
+                    <select name="role">{}</select>
+                    <button type="submit" class="btn btn-secondary">Change</button>
+                </form>
+            </td>
+            <td>{}</td>
+            <td>
+                <form method="POST" action="/r/{}/settings/collaborators" class="inline-form">
+                    <input type="hidden" name="action" value="remove">
+                    <input type="hidden" name="user_id" value="{}">
+                    <button type="submit" class="btn btn-danger"
+                        onclick="return confirm('Remove {} from this repository?')">Remove</button>
+                </form>
+            </td>
+        </tr>"#,
+        html_escape(&collaborator.username),
+        repo.repo_hash,
+        collaborator.user_id,
+        role_options(&collaborator.role),
+        collaborator.created_at,
+        repo.repo_hash,
+        collaborator.user_id,
+        html_escape(&collaborator.username),
+    )
+}
+
+fn invitation_row(repo: &Repository, invitation: &RepoInvitation) -> String {
+    format!(
+        r#"<tr>
+            <td>{}</td>
+            <td>{}</td>
+            <td>{} by {}</td>
+            <td>
+                <form method="POST" action="/r/{}/settings/collaborators" class="inline-form">
+                    <input type="hidden" name="action" value="cancel">
+                    <input type="hidden" name="invitation_id" value="{}">
+                    <button type="submit" class="btn btn-secondary">Cancel</button>
+                </form>
+            </td>
+        </tr>"#,
+        html_escape(&invitation.invitee_username),
+        invitation.role,
+        invitation.created_at,
+        html_escape(&invitation.inviter_username),
+        repo.repo_hash,
+        invitation.id,
+    )
+}
+
+pub fn render(repo: &Repository, collaborators: &[Collaborator], invitations: &[RepoInvitation]) -> String {
+    let collaborators_html = if collaborators.is_empty() {
+        "<p class='empty-state'>No collaborators yet</p>".to_string()
+    } else {
+        format!(
+            r#"<table class="collab-table">
+                <thead>
+                    <tr><th>User</th><th>Role</th><th>Since</th><th></th></tr>
+                </thead>
+                <tbody>{}</tbody>
+            </table>"#,
+            collaborators
+                .iter()
+                .map(|c| collaborator_row(repo, c))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    };
+
+    let invitations_html = if invitations.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#"<div class="section">
+        <h2>Pending Invitations</h2>
+        <table class="collab-table">
+            <thead>
+                <tr><th>User</th><th>Role</th><th>Invited</th><th></th></tr>
+            </thead>
+            <tbody>{}</tbody>
+        </table>
+    </div>"#,
+            invitations
+                .iter()
+                .map(|i| invitation_row(repo, i))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    };
+
+    let content = format!(
+        r#"
+    <div class="breadcrumb">
+        <a href="/r/{}">← Back to Repository</a>
+    </div>
+
+    <div class="repo-header">
+        <h1>{}</h1>
+        <p class="repo-description">Collaborators</p>
+    </div>
+
+    <div class="repo-nav">
+        <a href="/r/{}/files" class="nav-tab">Files</a>
+        <a href="/r/{}/commits" class="nav-tab">Commits</a>
+        <a href="/r/{}/branches" class="nav-tab">Branches</a>
+        <a href="/r/{}/clone" class="nav-tab">Clone</a>
+        <a href="/r/{}/settings" class="nav-tab">Settings</a>
+        <a href="/r/{}/settings/branches" class="nav-tab">Branch Protection</a>
+        <a href="/r/{}/settings/collaborators" class="nav-tab active">Collaborators</a>
+    </div>
+
+    <div class="section">
+        <h2>Collaborators</h2>
+        <p><strong>read</strong> can browse and clone, <strong>write</strong> can also push, and <strong>admin</strong> can also change settings and manage collaborators.</p>
+        {}
+    </div>
+
+    {}
+
+    <div class="section">
+        <h2>Invite a Collaborator</h2>
+        <p>The user gets access once they accept the invitation from their profile.</p>
+        <form method="POST" action="/r/{}/settings/collaborators">
+            <input type="hidden" name="action" value="invite">
+            <div class="form-group">
+                <label for="username">Username</label>
+                <input type="text" id="username" name="username" required maxlength="32">
+            </div>
+            <div class="form-group">
+                <label for="role">Role</label>
+                <select id="role" name="role">{}</select>
+            </div>
+            <button type="submit" class="btn btn-primary">Send Invitation</button>
+        </form>
+    </div>
+
+    <style>
+        .collab-table {{
+            width: 100%;
+            border-collapse: collapse;
+            margin-top: 1rem;
+        }}
+
+        .collab-table th, .collab-table td {{
+            padding: 0.75rem 1rem;
+            text-align: left;
+            border-bottom: 1px solid var(--border-color);
+        }}
+
+        .inline-form {{
+            display: inline;
+        }}
+    </style>
+    "#,
+        repo.repo_hash,
+        html_escape(&repo.name),
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        collaborators_html,
+        invitations_html,
+        repo.repo_hash,
+        role_options("write"),
+    );
+
+    render_page(&format!("Collaborators - {}", repo.name), &content)
+}
@@ -1,11 +1,45 @@
 // src/templates/create_repo.rs
-use super::render_page;
+use super::{html_escape, render_page};
+use crate::models::Organization;
 
-pub fn render() -> String {
-    let content = r#"
+/// `orgs` are the organizations the user may create repositories in;
+/// `selected` preselects one of them
+pub fn render(username: &str, orgs: &[Organization], selected: Option<&str>) -> String {
+    let owner_html = if orgs.is_empty() {
+        String::new()
+    } else {
+        let options = orgs
+            .iter()
+            .map(|org| {
+                format!(
+                    r#"<option value="{}" {}>{}</option>"#,
+                    html_escape(&org.name),
+                    if selected == Some(org.name.as_str()) { "selected" } else { "" },
+                    html_escape(&org.name)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("");
+        format!(
+            r#"<div class="form-group">
+            <label for="owner">Owner</label>
+            <select id="owner" name="owner">
+                <option value="">{}</option>
+                {}
+            </select>
+        </div>
+        "#,
+            html_escape(username),
+            options
+        )
+    };
+
+    let content = format!(
+        r#"
     <h1>Create New Repository</h1>
     
     <form method="POST" action="/repos/new" class="repo-form">
+        {}
         <div class="form-group">
             <label for="name">Repository Name *</label>
             <input type="text" id="name" name="name" required 
@@ -34,16 +68,16 @@ pub fn render() -> String {
     </form>
     
     <style>
-        .repo-form {
+        .repo-form {{
             max-width: 700px;
             margin: 2rem auto;
             background: var(--bg-glass);
             padding: 3rem;
             border-radius: var(--border-radius);
             border: 2px solid var(--border-color);
-        }
+        }}
         
-        textarea {
+        textarea {{
             width: 100%;
             padding: 1rem;
             border: 2px solid var(--border-color);
@@ -52,31 +86,33 @@ pub fn render() -> String {
             color: var(--text-color);
             font-family: inherit;
             resize: vertical;
-        }
+        }}
         
-        textarea:focus {
+        textarea:focus {{
             outline: none;
             border-color: var(--primary-color);
             box-shadow: 0 0 20px rgba(0, 255, 136, 0.3);
-        }
+        }}
         
-        .form-group small {
+        .form-group small {{
             display: block;
             margin-top: 0.5rem;
             color: var(--text-muted);
-        }
+        }}
         
-        .form-group label input[type="checkbox"] {
+        .form-group label input[type="checkbox"] {{
             margin-right: 0.5rem;
-        }
+        }}
         
-        .form-actions {
+        .form-actions {{
             display: flex;
             gap: 1rem;
             margin-top: 2rem;
-        }
+        }}
     </style>
-    "#;
+    "#,
+        owner_html
+    );
     
-    render_page("Create Repository", content)
+    render_page("Create Repository", &content)
 }
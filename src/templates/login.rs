@@ -30,6 +30,7 @@ pub fn render_with_user(success_msg: Option<&str>, username: Option<&str>) -> St
         </form>
         <p class="auth-footer">
             Don't have an account? <a href="/signup">Sign up</a>
+            · <a href="/forgot-password">Forgot password?</a>
         </p>
     </div>
     
@@ -50,3 +51,46 @@ pub fn render_with_user(success_msg: Option<&str>, username: Option<&str>) -> St
     
     render_page_with_user("Login", &content, username)
 }
+
+/// The second login step. `error` is shown after a wrong code.
+pub fn render_two_factor(error: Option<&str>) -> String {
+    let error_html = match error {
+        Some(message) => format!(r#"<div class="error-message">{}</div>"#, super::html_escape(message)),
+        None => String::new(),
+    };
+
+    let content = format!(
+        r#"
+    <div class="auth-container">
+        <h1>🔐 Two-Factor Authentication</h1>
+        {}
+        <form class="auth-form" method="POST" action="/login/2fa">
+            <div class="form-group">
+                <label for="code">Authentication code</label>
+                <input type="text" id="code" name="code" required autofocus
+                    autocomplete="one-time-code" inputmode="numeric" placeholder="123456">
+            </div>
+            <button type="submit" class="btn btn-primary btn-full">Verify</button>
+        </form>
+        <p class="auth-footer">
+            Open your authenticator app, or enter one of your recovery codes.
+            <a href="/login">Start over</a>
+        </p>
+    </div>
+
+    <style>
+        .error-message {{
+            background: rgba(255, 68, 68, 0.1);
+            border: 2px solid rgba(255, 68, 68, 0.3);
+            border-radius: 15px;
+            padding: 1rem 2rem;
+            margin: 0 0 2rem 0;
+            text-align: center;
+        }}
+    </style>
+    "#,
+        error_html
+    );
+
+    render_page_with_user("Two-Factor Authentication", &content, None)
+}
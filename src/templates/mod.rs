@@ -22,6 +22,15 @@ pub mod tags;
 pub mod starred;
 pub mod pinned;
 pub mod profile;
+pub mod repo_settings;
+pub mod branch_settings;
+pub mod collaborators;
+pub mod org;
+pub mod tokens;
+pub mod sessions;
+pub mod two_factor;
+pub mod password;
+pub mod account_settings;
 
 mod layout;
 
@@ -0,0 +1,452 @@
+// src/templates/org.rs
+use super::{html_escape, render_page};
+use crate::auth::repo_access::RepoRole;
+use crate::models::{OrgMember, Organization, Repository, Team};
+use crate::services::organizations::ORG_ROLES;
+
+const STYLE: &str = r#"
+    <style>
+        .org-table {
+            width: 100%;
+            border-collapse: collapse;
+            margin-bottom: 1.5rem;
+        }
+
+        .org-table th, .org-table td {
+            padding: 0.75rem 1rem;
+            text-align: left;
+            border-bottom: 1px solid var(--border-color);
+        }
+
+        .inline-form {
+            display: inline;
+        }
+
+        .team-card {
+            border: 1px solid var(--border-color);
+            border-radius: var(--border-radius);
+            padding: 1.5rem;
+            margin-bottom: 1.5rem;
+        }
+    </style>
+"#;
+
+fn options(values: &[&str], selected: &str) -> String {
+    values
+        .iter()
+        .map(|value| {
+            format!(
+                r#"<option value="{}" {}>{}</option>"#,
+                value,
+                if *value == selected { "selected" } else { "" },
+                value
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("")
+}
+
+fn repo_role_options(selected: &str) -> String {
+    options(&RepoRole::GRANTABLE.map(|r| r.as_str()), selected)
+}
+
+fn storage_summary(org: &Organization) -> String {
+    format!(
+        "{} MB / {} GB",
+        org.storage_used / (1024 * 1024),
+        org.storage_quota / (1024 * 1024 * 1024)
+    )
+}
+
+pub fn render_new() -> String {
+    let content = format!(
+        r#"
+    <h1>Create Organization</h1>
+
+    <form method="POST" action="/orgs/new" class="section">
+        <div class="form-group">
+            <label for="name">Organization Name *</label>
+            <input type="text" id="name" name="name" required minlength="3" maxlength="32" placeholder="my-company">
+            <small>Repositories will live at /name/repository</small>
+        </div>
+        <div class="form-group">
+            <label for="description">Description</label>
+            <input type="text" id="description" name="description" maxlength="500">
+        </div>
+        <button type="submit" class="btn btn-primary">Create Organization</button>
+        <a href="/profile" class="btn btn-secondary">Cancel</a>
+    </form>
+    {}
+    "#,
+        STYLE
+    );
+
+    render_page("Create Organization", &content)
+}
+
+pub fn render_dashboard(
+    org: &Organization,
+    role: Option<&str>,
+    repos: &[Repository],
+    members: &[OrgMember],
+    teams: &[Team],
+) -> String {
+    let repos_html = if repos.is_empty() {
+        "<p class='empty-state'>No repositories yet</p>".to_string()
+    } else {
+        let rows = repos
+            .iter()
+            .map(|repo| {
+                format!(
+                    r#"<tr>
+                        <td><a href="/{}/{}">{}</a></td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>"#,
+                    html_escape(&org.name),
+                    html_escape(&repo.name),
+                    html_escape(&repo.name),
+                    html_escape(repo.description.as_deref().unwrap_or("")),
+                    if repo.is_private != 0 { "Private" } else { "Public" },
+                    repo.last_updated
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            r#"<table class="org-table">
+                <thead>
+                    <tr><th>Repository</th><th>Description</th><th>Visibility</th><th>Updated</th></tr>
+                </thead>
+                <tbody>{}</tbody>
+            </table>"#,
+            rows
+        )
+    };
+
+    // Membership is only shown to members
+    let members_html = if role.is_none() {
+        String::new()
+    } else {
+        let rows = members
+            .iter()
+            .map(|m| format!("<tr><td>{}</td><td>{}</td></tr>", html_escape(&m.username), m.role))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let team_rows = teams
+            .iter()
+            .map(|team| {
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    html_escape(&team.name),
+                    html_escape(&team.members.join(", ")),
+                    team.repos
+                        .iter()
+                        .map(|r| format!("{} ({})", html_escape(&r.repo_name), r.role))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            r#"<div class="section">
+        <h2>Members</h2>
+        <table class="org-table">
+            <thead><tr><th>User</th><th>Role</th></tr></thead>
+            <tbody>{}</tbody>
+        </table>
+    </div>
+
+    <div class="section">
+        <h2>Teams</h2>
+        {}
+    </div>"#,
+            rows,
+            if teams.is_empty() {
+                "<p class='empty-state'>No teams yet</p>".to_string()
+            } else {
+                format!(
+                    r#"<table class="org-table">
+            <thead><tr><th>Team</th><th>Members</th><th>Repositories</th></tr></thead>
+            <tbody>{}</tbody>
+        </table>"#,
+                    team_rows
+                )
+            }
+        )
+    };
+
+    let actions_html = if role == Some("owner") {
+        format!(
+            r#"<div class="action-buttons">
+            <a href="/repos/new?owner={}" class="btn btn-primary">New Repository</a>
+            <a href="/orgs/{}/settings" class="btn btn-secondary">Settings</a>
+        </div>"#,
+            html_escape(&org.name),
+            html_escape(&org.name)
+        )
+    } else {
+        String::new()
+    };
+
+    let content = format!(
+        r#"
+    <div class="repo-header">
+        <h1>{}</h1>
+        <p class="repo-description">{}</p>
+        {}
+    </div>
+
+    <div class="stats-grid">
+        <div class="stat-card">
+            <div class="stat-label">Repositories</div>
+            <div class="stat-value">{}</div>
+        </div>
+        <div class="stat-card">
+            <div class="stat-label">Members</div>
+            <div class="stat-value">{}</div>
+        </div>
+        <div class="stat-card">
+            <div class="stat-label">Storage Used</div>
+            <div class="stat-value">{}</div>
+        </div>
+    </div>
+
+    <div class="section">
+        <h2>Repositories</h2>
+        {}
+    </div>
+
+    {}
+    {}
+    "#,
+        html_escape(&org.name),
+        html_escape(org.description.as_deref().unwrap_or("")),
+        actions_html,
+        repos.len(),
+        members.len(),
+        storage_summary(org),
+        repos_html,
+        members_html,
+        STYLE
+    );
+
+    render_page(&org.name, &content)
+}
+
+fn member_row(org: &Organization, member: &OrgMember) -> String {
+    format!(
+        r#"<tr>
+            <td>{}</td>
+            <td>
+                <form method="POST" action="/orgs/{}/settings" class="inline-form">
+                    <input type="hidden" name="action" value="set_role">
+                    <input type="hidden" name="username" value="{}">
+                    <select name="role">{}</select>
+                    <button type="submit" class="btn btn-secondary">Change</button>
+                </form>
+            </td>
+            <td>{}</td>
+            <td>
+                <form method="POST" action="/orgs/{}/settings" class="inline-form">
+                    <input type="hidden" name="action" value="remove_member">
+                    <input type="hidden" name="username" value="{}">
+                    <button type="submit" class="btn btn-danger"
+                        onclick="return confirm('Remove {} from the organization and its teams?')">Remove</button>
+                </form>
+            </td>
+        </tr>"#,
+        html_escape(&member.username),
+        html_escape(&org.name),
+        html_escape(&member.username),
+        options(&ORG_ROLES, &member.role),
+        member.created_at,
+        html_escape(&org.name),
+        html_escape(&member.username),
+        html_escape(&member.username),
+    )
+}
+
+fn team_card(org: &Organization, team: &Team) -> String {
+    let members = team
+        .members
+        .iter()
+        .map(|username| {
+            format!(
+                r#"<tr>
+                    <td>{}</td>
+                    <td>
+                        <form method="POST" action="/orgs/{}/settings" class="inline-form">
+                            <input type="hidden" name="action" value="remove_team_member">
+                            <input type="hidden" name="team" value="{}">
+                            <input type="hidden" name="username" value="{}">
+                            <button type="submit" class="btn btn-secondary">Remove</button>
+                        </form>
+                    </td>
+                </tr>"#,
+                html_escape(username),
+                html_escape(&org.name),
+                html_escape(&team.name),
+                html_escape(username),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let repos = team
+        .repos
+        .iter()
+        .map(|grant| {
+            format!(
+                r#"<tr>
+                    <td>{}</td>
+                    <td>{}</td>
+                    <td>
+                        <form method="POST" action="/orgs/{}/settings" class="inline-form">
+                            <input type="hidden" name="action" value="revoke_repo">
+                            <input type="hidden" name="team" value="{}">
+                            <input type="hidden" name="repo" value="{}">
+                            <button type="submit" class="btn btn-secondary">Revoke</button>
+                        </form>
+                    </td>
+                </tr>"#,
+                html_escape(&grant.repo_name),
+                grant.role,
+                html_escape(&org.name),
+                html_escape(&team.name),
+                html_escape(&grant.repo_name),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        r#"<div class="team-card">
+        <h3>{}</h3>
+        <p>{}</p>
+        <table class="org-table">
+            <thead><tr><th>Member</th><th></th></tr></thead>
+            <tbody>{}</tbody>
+        </table>
+        <form method="POST" action="/orgs/{}/settings">
+            <input type="hidden" name="action" value="add_team_member">
+            <input type="hidden" name="team" value="{}">
+            <input type="text" name="username" required maxlength="32" placeholder="Username">
+            <button type="submit" class="btn btn-secondary">Add Member</button>
+        </form>
+        <table class="org-table">
+            <thead><tr><th>Repository</th><th>Role</th><th></th></tr></thead>
+            <tbody>{}</tbody>
+        </table>
+        <form method="POST" action="/orgs/{}/settings">
+            <input type="hidden" name="action" value="grant_repo">
+            <input type="hidden" name="team" value="{}">
+            <input type="text" name="repo" required maxlength="64" placeholder="Repository">
+            <select name="role">{}</select>
+            <button type="submit" class="btn btn-secondary">Grant Access</button>
+        </form>
+        <form method="POST" action="/orgs/{}/settings" class="inline-form">
+            <input type="hidden" name="action" value="delete_team">
+            <input type="hidden" name="team" value="{}">
+            <button type="submit" class="btn btn-danger"
+                onclick="return confirm('Delete team {}?')">Delete Team</button>
+        </form>
+    </div>"#,
+        html_escape(&team.name),
+        html_escape(team.description.as_deref().unwrap_or("")),
+        members,
+        html_escape(&org.name),
+        html_escape(&team.name),
+        repos,
+        html_escape(&org.name),
+        html_escape(&team.name),
+        repo_role_options("write"),
+        html_escape(&org.name),
+        html_escape(&team.name),
+        html_escape(&team.name),
+    )
+}
+
+pub fn render_settings(org: &Organization, members: &[OrgMember], teams: &[Team]) -> String {
+    let member_rows = members
+        .iter()
+        .map(|m| member_row(org, m))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let teams_html = if teams.is_empty() {
+        "<p class='empty-state'>No teams yet</p>".to_string()
+    } else {
+        teams.iter().map(|t| team_card(org, t)).collect::<Vec<_>>().join("\n")
+    };
+
+    let content = format!(
+        r#"
+    <div class="breadcrumb">
+        <a href="/orgs/{}">← Back to {}</a>
+    </div>
+
+    <h1>Organization Settings</h1>
+
+    <div class="section">
+        <h2>Profile</h2>
+        <form method="POST" action="/orgs/{}/settings">
+            <input type="hidden" name="action" value="update">
+            <div class="form-group">
+                <label for="description">Description</label>
+                <input type="text" id="description" name="description" maxlength="500" value="{}">
+            </div>
+            <button type="submit" class="btn btn-primary">Save</button>
+        </form>
+        <p>Storage used: {}</p>
+    </div>
+
+    <div class="section">
+        <h2>Members</h2>
+        <p><strong>owner</strong> manages the organization and owns every repository in it; <strong>member</strong> can read them and gets more through teams.</p>
+        <table class="org-table">
+            <thead><tr><th>User</th><th>Role</th><th>Since</th><th></th></tr></thead>
+            <tbody>{}</tbody>
+        </table>
+        <form method="POST" action="/orgs/{}/settings">
+            <input type="hidden" name="action" value="add_member">
+            <input type="text" name="username" required maxlength="32" placeholder="Username">
+            <select name="role">{}</select>
+            <button type="submit" class="btn btn-primary">Add Member</button>
+        </form>
+    </div>
+
+    <div class="section">
+        <h2>Teams</h2>
+        <p>Teams give their members read, write or admin access to chosen repositories.</p>
+        {}
+        <form method="POST" action="/orgs/{}/settings">
+            <input type="hidden" name="action" value="create_team">
+            <div class="form-group">
+                <label for="team-name">Team Name</label>
+                <input type="text" id="team-name" name="team" required minlength="3" maxlength="64">
+            </div>
+            <div class="form-group">
+                <label for="team-description">Description</label>
+                <input type="text" id="team-description" name="description" maxlength="500">
+            </div>
+            <button type="submit" class="btn btn-primary">Create Team</button>
+        </form>
+    </div>
+    {}
+    "#,
+        html_escape(&org.name),
+        html_escape(&org.name),
+        html_escape(&org.name),
+        html_escape(org.description.as_deref().unwrap_or("")),
+        storage_summary(org),
+        member_rows,
+        html_escape(&org.name),
+        options(&ORG_ROLES, "member"),
+        teams_html,
+        html_escape(&org.name),
+        STYLE
+    );
+
+    render_page(&format!("Settings - {}", org.name), &content)
+}
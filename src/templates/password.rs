@@ -0,0 +1,157 @@
+// src/templates/password.rs
+use super::{html_escape, render_page_with_user};
+
+fn notice_html(error: Option<&str>, success: Option<&str>) -> String {
+    match (error, success) {
+        (Some(message), _) => format!(r#"<div class="error-message">{}</div>"#, html_escape(message)),
+        (None, Some(message)) => format!(r#"<div class="success-message">✅ {}</div>"#, html_escape(message)),
+        (None, None) => String::new(),
+    }
+}
+
+const STYLE: &str = r#"
+    <style>
+        .error-message {
+            background: rgba(255, 68, 68, 0.1);
+            border: 2px solid rgba(255, 68, 68, 0.3);
+            border-radius: 15px;
+            padding: 1rem 2rem;
+            margin: 0 0 2rem 0;
+            text-align: center;
+        }
+
+        .success-message {
+            background: rgba(0, 255, 136, 0.1);
+            border: 2px solid rgba(0, 255, 136, 0.3);
+            border-radius: 15px;
+            padding: 1rem 2rem;
+            margin: 0 0 2rem 0;
+            color: var(--primary-color);
+            text-align: center;
+        }
+    </style>
+"#;
+
+fn new_password_fields() -> &'static str {
+    r#"<div class="form-group">
+                <label for="new_password">New password</label>
+                <input type="password" id="new_password" name="new_password" required minlength="8" autocomplete="new-password">
+                <small style="color: var(--text-muted); display: block; margin-top: 0.5rem;">At least 8 characters, with a letter and a number</small>
+            </div>
+            <div class="form-group">
+                <label for="confirm_password">Confirm new password</label>
+                <input type="password" id="confirm_password" name="confirm_password" required minlength="8" autocomplete="new-password">
+            </div>"#
+}
+
+/// Asks for the address to send a reset link to. `sent` shows the same
+/// confirmation whether or not an account has that address.
+pub fn render_forgot(sent: bool, error: Option<&str>) -> String {
+    let notice = notice_html(
+        error,
+        sent.then_some("If an account uses that address, we've emailed it a link to reset the password."),
+    );
+
+    let content = format!(
+        r#"
+    <div class="auth-container">
+        <h1>🔑 Forgot Password</h1>
+        {}
+        <form class="auth-form" method="POST" action="/forgot-password">
+            <div class="form-group">
+                <label for="email">Email address</label>
+                <input type="email" id="email" name="email" required autofocus autocomplete="email">
+            </div>
+            <button type="submit" class="btn btn-primary btn-full">Send Reset Link</button>
+        </form>
+        <p class="auth-footer">
+            Remembered it? <a href="/login">Login</a>
+        </p>
+    </div>
+    {}
+    "#,
+        notice, STYLE
+    );
+
+    render_page_with_user("Forgot Password", &content, None)
+}
+
+/// The form behind a reset link
+pub fn render_reset(token: &str, error: Option<&str>) -> String {
+    let content = format!(
+        r#"
+    <div class="auth-container">
+        <h1>🔑 Reset Password</h1>
+        {}
+        <p>You'll be signed out everywhere and your access tokens will be revoked.</p>
+        <form class="auth-form" method="POST" action="/reset-password">
+            <input type="hidden" name="token" value="{}">
+            {}
+            <button type="submit" class="btn btn-primary btn-full">Set New Password</button>
+        </form>
+    </div>
+    {}
+    "#,
+        notice_html(error, None),
+        html_escape(token),
+        new_password_fields(),
+        STYLE
+    );
+
+    render_page_with_user("Reset Password", &content, None)
+}
+
+/// Shown when a generated password must be replaced before going on
+pub fn render_change(username: &str, error: Option<&str>) -> String {
+    let content = format!(
+        r#"
+    <div class="auth-container">
+        <h1>🔑 Choose a New Password</h1>
+        {}
+        <p>Your password was generated for you. Choose your own before continuing.</p>
+        <form class="auth-form" method="POST" action="/change-password">
+            <div class="form-group">
+                <label for="current_password">Current password</label>
+                <input type="password" id="current_password" name="current_password" required autocomplete="current-password">
+            </div>
+            {}
+            <button type="submit" class="btn btn-primary btn-full">Change Password</button>
+        </form>
+    </div>
+    {}
+    "#,
+        notice_html(error, None),
+        new_password_fields(),
+        STYLE
+    );
+
+    render_page_with_user("Change Password", &content, Some(username))
+}
+
+/// The outcome of following a verification link
+pub fn render_verified(result: Result<&str, &str>) -> String {
+    let (notice, link) = match result {
+        Ok(email) => (
+            notice_html(None, Some(&format!("{} is verified.", email))),
+            r#"<a href="/profile" class="btn btn-primary">Go to Profile</a>"#,
+        ),
+        Err(message) => (
+            notice_html(Some(message), None),
+            r#"<a href="/profile" class="btn btn-primary">Send a New Link</a>"#,
+        ),
+    };
+
+    let content = format!(
+        r#"
+    <div class="auth-container">
+        <h1>📧 Email Verification</h1>
+        {}
+        <div style="text-align: center;">{}</div>
+    </div>
+    {}
+    "#,
+        notice, link, STYLE
+    );
+
+    render_page_with_user("Email Verification", &content, None)
+}
@@ -1,14 +1,193 @@
 
 // src/templates/profile.rs
-use super::render_page;
-use crate::models::{User, Repository};
+use super::{html_escape, render_page};
+use crate::models::{Organization, RepoInvitation, Repository, SshKey, User};
+use crate::services::accounts;
 
+#[allow(clippy::too_many_arguments)]
 pub fn render(
     user: &User,
     repos: &[Repository],
     starred: &[Repository],
     pinned: &[Repository],
+    ssh_keys: &[SshKey],
+    invitations: &[RepoInvitation],
+    shared: &[(Repository, String)],
+    orgs: &[(Organization, String)],
 ) -> String {
+    let orgs_html = if orgs.is_empty() {
+        "<p class='empty-state'>You are not in any organizations</p>".to_string()
+    } else {
+        let rows = orgs
+            .iter()
+            .map(|(org, role)| {
+                format!(
+                    r#"<tr><td><a href="/orgs/{}">{}</a></td><td>{}</td></tr>"#,
+                    html_escape(&org.name),
+                    html_escape(&org.name),
+                    role
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            r#"<table class="keys-table">
+                <thead>
+                    <tr><th>Organization</th><th>Role</th></tr>
+                </thead>
+                <tbody>{}</tbody>
+            </table>"#,
+            rows
+        )
+    };
+
+    let invitations_html = if invitations.is_empty() {
+        String::new()
+    } else {
+        let rows = invitations
+            .iter()
+            .map(|invitation| {
+                format!(
+                    r#"<tr>
+                        <td>{}/{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>
+                            <form method="POST" action="/profile/invitations" style="display:inline;">
+                                <input type="hidden" name="action" value="accept">
+                                <input type="hidden" name="invitation_id" value="{}">
+                                <button type="submit" class="btn btn-primary">Accept</button>
+                            </form>
+                            <form method="POST" action="/profile/invitations" style="display:inline;">
+                                <input type="hidden" name="action" value="decline">
+                                <input type="hidden" name="invitation_id" value="{}">
+                                <button type="submit" class="btn btn-secondary">Decline</button>
+                            </form>
+                        </td>
+                    </tr>"#,
+                    html_escape(&invitation.owner_username),
+                    html_escape(&invitation.repo_name),
+                    invitation.role,
+                    html_escape(&invitation.inviter_username),
+                    invitation.id,
+                    invitation.id
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            r#"<div class="section">
+        <h2>Invitations</h2>
+        <table class="keys-table">
+            <thead>
+                <tr><th>Repository</th><th>Role</th><th>Invited by</th><th></th></tr>
+            </thead>
+            <tbody>{}</tbody>
+        </table>
+    </div>"#,
+            rows
+        )
+    };
+
+    let shared_html = if shared.is_empty() {
+        "<p class='empty-state'>No repositories are shared with you</p>".to_string()
+    } else {
+        let rows = shared
+            .iter()
+            .map(|(repo, role)| {
+                format!(
+                    r#"<tr>
+                        <td><a href="/r/{}">{}</a></td>
+                        <td>{}</td>
+                        <td>
+                            <form method="POST" action="/profile/invitations" style="display:inline;">
+                                <input type="hidden" name="action" value="leave">
+                                <input type="hidden" name="repo_hash" value="{}">
+                                <button type="submit" class="btn btn-danger"
+                                    onclick="return confirm('Give up access to this repository?')">Leave</button>
+                            </form>
+                        </td>
+                    </tr>"#,
+                    repo.repo_hash,
+                    html_escape(&repo.name),
+                    role,
+                    repo.repo_hash
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            r#"<table class="keys-table">
+                <thead>
+                    <tr><th>Repository</th><th>Role</th><th></th></tr>
+                </thead>
+                <tbody>{}</tbody>
+            </table>"#,
+            rows
+        )
+    };
+
+    let keys_html = if ssh_keys.is_empty() {
+        "<p class='empty-state'>No SSH keys yet</p>".to_string()
+    } else {
+        ssh_keys
+            .iter()
+            .map(|key| {
+                format!(
+                    r#"<tr>
+                        <td>{}</td>
+                        <td><code>{}</code></td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>
+                            <form method="POST" action="/profile/keys" style="display:inline;">
+                                <input type="hidden" name="action" value="delete">
+                                <input type="hidden" name="key_id" value="{}">
+                                <button type="submit" class="btn btn-danger"
+                                    onclick="return confirm('Delete this SSH key?')">Delete</button>
+                            </form>
+                        </td>
+                    </tr>"#,
+                    html_escape(&key.title),
+                    key.fingerprint,
+                    key.created_at,
+                    key.last_used_at.as_deref().unwrap_or("Never"),
+                    key.id
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let keys_html = if ssh_keys.is_empty() {
+        keys_html
+    } else {
+        format!(
+            r#"<table class="keys-table">
+                <thead>
+                    <tr><th>Title</th><th>Fingerprint</th><th>Added</th><th>Last Used</th><th></th></tr>
+                </thead>
+                <tbody>{}</tbody>
+            </table>"#,
+            keys_html
+        )
+    };
+
+    let email_html = if !accounts::has_real_email(user) {
+        "<p class='empty-state'>No email address. Without one you can't reset a forgotten password. <a href='/profile/settings'>Add one</a></p>".to_string()
+    } else if user.email_verified != 0 {
+        format!("<p>{} <span class='badge'>✅ Verified</span></p>", html_escape(&user.email))
+    } else {
+        format!(
+            r#"<p>{} <span class='badge'>Not verified</span></p>
+        <p>Open the link we emailed you to verify this address.</p>
+        <form method="POST" action="/profile/email">
+            <input type="hidden" name="action" value="resend">
+            <button type="submit" class="btn btn-secondary">Resend Verification Email</button>
+        </form>"#,
+            html_escape(&user.email)
+        )
+    };
+
     let content = format!(
         r#"
     <h1>Profile: {}</h1>
@@ -32,10 +211,70 @@ pub fn render(
         </div>
     </div>
     
+    {}
+
+    <div class="section">
+        <h2>Email</h2>
+        {}
+    </div>
+
+    <div class="section">
+        <h2>Shared With You</h2>
+        <p>Repositories you collaborate on.</p>
+        {}
+    </div>
+
+    <div class="section">
+        <h2>Organizations</h2>
+        {}
+        <a href="/orgs/new" class="btn btn-secondary">New Organization</a>
+    </div>
+
+    <div class="section">
+        <h2>SSH Keys</h2>
+        <p>Keys allowed to push and fetch over SSH.</p>
+        {}
+        <form method="POST" action="/profile/keys" class="ssh-key-form">
+            <input type="hidden" name="action" value="add">
+            <div class="form-group">
+                <label for="title">Title</label>
+                <input type="text" id="title" name="title" required maxlength="100" placeholder="Work laptop">
+            </div>
+            <div class="form-group">
+                <label for="key">Public key</label>
+                <textarea id="key" name="key" rows="3" required placeholder="ssh-ed25519 AAAA... you@example.com"></textarea>
+            </div>
+            <button type="submit" class="btn btn-primary">Add SSH Key</button>
+        </form>
+    </div>
+    
+    <style>
+        .keys-table {{
+            width: 100%;
+            border-collapse: collapse;
+            margin-bottom: 1.5rem;
+        }}
+        
+        .keys-table th, .keys-table td {{
+            padding: 0.75rem 1rem;
+            text-align: left;
+            border-bottom: 1px solid var(--border-color);
+        }}
+        
+        .ssh-key-form textarea {{
+            width: 100%;
+            font-family: monospace;
+        }}
+    </style>
+    
     <div class="section">
         <h2>Actions</h2>
         <div class="action-buttons">
             <a href="/repos/new" class="btn btn-primary">Create Repository</a>
+            <a href="/profile/tokens" class="btn btn-secondary">Access Tokens</a>
+            <a href="/profile/sessions" class="btn btn-secondary">Active Sessions</a>
+            <a href="/profile/2fa" class="btn btn-secondary">Two-Factor Authentication</a>
+            <a href="/profile/settings" class="btn btn-secondary">Account Settings</a>
             <a href="/dashboard" class="btn btn-secondary">Dashboard</a>
             <form method="POST" action="/logout" style="display:inline;">
                 <button type="submit" class="btn btn-danger">Logout</button>
@@ -48,7 +287,12 @@ pub fn render(
         starred.len(),
         pinned.len(),
         user.storage_used / (1024 * 1024),
-        user.storage_quota / (1024 * 1024 * 1024)
+        user.storage_quota / (1024 * 1024 * 1024),
+        invitations_html,
+        email_html,
+        shared_html,
+        orgs_html,
+        keys_html
     );
     
     render_page("Profile", &content)
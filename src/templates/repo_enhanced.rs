@@ -1,15 +1,16 @@
 // src/templates/repo_enhanced.rs
-use super::render_page;
-use crate::models::{NodeInfo, Repository};
+use super::{html_escape, render_page};
+use crate::auth::repo_access::RepoRole;
+use crate::models::{ReplicaSync, Repository};
 
 pub async fn render_with_readme(
     repo: &Repository,
     owner_username: &str,
     replica_count: i64,
-    nodes: &[NodeInfo],
+    replicas: &[ReplicaSync],
     tags: &[String],
     star_count: i64,
-    is_owner: bool,
+    role: Option<RepoRole>,
     is_starred: bool,
     is_pinned: bool,
     readme_html: Option<String>,
@@ -38,17 +39,27 @@ pub async fn render_with_readme(
         format!(r#"<div class="repo-tags">{}</div>"#, tags_list)
     };
 
-    let action_buttons = if is_owner {
-        format!(
-            r#"
+    let action_buttons = if role >= Some(RepoRole::Admin) {
+        // Admin collaborators get the settings but only the owner may delete
+        let delete_button = if role == Some(RepoRole::Owner) {
+            format!(
+                r#"
         <form method="POST" action="/repos/action" style="display:inline;">
             <input type="hidden" name="repo_hash" value="{}">
             <input type="hidden" name="action" value="delete">
             <button type="submit" class="btn btn-danger" 
                 onclick="return confirm('Delete this repository permanently?')">Delete Repository</button>
-        </form>
+        </form>"#,
+                repo.repo_hash
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            r#"
+        <a href="/r/{}/settings" class="btn btn-secondary">Settings</a>{}
         "#,
-            repo.repo_hash
+            repo.repo_hash, delete_button
         )
     } else {
         let star_action = if is_starred { "unstar" } else { "star" };
@@ -176,6 +187,73 @@ pub async fn render_with_readme(
     } else {
         String::new()
     };
+    let replicas_section = if replicas.is_empty() {
+        String::new()
+    } else {
+        let rows = replicas
+            .iter()
+            .map(|r| {
+                let (label, class) = match r.refs_behind {
+                    Some(0) => ("In sync".to_string(), "sync-ok"),
+                    Some(n) => (format!("Behind ({} refs)", n), "sync-behind"),
+                    None => ("Unknown".to_string(), "sync-unknown"),
+                };
+                format!(
+                    r#"<tr>
+                <td><code>{}</code>{}</td>
+                <td>{}</td>
+                <td><span class="sync-badge {}">{}</span></td>
+                <td>{}</td>
+            </tr>"#,
+                    html_escape(r.node_id.get(..12).unwrap_or(&r.node_id)),
+                    if r.is_anchor != 0 { " ⚓" } else { "" },
+                    html_escape(&r.node_state),
+                    class,
+                    label,
+                    r.synced_at.as_deref().unwrap_or("never"),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!(
+            r#"
+    <div class="section">
+        <h2>Replicas</h2>
+        <table class="replica-table">
+            <thead><tr><th>Node</th><th>State</th><th>Refs</th><th>Last Synced</th></tr></thead>
+            <tbody>{}</tbody>
+        </table>
+    </div>
+    
+    <style>
+        .replica-table {{
+            width: 100%;
+            border-collapse: collapse;
+        }}
+        
+        .replica-table th,
+        .replica-table td {{
+            padding: 0.75rem;
+            text-align: left;
+            border-bottom: 1px solid var(--border-color);
+        }}
+        
+        .sync-badge {{
+            padding: 0.2rem 0.6rem;
+            border-radius: 8px;
+            font-size: 0.85rem;
+        }}
+        
+        .sync-ok {{ background: rgba(0, 255, 136, 0.2); }}
+        .sync-behind {{ background: rgba(255, 200, 0, 0.25); }}
+        .sync-unknown {{ background: var(--bg-glass); }}
+    </style>
+    "#,
+            rows
+        )
+    };
+
     let content = format!(
         r#"
     <div class="breadcrumb">
@@ -228,6 +306,8 @@ pub async fn render_with_readme(
     
     {}
     
+    {}
+    
     <div class="section">
         <h2>Clone This Repository</h2>
         <a href="/r/{}/clone" class="btn btn-primary">View Clone Instructions</a>
@@ -249,6 +329,7 @@ pub async fn render_with_readme(
         health_status.1,
         health_status.0,
         readme_section, // Now properly used
+        replicas_section,
         repo.repo_hash,
     );
 
@@ -0,0 +1,206 @@
+// src/templates/repo_settings.rs
+use super::{html_escape, render_page};
+use crate::models::{PushRules, Repository};
+use crate::storage::UploadPackSettings;
+
+fn setting_row(label: &str, enabled: bool, detail: &str) -> String {
+    format!(
+        r#"<tr>
+            <td>{}</td>
+            <td><span class="setting-badge {}">{}</span></td>
+            <td class="setting-detail">{}</td>
+        </tr>"#,
+        label,
+        if enabled { "setting-on" } else { "setting-off" },
+        if enabled { "Enabled" } else { "Disabled" },
+        detail
+    )
+}
+
+pub fn render(
+    repo: &Repository,
+    upload_pack: &UploadPackSettings,
+    push_rules: &PushRules,
+    protected_count: usize,
+    sends_email: bool,
+) -> String {
+    let verified_email_row = if sends_email {
+        format!(
+            r#"<label>
+                    <input type="checkbox" name="require_verified_email" value="1" {}>
+                    Reject commits whose author email is not a verified account email
+                </label>
+                <small>Authors verify their address under Account Settings.</small>"#,
+            if push_rules.require_verified_email != 0 { "checked" } else { "" }
+        )
+    } else {
+        r#"<label>
+                    <input type="checkbox" disabled>
+                    Reject commits whose author email is not a verified account email
+                </label>
+                <small>Unavailable until the server is set up to send email, since authors couldn't verify their addresses.</small>"#
+            .to_string()
+    };
+
+    let rows = [
+        setting_row(
+            "Partial clone",
+            upload_pack.partial_clone(),
+            "<code>git clone --filter=blob:none</code> skips file contents until they are checked out",
+        ),
+        setting_row(
+            "Object filters",
+            upload_pack.allow_filter,
+            "<code>uploadpack.allowFilter</code>",
+        ),
+        setting_row(
+            "Fetch reachable objects by id",
+            upload_pack.allow_reachable_sha1_in_want,
+            "<code>uploadpack.allowReachableSHA1InWant</code>, used to fetch blobs a partial clone left out",
+        ),
+        setting_row(
+            "Shallow clone",
+            true,
+            "<code>git clone --depth=1</code> is always available",
+        ),
+    ]
+    .join("\n");
+
+    let content = format!(
+        r#"
+    <div class="breadcrumb">
+        <a href="/r/{}">← Back to Repository</a>
+    </div>
+
+    <div class="repo-header">
+        <h1>{}</h1>
+        <p class="repo-description">Settings</p>
+    </div>
+
+    <div class="repo-nav">
+        <a href="/r/{}/files" class="nav-tab">Files</a>
+        <a href="/r/{}/commits" class="nav-tab">Commits</a>
+        <a href="/r/{}/branches" class="nav-tab">Branches</a>
+        <a href="/r/{}/clone" class="nav-tab">Clone</a>
+        <a href="/r/{}/settings" class="nav-tab active">Settings</a>
+        <a href="/r/{}/settings/branches" class="nav-tab">Branch Protection</a>
+        <a href="/r/{}/settings/collaborators" class="nav-tab">Collaborators</a>
+    </div>
+
+    <div class="section">
+        <h2>Repository Name</h2>
+        <p>Links, clones and API calls using the old name keep working after a rename.</p>
+        <form method="POST" action="/r/{}/settings/rename" class="settings-form">
+            <div class="form-group">
+                <label for="name">Name</label>
+                <input type="text" id="name" name="name" required maxlength="64" value="{}">
+            </div>
+            <button type="submit" class="btn btn-primary">Rename</button>
+        </form>
+    </div>
+
+    <div class="section">
+        <h2>Clone &amp; Fetch</h2>
+        <table class="settings-table">
+            <thead>
+                <tr><th>Feature</th><th>Status</th><th>Details</th></tr>
+            </thead>
+            <tbody>
+                {}
+            </tbody>
+        </table>
+    </div>
+
+    <div class="section">
+        <h2>Push Rules</h2>
+        <p>Pushes that break a rule are refused and the reason is shown to the pusher.</p>
+        <form method="POST" action="/r/{}/settings/push-rules" class="settings-form">
+            <div class="form-group">
+                <label for="max_blob_size_mb">Maximum file size (MB)</label>
+                <input type="number" id="max_blob_size_mb" name="max_blob_size_mb" min="0" step="any"
+                    value="{}" placeholder="No limit">
+            </div>
+            <div class="form-group">
+                <label for="forbidden_paths">Forbidden paths</label>
+                <textarea id="forbidden_paths" name="forbidden_paths" rows="4"
+                    placeholder="*.pem&#10;.env&#10;secrets/**">{}</textarea>
+                <small>One pattern per line. <code>*</code> stays within a directory, <code>**</code> crosses directories, and patterns without a <code>/</code> match file names anywhere.</small>
+            </div>
+            <div class="form-group">
+                {}
+            </div>
+            <button type="submit" class="btn btn-primary">Save Push Rules</button>
+        </form>
+    </div>
+
+    <div class="section">
+        <h2>Protected Branches</h2>
+        <p>{}</p>
+        <a href="/r/{}/settings/branches" class="btn btn-secondary">Manage Branch Protection</a>
+    </div>
+
+    <style>
+        .settings-table {{
+            width: 100%;
+            border-collapse: collapse;
+            margin-top: 1rem;
+        }}
+
+        .settings-table th, .settings-table td {{
+            padding: 0.75rem 1rem;
+            text-align: left;
+            border-bottom: 1px solid var(--border-color);
+        }}
+
+        .setting-detail {{
+            color: var(--text-secondary);
+        }}
+
+        .setting-badge {{
+            padding: 0.2rem 0.6rem;
+            border-radius: 8px;
+            font-size: 0.85rem;
+        }}
+
+        .settings-form {{
+            margin-top: 1rem;
+        }}
+
+        .settings-form textarea {{
+            width: 100%;
+            font-family: monospace;
+        }}
+
+        .setting-on {{ background: rgba(0, 255, 136, 0.2); }}
+        .setting-off {{ background: rgba(255, 80, 80, 0.25); }}
+    </style>
+    "#,
+        repo.repo_hash,
+        html_escape(&repo.name),
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        repo.repo_hash,
+        html_escape(&repo.name),
+        rows,
+        repo.repo_hash,
+        push_rules
+            .max_blob_size
+            .map(|bytes| format!("{}", bytes as f64 / (1024.0 * 1024.0)))
+            .unwrap_or_default(),
+        html_escape(&push_rules.forbidden_paths),
+        verified_email_row,
+        match protected_count {
+            0 => "No branches are protected.".to_string(),
+            1 => "1 branch protection rule.".to_string(),
+            n => format!("{} branch protection rules.", n),
+        },
+        repo.repo_hash
+    );
+
+    render_page(&format!("Settings - {}", repo.name), &content)
+}
@@ -0,0 +1,147 @@
+// src/templates/sessions.rs
+use super::{html_escape, render_page};
+use crate::auth::session::ActiveSession;
+use crate::db::ActivityLogEntry;
+
+fn session_row(active: &ActiveSession, current_key: &str) -> String {
+    let session = &active.session;
+    let action = if active.key == current_key {
+        "<span class='badge'>This session</span>".to_string()
+    } else {
+        format!(
+            r#"<form method="POST" action="/profile/sessions" class="inline-form">
+                    <input type="hidden" name="action" value="revoke">
+                    <input type="hidden" name="session_key" value="{}">
+                    <button type="submit" class="btn btn-danger">Revoke</button>
+                </form>"#,
+            html_escape(&active.key)
+        )
+    };
+
+    format!(
+        r#"<tr>
+            <td>{}</td>
+            <td class="user-agent">{}</td>
+            <td>{}</td>
+            <td>{}</td>
+            <td>{}</td>
+            <td>{}</td>
+        </tr>"#,
+        html_escape(session.ip_address.as_deref().unwrap_or("Unknown")),
+        html_escape(session.user_agent.as_deref().unwrap_or("Unknown")),
+        session.created_at.format("%Y-%m-%d %H:%M UTC"),
+        session.last_seen.format("%Y-%m-%d %H:%M UTC"),
+        session.expires_at.format("%Y-%m-%d %H:%M UTC"),
+        action
+    )
+}
+
+fn activity_row(entry: &ActivityLogEntry) -> String {
+    let event = match entry.action.as_str() {
+        "login" => "✅ Signed in",
+        "login_failed" => "⚠️ Failed sign-in",
+        "account_locked" => "🔒 Locked",
+        "account_unlocked" => "🔓 Unlocked",
+        other => other,
+    };
+
+    format!(
+        r#"<tr>
+            <td>{}</td>
+            <td>{}</td>
+            <td>{}</td>
+            <td>{}</td>
+        </tr>"#,
+        html_escape(event),
+        html_escape(entry.details.as_deref().unwrap_or("")),
+        html_escape(entry.ip_address.as_deref().unwrap_or("Unknown")),
+        html_escape(&entry.created_at)
+    )
+}
+
+/// `current_key` marks the session viewing the page, which is logged out
+/// rather than revoked. `activity` is the account's recent sign-in events.
+pub fn render(sessions: &[ActiveSession], current_key: &str, activity: &[ActivityLogEntry]) -> String {
+    let rows = sessions
+        .iter()
+        .map(|active| session_row(active, current_key))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let others_html = if sessions.iter().any(|active| active.key != current_key) {
+        r#"<form method="POST" action="/profile/sessions">
+            <input type="hidden" name="action" value="revoke_others">
+            <button type="submit" class="btn btn-danger"
+                onclick="return confirm('Sign out of every other session?')">Sign Out Everywhere Else</button>
+        </form>"#
+    } else {
+        ""
+    };
+
+    let activity_html = if activity.is_empty() {
+        "<p>No recent sign-in activity.</p>".to_string()
+    } else {
+        format!(
+            r#"<table class="sessions-table">
+            <thead>
+                <tr><th>Event</th><th>Details</th><th>IP Address</th><th>When</th></tr>
+            </thead>
+            <tbody>{}</tbody>
+        </table>"#,
+            activity.iter().map(activity_row).collect::<Vec<_>>().join("\n")
+        )
+    };
+
+    let content = format!(
+        r#"
+    <div class="breadcrumb">
+        <a href="/profile">← Back to Profile</a>
+    </div>
+
+    <h1>Active Sessions</h1>
+
+    <div class="section">
+        <p>These browsers are signed in to your account. Revoke any you don't recognize.</p>
+        <table class="sessions-table">
+            <thead>
+                <tr><th>IP Address</th><th>Browser</th><th>Signed In</th><th>Last Seen</th><th>Expires</th><th></th></tr>
+            </thead>
+            <tbody>{}</tbody>
+        </table>
+        {}
+    </div>
+
+    <div class="section">
+        <h2>Recent Sign-In Activity</h2>
+        <p>Failed attempts you don't recognize may mean someone is guessing your password.</p>
+        {}
+    </div>
+
+    <style>
+        .sessions-table {{
+            width: 100%;
+            border-collapse: collapse;
+            margin-bottom: 1.5rem;
+        }}
+
+        .sessions-table th, .sessions-table td {{
+            padding: 0.75rem 1rem;
+            text-align: left;
+            border-bottom: 1px solid var(--border-color);
+        }}
+
+        .sessions-table .user-agent {{
+            max-width: 20rem;
+            overflow-wrap: anywhere;
+        }}
+
+        .inline-form {{
+            display: inline;
+        }}
+    </style>
+    "#,
+        rows, others_html, activity_html
+    );
+
+    render_page("Active Sessions", &content)
+}
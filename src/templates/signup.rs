@@ -15,6 +15,11 @@ pub fn render_with_user(username: Option<&str>) -> String {
                 <input type="text" id="username" name="username" required minlength="3" maxlength="32" autocomplete="username">
                 <small style="color: var(--text-muted); display: block; margin-top: 0.5rem;">3-32 characters</small>
             </div>
+            <div class="form-group">
+                <label for="email">Email <span style="color: var(--text-muted);">(optional)</span></label>
+                <input type="email" id="email" name="email" maxlength="254" autocomplete="email">
+                <small style="color: var(--text-muted); display: block; margin-top: 0.5rem;">Needed to reset a forgotten password</small>
+            </div>
             <div class="form-group">
                 <label for="password">Password</label>
                 <input type="password" id="password" name="password" required minlength="8" autocomplete="new-password">
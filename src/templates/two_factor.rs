@@ -0,0 +1,127 @@
+// src/templates/two_factor.rs
+use super::{html_escape, render_page};
+use crate::auth::totp;
+use crate::services::two_factor::Enrollment;
+
+/// What the two-factor settings page shows
+pub struct TwoFactorView<'a> {
+    pub enabled: bool,
+    /// Set while enrolling, before the first code is confirmed
+    pub setup: Option<&'a Enrollment>,
+    /// Recovery codes that were just created and are shown once
+    pub recovery_codes: Option<&'a [String]>,
+    pub unused_recovery_codes: i64,
+    /// Whether an admin must enable two-factor to keep using admin pages
+    pub required: bool,
+}
+
+fn code_form(action: &str, button: &str, class: &str) -> String {
+    format!(
+        r#"<form method="POST" action="/profile/2fa" class="code-form">
+            <input type="hidden" name="action" value="{}">
+            <input type="text" name="code" required autocomplete="one-time-code"
+                placeholder="Authentication or recovery code">
+            <button type="submit" class="btn {}">{}</button>
+        </form>"#,
+        action, class, button
+    )
+}
+
+pub fn render(view: &TwoFactorView) -> String {
+    let required_html = if view.required && !view.enabled {
+        r#"<div class="section notice">
+        <p><strong>Two-factor authentication is required for admins.</strong> Set it up to keep using the admin pages.</p>
+    </div>"#
+    } else {
+        ""
+    };
+
+    let recovery_html = match view.recovery_codes {
+        Some(codes) => format!(
+            r#"<div class="section new-token">
+        <h2>Recovery Codes</h2>
+        <p>Store these somewhere safe. Each one signs you in once if you lose your authenticator. They will not be shown again.</p>
+        <pre><code>{}</code></pre>
+    </div>"#,
+            codes.iter().map(|c| html_escape(c)).collect::<Vec<_>>().join("\n")
+        ),
+        None => String::new(),
+    };
+
+    let body = if view.enabled {
+        format!(
+            r#"<p>✅ Two-factor authentication is <strong>enabled</strong>. You have {} unused recovery codes.</p>
+
+        <h3>New Recovery Codes</h3>
+        <p>Replaces all of your current recovery codes.</p>
+        {}
+
+        <h3>Disable</h3>
+        {}"#,
+            view.unused_recovery_codes,
+            code_form("regenerate", "Generate New Codes", "btn-secondary"),
+            code_form("disable", "Disable Two-Factor", "btn-danger")
+        )
+    } else if let Some(setup) = view.setup {
+        let qr = totp::qr_svg(&setup.otpauth_uri).unwrap_or_default();
+        format!(
+            r#"<p>Scan this code with your authenticator app, then enter the code it shows.</p>
+        <div class="qr-code">{}</div>
+        <p>Can't scan it? Enter this key instead: <code>{}</code></p>
+        <p><a href="{}">Open in an authenticator app</a></p>
+        <form method="POST" action="/profile/2fa" class="code-form">
+            <input type="hidden" name="action" value="confirm">
+            <input type="text" name="code" required autocomplete="one-time-code"
+                inputmode="numeric" placeholder="123456">
+            <button type="submit" class="btn btn-primary">Enable</button>
+        </form>"#,
+            qr,
+            html_escape(&setup.secret),
+            html_escape(&setup.otpauth_uri)
+        )
+    } else {
+        r#"<p>Two-factor authentication is <strong>off</strong>. With it on, signing in also needs a code from an authenticator app.</p>
+        <form method="POST" action="/profile/2fa">
+            <input type="hidden" name="action" value="begin">
+            <button type="submit" class="btn btn-primary">Set Up Two-Factor</button>
+        </form>"#
+            .to_string()
+    };
+
+    let content = format!(
+        r#"
+    <div class="breadcrumb">
+        <a href="/profile">← Back to Profile</a>
+    </div>
+
+    <h1>Two-Factor Authentication</h1>
+
+    {}
+    {}
+
+    <div class="section">
+        {}
+    </div>
+
+    <style>
+        .qr-code svg {{
+            background: white;
+            padding: 1rem;
+        }}
+
+        .code-form {{
+            display: flex;
+            gap: 0.5rem;
+            margin-bottom: 1.5rem;
+        }}
+
+        .new-token pre {{
+            overflow-x: auto;
+        }}
+    </style>
+    "#,
+        required_html, recovery_html, body
+    );
+
+    render_page("Two-Factor Authentication", &content)
+}
@@ -0,0 +1,62 @@
+// src/test_support.rs
+//! A migrated database in its own temporary directory, which is removed
+//! when the `TestDb` is dropped.
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::db::Database;
+use crate::models::{CreateUserRequest, User};
+
+pub struct TestDb {
+    db: Database,
+    dir: PathBuf,
+}
+
+impl TestDb {
+    pub async fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("hyrule-test-{:016x}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::new(&format!("sqlite://{}?mode=rwc", dir.join("hyrule.db").display()))
+            .await
+            .unwrap();
+        db.migrate().await.unwrap();
+        TestDb { db, dir }
+    }
+
+    /// Scratch space that is cleaned up with the database
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub async fn add_user(&self, username: &str, email: &str, password_hash: &str) -> User {
+        self.db
+            .create_user(&CreateUserRequest {
+                username: username.to_string(),
+                email: email.to_string(),
+                password_hash: password_hash.to_string(),
+                public_key: String::new(),
+                storage_quota: 1 << 30,
+            })
+            .await
+            .unwrap()
+    }
+
+    /// A user with an address at example.com and no password
+    pub async fn add_named_user(&self, username: &str) -> User {
+        self.add_user(username, &format!("{}@example.com", username), "").await
+    }
+}
+
+impl Deref for TestDb {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        &self.db
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
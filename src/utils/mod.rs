@@ -1,3 +1,4 @@
 // src/utils/mod.rs
 pub mod validation;
 pub mod hash;
+pub mod paths;
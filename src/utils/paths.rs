@@ -0,0 +1,28 @@
+// src/utils/paths.rs
+
+/// Web path of a repository, e.g. `/alice/demo`
+pub fn repo_url(owner: &str, name: &str) -> String {
+    format!("/{}/{}", urlencoding::encode(owner), urlencoding::encode(name))
+}
+
+/// Split a `demo.git` path segment into the repository name and whether
+/// the `.git` suffix was present
+pub fn strip_git_suffix(segment: &str) -> (&str, bool) {
+    match segment.strip_suffix(".git") {
+        Some(name) => (name, true),
+        None => (segment, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_paths() {
+        assert_eq!(repo_url("alice", "demo"), "/alice/demo");
+        assert_eq!(repo_url("alice", "my repo"), "/alice/my%20repo");
+        assert_eq!(strip_git_suffix("demo.git"), ("demo", true));
+        assert_eq!(strip_git_suffix("demo"), ("demo", false));
+    }
+}
@@ -102,8 +102,14 @@ pub fn validate_username(username: &str) -> Result<(), &'static str> {
         return Err("Username can only contain letters, numbers, underscores, and hyphens");
     }
     
-    // Prevent reserved names
-    let reserved = ["admin", "root", "system", "api", "www", "ftp", "mail"];
+    // Prevent reserved names, including the top-level routes that would
+    // shadow `/:owner/:repo`
+    let reserved = [
+        "admin", "root", "system", "api", "www", "ftp", "mail",
+        "git", "r", "static", "explore", "docs", "about", "dashboard", "profile", "search",
+        "tags", "starred", "pinned", "repos", "login", "signup", "logout", "orgs",
+        "change-password", "forgot-password", "reset-password", "verify-email",
+    ];
     if reserved.contains(&username.to_lowercase().as_str()) {
         return Err("Username is reserved");
     }
@@ -111,6 +117,19 @@ pub fn validate_username(username: &str) -> Result<(), &'static str> {
     Ok(())
 }
 
+/// Validates an email address an account can use
+pub fn validate_email(email: &str) -> Result<(), &'static str> {
+    if email.len() > 254 {
+        return Err("Email address too long");
+    }
+
+    if !validator::validate_email(email) {
+        return Err("Invalid email address");
+    }
+
+    Ok(())
+}
+
 /// Validates repository name
 pub fn validate_repo_name(name: &str) -> Result<(), &'static str> {
     if name.len() < MIN_REPO_NAME_LENGTH {
@@ -125,9 +144,14 @@ pub fn validate_repo_name(name: &str) -> Result<(), &'static str> {
         return Err("Repository name can only contain letters, numbers, underscores, and hyphens");
     }
     
-    // Prevent names that could cause issues
-    let reserved = ["git", "config", "objects", "refs", "HEAD"];
-    if reserved.contains(&name) {
+    // Prevent names that could cause issues, including the routes that
+    // would shadow `/api/repos/:owner/:repo`
+    let reserved = [
+        "git", "config", "objects", "refs", "head",
+        "fork", "stats", "protected-branches", "collaborators", "invitations", "nodes",
+        "readme", "replicate", "placement", "star", "pin", "unpin", "tags", "pack",
+    ];
+    if reserved.contains(&name.to_lowercase().as_str()) {
         return Err("Repository name is reserved");
     }
     
@@ -224,6 +248,39 @@ mod tests {
         assert!(validate_username("ab").is_err()); // Too short
         assert!(validate_username("user<script>").is_err()); // Invalid chars
         assert!(validate_username("admin").is_err()); // Reserved
+        assert!(validate_username("Verify-Email").is_err()); // Reserved
+    }
+
+    #[test]
+    fn test_top_level_routes_are_reserved() {
+        // Paths in the router sources, whose first segments would shadow
+        // an owner's page
+        let path = Regex::new(r#""/([A-Za-z0-9_-]+)"#).unwrap();
+        for source in [include_str!("../main.rs"), include_str!("../routes.rs")] {
+            for segment in path.captures_iter(source) {
+                assert!(validate_username(&segment[1]).is_err(), "/{} is not reserved", &segment[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_repo_routes_are_reserved() {
+        // Static siblings of the name-addressed repository route
+        let path = Regex::new(r#""/api/repos/:hash/([A-Za-z0-9_-]+)"#).unwrap();
+        for source in [include_str!("../main.rs"), include_str!("../routes.rs")] {
+            for segment in path.captures_iter(source) {
+                assert!(validate_repo_name(&segment[1]).is_err(), "{} is not reserved", &segment[1]);
+            }
+        }
+        assert!(validate_repo_name("Stats").is_err());
+        assert!(validate_repo_name("stats-page").is_ok());
+    }
+
+    #[test]
+    fn test_email_validation() {
+        assert!(validate_email("alice@example.com").is_ok());
+        assert!(validate_email("alice").is_err());
+        assert!(validate_email("alice@").is_err());
     }
 
     #[test]